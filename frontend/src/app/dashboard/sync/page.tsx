@@ -4,7 +4,7 @@ import { useEffect, useMemo, useState, useRef, useCallback } from "react";
 import { Card, Button, Badge, Modal, Input } from "@/components/ui";
 import { useStore } from "@/stores/useStore";
 import { api } from "@/lib/api";
-import { SyncConfig, Host, SyncLogEntry } from "@/types/api";
+import { SyncConfig, Host, SyncLogEntry, SyncProgress } from "@/types/api";
 import { Plus, Trash2, Pencil, Play, FileText, Clock, RefreshCw, AlertTriangle } from "lucide-react";
 
 function LogModal({
@@ -21,7 +21,9 @@ function LogModal({
   const [logs, setLogs] = useState<SyncLogEntry[]>([]);
   const [loading, setLoading] = useState(false);
   const [wsConnected, setWsConnected] = useState(false);
+  const [progress, setProgress] = useState<SyncProgress | null>(null);
   const wsRef = useRef<WebSocket | null>(null);
+  const progressWsRef = useRef<WebSocket | null>(null);
   const logsEndRef = useRef<HTMLDivElement>(null);
   const logsContainerRef = useRef<HTMLDivElement>(null);
   const isUnmountedRef = useRef(false);
@@ -74,6 +76,19 @@ function LogModal({
       }
     };
     wsRef.current = ws;
+
+    const progressWs = new WebSocket(
+      `${protocol}://${window.location.host}/api/syncs/${syncId}/progress?token=${token}`
+    );
+    progressWs.onmessage = (event) => {
+      if (isUnmountedRef.current) return;
+      try {
+        setProgress(JSON.parse(event.data) as SyncProgress);
+      } catch (e) {
+        console.error("Failed to parse progress:", e);
+      }
+    };
+    progressWsRef.current = progressWs;
   }, [syncId]);
 
   const disconnectWs = useCallback(() => {
@@ -81,9 +96,21 @@ function LogModal({
       wsRef.current.close();
       wsRef.current = null;
     }
+    if (progressWsRef.current) {
+      progressWsRef.current.close();
+      progressWsRef.current = null;
+    }
     setWsConnected(false);
+    setProgress(null);
   }, []);
 
+  const formatBytes = (bytes: number) => {
+    if (bytes >= 1024 * 1024 * 1024) return `${(bytes / 1024 / 1024 / 1024).toFixed(2)} GB`;
+    if (bytes >= 1024 * 1024) return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+    if (bytes >= 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+    return `${bytes} B`;
+  };
+
   useEffect(() => {
     isUnmountedRef.current = false;
     if (isOpen) {
@@ -121,6 +148,25 @@ function LogModal({
           </Button>
         </div>
 
+        {progress && (
+          <div className="space-y-1">
+            <div className="flex justify-between text-xs text-slate-500">
+              <span className="truncate">{progress.path}</span>
+              <span className="shrink-0">
+                {progress.percent.toFixed(1)}% · {formatBytes(progress.bytes_transferred)}/
+                {formatBytes(progress.bytes_total)} · {formatBytes(progress.speed)}/s
+                {progress.eta != null && progress.percent < 100 ? ` · 剩余 ${progress.eta}s` : ""}
+              </span>
+            </div>
+            <div className="h-2 bg-slate-200 rounded-full overflow-hidden">
+              <div
+                className="h-full bg-indigo-500 transition-all"
+                style={{ width: `${Math.min(progress.percent, 100)}%` }}
+              />
+            </div>
+          </div>
+        )}
+
         <div
           ref={logsContainerRef}
           className="max-h-96 overflow-y-auto bg-slate-900 rounded-lg p-4 font-mono text-sm"
@@ -161,6 +207,8 @@ const defaultSyncForm = {
   host_id: "",
   local_paths_text: "",
   remote_path: "",
+  pre_command: "",
+  post_command: "",
   delete: false,
   exclude_text: "",
   include_text: "",
@@ -261,6 +309,8 @@ export default function SyncPage() {
         host_id: matchedHost?.id || "",
         local_paths_text: (sync.local_paths || []).join("\n"),
         remote_path: sync.remote_path || "",
+        pre_command: sync.pre_command || "",
+        post_command: sync.post_command || "",
         delete: !!sync.options?.delete,
         exclude_text: (sync.options?.exclude || []).join("\n"),
         include_text: (sync.options?.include || []).join("\n"),
@@ -348,6 +398,8 @@ export default function SyncPage() {
           follow_symlinks: !!syncForm.follow_symlinks,
         },
         schedule,
+        pre_command: syncForm.pre_command.trim() || null,
+        post_command: syncForm.post_command.trim() || null,
       };
 
       if (editingSyncId) {
@@ -646,6 +698,20 @@ export default function SyncPage() {
             onChange={(e) => setSyncForm({ ...syncForm, remote_path: e.target.value })}
           />
 
+          <Input
+            label="传输前命令（可选）"
+            placeholder="如 pg_dump -f /data/db.sql mydb，非 0 退出将中止同步"
+            value={syncForm.pre_command}
+            onChange={(e) => setSyncForm({ ...syncForm, pre_command: e.target.value })}
+          />
+
+          <Input
+            label="传输后命令（可选）"
+            placeholder="如 rm -f /data/db.sql"
+            value={syncForm.post_command}
+            onChange={(e) => setSyncForm({ ...syncForm, post_command: e.target.value })}
+          />
+
           <div>
             <label className="block text-sm font-semibold text-slate-700 mb-2">
               选择主机
@@ -195,6 +195,8 @@ export default function TerminalsPage() {
   const [selectedTerminalId, setSelectedTerminalId] = useState<string | null>(null);
   const [iframeKeys, setIframeKeys] = useState<Record<string, number>>({});
   const iframeRefs = useRef<Record<string, HTMLIFrameElement | null>>({});
+  // iframe key -> 带票据的地址，每次（重新）加载 iframe 都换一张新票据
+  const [embedUrls, setEmbedUrls] = useState<Record<string, string>>({});
 
   // 动态 favicon：terminalId -> svgUrl
   const [dynamicFavicons, setDynamicFavicons] = useState<Record<string, string>>({});
@@ -248,6 +250,28 @@ export default function TerminalsPage() {
     return () => window.removeEventListener('message', handler);
   }, [terminals]);
 
+  useEffect(() => {
+    const running = terminals.filter(t => t.status.running);
+    const keys = new Set(running.map(t => `${t.id}-${iframeKeys[t.id] || 0}`));
+    // 已停止或已刷新的 iframe 的票据作废，再次显示时重新申请
+    setEmbedUrls(prev => Object.fromEntries(Object.entries(prev).filter(([key]) => keys.has(key))));
+    for (const terminal of running) {
+      const key = `${terminal.id}-${iframeKeys[terminal.id] || 0}`;
+      if (embedUrls[key] !== undefined) continue;
+      setEmbedUrls(prev => ({ ...prev, [key]: "" }));
+      api.createTerminalTicket(terminal.id)
+        .then(({ ticket }) => setEmbedUrls(prev => ({ ...prev, [key]: terminalEmbedUrl(terminal, ticket) })))
+        .catch((error) => {
+          console.error("Failed to issue terminal ticket:", error);
+          setEmbedUrls(prev => {
+            const next = { ...prev };
+            delete next[key];
+            return next;
+          });
+        });
+    }
+  }, [terminals, iframeKeys]);
+
   // 切换终端时自动聚焦到 iframe
   useEffect(() => {
     if (selectedTerminalId && iframeRefs.current[selectedTerminalId]) {
@@ -391,6 +415,16 @@ export default function TerminalsPage() {
     return `${scheme}://${host}:${port}`;
   };
 
+  // 内嵌的终端通过 miao 的 WebSocket 代理连接 gotty，重启终端时无需刷新 iframe；
+  // 代理地址只带一次性票据，登录 token 不会出现在 iframe 地址里
+  const terminalEmbedUrl = (terminal: typeof terminals[number], ticket: string) => {
+    const url = terminalUrl(terminal);
+    if (!url) return "";
+    const wsProtocol = window.location.protocol === "https:" ? "wss:" : "ws:";
+    const proxyUrl = `${wsProtocol}//${window.location.host}/api/terminals/${terminal.id}/ws?ticket=${encodeURIComponent(ticket)}`;
+    return `${url}/#miao_ws=${encodeURIComponent(proxyUrl)}`;
+  };
+
   const openModal = (terminal?: typeof terminals[number]) => {
     if (terminal) {
       setEditingId(terminal.id);
@@ -663,11 +697,11 @@ export default function TerminalsPage() {
       {terminals.some(t => t.status.running) && (
         <Card className="p-0 overflow-hidden">
           <div className="relative" style={{ height: '70vh' }}>
-            {terminals.filter(t => t.status.running).map(terminal => (
+            {terminals.filter(t => t.status.running && embedUrls[`${t.id}-${iframeKeys[t.id] || 0}`]).map(terminal => (
               <iframe
                 key={`${terminal.id}-${iframeKeys[terminal.id] || 0}`}
                 ref={(el) => { iframeRefs.current[terminal.id] = el; }}
-                src={terminalUrl(terminal)}
+                src={embedUrls[`${terminal.id}-${iframeKeys[terminal.id] || 0}`]}
                 className="absolute inset-0 w-full h-full border-0"
                 style={{
                   visibility: selectedTerminalId === terminal.id ? 'visible' : 'hidden',
@@ -13,16 +13,22 @@ interface PerformanceTrendChartProps {
   onRangeChange: (value: string) => void;
 }
 
-function buildPolyline(values: number[], width: number, height: number): string {
-  if (values.length === 0) {
-    return "";
-  }
+// null values (gaps in the series) break the line into separate segments
+function buildPath(values: Array<number | null>, width: number, height: number): string {
+  let penDown = false;
   return values
     .map((value, index) => {
+      if (value === null) {
+        penDown = false;
+        return "";
+      }
       const x = (index / Math.max(values.length - 1, 1)) * width;
       const y = height - (value / 100) * height;
-      return `${x},${y}`;
+      const command = penDown ? "L" : "M";
+      penDown = true;
+      return `${command}${x},${y}`;
     })
+    .filter(Boolean)
     .join(" ");
 }
 
@@ -40,9 +46,14 @@ export function PerformanceTrendChart({
 
   const seriesLength = series.length;
   const cpuValues = series.map((point) => point.cpuPercent);
-  const hasGpu = series.some((point) => point.gpuPercent !== undefined);
-  const gpuValues = series.map((point) => point.gpuPercent ?? 0);
+  const hasGpu = series.some(
+    (point) => point.gpuPercent !== undefined && point.gpuPercent !== null
+  );
+  const gpuValues = series.map((point) => point.gpuPercent ?? null);
   const memoryValues = series.map((point) => {
+    if (point.memoryUsedKb === null) {
+      return null;
+    }
     if (!memoryTotalBytes) {
       return 0;
     }
@@ -52,6 +63,9 @@ export function PerformanceTrendChart({
     );
   });
   const diskValues = series.map((point) => {
+    if (point.diskUsedBytes === null || point.diskTotalBytes === null) {
+      return null;
+    }
     if (point.diskTotalBytes === 0) {
       return 0;
     }
@@ -87,8 +101,8 @@ export function PerformanceTrendChart({
     setHoverIndex(null);
   };
 
-  const formatPercentValue = (value?: number) => {
-    if (value === undefined || Number.isNaN(value)) {
+  const formatPercentValue = (value?: number | null) => {
+    if (value === undefined || value === null || Number.isNaN(value)) {
       return "-";
     }
     return `${Math.round(value)}%`;
@@ -132,14 +146,17 @@ export function PerformanceTrendChart({
   })();
 
   const hoverMemoryPercent =
-    hoverPoint && memoryTotalBytes
+    hoverPoint && hoverPoint.memoryUsedKb !== null && memoryTotalBytes
       ? Math.min(
           100,
           Math.round((hoverPoint.memoryUsedKb * 1024 * 100) / memoryTotalBytes)
         )
       : undefined;
   const hoverDiskPercent =
-    hoverPoint && hoverPoint.diskTotalBytes > 0
+    hoverPoint &&
+    hoverPoint.diskUsedBytes !== null &&
+    hoverPoint.diskTotalBytes !== null &&
+    hoverPoint.diskTotalBytes > 0
       ? Math.min(
           100,
           Math.round((hoverPoint.diskUsedBytes * 100) / hoverPoint.diskTotalBytes)
@@ -149,7 +166,7 @@ export function PerformanceTrendChart({
     ? new Date(hoverPoint.timestamp * 1000).toLocaleTimeString()
     : "";
   const hoverCpuY =
-    hoverPoint && seriesLength > 1
+    hoverPoint && hoverPoint.cpuPercent !== null && seriesLength > 1
       ? height - (hoverPoint.cpuPercent / 100) * height
       : 0;
   const hoverMemoryY =
@@ -157,7 +174,7 @@ export function PerformanceTrendChart({
       ? height - (hoverMemoryPercent / 100) * height
       : 0;
   const hoverGpuY =
-    hoverPoint?.gpuPercent !== undefined
+    hoverPoint?.gpuPercent !== undefined && hoverPoint.gpuPercent !== null
       ? height - (hoverPoint.gpuPercent / 100) * height
       : 0;
   const hoverDiskY =
@@ -223,30 +240,30 @@ export function PerformanceTrendChart({
               className="h-40 w-full"
               preserveAspectRatio="none"
             >
-              <polyline
+              <path
                 fill="none"
                 stroke="#6366F1"
                 strokeWidth="2"
-                points={buildPolyline(cpuValues, width, height)}
+                d={buildPath(cpuValues, width, height)}
               />
-              <polyline
+              <path
                 fill="none"
                 stroke="#10B981"
                 strokeWidth="2"
-                points={buildPolyline(memoryValues, width, height)}
+                d={buildPath(memoryValues, width, height)}
               />
-              <polyline
+              <path
                 fill="none"
                 stroke="#A855F7"
                 strokeWidth="2"
-                points={buildPolyline(gpuValues, width, height)}
+                d={buildPath(gpuValues, width, height)}
                 style={{ display: hasGpu ? "block" : "none" }}
               />
-              <polyline
+              <path
                 fill="none"
                 stroke="#F59E0B"
                 strokeWidth="2"
-                points={buildPolyline(diskValues, width, height)}
+                d={buildPath(diskValues, width, height)}
               />
               {hoverIndex !== null && (
                 <>
@@ -258,9 +275,13 @@ export function PerformanceTrendChart({
                     stroke="#CBD5F5"
                     strokeWidth="1"
                   />
-                  <circle cx={hoverX} cy={hoverCpuY} r="3" fill="#6366F1" />
-                  <circle cx={hoverX} cy={hoverMemoryY} r="3" fill="#10B981" />
-                  {hoverPoint?.gpuPercent !== undefined && (
+                  {hoverPoint?.cpuPercent !== null && (
+                    <circle cx={hoverX} cy={hoverCpuY} r="3" fill="#6366F1" />
+                  )}
+                  {hoverMemoryPercent !== undefined && (
+                    <circle cx={hoverX} cy={hoverMemoryY} r="3" fill="#10B981" />
+                  )}
+                  {hoverPoint?.gpuPercent !== undefined && hoverPoint.gpuPercent !== null && (
                     <circle cx={hoverX} cy={hoverGpuY} r="3" fill="#A855F7" />
                   )}
                   {hoverDiskPercent !== undefined && (
@@ -305,7 +326,7 @@ export function PerformanceTrendChart({
                 </span>
                 <span>{formatPercentValue(hoverMemoryPercent)}</span>
               </div>
-              {hoverPoint.gpuPercent !== undefined && (
+              {hoverPoint.gpuPercent !== undefined && hoverPoint.gpuPercent !== null && (
                 <div className="flex items-center justify-between">
                   <span className="flex items-center gap-1">
                     <span className="h-1.5 w-1.5 rounded-full bg-purple-500" />
@@ -7,7 +7,13 @@ import {
   ManualNode,
   ProxyGroup,
   SyncConfig,
+  SyncDryRunResult,
   SyncLogEntry,
+  SyncRunRecord,
+  ConfigBundle,
+  ConfigImportResult,
+  ConfigValidationResult,
+  NodeImportResult,
   TerminalLogEntry,
   TcpTunnel,
   Terminal,
@@ -148,6 +154,30 @@ class ApiClient {
     return res.data;
   }
 
+  // Config
+  async exportConfig(includeSecrets = false): Promise<ConfigBundle> {
+    const params = new URLSearchParams();
+    if (includeSecrets) params.set("include_secrets", "true");
+    const res = await this.fetch<{ data: ConfigBundle }>(`/api/config/export?${params.toString()}`);
+    return res.data;
+  }
+
+  async importConfig(bundle: ConfigBundle): Promise<ConfigImportResult> {
+    const res = await this.fetch<{ data: ConfigImportResult }>("/api/config/import", {
+      method: "POST",
+      body: JSON.stringify(bundle),
+    });
+    return res.data;
+  }
+
+  async validateConfig(config: Record<string, unknown>): Promise<ConfigValidationResult> {
+    const res = await this.fetch<{ data: ConfigValidationResult }>("/api/config/validate", {
+      method: "POST",
+      body: JSON.stringify(config),
+    });
+    return res.data;
+  }
+
   // Binaries
   async getBinariesStatus(): Promise<{
     sing_box: { installed: boolean; path: string };
@@ -206,11 +236,11 @@ class ApiClient {
         step: string;
         series: Array<{
           timestamp: number;
-          cpu_percent: number;
-          memory_used_kb: number;
-          gpu_percent?: number;
-          disk_used_bytes: number;
-          disk_total_bytes: number;
+          cpu_percent: number | null;
+          memory_used_kb: number | null;
+          gpu_percent?: number | null;
+          disk_used_bytes: number | null;
+          disk_total_bytes: number | null;
         }>;
       };
     }>(
@@ -284,6 +314,14 @@ class ApiClient {
     });
   }
 
+  async importNodes(links: string): Promise<NodeImportResult> {
+    const res = await this.fetch<{ data: NodeImportResult }>("/api/nodes/import", {
+      method: "POST",
+      body: JSON.stringify({ links }),
+    });
+    return res.data;
+  }
+
   async updateNode(tag: string, config: Partial<ManualNode>): Promise<void> {
     await this.fetch(`/api/nodes/${encodeURIComponent(tag)}`, {
       method: "PUT",
@@ -443,6 +481,13 @@ class ApiClient {
     });
   }
 
+  async dryRunSync(id: string): Promise<SyncDryRunResult> {
+    const res = await this.fetch<{ data: SyncDryRunResult }>(`/api/syncs/${id}/dry-run`, {
+      method: "POST",
+    });
+    return res.data;
+  }
+
   async getTerminalLogs(id: string, limit?: number): Promise<TerminalLogEntry[]> {
     const params = new URLSearchParams();
     if (limit) params.set("limit", limit.toString());
@@ -484,6 +529,13 @@ class ApiClient {
     return res.data;
   }
 
+  async getSyncHistory(id: string, limit?: number): Promise<SyncRunRecord[]> {
+    const params = new URLSearchParams();
+    if (limit) params.set("limit", limit.toString());
+    const res = await this.fetch<{ data: SyncRunRecord[] }>(`/api/syncs/${id}/history?${params.toString()}`);
+    return res.data;
+  }
+
   // TCP Tunnels
   async getTcpTunnels(): Promise<{ supported: boolean; items: TcpTunnel[] }> {
     const res = await this.fetch<{
@@ -852,6 +904,15 @@ class ApiClient {
     });
   }
 
+  // 内嵌终端连接 WebSocket 代理用的一次性票据
+  async createTerminalTicket(id: string): Promise<{ ticket: string; expires_in: number }> {
+    const res = await this.fetch<{ data: { ticket: string; expires_in: number } }>(
+      `/api/terminals/${id}/ticket`,
+      { method: "POST" }
+    );
+    return res.data;
+  }
+
   async upgradeGotty(): Promise<void> {
     await this.fetch("/api/gotty/upgrade", {
       method: "POST",
@@ -84,13 +84,14 @@ export interface SystemStatus {
   nvidiaAvailable: boolean;
 }
 
+/** Buckets without samples (host/service down) carry null values. */
 export interface SystemMetricsPoint {
   timestamp: number;
-  cpuPercent: number;
-  memoryUsedKb: number;
-  gpuPercent?: number;
-  diskUsedBytes: number;
-  diskTotalBytes: number;
+  cpuPercent: number | null;
+  memoryUsedKb: number | null;
+  gpuPercent?: number | null;
+  diskUsedBytes: number | null;
+  diskTotalBytes: number | null;
 }
 
 export interface SystemMetricsResponse {
@@ -133,7 +134,7 @@ export interface ProxyGroup {
 }
 
 // Manual Node Types
-export type ManualNodeType = "hysteria2" | "anytls" | "shadowsocks" | "ssh";
+export type ManualNodeType = "hysteria2" | "anytls" | "shadowsocks" | "vless" | "trojan" | "ssh";
 
 export interface ManualNode {
   tag: string;
@@ -146,6 +147,19 @@ export interface ManualNode {
   private_key_passphrase?: string;
   sni?: string;
   cipher?: string;
+  uuid?: string;
+  flow?: string;
+}
+
+export interface NodeImportResult {
+  imported: number;
+  failed: number;
+  results: {
+    line: number;
+    tag?: string;
+    success: boolean;
+    error?: string;
+  }[];
 }
 
 // Traffic Types
@@ -185,6 +199,8 @@ export interface SyncConfig {
     cron?: string | null;
     timezone?: string | null;
   };
+  pre_command?: string | null;
+  post_command?: string | null;
   status?: {
     state: "running" | "stopped" | "idle" | "error";
     last_error?: {
@@ -193,6 +209,42 @@ export interface SyncConfig {
   };
 }
 
+export interface SyncDryRunChange {
+  path: string;
+  change_type: "created" | "updated" | "deleted";
+}
+
+export interface SyncDryRunResult {
+  created: number;
+  updated: number;
+  deleted: number;
+  paths: {
+    local_path: string;
+    remote_path: string;
+    changes: SyncDryRunChange[];
+    error?: string;
+  }[];
+}
+
+export interface SyncProgress {
+  path: string;
+  percent: number;
+  bytes_transferred: number;
+  bytes_total: number;
+  speed: number;
+  eta?: number | null;
+}
+
+export interface SyncRunRecord {
+  sync_id: string;
+  started_at_ms: number;
+  finished_at_ms: number;
+  status: "ok" | "error" | "cancelled";
+  error?: string | null;
+  files_transferred: number;
+  bytes_sent: number;
+}
+
 export interface SyncLogEntry {
   timestamp: number;
   level: string;
@@ -409,11 +461,39 @@ export interface Subscription {
   source: {
     url: string;
   };
+  refresh_interval_secs?: number;
 }
 
 export interface SubscriptionRequest {
   name?: string;
   enabled?: boolean;
   url: string;
+  /** 0 disables scheduled refresh */
+  refresh_interval_secs?: number;
+}
+
+
+// Config bundle (export / import)
+export interface ConfigBundle {
+  app_version: string;
+  exported_at_ms: number;
+  secrets_included: boolean;
+  config: Record<string, unknown>;
 }
 
+export interface ConfigImportResult {
+  schema_version: number;
+  migrations_applied: number[];
+  restart_error?: string;
+}
+
+export interface ConfigProblem {
+  section: string;
+  id?: string;
+  message: string;
+}
+
+export interface ConfigValidationResult {
+  valid: boolean;
+  problems: ConfigProblem[];
+}
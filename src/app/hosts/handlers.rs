@@ -290,6 +290,20 @@ pub async fn create_host(
             }
             HostAuth::Password { password }
         }
+        // 未指定认证方式时使用配置中的 default_ssh_auth
+        "" => {
+            let config = state.config.lock().await;
+            config
+                .default_ssh_auth
+                .as_ref()
+                .map(crate::host_auth_from_tunnel_auth)
+                .ok_or_else(|| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({"success": false, "error": "auth_type is required"})),
+                    )
+                })?
+        }
         other => {
             return Err((
                 StatusCode::BAD_REQUEST,
@@ -297,6 +311,9 @@ pub async fn create_host(
             ));
         }
     };
+    if let Err(e) = crate::check_host_auth_key(&auth).await {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({"success": false, "error": e}))));
+    }
 
     let host = crate::HostConfig {
         id: id.clone(),
@@ -382,6 +399,7 @@ pub async fn update_host(
     Path(id): Path<String>,
     Json(req): Json<HostUpdateRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let resync = req.resync;
     let updated = {
         let mut config = state.config.lock().await;
         let pos = config.hosts.iter().position(|h| h.id == id)
@@ -436,6 +454,9 @@ pub async fn update_host(
                 ));
             }
         };
+        if let Err(e) = crate::check_host_auth_key(&auth).await {
+            return Err((StatusCode::BAD_REQUEST, Json(json!({"success": false, "error": e}))));
+        }
 
         let host = crate::HostConfig {
             id: id.to_string(),
@@ -520,7 +541,28 @@ pub async fn update_host(
             .map(|dt| dt.to_rfc3339()).unwrap_or_default()),
     };
 
-    Ok(Json(json!({"success": true, "message": "Host updated", "data": response})))
+    let mut body = json!({"success": true, "message": "Host updated", "data": response});
+    if resync {
+        body["resync"] = match crate::resync_host_items(&state, &id).await {
+            Ok(summary) => json!(summary),
+            Err(e) => json!({"error": e}),
+        };
+    }
+    Ok(Json(body))
+}
+
+/// 将主机当前配置重新同步到由它创建的隧道和同步任务
+pub async fn resync_host(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if !state.config.lock().await.hosts.iter().any(|h| h.id == id) {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"success": false, "error": "Host not found"}))));
+    }
+    let summary = crate::resync_host_items(&state, &id)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({"success": false, "error": e}))))?;
+    Ok(Json(json!({"success": true, "message": "Host resynced", "data": summary})))
 }
 
 /// 删除主机
@@ -546,6 +588,7 @@ pub async fn delete_host(
 pub async fn test_ssh(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(params): Query<SSHTestParams>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     use crate::test_ssh_connection;
 
@@ -559,7 +602,7 @@ pub async fn test_ssh(
     };
 
     // 执行 SSH 连接测试
-    match test_ssh_connection(&host, Some(&all_hosts)).await {
+    match test_ssh_connection(&host, Some(&all_hosts), params.timeout_secs).await {
         Ok(latency_ms) => {
             let response = SSHTestResponse {
                 id: host.id.clone(),
@@ -695,10 +738,10 @@ pub async fn import_hosts(
     {
         let config = state.config.lock().await;
         let existing_hosts: std::collections::HashSet<String> = config.hosts.iter()
-            .map(|h| format!("{}@{}:{}", h.username, h.host, h.port)).collect();
+            .map(|h| format!("{}@{}", h.username, crate::join_host_port(&h.host, h.port))).collect();
 
         for (_idx, host_req) in req.hosts.into_iter().enumerate() {
-            let key = format!("{}@{}:{}", host_req.username, host_req.host, host_req.port);
+            let key = format!("{}@{}", host_req.username, crate::join_host_port(&host_req.host, host_req.port));
 
             if !req.replace_existing && existing_hosts.contains(&key) {
                 skipped += 1;
@@ -29,7 +29,9 @@ pub struct HostCreateRequest {
     #[validate(length(min = 1, max = 64))]
     pub username: String,
 
+    /// 为空时使用配置中的 `default_ssh_auth`
     #[schema(example = "password")]
+    #[serde(default)]
     pub auth_type: String,
 
     pub password: Option<String>,
@@ -107,6 +109,10 @@ pub struct HostUpdateRequest {
 
     #[validate(range(min = 1000, max = 300000))]
     pub keepalive_interval_ms: Option<u32>,
+
+    /// 更新后把新的连接信息同步到由该主机创建的隧道和同步任务
+    #[serde(default)]
+    pub resync: bool,
 }
 
 /// 批量删除请求
@@ -123,6 +129,13 @@ pub struct ImportHostsRequest {
     pub replace_existing: bool,
 }
 
+/// SSH 测试查询参数
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SSHTestParams {
+    /// 连接超时（秒），默认 5，最大 60
+    pub timeout_secs: Option<u64>,
+}
+
 /// 列表查询参数
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct HostListParams {
@@ -17,6 +17,7 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/api/v1/hosts/{id}", get(get_host))
         .route("/api/v1/hosts/{id}", put(update_host))
         .route("/api/v1/hosts/{id}", delete(delete_host))
+        .route("/api/v1/hosts/{id}/resync", post(resync_host))
         // 主机测试
         .route("/api/v1/hosts/{id}/test/ssh", post(test_ssh))
         .route("/api/v1/hosts/{id}/test/ping", post(test_ping))
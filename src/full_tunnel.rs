@@ -1,7 +1,11 @@
-use crate::{save_config, AppState, TcpTunnelConfig, TcpTunnelManagedBy, TcpTunnelSetConfig};
+use crate::{
+    save_config, AppState, TcpTunnelConfig, TcpTunnelDirection, TcpTunnelManagedBy,
+    TcpTunnelSetConfig, TcpTunnelSetPortStrategy, TcpTunnelSetScanMethod,
+};
 use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::sync::Arc;
-use tokio::sync::{Mutex, watch};
+use tokio::sync::{Mutex, Notify, watch};
 use tokio::time::{sleep, Duration, Instant};
 
 #[derive(Clone, Debug, Default)]
@@ -11,8 +15,23 @@ pub struct FullTunnelSetRuntime {
     pub last_error: Option<String>,
 }
 
+/// Result of an on-demand scan (POST /api/tcp-tunnel-sets/{id}/scan-now).
+#[derive(Clone, Debug, Default)]
+pub struct FullTunnelScanReport {
+    /// Scan method that produced the result (ss / netstat / proc).
+    pub method: Option<&'static str>,
+    /// All local listening ports found by the scan, before include/exclude filtering.
+    pub raw_ports: Vec<u16>,
+    /// Ports left after include/exclude filtering, i.e. what the set would tunnel.
+    pub candidate_ports: Vec<u16>,
+    pub error: Option<String>,
+    /// Whether a running set loop was woken to reconcile immediately.
+    pub triggered: bool,
+}
+
 struct SetHandle {
     stop_tx: watch::Sender<bool>,
+    wake: Arc<Notify>,
     join: tokio::task::JoinHandle<()>,
 }
 
@@ -37,6 +56,38 @@ impl FullTunnelManager {
         m.get(set_id).cloned().unwrap_or_default()
     }
 
+    /// Scan listening ports right now and wake the set loop so it reconciles without
+    /// waiting for `scan_interval_ms`.
+    pub async fn scan_now(&self, set_cfg: &TcpTunnelSetConfig) -> FullTunnelScanReport {
+        let mut report = FullTunnelScanReport::default();
+        match scan_listen_ports(set_cfg.scan_method, set_cfg.skip_loopback).await {
+            Ok((method, raw)) => {
+                report.method = Some(method);
+                let mut candidates: Vec<u16> = filter_ports(set_cfg, &raw).into_iter().collect();
+                candidates.sort_unstable();
+                let mut raw: Vec<u16> = raw.into_iter().collect();
+                raw.sort_unstable();
+                report.raw_ports = raw;
+                report.candidate_ports = candidates;
+            }
+            Err(e) => report.error = Some(e),
+        }
+
+        {
+            let mut st = self.inner.status.lock().await;
+            let entry = st.entry(set_cfg.id.clone()).or_default();
+            entry.last_scan_at = Some(Instant::now());
+            entry.last_error = report.error.clone();
+        }
+
+        let handles = self.inner.handles.lock().await;
+        if let Some(h) = handles.get(&set_cfg.id) {
+            h.wake.notify_one();
+            report.triggered = true;
+        }
+        report
+    }
+
     pub async fn sync_from_config(&self, state: Arc<AppState>, sets: Vec<TcpTunnelSetConfig>) {
         let mut handles = self.inner.handles.lock().await;
         let mut desired: HashMap<String, TcpTunnelSetConfig> = HashMap::new();
@@ -79,13 +130,15 @@ impl FullTunnelManager {
             }
 
             let (stop_tx, stop_rx) = watch::channel(false);
+            let wake = Arc::new(Notify::new());
             let state_clone = state.clone();
             let manager = self.clone();
+            let wake_clone = wake.clone();
             let join = tokio::spawn(async move {
-                run_set_loop(manager, state_clone, cfg, stop_rx).await;
+                run_set_loop(manager, state_clone, cfg, stop_rx, wake_clone).await;
             });
 
-            handles.insert(id.clone(), SetHandle { stop_tx, join });
+            handles.insert(id.clone(), SetHandle { stop_tx, wake, join });
             let mut st = self.inner.status.lock().await;
             st.entry(id.clone()).or_default().enabled = true;
         }
@@ -102,6 +155,7 @@ async fn run_set_loop(
     state: Arc<AppState>,
     set_cfg: TcpTunnelSetConfig,
     mut stop_rx: watch::Receiver<bool>,
+    wake: Arc<Notify>,
 ) {
     let mut missing_since: HashMap<u16, Instant> = HashMap::new();
     let scan_interval = Duration::from_millis(set_cfg.scan_interval_ms.max(500).min(60_000));
@@ -122,27 +176,21 @@ async fn run_set_loop(
             entry.last_error = None;
         }
 
-        let ports_now = match scan_listen_ports().await {
-            Ok(p) => p,
+        let ports_now = match scan_listen_ports(set_cfg.scan_method, set_cfg.skip_loopback).await {
+            Ok((_, p)) => p,
             Err(e) => {
                 let mut st = manager.inner.status.lock().await;
                 st.entry(set_cfg.id.clone()).or_default().last_error = Some(e);
                 tokio::select! {
                     _ = sleep(scan_interval) => {},
                     _ = stop_rx.changed() => {},
+                    _ = wake.notified() => {},
                 }
                 continue;
             }
         };
 
-        let mut ports_now: HashSet<u16> = ports_now
-            .into_iter()
-            .filter(|p| !set_cfg.exclude_ports.iter().any(|x| x == p))
-            .collect();
-        if set_cfg.include_ports_enabled {
-            let include: HashSet<u16> = set_cfg.include_ports.iter().cloned().collect();
-            ports_now.retain(|p| include.contains(p));
-        }
+        let ports_now = filter_ports(&set_cfg, &ports_now);
 
         // Build managed map (port -> tunnel id)
         let (managed_map, all_tunnels) = {
@@ -185,6 +233,17 @@ async fn run_set_loop(
                         }
                     }
                     if need_apply {
+                        crate::broadcast_event(
+                            "info",
+                            "tunnel_set",
+                            &set_cfg.id,
+                            "port_removed",
+                            &format!(
+                                "TCP tunnel set {}: port {} gone, tunnel {} removed",
+                                set_cfg.id, p, tid
+                            ),
+                            serde_json::json!({ "local_port": p, "tunnel_id": tid }),
+                        );
                         let tunnels = { state.config.lock().await.tcp_tunnels.clone() };
                         state.tcp_tunnel.apply_config(&tunnels).await;
                     }
@@ -211,8 +270,11 @@ async fn run_set_loop(
                 let end = (idx + batch_size).min(to_add.len());
                 let chunk = &to_add[idx..end];
                 let mut changed = false;
+                let mut added: Vec<(u16, u16, String)> = Vec::new();
+                let mut mapping_errors: Vec<String> = Vec::new();
                 {
                     let mut cfg = state.config.lock().await;
+                    let mut claimed = claimed_remote_ports(&cfg.tcp_tunnels, &set_cfg.ssh_host);
                     for p in chunk {
                         // set-dimension existence check is enough; do not touch existing entries
                         let exists = cfg.tcp_tunnels.iter().any(|t| {
@@ -228,21 +290,33 @@ async fn run_set_loop(
                             continue;
                         }
 
+                        let remote_port = match resolve_remote_port(&set_cfg, *p, &claimed) {
+                            Ok(rp) => rp,
+                            Err(e) => {
+                                mapping_errors.push(e);
+                                continue;
+                            }
+                        };
+                        claimed.insert(remote_port);
+
                         let id = crate::generate_tunnel_id();
+                        added.push((*p, remote_port, id.clone()));
                         cfg.tcp_tunnels.push(TcpTunnelConfig {
                             id,
                             name: None,
                             enabled: set_cfg.enabled,
+                            direction: TcpTunnelDirection::Reverse,
                             local_addr: "127.0.0.1".to_string(),
                             local_port: *p,
                             remote_bind_addr: set_cfg.remote_bind_addr.clone(),
-                            remote_port: *p,
+                            remote_port,
                             ssh_host: set_cfg.ssh_host.clone(),
                             ssh_port: set_cfg.ssh_port,
                             username: set_cfg.username.clone(),
                             auth: set_cfg.auth.clone(),
                             strict_host_key_checking: set_cfg.strict_host_key_checking,
                             host_key_fingerprint: set_cfg.host_key_fingerprint.clone(),
+                            known_hosts_path: set_cfg.known_hosts_path.clone(),
                             allow_public_bind: set_cfg.remote_bind_addr == "0.0.0.0",
                             connect_timeout_ms: set_cfg.connect_timeout_ms,
                             keepalive_interval_ms: 10_000,
@@ -251,6 +325,7 @@ async fn run_set_loop(
                                 set_id: set_cfg.id.clone(),
                                 managed_port: *p,
                             }),
+                            host_id: None,
                         });
                         changed = true;
                     }
@@ -258,6 +333,28 @@ async fn run_set_loop(
                         let _ = save_config(&cfg).await;
                     }
                 }
+                if !mapping_errors.is_empty() {
+                    let mut st = manager.inner.status.lock().await;
+                    st.entry(set_cfg.id.clone()).or_default().last_error =
+                        Some(mapping_errors.join("; "));
+                }
+                for (local_port, remote_port, tunnel_id) in added.iter() {
+                    crate::broadcast_event(
+                        "info",
+                        "tunnel_set",
+                        &set_cfg.id,
+                        "port_added",
+                        &format!(
+                            "TCP tunnel set {}: port {} -> remote {} (tunnel {})",
+                            set_cfg.id, local_port, remote_port, tunnel_id
+                        ),
+                        serde_json::json!({
+                            "local_port": local_port,
+                            "remote_port": remote_port,
+                            "tunnel_id": tunnel_id,
+                        }),
+                    );
+                }
                 if changed {
                     let tunnels = { state.config.lock().await.tcp_tunnels.clone() };
                     state.tcp_tunnel.apply_config(&tunnels).await;
@@ -282,21 +379,335 @@ async fn run_set_loop(
         tokio::select! {
             _ = sleep(scan_interval) => {},
             _ = stop_rx.changed() => {},
+            _ = wake.notified() => {},
+        }
+    }
+}
+
+/// Sorted, non-overlapping inclusive port ranges.
+type PortRanges = Vec<(u16, u16)>;
+
+/// Sort and merge overlapping or adjacent ranges.
+fn merge_ranges(mut ranges: PortRanges) -> PortRanges {
+    ranges.sort_unstable();
+    let mut merged: PortRanges = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn ranges_intersect(a: &[(u16, u16)], b: &[(u16, u16)]) -> bool {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let ((a0, a1), (b0, b1)) = (a[i], b[j]);
+        if a0 <= b1 && b0 <= a1 {
+            return true;
+        }
+        if a1 < b1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    false
+}
+
+/// Local ports a set maps: the include list, or every port, minus the exclude list.
+fn local_port_ranges(set_cfg: &TcpTunnelSetConfig) -> PortRanges {
+    let mut excluded: Vec<u16> = set_cfg.exclude_ports.clone();
+    excluded.sort_unstable();
+    excluded.dedup();
+    if set_cfg.include_ports_enabled {
+        return merge_ranges(
+            set_cfg
+                .include_ports
+                .iter()
+                .filter(|p| **p != 0 && excluded.binary_search(p).is_err())
+                .map(|p| (*p, *p))
+                .collect(),
+        );
+    }
+    let mut ranges = Vec::new();
+    let mut start: u32 = 1;
+    for p in excluded.into_iter().filter(|p| *p != 0) {
+        if p as u32 > start {
+            ranges.push((start as u16, p - 1));
+        }
+        start = p as u32 + 1;
+    }
+    if start <= u16::MAX as u32 {
+        ranges.push((start as u16, u16::MAX));
+    }
+    ranges
+}
+
+/// Remote ports a set can claim: the mapped include list, or the whole mapped range
+/// when it scans every listener. `auto` allocates upwards from `port_base`, so it is
+/// modelled as `port_base..` (limited to as many ports as the include list has).
+fn remote_port_claims(set_cfg: &TcpTunnelSetConfig) -> PortRanges {
+    let local = local_port_ranges(set_cfg);
+    match set_cfg.port_strategy {
+        TcpTunnelSetPortStrategy::Same => local,
+        TcpTunnelSetPortStrategy::Offset => {
+            let base = set_cfg.port_base as u32;
+            local
+                .into_iter()
+                .filter(|(start, _)| base + (*start as u32) <= u16::MAX as u32)
+                .map(|(start, end)| {
+                    (
+                        (base + start as u32) as u16,
+                        (base + end as u32).min(u16::MAX as u32) as u16,
+                    )
+                })
+                .collect()
+        }
+        TcpTunnelSetPortStrategy::Auto => {
+            let count: u32 = local.iter().map(|(s, e)| (*e - *s) as u32 + 1).sum();
+            if count == 0 {
+                return Vec::new();
+            }
+            let start = set_cfg.port_base.max(1);
+            let end = if set_cfg.include_ports_enabled {
+                (start as u32 + count - 1).min(u16::MAX as u32) as u16
+            } else {
+                u16::MAX
+            };
+            vec![(start, end)]
+        }
+    }
+}
+
+/// Whether two sets' remote listeners can clash: a wildcard bind covers every address
+/// on the host, otherwise only the same address collides.
+fn remote_binds_overlap(a: &str, b: &str) -> bool {
+    fn normalize(addr: &str) -> &str {
+        match addr.trim() {
+            "localhost" => "127.0.0.1",
+            other => other,
+        }
+    }
+    let wildcard = |addr: &str| matches!(normalize(addr), "" | "0.0.0.0" | "::" | "*");
+    wildcard(a) || wildcard(b) || normalize(a) == normalize(b)
+}
+
+fn sets_may_collide(a: &TcpTunnelSetConfig, b: &TcpTunnelSetConfig) -> bool {
+    a.id != b.id
+        && a.ssh_host == b.ssh_host
+        && remote_binds_overlap(&a.remote_bind_addr, &b.remote_bind_addr)
+}
+
+/// Ids of other enabled sets whose remote ports on the same SSH host and bind
+/// address intersect this one's.
+pub(crate) fn find_set_conflicts(
+    set_cfg: &TcpTunnelSetConfig,
+    sets: &[TcpTunnelSetConfig],
+) -> Vec<String> {
+    let mut mine: Option<PortRanges> = None;
+    sets.iter()
+        .filter(|other| other.enabled && sets_may_collide(set_cfg, other))
+        .filter(|other| {
+            let mine = mine.get_or_insert_with(|| remote_port_claims(set_cfg));
+            ranges_intersect(mine, &remote_port_claims(other))
+        })
+        .map(|other| other.id.clone())
+        .collect()
+}
+
+/// Conflicts of every enabled set, keyed by set id; each set's claims are computed once.
+pub(crate) fn all_set_conflicts(sets: &[TcpTunnelSetConfig]) -> HashMap<String, Vec<String>> {
+    let enabled: Vec<(&TcpTunnelSetConfig, PortRanges)> = sets
+        .iter()
+        .filter(|s| s.enabled)
+        .map(|s| (s, remote_port_claims(s)))
+        .collect();
+    let mut conflicts: HashMap<String, Vec<String>> = HashMap::new();
+    for (i, (a, a_claims)) in enabled.iter().enumerate() {
+        for (b, b_claims) in enabled.iter().skip(i + 1) {
+            if sets_may_collide(a, b) && ranges_intersect(a_claims, b_claims) {
+                conflicts.entry(a.id.clone()).or_default().push(b.id.clone());
+                conflicts.entry(b.id.clone()).or_default().push(a.id.clone());
+            }
+        }
+    }
+    conflicts
+}
+
+/// Remote ports already bound (or about to be) on `ssh_host` by any configured tunnel.
+pub(crate) fn claimed_remote_ports(tunnels: &[TcpTunnelConfig], ssh_host: &str) -> HashSet<u16> {
+    tunnels
+        .iter()
+        // Forward tunnels do not bind a remote port.
+        .filter(|t| t.ssh_host == ssh_host && t.direction == TcpTunnelDirection::Reverse)
+        .map(|t| t.remote_port)
+        .collect()
+}
+
+/// Map a scanned local port to the remote port according to the set's strategy.
+pub(crate) fn resolve_remote_port(
+    set_cfg: &TcpTunnelSetConfig,
+    local_port: u16,
+    claimed: &HashSet<u16>,
+) -> Result<u16, String> {
+    let remote_port = match set_cfg.port_strategy {
+        TcpTunnelSetPortStrategy::Same => local_port,
+        TcpTunnelSetPortStrategy::Offset => set_cfg
+            .port_base
+            .checked_add(local_port)
+            .ok_or_else(|| {
+                format!(
+                    "port {local_port}: port_base {} + {local_port} exceeds 65535",
+                    set_cfg.port_base
+                )
+            })?,
+        TcpTunnelSetPortStrategy::Auto => {
+            return (set_cfg.port_base.max(1)..=u16::MAX)
+                .find(|rp| !claimed.contains(rp))
+                .ok_or_else(|| {
+                    format!(
+                        "port {local_port}: no free remote port >= {}",
+                        set_cfg.port_base
+                    )
+                });
         }
+    };
+    if claimed.contains(&remote_port) {
+        return Err(format!(
+            "port {local_port}: remote port {remote_port} on {} is already used by another tunnel",
+            set_cfg.ssh_host
+        ));
     }
+    Ok(remote_port)
 }
 
-async fn scan_listen_ports() -> Result<HashSet<u16>, String> {
-    if let Ok(p) = scan_from_ss().await {
-        return Ok(p);
+/// Apply the set's exclude/include lists to scanned ports.
+fn filter_ports(set_cfg: &TcpTunnelSetConfig, ports: &HashSet<u16>) -> HashSet<u16> {
+    let mut out: HashSet<u16> = ports
+        .iter()
+        .filter(|p| !set_cfg.exclude_ports.contains(p))
+        .cloned()
+        .collect();
+    if set_cfg.include_ports_enabled {
+        let include: HashSet<u16> = set_cfg.include_ports.iter().cloned().collect();
+        out.retain(|p| include.contains(p));
     }
-    if let Ok(p) = scan_from_netstat().await {
-        return Ok(p);
+    out
+}
+
+/// Scan local listening TCP ports; returns the method that succeeded with the ports.
+async fn scan_listen_ports(
+    method: TcpTunnelSetScanMethod,
+    skip_loopback: bool,
+) -> Result<(&'static str, HashSet<u16>), String> {
+    match method {
+        TcpTunnelSetScanMethod::Ss => scan_from_ss(skip_loopback).await.map(|p| ("ss", p)),
+        TcpTunnelSetScanMethod::Netstat => scan_from_netstat(skip_loopback).await.map(|p| ("netstat", p)),
+        TcpTunnelSetScanMethod::Proc => scan_from_proc(skip_loopback).await.map(|p| ("proc", p)),
+        TcpTunnelSetScanMethod::Auto => {
+            let mut errors: Vec<String> = Vec::new();
+            match scan_from_ss(skip_loopback).await {
+                Ok(p) => return Ok(("ss", p)),
+                Err(e) => errors.push(e),
+            }
+            match scan_from_netstat(skip_loopback).await {
+                Ok(p) => return Ok(("netstat", p)),
+                Err(e) => errors.push(e),
+            }
+            match scan_from_proc(skip_loopback).await {
+                Ok(p) => return Ok(("proc", p)),
+                Err(e) => errors.push(e),
+            }
+            Err(format!("Failed to scan ports: {}", errors.join("; ")))
+        }
     }
-    Err("Failed to scan ports: ss and netstat both failed".to_string())
 }
 
-async fn scan_from_ss() -> Result<HashSet<u16>, String> {
+async fn scan_from_proc(skip_loopback: bool) -> Result<HashSet<u16>, String> {
+    let mut ports: HashSet<u16> = HashSet::new();
+    let mut read_any = false;
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        // tcp6 is absent when IPv6 is disabled; only fail if neither file is readable.
+        let Ok(text) = tokio::fs::read_to_string(path).await else {
+            continue;
+        };
+        read_any = true;
+        ports.extend(parse_proc_net_tcp(&text, skip_loopback));
+    }
+    if !read_any {
+        return Err("read /proc/net/tcp failed".to_string());
+    }
+    Ok(ports)
+}
+
+/// Parse /proc/net/tcp{,6}: `sl local_address rem_address st ...`, where local_address is
+/// `HEXADDR:HEXPORT` and state 0A is LISTEN.
+fn parse_proc_net_tcp(text: &str, skip_loopback: bool) -> HashSet<u16> {
+    let mut ports: HashSet<u16> = HashSet::new();
+    for line in text.lines().skip(1) {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 4 || cols[3] != "0A" {
+            continue;
+        }
+        if let Some((addr_hex, port_hex)) = cols[1].rsplit_once(':') {
+            if skip_loopback && proc_addr_is_loopback(addr_hex) {
+                continue;
+            }
+            if let Ok(p) = u16::from_str_radix(port_hex, 16) {
+                ports.insert(p);
+            }
+        }
+    }
+    ports
+}
+
+/// /proc/net/tcp{,6} addresses are printed as 32-bit words in host byte order.
+fn proc_addr_is_loopback(addr_hex: &str) -> bool {
+    if addr_hex.len() % 8 != 0 {
+        return false;
+    }
+    let mut bytes: Vec<u8> = Vec::with_capacity(16);
+    for i in (0..addr_hex.len()).step_by(8) {
+        let Some(word) = addr_hex.get(i..i + 8).and_then(|w| u32::from_str_radix(w, 16).ok())
+        else {
+            return false;
+        };
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::from([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes);
+            IpAddr::from(octets)
+        }
+        _ => return false,
+    };
+    ip_is_loopback(ip)
+}
+
+fn ip_is_loopback(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_loopback(),
+        IpAddr::V6(v6) => {
+            v6.is_loopback() || v6.to_ipv4_mapped().map(|v4| v4.is_loopback()).unwrap_or(false)
+        }
+    }
+}
+
+/// `127.0.0.1:80`, `[::1]:80`, `::1:80`, `127.0.0.53%lo:53` -> loopback?
+fn listener_is_loopback(local: &str) -> bool {
+    let Some((addr, _)) = local.trim().rsplit_once(':') else {
+        return false;
+    };
+    let addr = addr.trim_start_matches('[').trim_end_matches(']');
+    let addr = addr.split('%').next().unwrap_or(addr);
+    addr.parse::<IpAddr>().map(ip_is_loopback).unwrap_or(false)
+}
+
+async fn scan_from_ss(skip_loopback: bool) -> Result<HashSet<u16>, String> {
     let out = tokio::process::Command::new("ss")
         .args(["-plunt"])
         .output()
@@ -306,10 +717,10 @@ async fn scan_from_ss() -> Result<HashSet<u16>, String> {
         return Err(format!("ss failed: {}", out.status));
     }
     let text = String::from_utf8_lossy(&out.stdout);
-    parse_ss_output(&text)
+    parse_ss_output(&text, skip_loopback)
 }
 
-fn parse_ss_output(text: &str) -> Result<HashSet<u16>, String> {
+fn parse_ss_output(text: &str, skip_loopback: bool) -> Result<HashSet<u16>, String> {
     let mut ports: HashSet<u16> = HashSet::new();
     for line in text.lines() {
         let l = line.trim();
@@ -326,6 +737,9 @@ fn parse_ss_output(text: &str) -> Result<HashSet<u16>, String> {
             continue;
         }
         // Local Address:Port is usually at index 4 for ss -plunt
+        if skip_loopback && listener_is_loopback(cols[4]) {
+            continue;
+        }
         if let Some(p) = extract_port(cols[4]) {
             ports.insert(p);
         }
@@ -333,7 +747,7 @@ fn parse_ss_output(text: &str) -> Result<HashSet<u16>, String> {
     Ok(ports)
 }
 
-async fn scan_from_netstat() -> Result<HashSet<u16>, String> {
+async fn scan_from_netstat(skip_loopback: bool) -> Result<HashSet<u16>, String> {
     let out = tokio::process::Command::new("netstat")
         .args(["-anltp"])
         .output()
@@ -343,10 +757,10 @@ async fn scan_from_netstat() -> Result<HashSet<u16>, String> {
         return Err(format!("netstat failed: {}", out.status));
     }
     let text = String::from_utf8_lossy(&out.stdout);
-    parse_netstat_output(&text)
+    parse_netstat_output(&text, skip_loopback)
 }
 
-fn parse_netstat_output(text: &str) -> Result<HashSet<u16>, String> {
+fn parse_netstat_output(text: &str, skip_loopback: bool) -> Result<HashSet<u16>, String> {
     let mut ports: HashSet<u16> = HashSet::new();
     for line in text.lines() {
         let l = line.trim();
@@ -363,6 +777,9 @@ fn parse_netstat_output(text: &str) -> Result<HashSet<u16>, String> {
         if proto != "tcp" || state != "LISTEN" {
             continue;
         }
+        if skip_loopback && listener_is_loopback(local) {
+            continue;
+        }
         if let Some(p) = extract_port(local) {
             ports.insert(p);
         }
@@ -378,3 +795,100 @@ fn extract_port(s: &str) -> Option<u16> {
     let port_str = it.next()?;
     port_str.parse::<u16>().ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn set(id: &str, extra: serde_json::Value) -> TcpTunnelSetConfig {
+        let mut value = json!({
+            "id": id,
+            "enabled": true,
+            "ssh_host": "203.0.113.1",
+            "username": "root",
+            "auth": { "type": "password", "password": "x" },
+        });
+        for (k, v) in extra.as_object().unwrap() {
+            value[k] = v.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn resolve_remote_port_cases() {
+        let claimed: HashSet<u16> = [8080, 10000, 10001].into_iter().collect();
+        let cases = [
+            ("same", json!({}), 3000, Ok(3000)),
+            ("same claimed", json!({}), 8080, Err(())),
+            ("offset", json!({ "port_strategy": "offset", "port_base": 20000 }), 80, Ok(20080)),
+            ("offset overflow", json!({ "port_strategy": "offset", "port_base": 65000 }), 1000, Err(())),
+            ("offset claimed", json!({ "port_strategy": "offset", "port_base": 8000 }), 80, Err(())),
+            ("auto skips claimed", json!({ "port_strategy": "auto", "port_base": 10000 }), 22, Ok(10002)),
+            ("auto base 0 starts at 1", json!({ "port_strategy": "auto" }), 22, Ok(1)),
+        ];
+        for (name, extra, local, want) in cases {
+            let got = resolve_remote_port(&set("s", extra), local, &claimed).map_err(|_| ());
+            assert_eq!(got, want, "{}", name);
+        }
+    }
+
+    #[test]
+    fn remote_port_claims_cases() {
+        let cases = [
+            ("same include", json!({ "include_ports_enabled": true, "include_ports": [22, 23, 80] }), vec![(22, 23), (80, 80)]),
+            ("same all minus excludes", json!({ "exclude_ports": [22, 1] }), vec![(2, 21), (23, 65535)]),
+            (
+                "offset clamps at 65535",
+                json!({ "port_strategy": "offset", "port_base": 60000, "exclude_ports": [10] }),
+                vec![(60001, 60009), (60011, 65535)],
+            ),
+            (
+                "auto include sized",
+                json!({ "port_strategy": "auto", "port_base": 30000, "include_ports_enabled": true, "include_ports": [1, 2, 3] }),
+                vec![(30000, 30002)],
+            ),
+            ("auto empty include", json!({ "port_strategy": "auto", "include_ports_enabled": true }), vec![]),
+        ];
+        for (name, extra, want) in cases {
+            assert_eq!(remote_port_claims(&set("s", extra)), want, "{}", name);
+        }
+    }
+
+    #[test]
+    fn find_set_conflicts_cases() {
+        let include = |ports: &[u16]| json!({ "include_ports_enabled": true, "include_ports": ports });
+        let cases = [
+            ("overlapping include", include(&[80, 443]), include(&[443]), true),
+            ("disjoint include", include(&[80]), include(&[81]), false),
+            ("scan-all overlaps include", json!({}), include(&[8080]), true),
+            ("different ssh host", include(&[80]), json!({ "ssh_host": "203.0.113.2", "include_ports_enabled": true, "include_ports": [80] }), false),
+            (
+                "different bind address",
+                json!({ "remote_bind_addr": "127.0.0.1", "include_ports_enabled": true, "include_ports": [80] }),
+                json!({ "remote_bind_addr": "10.0.0.1", "include_ports_enabled": true, "include_ports": [80] }),
+                false,
+            ),
+            (
+                "wildcard bind covers every address",
+                json!({ "remote_bind_addr": "0.0.0.0", "include_ports_enabled": true, "include_ports": [80] }),
+                json!({ "remote_bind_addr": "10.0.0.1", "include_ports_enabled": true, "include_ports": [80] }),
+                true,
+            ),
+            (
+                "offset shifts into the other range",
+                json!({ "port_strategy": "offset", "port_base": 10000, "include_ports_enabled": true, "include_ports": [80] }),
+                include(&[10080]),
+                true,
+            ),
+            ("disabled other", include(&[80]), json!({ "enabled": false, "include_ports_enabled": true, "include_ports": [80] }), false),
+        ];
+        for (name, a, b, want) in cases {
+            let sets = [set("a", a), set("b", b)];
+            let got = find_set_conflicts(&sets[0], &sets);
+            assert_eq!(!got.is_empty(), want, "{}", name);
+            let all = all_set_conflicts(&sets);
+            assert_eq!(all.get("a").is_some_and(|c| c == &["b"]), want, "{} (all)", name);
+        }
+    }
+}
@@ -6,7 +6,7 @@ use axum::{
     http::{Request, StatusCode, HeaderMap},
     middleware::{self, Next},
     response::{Json, Response},
-    routing::{delete, get, post, put},
+    routing::{any, delete, get, post, put},
     Router,
 };
 use base64::Engine;
@@ -28,21 +28,26 @@ use std::path::{Path as StdPath, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use tokio_tungstenite::connect_async;
 use tokio::sync::{broadcast, Mutex};
 use tokio::task::spawn_blocking;
 use tokio::time::sleep;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use chrono::Utc;
 use rust_embed::RustEmbed;
 use axum::extract::DefaultBodyLimit;
 use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use std::convert::Infallible;
 
 mod tcp_tunnel;
 mod full_tunnel;
 mod sync;
 mod app;
+mod openapi;
+mod secrets;
 
 // Version embedded at compile time
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -112,10 +117,24 @@ fn default_metrics_retention_days() -> u32 {
     7
 }
 
+fn default_metrics_rollup_retention_days() -> u32 {
+    365
+}
+
 fn default_metrics_sample_interval_secs() -> u64 {
     5
 }
 
+const SYSTEM_CACHE_WARMUP_TIMEOUT_SECS: u64 = 5;
+
+fn default_metrics_cache_ttl_secs() -> u64 {
+    10
+}
+
+fn default_metrics_sample_stagger_ms() -> u64 {
+    2_000
+}
+
 fn default_terminal_addr() -> String {
     "127.0.0.1".to_string()
 }
@@ -139,7 +158,7 @@ fn default_tcp_tunnel_backoff() -> TcpTunnelBackoff {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum TcpTunnelAuth {
     Password { password: String },
@@ -150,7 +169,7 @@ enum TcpTunnelAuth {
     },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
 struct TcpTunnelBackoff {
     base_ms: u64,
     max_ms: u64,
@@ -164,6 +183,11 @@ struct TcpTunnelConfig {
     #[serde(default)]
     enabled: bool,
 
+    /// reverse: 远端监听 remote_bind_addr:remote_port 转发到本地（ssh -R）；
+    /// forward: 本地监听 local_addr:local_port，经 SSH 服务器连到 remote_bind_addr:remote_port（ssh -L）
+    #[serde(default)]
+    direction: TcpTunnelDirection,
+
     #[serde(default = "default_local_addr")]
     local_addr: String,
     local_port: u16,
@@ -183,6 +207,8 @@ struct TcpTunnelConfig {
     strict_host_key_checking: bool,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     host_key_fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    known_hosts_path: Option<String>, // OpenSSH known_hosts 文件，可替代或补充 host_key_fingerprint
     #[serde(default)]
     allow_public_bind: bool,
 
@@ -196,6 +222,19 @@ struct TcpTunnelConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     managed_by: Option<TcpTunnelManagedBy>,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host_id: Option<String>, // 创建/更新时引用的主机 id，用于对比主机配置是否已变化
+
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum TcpTunnelDirection {
+    /// remote bind -> local target (ssh -R)
+    #[default]
+    Reverse,
+    /// local bind -> target reachable from the SSH server (ssh -L)
+    Forward,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -226,6 +265,8 @@ struct TcpTunnelSetConfig {
     strict_host_key_checking: bool,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     host_key_fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    known_hosts_path: Option<String>, // OpenSSH known_hosts 文件，可替代或补充 host_key_fingerprint
 
     #[serde(default)]
     include_ports_enabled: bool,
@@ -244,6 +285,42 @@ struct TcpTunnelSetConfig {
     #[serde(default = "default_tunnel_set_start_batch_interval_ms")]
     start_batch_interval_ms: u64,
 
+    /// How remote ports are derived from scanned local listen ports.
+    #[serde(default)]
+    port_strategy: TcpTunnelSetPortStrategy,
+    /// Base port used by the `offset` and `auto` strategies.
+    #[serde(default)]
+    port_base: u16,
+    /// How local listening ports are discovered.
+    #[serde(default)]
+    scan_method: TcpTunnelSetScanMethod,
+    /// Ignore listeners bound only to loopback (127.0.0.0/8, ::1).
+    #[serde(default)]
+    skip_loopback: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum TcpTunnelSetPortStrategy {
+    /// remote port = local port
+    #[default]
+    Same,
+    /// remote port = port_base + local port
+    Offset,
+    /// lowest unclaimed remote port >= port_base on the same SSH host
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum TcpTunnelSetScanMethod {
+    /// try ss, then netstat, then /proc/net/tcp
+    #[default]
+    Auto,
+    Ss,
+    Netstat,
+    /// parse /proc/net/tcp and /proc/net/tcp6 directly (no external command)
+    Proc,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -276,7 +353,7 @@ struct SyncSshConfig {
     auth: TcpTunnelAuth,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default, ToSchema)]
 #[serde(default)]
 struct SyncOptions {
     #[serde(default)]
@@ -299,7 +376,7 @@ struct SyncOptions {
 
 fn default_compression_level() -> u8 { 3 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
 #[serde(default)]
 struct SyncSchedule {
     #[serde(default = "default_true")]
@@ -336,6 +413,17 @@ struct SyncConfig {
     options: SyncOptions,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     schedule: Option<SyncSchedule>,
+    /// 经由该 TCP 隧道（同一 SSH 主机）确认可达后才运行/启用调度
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    via_tunnel_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host_id: Option<String>, // 创建/更新时引用的主机 id，主机变更后可重新同步
+    /// 传输前在本地执行的命令（sh -c），非 0 退出则中止本次同步
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pre_command: Option<String>,
+    /// 传输后在本地执行的命令，pre_command 成功后无论传输成败都会执行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    post_command: Option<String>,
 }
 
 impl Default for SyncConfig {
@@ -356,6 +444,10 @@ impl Default for SyncConfig {
             },
             options: SyncOptions::default(),
             schedule: None,
+            via_tunnel_id: None,
+            host_id: None,
+            pre_command: None,
+            post_command: None,
         }
     }
 }
@@ -411,6 +503,8 @@ struct TerminalNodeConfig {
     auth_password: Option<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     extra_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    startup_check_ms: Option<u64>,  // 启动后存活/端口检查时长，默认 DEFAULT_STARTUP_CHECK_MS
 }
 
 impl Default for TerminalNodeConfig {
@@ -426,6 +520,7 @@ impl Default for TerminalNodeConfig {
             auth_username: None,
             auth_password: None,
             extra_args: default_terminal_extra_args(),
+            startup_check_ms: None,
         }
     }
 }
@@ -448,6 +543,8 @@ struct AppConfig {
     args: Vec<String>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    startup_check_ms: Option<u64>,  // 启动后存活检查时长，默认 DEFAULT_STARTUP_CHECK_MS
 }
 
 impl Default for AppConfig {
@@ -461,6 +558,7 @@ impl Default for AppConfig {
             command: String::new(),
             args: Vec::new(),
             env: HashMap::new(),
+            startup_check_ms: None,
         }
     }
 }
@@ -512,6 +610,8 @@ struct IVncStatus {
     pid: Option<u32>,
     uptime_secs: Option<u64>,
     port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_exit: Option<ProcessExitInfo>,
 }
 
 struct IVncProcess {
@@ -528,8 +628,20 @@ struct MetricsConfig {
     storage_path: String,
     #[serde(default = "default_metrics_retention_days")]
     retention_days: u32,
+    /// Hourly rollups are kept this long after raw samples are pruned (0 = forever).
+    #[serde(default = "default_metrics_rollup_retention_days")]
+    rollup_retention_days: u32,
     #[serde(default = "default_metrics_sample_interval_secs")]
     sample_interval_secs: u64,
+    /// Random delay (0..=N ms) before the first sample.
+    #[serde(default = "default_metrics_sample_stagger_ms")]
+    sample_stagger_ms: u64,
+    /// Random +/- jitter applied to every interval, in percent (0 = off, max 50).
+    #[serde(default)]
+    sample_jitter_percent: u8,
+    /// System info/status caches older than this are refreshed on read (0 = never expire).
+    #[serde(default = "default_metrics_cache_ttl_secs")]
+    cache_ttl_secs: u64,
 }
 
 impl Default for MetricsConfig {
@@ -538,7 +650,11 @@ impl Default for MetricsConfig {
             enabled: default_metrics_enabled(),
             storage_path: default_metrics_storage_path(),
             retention_days: default_metrics_retention_days(),
+            rollup_retention_days: default_metrics_rollup_retention_days(),
             sample_interval_secs: default_metrics_sample_interval_secs(),
+            sample_stagger_ms: default_metrics_sample_stagger_ms(),
+            sample_jitter_percent: 0,
+            cache_ttl_secs: default_metrics_cache_ttl_secs(),
         }
     }
 }
@@ -546,7 +662,43 @@ impl Default for MetricsConfig {
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 enum SubscriptionSource {
-    Url { url: String },
+    Url {
+        url: String,
+        /// Extra request headers (e.g. Authorization) sent when fetching.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user_agent: Option<String>,
+    },
+    /// 浅克隆 Git 仓库，仓库根目录下的文件即订阅文件
+    Git {
+        repo: String,
+        /// 不设置则使用远端默认分支
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        branch: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        credentials: Option<GitCredentials>,
+    },
+}
+
+/// 拉取私有仓库的凭据
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GitCredentials {
+    /// HTTPS 访问令牌（如 GitHub PAT），以 Basic 认证头发送
+    Token {
+        token: String,
+        /// 默认 x-access-token
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    /// SSH 私钥路径，用于 git@ / ssh:// 仓库；主机密钥始终严格校验，
+    /// known_hosts_path 不设置时使用 ssh 默认的 ~/.ssh/known_hosts
+    KeyPath {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        known_hosts_path: Option<String>,
+    },
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -558,6 +710,9 @@ struct SubscriptionConfig {
     enabled: bool,
     #[serde(flatten)]
     source: SubscriptionSource,
+    /// 定时重新拉取的间隔（秒），不设置则只在手动 reload / 重新生成配置时更新
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refresh_interval_secs: Option<u64>,
 }
 
 // Host configuration for SSH connections
@@ -575,6 +730,19 @@ pub enum HostAuth {
     },
 }
 
+/// `default_ssh_auth` 转为主机认证
+pub(crate) fn host_auth_from_tunnel_auth(auth: &TcpTunnelAuth) -> HostAuth {
+    match auth {
+        TcpTunnelAuth::Password { password } => HostAuth::Password {
+            password: Some(password.clone()),
+        },
+        TcpTunnelAuth::PrivateKeyPath { path, passphrase } => HostAuth::PrivateKeyPath {
+            path: path.clone(),
+            passphrase: passphrase.clone(),
+        },
+    }
+}
+
 pub fn default_private_key_path() -> Option<String> {
     let candidates = ["id_ed25519", "id_rsa", "id_ecdsa", "id_dsa"];
     for name in candidates {
@@ -594,8 +762,95 @@ fn resolve_private_key_path(path: &str) -> Result<String, String> {
     default_private_key_path().ok_or_else(|| "Private key path is required".to_string())
 }
 
-/// Test SSH connection and return latency on success
-async fn test_ssh_connection(cfg: &HostConfig, all_hosts: Option<&[HostConfig]>) -> Result<f64, String> {
+/// 保存时校验私钥文件：存在、可读、能解析；全局可读时仅告警
+fn validate_private_key_file(path: &str, passphrase: Option<&str>) -> Result<(), String> {
+    let meta = fs::metadata(path)
+        .map_err(|e| format!("Private key file '{}' not found: {}", path, e))?;
+    if !meta.is_file() {
+        return Err(format!("Private key path '{}' is not a regular file", path));
+    }
+    if meta.permissions().mode() & 0o004 != 0 {
+        log_warning!("私钥文件 {} 对所有用户可读，建议执行 chmod 600", path);
+    }
+    fs::File::open(path)
+        .map_err(|e| format!("Private key file '{}' is not readable: {}", path, e))?;
+    russh::keys::load_secret_key(path, passphrase).map_err(|e| {
+        format!(
+            "Private key file '{}' could not be parsed (wrong passphrase or not a private key): {}",
+            path, e
+        )
+    })?;
+    Ok(())
+}
+
+fn validate_tunnel_auth_key(auth: &TcpTunnelAuth) -> Result<(), String> {
+    if let TcpTunnelAuth::PrivateKeyPath { path, passphrase } = auth {
+        let resolved = resolve_private_key_path(path)?;
+        validate_private_key_file(&resolved, passphrase.as_deref())?;
+    }
+    Ok(())
+}
+
+/// 去掉首尾空白，空字符串视为未设置
+fn normalize_known_hosts_path(path: Option<String>) -> Option<String> {
+    path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty())
+}
+
+/// 开启严格主机密钥校验时，指纹与 known_hosts 文件至少要有一个
+fn validate_host_key_settings(
+    strict_host_key_checking: bool,
+    host_key_fingerprint: &str,
+    known_hosts_path: Option<&str>,
+) -> Result<(), String> {
+    if !strict_host_key_checking {
+        return Ok(());
+    }
+    match known_hosts_path {
+        Some(path) if !StdPath::new(path).is_file() => {
+            Err(format!("known_hosts file not found: {}", path))
+        }
+        None if host_key_fingerprint.trim().is_empty() => Err(
+            "host_key_fingerprint or known_hosts_path is required when strict_host_key_checking is true"
+                .to_string(),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn validate_host_auth_key(auth: &HostAuth) -> Result<(), String> {
+    if let HostAuth::PrivateKeyPath { path, passphrase } = auth {
+        let resolved = resolve_private_key_path(path)?;
+        validate_private_key_file(&resolved, passphrase.as_deref())?;
+    }
+    Ok(())
+}
+
+/// 带口令的 bcrypt-KDF 私钥解析可能耗时数秒，handler 里通过阻塞线程池校验
+async fn check_tunnel_auth_key(auth: &TcpTunnelAuth) -> Result<(), String> {
+    let auth = auth.clone();
+    tokio::task::spawn_blocking(move || validate_tunnel_auth_key(&auth))
+        .await
+        .map_err(|e| format!("Private key check failed: {}", e))?
+}
+
+async fn check_host_auth_key(auth: &HostAuth) -> Result<(), String> {
+    let auth = auth.clone();
+    tokio::task::spawn_blocking(move || validate_host_auth_key(&auth))
+        .await
+        .map_err(|e| format!("Private key check failed: {}", e))?
+}
+
+/// 主机连接测试的默认/最大超时（秒），可由请求覆盖
+const HOST_TEST_DEFAULT_TIMEOUT_SECS: u64 = 5;
+const HOST_TEST_MAX_TIMEOUT_SECS: u64 = 60;
+
+/// Test SSH connection and return latency on success.
+/// `timeout_secs` overrides the host's connection timeout (and the 5s default used via jump hosts).
+async fn test_ssh_connection(
+    cfg: &HostConfig,
+    all_hosts: Option<&[HostConfig]>,
+    timeout_secs: Option<u64>,
+) -> Result<f64, String> {
     use russh::client;
     use russh::keys::key::PrivateKeyWithHashAlg;
     use russh::keys::load_secret_key;
@@ -621,7 +876,10 @@ async fn test_ssh_connection(cfg: &HostConfig, all_hosts: Option<&[HostConfig]>)
             let jump_host = hosts.iter()
                 .find(|h| &h.id == jump_id)
                 .ok_or_else(|| format!("Jump host '{}' not found in configuration", jump_id))?;
-            return test_ssh_via_jump(cfg, jump_host, start).await;
+            let timeout_secs = timeout_secs
+                .unwrap_or(HOST_TEST_DEFAULT_TIMEOUT_SECS)
+                .clamp(1, HOST_TEST_MAX_TIMEOUT_SECS);
+            return test_ssh_via_jump(cfg, jump_host, start, timeout_secs).await;
         }
     }
 
@@ -641,7 +899,10 @@ async fn test_ssh_connection(cfg: &HostConfig, all_hosts: Option<&[HostConfig]>)
     };
     let client_cfg = Arc::new(client_cfg);
     let addr = (cfg.host.as_str(), cfg.port);
-    let connect_timeout = Duration::from_millis(cfg.connection_timeout_ms.max(1000).min(60000));
+    let connect_timeout = match timeout_secs {
+        Some(secs) => Duration::from_secs(secs.clamp(1, HOST_TEST_MAX_TIMEOUT_SECS)),
+        None => Duration::from_millis(cfg.connection_timeout_ms.max(1000).min(60000)),
+    };
 
     let mut session = tokio::time::timeout(
         connect_timeout,
@@ -697,7 +958,12 @@ async fn test_ssh_connection(cfg: &HostConfig, all_hosts: Option<&[HostConfig]>)
     Ok(start.elapsed().as_secs_f64() * 1000.0)
 }
 
-async fn test_ssh_via_jump(target: &HostConfig, jump: &HostConfig, start: std::time::Instant) -> Result<f64, String> {
+async fn test_ssh_via_jump(
+    target: &HostConfig,
+    jump: &HostConfig,
+    start: std::time::Instant,
+    timeout_secs: u64,
+) -> Result<f64, String> {
     use russh::client;
     use russh::keys::key::PrivateKeyWithHashAlg;
     use russh::keys::load_secret_key;
@@ -750,9 +1016,11 @@ async fn test_ssh_via_jump(target: &HostConfig, jump: &HostConfig, start: std::t
     // 使用 shell 转义防止命令注入
     let escaped_host = target.host.replace("'", "'\\''");
     let escaped_user = target.username.replace("'", "'\\''");
+    // BatchMode 避免目标主机等待交互式密码输入而挂起
     let test_cmd = format!(
-        "nc -z -w 5 '{}' {} 2>&1 || ssh -o ConnectTimeout=5 -o StrictHostKeyChecking=no '{}@{}' -p {} exit 2>&1",
-        escaped_host, target.port, escaped_user, escaped_host, target.port
+        "nc -z -w {t} '{}' {} 2>&1 || ssh -o BatchMode=yes -o ConnectTimeout={t} -o StrictHostKeyChecking=no '{}@{}' -p {} exit 2>&1",
+        escaped_host, target.port, escaped_user, escaped_host, target.port,
+        t = timeout_secs
     );
     channel.exec(true, test_cmd).await.map_err(|e| format!("exec: {e:?}"))?;
 
@@ -762,6 +1030,10 @@ async fn test_ssh_via_jump(target: &HostConfig, jump: &HostConfig, start: std::t
             russh::ChannelMsg::Data { ref data } => output.extend_from_slice(data),
             russh::ChannelMsg::ExitStatus { exit_status } => {
                 if exit_status != 0 {
+                    let text = String::from_utf8_lossy(&output);
+                    if text.contains("Permission denied") {
+                        return Err("interactive auth not supported in test (target is reachable via jump host, but non-interactive login was refused)".to_string());
+                    }
                     return Err(format!("target unreachable via jump host (exit {})", exit_status));
                 }
                 break;
@@ -974,8 +1246,33 @@ pub struct HostTestResult {
     pub ping_avg_ms: Option<f64>,
 }
 
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+enum ConnectivityMethod {
+    #[serde(alias = "head")]
+    Head,
+    #[default]
+    #[serde(alias = "get")]
+    Get,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+struct ConnectivityPreset {
+    id: String,
+    name: String,
+    url: String,
+    #[serde(default)]
+    method: ConnectivityMethod,
+    /// Exact code ("204") or class ("2xx"); None accepts 2xx and 3xx.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expected_status: Option<String>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct Config {
+    /// 配置结构版本，加载时由 run_config_migrations 逐步升级（缺省为 0，即最早的格式）
+    #[serde(default)]
+    schema_version: u32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     port: Option<u16>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -983,6 +1280,32 @@ struct Config {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     password: Option<String>,  // 登录密码
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    jwt_secret: Option<String>,  // JWT 签名密钥，首次启动随机生成；轮换后所有已签发 token 失效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token_ttl_secs: Option<u64>,  // 登录 token 有效期（秒），默认 24 小时
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ui_enabled: Option<bool>,  // false: 只提供 API，不托管前端页面
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encrypt_secrets: Option<bool>,  // true: 密码/私钥口令加密后写入 config.yaml
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_body_bytes: Option<usize>,  // API 请求体大小上限（字节），默认 16 MiB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metrics_port: Option<u16>,  // 独立的只读监听端口，仅提供 /metrics 与 /healthz、/readyz（无需登录）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_tunnels: Option<usize>,  // TCP 隧道数量上限（不含完整隧道自动生成的），默认 DEFAULT_MAX_TUNNELS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_syncs: Option<usize>,  // 同步任务数量上限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_terminals: Option<usize>,  // 终端数量上限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_apps: Option<usize>,  // 应用数量上限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_vnc_sessions: Option<usize>,  // 同时运行的 iVnc 实例上限（目前最多 1 个，0 表示禁止启动）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls_cert_path: Option<String>,  // HTTPS 证书（PEM）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls_key_path: Option<String>,  // HTTPS 私钥（PEM）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     terminal: Option<TerminalConfigLegacy>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     terminals: Vec<TerminalNodeConfig>,
@@ -992,6 +1315,8 @@ struct Config {
     syncs: Vec<SyncConfig>,
     #[serde(default)]
     selections: HashMap<String, String>, // selector group -> node name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    selection_keys: HashMap<String, String>, // selector group -> stable node key (type+server+port)
     #[serde(default)]
     nodes: Vec<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -999,6 +1324,10 @@ struct Config {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     dns_candidates: Option<Vec<String>>,
 
+    /// 节点测速请求未指定 url 时使用的探测地址，无效时回退到默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxy_check_url: Option<String>,
+
     // SSH reverse TCP tunnels (optional)
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     tcp_tunnels: Vec<TcpTunnelConfig>,
@@ -1009,23 +1338,144 @@ struct Config {
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     subscriptions: Vec<SubscriptionConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subscription_fetch_timeout_ms: Option<u64>,
+    /// 节点增删改后后台重新生成配置的合并窗口（ms），0 表示立即执行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regenerate_debounce_ms: Option<u64>,
+    /// 代理 / DNS 切换时 POST 通知的 webhook 地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify_webhook_url: Option<String>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     hosts: Vec<HostConfig>,
+    /// 新建主机/隧道/隧道集时请求未带认证信息则使用此项
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_ssh_auth: Option<TcpTunnelAuth>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     host_groups: Vec<HostGroupConfig>,
 
     #[serde(default)]
     metrics: MetricsConfig,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    connectivity_presets: Vec<ConnectivityPreset>,
 }
 
+/// 配置文件路径（相对工作目录），其余状态文件与它放在同一目录
+const CONFIG_FILE: &str = "config.yaml";
 const DEFAULT_PORT: u16 = 6161;
 const DEFAULT_TERMINAL_PORT: u16 = 7681;
 const DEFAULT_DNS_ACTIVE: &str = "doh-cf";
+const DEFAULT_SUBSCRIPTION_FETCH_TIMEOUT_MS: u64 = 15_000;
+/// Placeholder returned instead of secret values (e.g. subscription auth headers).
+const REDACTED_SECRET: &str = "******";
+const DEFAULT_REGENERATE_DEBOUNCE_MS: u64 = 1_500;
+const NOTIFY_WEBHOOK_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+const DEFAULT_TOKEN_TTL_SECS: u64 = 24 * 3600;
+/// 应用/终端启动后等待多久确认进程仍存活（终端还要求端口已监听）
+const DEFAULT_STARTUP_CHECK_MS: u64 = 1_000;
+const MAX_STARTUP_CHECK_MS: u64 = 30_000;
+const MIN_TOKEN_TTL_SECS: u64 = 60;
+/// token 有效期上限（一年），过大的值会让过期时间溢出
+const MAX_TOKEN_TTL_SECS: u64 = 365 * 24 * 3600;
+/// 各类资源的默认数量上限（可在 config.yaml 用 max_* 覆盖）
+const DEFAULT_MAX_TUNNELS: usize = 500;
+const DEFAULT_MAX_SYNCS: usize = 200;
+const DEFAULT_MAX_TERMINALS: usize = 50;
+const DEFAULT_MAX_APPS: usize = 100;
+const DEFAULT_MAX_VNC_SESSIONS: usize = 1;
+const SYNC_REACHABILITY_TIMEOUT_SECS: u64 = 5;
+/// 内置出站，总是追加到 proxy 选择器末尾，可像节点一样被选中（直连 / 拒绝）
+const SELECTOR_BUILTIN_OUTBOUNDS: &[&str] = &["direct", "block"];
+const SUBSCRIPTION_FETCH_ATTEMPTS: u32 = 3;
+const SUBSCRIPTION_FETCH_BACKOFF_MS: u64 = 1_000;
+/// clone/fetch 比单个 HTTP 请求慢，超时不低于此值
+const SUBSCRIPTION_GIT_MIN_TIMEOUT_SECS: u64 = 60;
+const SUBSCRIPTION_LOAD_CONCURRENCY: usize = 4;
+/// 定时刷新的最小间隔与检查周期（秒）
+const MIN_SUBSCRIPTION_REFRESH_INTERVAL_SECS: u64 = 60;
+const SUBSCRIPTION_REFRESH_TICK_SECS: u64 = 30;
+
+/// Startup overrides from MIAO_* environment variables.
+/// They take precedence over config.yaml but are never written back to it.
+#[derive(Clone, Debug, Default)]
+struct EnvOverrides {
+    port: Option<u16>,
+    password: Option<String>,
+    bind_addr: Option<std::net::IpAddr>,
+    jwt_secret: Option<String>,
+    data_dir: Option<PathBuf>,
+}
+
+fn load_env_overrides() -> Result<EnvOverrides, String> {
+    let read = |key: &str| -> Option<String> {
+        env::var(key).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+    };
+    let mut overrides = EnvOverrides::default();
+    if let Some(raw) = read("MIAO_PORT") {
+        match raw.parse::<u16>() {
+            Ok(port) if port > 0 => overrides.port = Some(port),
+            _ => return Err(format!("MIAO_PORT must be a port number (1-65535), got '{}'", raw)),
+        }
+    }
+    if let Some(password) = read("MIAO_PASSWORD") {
+        if password.len() < 4 {
+            return Err("MIAO_PASSWORD must be at least 4 characters".to_string());
+        }
+        overrides.password = Some(password);
+    }
+    if let Some(raw) = read("MIAO_BIND_ADDR") {
+        let addr = raw
+            .parse::<std::net::IpAddr>()
+            .map_err(|_| format!("MIAO_BIND_ADDR must be an IP address, got '{}'", raw))?;
+        overrides.bind_addr = Some(addr);
+    }
+    if let Some(secret) = read("MIAO_JWT_SECRET") {
+        if secret.len() < 16 {
+            return Err("MIAO_JWT_SECRET must be at least 16 characters".to_string());
+        }
+        overrides.jwt_secret = Some(secret);
+    }
+    if let Some(dir) = read("MIAO_DATA_DIR") {
+        overrides.data_dir = Some(PathBuf::from(dir));
+    }
+    Ok(overrides)
+}
+
+/// 当前 JWT 签名密钥：MIAO_JWT_SECRET 优先，否则使用 config.yaml 中持久化的随机密钥
+fn jwt_secret() -> String {
+    ENV_OVERRIDES
+        .lock()
+        .unwrap()
+        .jwt_secret
+        .clone()
+        .unwrap_or_else(|| JWT_SIGNING_SECRET.lock().unwrap().clone())
+}
 
-// JWT 密钥（生产环境应使用环境变量）
-const JWT_SECRET: &str = "miao_jwt_secret_key_change_in_production";
+/// 生成新的随机 JWT 签名密钥（两个 v4 UUID，约 244 位随机数）
+fn new_jwt_secret() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// 配置中没有 JWT 密钥时生成一个，返回是否有修改
+fn ensure_jwt_secret(config: &mut Config) -> bool {
+    if config.jwt_secret.as_deref().is_some_and(|s| !s.is_empty()) {
+        return false;
+    }
+    config.jwt_secret = Some(new_jwt_secret());
+    true
+}
+
+fn env_password_override() -> Option<String> {
+    ENV_OVERRIDES.lock().unwrap().password.clone()
+}
 const SUBSCRIPTIONS_ENABLED: bool = true;
 
 // JWT Claims 结构
@@ -1050,6 +1500,7 @@ struct PasswordChangeRequest {
 #[derive(Serialize)]
 struct LoginResponse {
     token: String,
+    expires_in: u64,  // token 有效期（秒）
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1074,6 +1525,7 @@ struct TcpTunnelItem {
     id: String,
     name: Option<String>,
     enabled: bool,
+    direction: TcpTunnelDirection,
     local_addr: String,
     local_port: u16,
     remote_bind_addr: String,
@@ -1084,10 +1536,14 @@ struct TcpTunnelItem {
     auth: TcpTunnelAuthPublic,
     strict_host_key_checking: bool,
     host_key_fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    known_hosts_path: Option<String>,
     allow_public_bind: bool,
     connect_timeout_ms: u64,
     keepalive_interval_ms: u64,
     reconnect_backoff_ms: TcpTunnelBackoff,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_id: Option<String>,
     status: tcp_tunnel::TunnelRuntimeStatus,
 }
 
@@ -1102,6 +1558,31 @@ struct TcpTunnelListResponse {
     items: Vec<TcpTunnelItem>,
 }
 
+#[derive(Serialize)]
+struct TcpTunnelSshSettings {
+    ssh_host: String,
+    ssh_port: u16,
+    username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth: Option<TcpTunnelAuthPublic>,
+}
+
+#[derive(Serialize)]
+struct TcpTunnelEffectiveResponse {
+    id: String,
+    host_id: Option<String>,
+    /// 隧道当前实际使用的 SSH 配置
+    tunnel: TcpTunnelSshSettings,
+    /// 引用主机的当前配置（未关联主机或主机已删除时为 None）
+    host: Option<TcpTunnelSshSettings>,
+    host_missing: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_error: Option<String>,
+    /// 与主机不一致的字段
+    drift: Vec<String>,
+    in_sync: bool,
+}
+
 #[derive(Serialize)]
 struct TcpTunnelSetListItem {
     id: String,
@@ -1117,6 +1598,7 @@ struct TcpTunnelSetListItem {
     include_ports: Vec<u16>,
     exclude_ports: Vec<u16>,
     connect_timeout_ms: u64,
+    conflicts: Vec<String>,
     status: tcp_tunnel::TunnelRuntimeStatus,
 }
 
@@ -1142,6 +1624,8 @@ struct TcpTunnelOverviewItem {
     ssh_host: String,
     ssh_port: u16,
     username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    direction: Option<TcpTunnelDirection>,
     remote_bind_addr: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     remote_port: Option<u16>,
@@ -1156,6 +1640,8 @@ struct TcpTunnelOverviewItem {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     host_key_fingerprint: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    known_hosts_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     allow_public_bind: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     connect_timeout_ms: Option<u64>,
@@ -1173,7 +1659,7 @@ struct TcpTunnelOverviewResponse {
     items: Vec<TcpTunnelOverviewItem>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct TcpTunnelUpsertRequest {
     #[serde(default)]
     id: Option<String>,
@@ -1184,6 +1670,8 @@ struct TcpTunnelUpsertRequest {
     #[serde(default)]
     host_id: Option<String>,
     #[serde(default)]
+    direction: Option<TcpTunnelDirection>,
+    #[serde(default)]
     local_addr: Option<String>,
     #[serde(default)]
     local_port: Option<u16>,
@@ -1204,6 +1692,8 @@ struct TcpTunnelUpsertRequest {
     #[serde(default)]
     host_key_fingerprint: Option<String>,
     #[serde(default)]
+    known_hosts_path: Option<String>,
+    #[serde(default)]
     allow_public_bind: Option<bool>,
     #[serde(default)]
     connect_timeout_ms: Option<u64>,
@@ -1219,12 +1709,22 @@ struct TcpTunnelTestResponse {
     latency_ms: Option<u64>,
 }
 
+#[derive(Serialize)]
+struct TcpTunnelSetScanResponse {
+    method: Option<String>,     // 实际成功的扫描方式: ss / netstat / proc
+    raw_ports: Vec<u16>,        // 扫描到的全部监听端口
+    candidate_ports: Vec<u16>,  // 经 include/exclude 过滤后的端口
+    managed_ports: Vec<u16>,    // 当前已由该 set 管理的端口
+    error: Option<String>,
+    triggered: bool,            // 是否唤醒了运行中的 set 立即同步
+}
+
 #[derive(Deserialize)]
 struct BulkIdsRequest {
     ids: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct TcpTunnelSetCreateRequest {
     #[serde(default)]
     name: Option<String>,
@@ -1245,6 +1745,8 @@ struct TcpTunnelSetCreateRequest {
     #[serde(default)]
     host_key_fingerprint: Option<String>,
     #[serde(default)]
+    known_hosts_path: Option<String>,
+    #[serde(default)]
     include_ports_enabled: Option<bool>,
     #[serde(default)]
     include_ports: Option<Vec<u16>>,
@@ -1260,6 +1762,14 @@ struct TcpTunnelSetCreateRequest {
     start_batch_size: Option<u64>,
     #[serde(default)]
     start_batch_interval_ms: Option<u64>,
+    #[serde(default)]
+    port_strategy: Option<TcpTunnelSetPortStrategy>,
+    #[serde(default)]
+    port_base: Option<u16>,
+    #[serde(default)]
+    scan_method: Option<TcpTunnelSetScanMethod>,
+    #[serde(default)]
+    skip_loopback: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -1274,6 +1784,8 @@ struct TcpTunnelSetDetailResponse {
     auth: TcpTunnelAuthPublic,
     strict_host_key_checking: bool,
     host_key_fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    known_hosts_path: Option<String>,
     include_ports_enabled: bool,
     include_ports: Vec<u16>,
     exclude_ports: Vec<u16>,
@@ -1282,6 +1794,11 @@ struct TcpTunnelSetDetailResponse {
     connect_timeout_ms: u64,
     start_batch_size: u64,
     start_batch_interval_ms: u64,
+    port_strategy: TcpTunnelSetPortStrategy,
+    port_base: u16,
+    scan_method: TcpTunnelSetScanMethod,
+    skip_loopback: bool,
+    conflicts: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -1311,6 +1828,39 @@ struct SyncRuntimeStatus {
     last_error: Option<SyncErrorInfo>,
 }
 
+/// dry-run 预演出的单个文件变更
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum SyncChangeType {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SyncDryRunChange {
+    path: String,
+    change_type: SyncChangeType,
+}
+
+/// 每个本地路径单独预演，某个路径失败不影响其它路径
+#[derive(Clone, Debug, Serialize)]
+struct SyncDryRunPathResult {
+    local_path: String,
+    remote_path: String,
+    changes: Vec<SyncDryRunChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SyncDryRunResponse {
+    created: usize,
+    updated: usize,
+    deleted: usize,
+    paths: Vec<SyncDryRunPathResult>,
+}
+
 impl Default for SyncRuntimeStatus {
     fn default() -> Self {
         Self {
@@ -1341,6 +1891,13 @@ struct SyncItem {
     auth: SyncAuthPublic,
     options: SyncOptions,
     schedule: Option<SyncSchedule>,
+    via_tunnel_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pre_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_command: Option<String>,
     status: SyncRuntimeStatus,
 }
 
@@ -1361,7 +1918,7 @@ struct SyncSaveResponse {
     item: SyncItem,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[derive(Deserialize)]
 struct SyncUpsertRequest {
     #[serde(default)]
@@ -1385,6 +1942,12 @@ struct SyncUpsertRequest {
     options: SyncOptions,
     #[serde(default)]
     schedule: Option<SyncSchedule>,
+    #[serde(default)]
+    via_tunnel_id: Option<String>,
+    #[serde(default)]
+    pre_command: Option<String>,
+    #[serde(default)]
+    post_command: Option<String>,
 }
 
 fn generate_tunnel_set_id() -> String {
@@ -1408,12 +1971,32 @@ struct WsAuthQuery {
     level: Option<String>,
     #[serde(default)]
     use_uploaded: Option<String>,
+    /// Comma separated event filter, e.g. `tunnel.state_changed,sync`
+    #[serde(default)]
+    events: Option<String>,
+}
+
+const SYSTEM_STATUS_SSE_INTERVAL_SECS: u64 = 3;
+
+#[derive(Deserialize)]
+struct SseStatusQuery {
+    token: String,
+    /// 推送间隔（秒），默认 3，最小 1
+    #[serde(default)]
+    interval_secs: Option<u64>,
 }
 
 struct SystemMonitor {
     machine: Mutex<Machine>,
     info_cache: Mutex<Option<serde_json::Value>>,
     status_cache: Mutex<Option<serde_json::Value>>,
+    refreshed_at: Mutex<Option<Instant>>,
+    /// 上一次读取的网卡累计收发字节数，用于计算速率
+    net_prev: Mutex<Option<(Instant, u64, u64)>>,
+    /// 上一次读取的 miao 自身 CPU 时间（clock ticks），用于计算自身 CPU 占用
+    self_cpu_prev: Mutex<Option<(Instant, u64)>>,
+    /// 托管子进程上一次的 CPU 时间（按 pid），只保留当前仍在运行的进程
+    child_cpu_prev: Mutex<HashMap<u32, (Instant, u64)>>,
 }
 
 impl SystemMonitor {
@@ -1422,8 +2005,24 @@ impl SystemMonitor {
             machine: Mutex::new(Machine::new()),
             info_cache: Mutex::new(None),
             status_cache: Mutex::new(None),
+            refreshed_at: Mutex::new(None),
+            net_prev: Mutex::new(None),
+            self_cpu_prev: Mutex::new(None),
+            child_cpu_prev: Mutex::new(HashMap::new()),
         }
     }
+
+    /// 缓存是否仍在 TTL 内（ttl_secs = 0 表示永不过期）
+    async fn is_fresh(&self, ttl_secs: u64) -> bool {
+        if ttl_secs == 0 {
+            return true;
+        }
+        self.refreshed_at
+            .lock()
+            .await
+            .map(|at| at.elapsed() < Duration::from_secs(ttl_secs))
+            .unwrap_or(false)
+    }
 }
 
 pub struct AppState {
@@ -1432,13 +2031,18 @@ pub struct AppState {
     subscriptions_root: PathBuf,
     subscription_status: Mutex<HashMap<String, SubscriptionRuntime>>,
     node_type_by_tag: Mutex<HashMap<String, String>>,
+    node_delays: Mutex<HashMap<String, NodeDelaySample>>,
     setup_required: AtomicBool,
     sing_box_pending_restart: AtomicBool,
+    /// 每次请求后台 regenerate 时递增，用于合并连续修改
+    regenerate_generation: AtomicU64,
     tcp_tunnel: tcp_tunnel::TunnelManager,
     full_tunnel: full_tunnel::FullTunnelManager,
     sync_manager: sync::SyncManager,
     system_monitor: SystemMonitor,
     metrics_config: MetricsConfig,
+    /// 常驻的指标库连接，首次使用时打开
+    metrics_db: Arc<StdMutex<Option<Connection>>>,
     ivnc_process: Arc<Mutex<Option<IVncProcess>>>,
     ivnc_config: Arc<Mutex<IVncConfig>>,
 }
@@ -1448,6 +2052,9 @@ struct SubscriptionRuntime {
     files: Vec<SubFileStatus>,
     error: Option<String>,
     updated_at: Option<i64>,
+    attempts: u32,
+    /// Fetch failed; nodes come from the last good cached copy.
+    stale: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1469,35 +2076,228 @@ struct Tls {
     #[serde(skip_serializing_if = "Option::is_none")]
     server_name: Option<String>,
     insecure: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alpn: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    utls: Option<Utls>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reality: Option<Reality>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ech: Option<Ech>,
 }
 
 #[derive(Serialize, Deserialize)]
-struct AnyTls {
-    #[serde(rename = "type")]
-    outbound_type: String,
-    tag: String,
-    server: String,
-    server_port: u16,
-    password: String,
-    tls: Tls,
+struct Utls {
+    enabled: bool,
+    fingerprint: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Shadowsocks {
-    #[serde(rename = "type")]
-    outbound_type: String,
-    tag: String,
-    server: String,
-    server_port: u16,
-    method: String,
-    password: String,
+/// sing-box `tls.reality`; `enabled: false` in a request clears it
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+struct Reality {
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default)]
+    public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    short_id: String,
 }
 
-// ============================================================================
-// API Response Types
-// ============================================================================
+/// sing-box `tls.ech`; `enabled: false` in a request clears it
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+struct Ech {
+    #[serde(default = "default_true")]
+    enabled: bool,
+    /// ECH config (PEM lines)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    config: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    config_path: Option<String>,
+}
+
+/// Normalize and cross-check REALITY / ECH settings for a TLS node.
+fn validate_tls_security(
+    node_type: &str,
+    server_name: Option<&str>,
+    reality: Option<Reality>,
+    ech: Option<Ech>,
+) -> Result<(Option<Reality>, Option<Ech>), String> {
+    let reality = reality.filter(|r| r.enabled).map(|mut r| {
+        r.public_key = r.public_key.trim().to_string();
+        r.short_id = r.short_id.trim().to_string();
+        r
+    });
+    let ech = ech.filter(|e| e.enabled).map(|mut e| {
+        e.config.retain(|line| !line.trim().is_empty());
+        e.config_path = e.config_path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+        e
+    });
 
-#[derive(Serialize)]
+    if reality.is_some() && ech.is_some() {
+        return Err("reality and ech cannot both be enabled".to_string());
+    }
+    if let Some(r) = reality.as_ref() {
+        if !supports_reality(node_type) {
+            return Err(format!("reality is not supported for {} nodes", node_type));
+        }
+        if r.public_key.is_empty() {
+            return Err("reality.public_key is required".to_string());
+        }
+        if r.short_id.len() > 16 || !r.short_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("reality.short_id must be up to 16 hex characters".to_string());
+        }
+        if server_name.map(|s| s.trim().is_empty()).unwrap_or(true) {
+            return Err("reality requires sni (server_name)".to_string());
+        }
+    }
+    if let Some(e) = ech.as_ref() {
+        if e.config.is_empty() && e.config_path.is_none() {
+            return Err("ech requires config or config_path".to_string());
+        }
+    }
+    Ok((reality, ech))
+}
+
+/// 可使用 uTLS 指纹的节点类型（基于 TCP 的 TLS）
+fn supports_utls(node_type: &str) -> bool {
+    matches!(node_type, "anytls" | "vless" | "trojan")
+}
+
+/// 可使用 REALITY 的节点类型
+fn supports_reality(node_type: &str) -> bool {
+    matches!(node_type, "anytls" | "vless")
+}
+
+const UTLS_FINGERPRINTS: &[&str] = &[
+    "chrome", "firefox", "edge", "safari", "360", "qq", "ios", "android", "random", "randomized",
+];
+
+/// Empty list clears ALPN.
+fn normalize_alpn(list: Vec<String>) -> Option<Vec<String>> {
+    let list: Vec<String> = list
+        .into_iter()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if list.is_empty() {
+        None
+    } else {
+        Some(list)
+    }
+}
+
+/// Empty string clears the fingerprint; uTLS is TCP-only, so QUIC nodes reject it.
+fn normalize_utls_fingerprint(
+    node_type: &str,
+    fingerprint: Option<String>,
+) -> Result<Option<String>, String> {
+    let Some(fp) = fingerprint.map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if !supports_utls(node_type) {
+        return Err(format!("utls_fingerprint is not supported for {} nodes", node_type));
+    }
+    if !UTLS_FINGERPRINTS.contains(&fp.as_str()) {
+        return Err(format!(
+            "Invalid utls_fingerprint '{}', expected one of: {}",
+            fp,
+            UTLS_FINGERPRINTS.join(", ")
+        ));
+    }
+    Ok(Some(fp))
+}
+
+fn utls_from_fingerprint(fingerprint: Option<String>) -> Option<Utls> {
+    fingerprint.map(|fingerprint| Utls {
+        enabled: true,
+        fingerprint,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnyTls {
+    #[serde(rename = "type")]
+    outbound_type: String,
+    tag: String,
+    server: String,
+    server_port: u16,
+    password: String,
+    tls: Tls,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Shadowsocks {
+    #[serde(rename = "type")]
+    outbound_type: String,
+    tag: String,
+    server: String,
+    server_port: u16,
+    method: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Vless {
+    #[serde(rename = "type")]
+    outbound_type: String,
+    tag: String,
+    server: String,
+    server_port: u16,
+    uuid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flow: Option<String>,
+    tls: Tls,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Trojan {
+    #[serde(rename = "type")]
+    outbound_type: String,
+    tag: String,
+    server: String,
+    server_port: u16,
+    password: String,
+    tls: Tls,
+}
+
+const VLESS_FLOWS: &[&str] = &["xtls-rprx-vision"];
+
+fn normalize_vless_uuid(uuid: Option<String>) -> Result<String, String> {
+    let uuid = uuid.map(|u| u.trim().to_string()).unwrap_or_default();
+    if uuid.is_empty() {
+        return Err("VLESS node requires uuid".to_string());
+    }
+    uuid::Uuid::parse_str(&uuid).map_err(|_| format!("Invalid uuid '{}'", uuid))?;
+    Ok(uuid)
+}
+
+/// Empty string clears the flow.
+fn normalize_vless_flow(flow: Option<String>) -> Result<Option<String>, String> {
+    let Some(flow) = flow.map(|f| f.trim().to_string()).filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    if !VLESS_FLOWS.contains(&flow.as_str()) {
+        return Err(format!(
+            "Invalid flow '{}', expected one of: {}",
+            flow,
+            VLESS_FLOWS.join(", ")
+        ));
+    }
+    Ok(Some(flow))
+}
+
+// ============================================================================
+// API Response Types
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+#[aliases(
+    ApiMessage = ApiResponse<serde_json::Value>,
+    NodeListApiResponse = ApiResponse<Vec<NodeInfo>>,
+    NodeDetailApiResponse = ApiResponse<NodeDetailResponse>,
+    NodeTestApiResponse = ApiResponse<NodeTestResponse>,
+    ConnectivityApiResponse = ApiResponse<ConnectivityResult>
+)]
 struct ApiResponse<T: Serialize> {
     success: bool,
     message: String,
@@ -1535,11 +2335,54 @@ impl<T: Serialize> ApiResponse<T> {
 // JWT Helper Functions
 // ============================================================================
 
-// 生成 JWT token
-fn generate_token() -> Result<String, jsonwebtoken::errors::Error> {
+fn token_ttl_secs(config: &Config) -> u64 {
+    config
+        .token_ttl_secs
+        .unwrap_or(DEFAULT_TOKEN_TTL_SECS)
+        .clamp(MIN_TOKEN_TTL_SECS, MAX_TOKEN_TTL_SECS)
+}
+
+// ============================================================================
+// Password Hashing
+// ============================================================================
+
+/// 登录密码以 argon2id PHC 字符串存储；旧配置中的明文在下次登录成功时升级
+fn hash_password(password: &str) -> Result<String, String> {
+    use argon2::password_hash::{rand_core::OsRng, PasswordHasher, SaltString};
+    let salt = SaltString::generate(&mut OsRng);
+    argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("密码哈希失败: {}", e))
+}
+
+fn is_password_hash(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+/// 校验密码：哈希走 argon2，明文（旧配置、MIAO_PASSWORD、默认密码）用常数时间比较
+fn verify_password(input: &str, stored: &str) -> bool {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+    use subtle::ConstantTimeEq;
+    if is_password_hash(stored) {
+        return PasswordHash::new(stored)
+            .map(|hash| {
+                argon2::Argon2::default()
+                    .verify_password(input.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false);
+    }
+    input.as_bytes().ct_eq(stored.as_bytes()).into()
+}
+
+// 生成 JWT token，返回 token 及有效期（秒）
+fn generate_token(ttl_secs: u64) -> Result<LoginResponse, jsonwebtoken::errors::Error> {
+    let ttl_secs = ttl_secs.min(MAX_TOKEN_TTL_SECS);
+    // 溢出时返回错误而不是 panic，调用方会回复“生成 token 失败”
     let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::days(30))
-        .expect("valid timestamp")
+        .checked_add_signed(chrono::Duration::seconds(ttl_secs as i64))
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?
         .timestamp() as usize;
 
     let claims = Claims {
@@ -1547,18 +2390,22 @@ fn generate_token() -> Result<String, jsonwebtoken::errors::Error> {
         exp: expiration,
     };
 
-    encode(
+    let token = encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-    )
+        &EncodingKey::from_secret(jwt_secret().as_bytes()),
+    )?;
+    Ok(LoginResponse {
+        token,
+        expires_in: ttl_secs,
+    })
 }
 
 // 验证 JWT token
 fn verify_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
     decode::<Claims>(
         token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
+        &DecodingKey::from_secret(jwt_secret().as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
@@ -1572,6 +2419,8 @@ struct StatusData {
     #[serde(skip_serializing_if = "Option::is_none")]
     uptime_secs: Option<u64>,
     pending_restart: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_exit: Option<ProcessExitInfo>,
 }
 
 #[derive(Serialize, Clone)]
@@ -1581,6 +2430,8 @@ struct TerminalRuntimeStatus {
     pid: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     uptime_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_exit: Option<ProcessExitInfo>,
 }
 
 #[derive(Serialize, Clone)]
@@ -1590,6 +2441,8 @@ struct AppRuntimeStatus {
     pid: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     uptime_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_exit: Option<ProcessExitInfo>,
 }
 
 #[derive(Serialize)]
@@ -1604,6 +2457,7 @@ struct TerminalItem {
     auth_username: Option<String>,
     auth_password: Option<String>,
     extra_args: Vec<String>,
+    startup_check_ms: Option<u64>,
     status: TerminalRuntimeStatus,
 }
 
@@ -1626,6 +2480,7 @@ struct AppItem {
     command: String,
     args: Vec<String>,
     env: HashMap<String, String>,
+    startup_check_ms: Option<u64>,
     status: AppRuntimeStatus,
 }
 
@@ -1660,6 +2515,8 @@ struct TerminalUpsertRequest {
     #[serde(default)]
     extra_args: Option<Vec<String>>,
     #[serde(default)]
+    startup_check_ms: Option<u64>,
+    #[serde(default)]
     restart: bool,
     #[serde(default)]
     clear_auth: bool,
@@ -1682,6 +2539,8 @@ struct AppUpsertRequest {
     #[serde(default)]
     env: Option<HashMap<String, String>>,
     #[serde(default)]
+    startup_check_ms: Option<u64>,
+    #[serde(default)]
     restart: bool,
 }
 
@@ -1700,12 +2559,14 @@ struct AppTemplateListResponse {
     items: Vec<AppTemplateItem>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema)]
 struct ConnectivityResult {
     name: String,
     url: String,
     latency_ms: Option<u64>,
     success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_code: Option<u16>,
 }
 
 #[derive(Serialize, Clone)]
@@ -1733,8 +2594,22 @@ struct SubFilesResponse {
 #[derive(Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 enum SubscriptionSourceResponse {
-    Url { url: String },
-    Git { repo: String, workdir: String },
+    Url {
+        url: String,
+        #[serde(skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        user_agent: Option<String>,
+    },
+    Git {
+        repo: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        branch: Option<String>,
+        /// token 已脱敏
+        #[serde(skip_serializing_if = "Option::is_none")]
+        credentials: Option<GitCredentials>,
+        workdir: String,
+    },
     Path { path: String },
 }
 
@@ -1746,9 +2621,17 @@ struct SubscriptionItem {
     enabled: bool,
     source: SubscriptionSourceResponse,
     #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     updated_at: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     last_error: Option<String>,
+    /// ok / stale (serving cached content) / error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    /// Fetch attempts used by the last load
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempts: Option<u32>,
     files: Vec<SubFileStatus>,
 }
 
@@ -1765,7 +2648,21 @@ struct SubscriptionSaveResponse {
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum SubscriptionSourceInput {
-    Url { url: String },
+    Url {
+        url: String,
+        #[serde(default)]
+        headers: Option<HashMap<String, String>>,
+        #[serde(default)]
+        user_agent: Option<String>,
+    },
+    Git {
+        repo: String,
+        #[serde(default)]
+        branch: Option<String>,
+        /// 更新时不传则保留原凭据；token/path 为空字符串表示清除
+        #[serde(default)]
+        credentials: Option<GitCredentials>,
+    },
 }
 
 #[derive(Deserialize)]
@@ -1774,6 +2671,9 @@ struct SubscriptionUpsertRequest {
     name: Option<String>,
     #[serde(default)]
     enabled: Option<bool>,
+    /// 0 关闭定时刷新；更新时不传则保持原值
+    #[serde(default)]
+    refresh_interval_secs: Option<u64>,
     #[serde(flatten)]
     source: SubscriptionSourceInput,
 }
@@ -1786,7 +2686,7 @@ struct LoadedSubscriptions {
     dir_error: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct NodeRequest {
     node_type: Option<String>,
     tag: String,
@@ -1804,9 +2704,35 @@ struct NodeRequest {
     sni: Option<String>,
     #[serde(default)]
     cipher: Option<String>,
+    /// Hysteria2 only (default 40)
+    #[serde(default)]
+    up_mbps: Option<u32>,
+    /// Hysteria2 only (default 350)
+    #[serde(default)]
+    down_mbps: Option<u32>,
+    /// Skip TLS certificate verification (default true); `tls_insecure` is accepted as an alias
+    #[serde(default, alias = "tls_insecure")]
+    insecure: Option<bool>,
+    /// TLS ALPN list (hysteria2/anytls)
+    #[serde(default)]
+    alpn: Option<Vec<String>>,
+    /// uTLS browser fingerprint, e.g. chrome (anytls/vless/trojan)
+    #[serde(default)]
+    utls_fingerprint: Option<String>,
+    /// REALITY (anytls/vless, requires sni; implies uTLS, default chrome)
+    #[serde(default)]
+    reality: Option<Reality>,
+    #[serde(default)]
+    ech: Option<Ech>,
+    /// VLESS only (required)
+    #[serde(default)]
+    uuid: Option<String>,
+    /// VLESS only, e.g. xtls-rprx-vision
+    #[serde(default)]
+    flow: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct NodeUpdateRequest {
     #[serde(default)]
     node_type: Option<String>,
@@ -1824,14 +2750,37 @@ struct NodeUpdateRequest {
     sni: Option<String>,
     #[serde(default)]
     cipher: Option<String>,
+    #[serde(default)]
+    up_mbps: Option<u32>,
+    #[serde(default)]
+    down_mbps: Option<u32>,
+    /// Omitted keeps the stored value; `tls_insecure` is accepted as an alias
+    #[serde(default, alias = "tls_insecure")]
+    insecure: Option<bool>,
+    /// Empty list clears ALPN
+    #[serde(default)]
+    alpn: Option<Vec<String>>,
+    /// Empty string clears the fingerprint
+    #[serde(default)]
+    utls_fingerprint: Option<String>,
+    #[serde(default)]
+    reality: Option<Reality>,
+    #[serde(default)]
+    ech: Option<Ech>,
+    /// VLESS only
+    #[serde(default)]
+    uuid: Option<String>,
+    /// VLESS only; empty string clears the flow
+    #[serde(default)]
+    flow: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct DeleteNodeRequest {
     tag: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct NodeInfo {
     node_type: String,
     tag: String,
@@ -1841,7 +2790,7 @@ struct NodeInfo {
     sni: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct NodeDetailResponse {
     node_type: String,
     tag: String,
@@ -1853,9 +2802,27 @@ struct NodeDetailResponse {
     cipher: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    up_mbps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    down_mbps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    insecure: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    utls_fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reality: Option<Reality>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ech: Option<Ech>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flow: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct NodeTestRequest {
     server: String,
     server_port: u16,
@@ -1863,7 +2830,7 @@ struct NodeTestRequest {
     timeout_ms: Option<u64>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct NodeTestResponse {
     latency_ms: u128,
 }
@@ -1887,9 +2854,173 @@ struct AppProcess {
     started_at: Instant,
 }
 
+/// 托管进程最近一次退出的状态（退出码或终止信号）
+#[derive(Serialize, Clone, Debug)]
+struct ProcessExitInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal_name: Option<String>,
+    description: String,
+    exited_at: i64,
+}
+
+impl ProcessExitInfo {
+    fn from_status(status: std::process::ExitStatus) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+        let code = status.code();
+        let signal = status.signal();
+        let signal_name = signal
+            .and_then(|sig| Signal::try_from(sig).ok())
+            .map(|sig| sig.as_str().to_string());
+        let description = match (code, signal, signal_name.as_deref()) {
+            (Some(126), _, _) => "exited with code 126 (permission denied / not executable)".to_string(),
+            (Some(127), _, _) => "exited with code 127 (command not found)".to_string(),
+            (Some(code), _, _) => format!("exited with code {}", code),
+            (None, Some(sig), Some(name)) => format!("killed by signal {} ({})", sig, name),
+            (None, Some(sig), None) => format!("killed by signal {}", sig),
+            (None, None, _) => "exited".to_string(),
+        };
+        Self {
+            code,
+            signal,
+            signal_name,
+            description,
+            exited_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+fn sing_box_process_key() -> String {
+    "sing-box".to_string()
+}
+
+fn terminal_process_key(id: &str) -> String {
+    format!("terminal:{}", id)
+}
+
+fn app_process_key(id: &str) -> String {
+    format!("app:{}", id)
+}
+
+fn ivnc_process_key() -> String {
+    "ivnc".to_string()
+}
+
+fn record_process_exit(key: String, status: std::process::ExitStatus) -> ProcessExitInfo {
+    let info = ProcessExitInfo::from_status(status);
+    log_warning!("Process {} {}", key, info.description);
+    if let Ok(mut map) = PROCESS_EXITS.lock() {
+        map.insert(key, info.clone());
+    }
+    info
+}
+
+fn last_process_exit(key: &str) -> Option<ProcessExitInfo> {
+    PROCESS_EXITS.lock().ok().and_then(|map| map.get(key).cloned())
+}
+
+fn utc8_now_string() -> String {
+    let utc8 = chrono::FixedOffset::east_opt(8 * 3600).unwrap();
+    Utc::now().with_timezone(&utc8).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// 从日志缓冲中取出某进程自 `since` 以来输出到 stderr 的最后几行
+fn captured_stderr(buffer: &StdMutex<VecDeque<String>>, process_name: &str, since: &str) -> Option<String> {
+    const MAX_LINES: usize = 10;
+    let prefix = format!("[{}] ", process_name);
+    let buffer = buffer.lock().ok()?;
+    let mut lines: Vec<String> = buffer
+        .iter()
+        .rev()
+        .filter_map(|raw| serde_json::from_str::<LogEntry>(raw).ok())
+        .take_while(|entry| entry.time.as_str() >= since)
+        .filter(|entry| entry.level == "error")
+        .filter_map(|entry| entry.message.strip_prefix(&prefix).map(|m| m.to_string()))
+        .take(MAX_LINES)
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// 启动后检查：`check_ms` 内进程退出则返回带 stderr 的错误；给出 `listen` 时
+/// 还要求端口开始监听（监听成功立即返回，超时仍未监听则结束进程并报错）
+async fn wait_for_startup(
+    child: &mut tokio::process::Child,
+    exit_key: String,
+    label: &str,
+    stderr: (&StdMutex<VecDeque<String>>, &str, &str),
+    check_ms: u64,
+    listen: Option<(&str, u16)>,
+) -> Result<(), String> {
+    let (buffer, process_name, since) = stderr;
+    let listen = listen.filter(|_| !process_stub_mode());
+    let deadline = Instant::now() + Duration::from_millis(check_ms);
+    loop {
+        if let Some(exit_status) = child.try_wait().map_err(|e| format!("等待进程失败: {}", e))? {
+            let exit = record_process_exit(exit_key, exit_status);
+            // 给日志读取任务一点时间把剩余输出读完
+            sleep(Duration::from_millis(100)).await;
+            return Err(match captured_stderr(buffer, process_name, since) {
+                Some(stderr) => format!("{} {} immediately: {}", label, exit.description, stderr),
+                None => format!("{} {} immediately", label, exit.description),
+            });
+        }
+        if let Some((addr, port)) = listen {
+            if port_is_listening(addr, port).await {
+                return Ok(());
+            }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        sleep((deadline - now).min(Duration::from_millis(100))).await;
+    }
+    if let Some((addr, port)) = listen {
+        let _ = child.start_kill();
+        let _ = child.wait().await;
+        return Err(format!(
+            "{} is running but not listening on {} after {}ms",
+            label,
+            join_host_port(addr, port),
+            check_ms
+        ));
+    }
+    Ok(())
+}
+
+/// 本机连接测试；通配地址按回环地址探测
+async fn port_is_listening(addr: &str, port: u16) -> bool {
+    let host = match addr.trim() {
+        "" | "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "::1",
+        other => other,
+    };
+    matches!(
+        tokio::time::timeout(
+            Duration::from_millis(200),
+            tokio::net::TcpStream::connect(join_host_port(host, port)),
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
 lazy_static! {
+    /// MIAO_STUB_PROCESSES=1：测试/运行时桩模式，sing-box、gotty、应用、iVnc 均以占位进程代替
+    static ref PROCESS_STUB_MODE: bool = env::var("MIAO_STUB_PROCESSES")
+        .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+        .unwrap_or(false);
     static ref SING_PROCESS: Mutex<Option<SingBoxProcess>> = Mutex::new(None);
     static ref GOTTY_PROCESSES: Mutex<HashMap<String, GottyProcess>> = Mutex::new(HashMap::new());
+    /// 串行化 node_delays.json 的写入，避免并发保存时旧快照覆盖新快照
+    static ref NODE_DELAYS_SAVE_LOCK: Mutex<()> = Mutex::new(());
     static ref APP_PROCESSES: Mutex<HashMap<String, AppProcess>> = Mutex::new(HashMap::new());
     static ref WS_CONNECT_ERROR_LOGS: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
     static ref LOG_BROADCAST: broadcast::Sender<String> = {
@@ -1913,6 +3044,16 @@ lazy_static! {
     };
     static ref SING_LOG_BUFFER: StdMutex<VecDeque<String>> = StdMutex::new(VecDeque::with_capacity(1000));
     static ref MIAO_PORT: StdMutex<u16> = StdMutex::new(6161);
+    static ref PROCESS_EXITS: StdMutex<HashMap<String, ProcessExitInfo>> = StdMutex::new(HashMap::new());
+    static ref METRIC_FAILURES_LOGGED: StdMutex<HashSet<&'static str>> = StdMutex::new(HashSet::new());
+    static ref ENV_OVERRIDES: StdMutex<EnvOverrides> = StdMutex::new(EnvOverrides::default());
+    static ref JWT_SIGNING_SECRET: StdMutex<String> = StdMutex::new(String::new());
+    /// 共享的 HTTP 客户端（连接池），供 webhook 通知与 Clash API 切换复用
+    static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::new();
+    /// 每个终端的重启代数，终端 WebSocket 代理据此区分重启断开与 shell 正常退出
+    static ref TERMINAL_RESTART_GENERATIONS: StdMutex<HashMap<String, u64>> = StdMutex::new(HashMap::new());
+    /// 终端 WebSocket 代理的一次性票据 -> (终端 id, 过期时间)
+    static ref TERMINAL_TICKETS: StdMutex<HashMap<String, (String, Instant)>> = StdMutex::new(HashMap::new());
 }
 
 // ============================================================================
@@ -1928,7 +3069,64 @@ fn broadcast_log(level: &str, message: &str) {
         "level": level,
         "message": message
     });
-    let entry_str = entry.to_string();
+    push_log_entry(entry.to_string());
+}
+
+/// Emit a structured event (`type: "event"`) on the main log stream.
+/// `message` keeps the human readable text so plain log viewers still work.
+fn broadcast_event(
+    level: &str,
+    subsystem: &str,
+    id: &str,
+    event: &str,
+    message: &str,
+    data: serde_json::Value,
+) {
+    use chrono::FixedOffset;
+    println!("{}", message);
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let time_str = Utc::now().with_timezone(&utc8).format("%Y-%m-%d %H:%M:%S").to_string();
+    let entry = serde_json::json!({
+        "time": time_str,
+        "level": level,
+        "message": message,
+        "type": "event",
+        "subsystem": subsystem,
+        "id": id,
+        "event": event,
+        "data": data
+    });
+    push_log_entry(entry.to_string());
+}
+
+/// 拼接 host:port，IPv6 字面量加方括号（`[2001:db8::1]:443`），已带括号的保持不变
+pub(crate) fn join_host_port(host: &str, port: impl std::fmt::Display) -> String {
+    let host = host.trim();
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    if bare.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{}]:{}", bare, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn random_u64() -> u64 {
+    (uuid::Uuid::new_v4().as_u128() >> 64) as u64
+}
+
+/// Spread `base` randomly by +/- `percent` (capped at 50%).
+fn jitter_duration(base: Duration, percent: u8) -> Duration {
+    let percent = percent.min(50) as u64;
+    if percent == 0 {
+        return base;
+    }
+    let base_ms = base.as_millis() as u64;
+    let spread = base_ms * percent / 100;
+    let offset = random_u64() % (spread * 2 + 1);
+    Duration::from_millis((base_ms - spread + offset).max(1))
+}
+
+fn push_log_entry(entry_str: String) {
     {
         let mut buffer = LOG_BUFFER.lock().expect("log buffer lock poisoned");
         buffer.push_back(entry_str.clone());
@@ -2023,9 +3221,25 @@ macro_rules! log_warning {
     }};
 }
 
+fn process_stub_mode() -> bool {
+    *PROCESS_STUB_MODE
+}
+
+/// Placeholder child used in stub mode: logs one line and sleeps until killed,
+/// so pid/uptime/stop/exit bookkeeping behaves like a real process.
+fn stub_process_command(process_name: &str) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("sh");
+    command
+        .arg("-c")
+        .arg("echo \"[stub] $0 started\"; exec sleep 2147483647")
+        .arg(process_name);
+    command
+}
+
 /// Spawns a child process with stdout/stderr piped and captured to the log broadcast.
 /// Returns the spawned Child. The caller is responsible for storing/managing the child.
-#[allow(dead_code)]
+/// Used for user-supplied commands (sync hooks), so it is NOT replaced in stub mode:
+/// a stubbed hook would block until its timeout instead of running.
 fn spawn_with_log_capture(
     command: &mut tokio::process::Command,
     process_name: String,
@@ -2072,6 +3286,13 @@ fn spawn_with_sing_log_capture(
     use std::process::Stdio;
     use tokio::io::{AsyncBufReadExt, BufReader};
 
+    let mut stub;
+    let command = if process_stub_mode() {
+        stub = stub_process_command(&process_name);
+        &mut stub
+    } else {
+        command
+    };
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
     let mut child = command.spawn()?;
 
@@ -2111,6 +3332,13 @@ fn spawn_with_gotty_log_capture(
     use std::process::Stdio;
     use tokio::io::{AsyncBufReadExt, BufReader};
 
+    let mut stub;
+    let command = if process_stub_mode() {
+        stub = stub_process_command(&process_name);
+        &mut stub
+    } else {
+        command
+    };
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
     let mut child = command.spawn()?;
 
@@ -2150,6 +3378,13 @@ fn spawn_with_app_log_capture(
     use std::process::Stdio;
     use tokio::io::{AsyncBufReadExt, BufReader};
 
+    let mut stub;
+    let command = if process_stub_mode() {
+        stub = stub_process_command(&process_name);
+        &mut stub
+    } else {
+        command
+    };
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
     let mut child = command.spawn()?;
 
@@ -2263,31 +3498,115 @@ async fn spa_fallback() -> Response {
     }
 }
 
+/// Unmatched /api/* paths: JSON 404 so API clients never receive index.html
+/// Body-limit rejections come back as plain text; turn them into an `ApiResponse`
+/// so import forms can show why the upload was refused.
+async fn json_payload_too_large(limit: usize, response: Response) -> Response {
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if is_json {
+        return response;
+    }
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(ApiResponse::<()>::error(format!(
+            "Request body too large (limit {} bytes, see max_body_bytes in config.yaml)",
+            limit
+        ))),
+    )
+        .into_response()
+}
+
+async fn api_not_found(Path(path): Path<String>) -> (StatusCode, Json<ApiResponse<()>>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApiResponse::error(format!("API endpoint not found: /api/{}", path))),
+    )
+}
+
+/// Fallback when the UI is disabled: plain 404 instead of index.html
+async fn not_found_fallback() -> Response {
+    (StatusCode::NOT_FOUND, "Not Found").into_response()
+}
+
 /// POST /api/login - User login
 async fn login(
     State(state): State<Arc<AppState>>,
     Json(req): Json<LoginRequest>,
 ) -> Json<ApiResponse<LoginResponse>> {
-    let config = state.config.lock().await;
-
-    // 获取配置中的密码，如果未设置则使用默认密码 "admin123"
-    let expected_password = config.password.as_deref().unwrap_or("admin123");
+    // argon2 校验/哈希是 CPU 密集操作：先拷贝出所需配置再释放锁，放到 blocking 线程池执行
+    let (expected_password, upgrade_plaintext, ttl_secs) = {
+        let config = state.config.lock().await;
+        // 优先使用 MIAO_PASSWORD，其次配置中的密码，都未设置则使用默认密码 "admin123"
+        let env_password = env_password_override();
+        // 配置中仍是明文密码：登录成功后透明升级为哈希
+        let upgrade_plaintext = env_password.is_none()
+            && config
+                .password
+                .as_deref()
+                .is_some_and(|stored| !is_password_hash(stored));
+        let expected_password = env_password
+            .or_else(|| config.password.clone())
+            .unwrap_or_else(|| "admin123".to_string());
+        (expected_password, upgrade_plaintext, token_ttl_secs(&config))
+    };
 
     // 验证密码
-    if req.password != expected_password {
-        return Json(ApiResponse {
-            success: false,
-            message: "密码错误".to_string(),
-            data: None,
-        });
+    let input = req.password;
+    let stored = expected_password.clone();
+    let checked = tokio::task::spawn_blocking(move || {
+        if !verify_password(&input, &stored) {
+            return None;
+        }
+        Some(upgrade_plaintext.then(|| hash_password(&input)))
+    })
+    .await;
+    let upgraded_hash = match checked {
+        Ok(Some(upgraded_hash)) => upgraded_hash,
+        Ok(None) => {
+            return Json(ApiResponse {
+                success: false,
+                message: "密码错误".to_string(),
+                data: None,
+            });
+        }
+        Err(e) => {
+            log_error!("Password verification task failed: {}", e);
+            return Json(ApiResponse::error("密码校验失败"));
+        }
+    };
+
+    match upgraded_hash {
+        Some(Ok(hash)) => {
+            let mut config = state.config.lock().await;
+            // 校验期间密码可能已被修改，只替换仍是刚才校验过的那份明文
+            if config.password.as_deref() == Some(expected_password.as_str()) {
+                let previous = config.password.replace(hash);
+                match save_config(&config).await {
+                    Ok(()) => log_info!("Upgraded stored login password to argon2 hash"),
+                    Err(e) => {
+                        config.password = previous;
+                        log_warning!("Failed to save hashed password: {}", e);
+                    }
+                }
+            }
+        }
+        Some(Err(e)) => log_warning!("{}", e),
+        None => {}
     }
 
     // 生成 token
-    match generate_token() {
-        Ok(token) => Json(ApiResponse {
+    match generate_token(ttl_secs) {
+        Ok(login) => Json(ApiResponse {
             success: true,
             message: "登录成功".to_string(),
-            data: Some(LoginResponse { token }),
+            data: Some(login),
         }),
         Err(_) => Json(ApiResponse {
             success: false,
@@ -2302,13 +3621,23 @@ async fn update_password(
     State(state): State<Arc<AppState>>,
     Json(req): Json<PasswordChangeRequest>,
 ) -> Json<ApiResponse<()>> {
+    if env_password_override().is_some() {
+        return Json(ApiResponse::error("密码由环境变量 MIAO_PASSWORD 指定，无法在此修改"));
+    }
+
     let password = req.password.trim();
     if password.len() < 4 {
         return Json(ApiResponse::error("密码至少 4 位"));
     }
 
+    let password = password.to_string();
+    let hash = match tokio::task::spawn_blocking(move || hash_password(&password)).await {
+        Ok(Ok(hash)) => hash,
+        Ok(Err(e)) => return Json(ApiResponse::error(e)),
+        Err(e) => return Json(ApiResponse::error(format!("密码哈希失败: {}", e))),
+    };
     let mut config = state.config.lock().await;
-    config.password = Some(password.to_string());
+    config.password = Some(hash);
     if let Err(e) = save_config(&config).await {
         return Json(ApiResponse::error(format!("保存配置失败: {}", e)));
     }
@@ -2316,16 +3645,231 @@ async fn update_password(
     Json(ApiResponse::success_no_data("密码已更新"))
 }
 
+/// POST /api/token/rotate - Regenerate the JWT secret, invalidating every issued token
+async fn rotate_token(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<LoginResponse>> {
+    if ENV_OVERRIDES.lock().unwrap().jwt_secret.is_some() {
+        return Json(ApiResponse::error("JWT 密钥由环境变量 MIAO_JWT_SECRET 指定，无法在此轮换"));
+    }
+
+    let secret = new_jwt_secret();
+    let ttl_secs = {
+        let mut config = state.config.lock().await;
+        let previous = config.jwt_secret.replace(secret.clone());
+        if let Err(e) = save_config(&config).await {
+            config.jwt_secret = previous;
+            return Json(ApiResponse::error(format!("保存配置失败: {}", e)));
+        }
+        token_ttl_secs(&config)
+    };
+    *JWT_SIGNING_SECRET.lock().unwrap() = secret;
+    log_warning!("JWT secret rotated, all existing sessions have been logged out");
+
+    // 旧 token 已全部失效，给调用方签发一个新的以免自己也被登出
+    match generate_token(ttl_secs) {
+        Ok(login) => Json(ApiResponse::success("JWT 密钥已轮换，其他会话需重新登录", login)),
+        Err(_) => Json(ApiResponse::error("JWT 密钥已轮换，但生成新 token 失败，请重新登录")),
+    }
+}
+
+/// POST /api/token/refresh - Exchange a still-valid token for a fresh one with a new expiry
+async fn refresh_token(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<LoginResponse>> {
+    // 路由位于 auth_middleware 之后：过期或签名密钥已轮换的 token 到不了这里
+    let ttl_secs = token_ttl_secs(&*state.config.lock().await);
+    match generate_token(ttl_secs) {
+        Ok(login) => Json(ApiResponse::success("token 已刷新", login)),
+        Err(_) => Json(ApiResponse::error("生成 token 失败")),
+    }
+}
+
 /// GET /api/status - Get sing-box running status
 async fn get_status(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<StatusData>> {
-    let mut lock = SING_PROCESS.lock().await;
+    let (running, pid, uptime_secs) = get_sing_box_runtime_status().await;
+
+    let pending_restart = state.sing_box_pending_restart.load(Ordering::Relaxed);
+    Json(ApiResponse::success(
+        if running { "running" } else { "stopped" },
+        StatusData {
+            running,
+            pid,
+            uptime_secs,
+            pending_restart,
+            last_exit: last_process_exit(&sing_box_process_key()),
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct DashboardTunnels {
+    total: usize,
+    forwarding: usize,
+    connecting: usize,
+    error: usize,
+    stopped: usize,
+    sets: usize,
+    sets_enabled: usize,
+}
+
+#[derive(Serialize)]
+struct DashboardSyncs {
+    total: usize,
+    enabled: usize,
+    running: usize,
+    error: usize,
+}
+
+#[derive(Serialize)]
+struct DashboardSubscriptions {
+    total: usize,
+    enabled: usize,
+    failing: usize,
+    stale: usize,
+}
 
-    let (running, pid, uptime_secs) = if let Some(ref mut proc) = *lock {
+#[derive(Serialize)]
+struct DashboardResponse {
+    sing_box: Option<StatusData>,
+    system: Option<serde_json::Value>,
+    tunnels: Option<DashboardTunnels>,
+    syncs: Option<DashboardSyncs>,
+    subscriptions: Option<DashboardSubscriptions>,
+    version: VersionInfo,
+    /// 超时或失败的分区：section -> error
+    errors: HashMap<String, String>,
+}
+
+const DASHBOARD_SECTION_TIMEOUT_MS: u64 = 2_000;
+
+async fn dashboard_section<T>(
+    fut: impl std::future::Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    tokio::time::timeout(Duration::from_millis(DASHBOARD_SECTION_TIMEOUT_MS), fut)
+        .await
+        .unwrap_or_else(|_| Err("timed out".to_string()))
+}
+
+async fn dashboard_tunnels(state: &AppState) -> Result<DashboardTunnels, String> {
+    let (tunnels, sets) = {
+        let config = state.config.lock().await;
+        (config.tcp_tunnels.clone(), config.tcp_tunnel_sets.clone())
+    };
+    let mut summary = DashboardTunnels {
+        total: 0,
+        forwarding: 0,
+        connecting: 0,
+        error: 0,
+        stopped: 0,
+        sets: sets.len(),
+        sets_enabled: sets.iter().filter(|s| s.enabled).count(),
+    };
+    for t in tunnels.iter().filter(|t| t.managed_by.is_none()) {
+        summary.total += 1;
+        let status = state.tcp_tunnel.get_status(&t.id).await.unwrap_or_default();
+        match status.state {
+            tcp_tunnel::TunnelState::Forwarding => summary.forwarding += 1,
+            tcp_tunnel::TunnelState::Connecting => summary.connecting += 1,
+            tcp_tunnel::TunnelState::Error => summary.error += 1,
+            tcp_tunnel::TunnelState::Stopped => summary.stopped += 1,
+        }
+    }
+    Ok(summary)
+}
+
+async fn dashboard_syncs(state: &AppState) -> Result<DashboardSyncs, String> {
+    let syncs = { state.config.lock().await.syncs.clone() };
+    let mut summary = DashboardSyncs {
+        total: syncs.len(),
+        enabled: syncs.iter().filter(|s| s.enabled).count(),
+        running: 0,
+        error: 0,
+    };
+    for sync in &syncs {
+        match state.sync_manager.get_status(&sync.id).await.state {
+            SyncState::Running => summary.running += 1,
+            SyncState::Error => summary.error += 1,
+            SyncState::Stopped => {}
+        }
+    }
+    Ok(summary)
+}
+
+async fn dashboard_subscriptions(state: &AppState) -> Result<DashboardSubscriptions, String> {
+    let (total, enabled) = {
+        let config = state.config.lock().await;
+        (
+            config.subscriptions.len(),
+            config.subscriptions.iter().filter(|s| s.enabled).count(),
+        )
+    };
+    let status = state.subscription_status.lock().await;
+    Ok(DashboardSubscriptions {
+        total,
+        enabled,
+        failing: status.values().filter(|r| r.error.is_some() && !r.stale).count(),
+        stale: status.values().filter(|r| r.stale).count(),
+    })
+}
+
+/// GET /api/dashboard - 首页汇总（sing-box、系统、隧道、同步、订阅、版本），各分区独立超时
+async fn get_dashboard(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<DashboardResponse>> {
+    let (sing_box, system, tunnels, syncs, subscriptions, version) = tokio::join!(
+        dashboard_section(async {
+            let (running, pid, uptime_secs) = get_sing_box_runtime_status().await;
+            Ok(StatusData {
+                running,
+                pid,
+                uptime_secs,
+                pending_restart: state.sing_box_pending_restart.load(Ordering::Relaxed),
+                last_exit: last_process_exit(&sing_box_process_key()),
+            })
+        }),
+        dashboard_section(load_system_status(&state)),
+        dashboard_section(dashboard_tunnels(&state)),
+        dashboard_section(dashboard_syncs(&state)),
+        dashboard_section(dashboard_subscriptions(&state)),
+        dashboard_section(async { get_version().await.0.data.ok_or_else(|| "unavailable".to_string()) }),
+    );
+
+    let mut errors: HashMap<String, String> = HashMap::new();
+    fn take<T>(section: &str, result: Result<T, String>, errors: &mut HashMap<String, String>) -> Option<T> {
+        result.map_err(|e| errors.insert(section.to_string(), e)).ok()
+    }
+    let version = take("version", version, &mut errors).unwrap_or_else(|| VersionInfo {
+        current: format!("v{}", VERSION),
+        latest: None,
+        has_update: false,
+        download_url: None,
+    });
+
+    Json(ApiResponse::success(
+        "Dashboard",
+        DashboardResponse {
+            sing_box: take("sing_box", sing_box, &mut errors),
+            system: take("system", system, &mut errors),
+            tunnels: take("tunnels", tunnels, &mut errors),
+            syncs: take("syncs", syncs, &mut errors),
+            subscriptions: take("subscriptions", subscriptions, &mut errors),
+            version,
+            errors,
+        },
+    ))
+}
+
+/// (running, pid, uptime_secs) of the sing-box child; reaps it if it has exited.
+async fn get_sing_box_runtime_status() -> (bool, Option<u32>, Option<u64>) {
+    let mut lock = SING_PROCESS.lock().await;
+    if let Some(ref mut proc) = *lock {
         match proc.child.try_wait() {
-            Ok(Some(_)) => {
+            Ok(Some(status)) => {
                 *lock = None;
+                record_process_exit(sing_box_process_key(), status);
                 (false, None, None)
             }
             Ok(None) => {
@@ -2336,43 +3880,122 @@ async fn get_status(
         }
     } else {
         (false, None, None)
+    }
+}
+
+/// 当前编译目标对应的发布包架构名（与 GitHub release 资产命名一致），不支持时返回 None
+fn binary_arch() -> Option<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => Some("amd64"),
+        "aarch64" => Some("arm64"),
+        _ => None,
+    }
+}
+
+fn unsupported_arch_message(name: &str) -> String {
+    format!(
+        "当前架构 {} 没有可用的 {} 发布包（仅支持 amd64/arm64）",
+        std::env::consts::ARCH,
+        name
+    )
+}
+
+/// ELF 头中 e_machine 对应当前架构的取值
+fn expected_elf_machine() -> Option<u16> {
+    match std::env::consts::ARCH {
+        "x86_64" => Some(0x3E),
+        "aarch64" => Some(0xB7),
+        _ => None,
+    }
+}
+
+#[derive(Serialize)]
+struct BinaryCapability {
+    installed: bool,
+    executable: bool,
+    /// None 表示无法判断（非 ELF 文件或未知架构）
+    arch_ok: Option<bool>,
+    available: bool,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// 检查托管二进制是否存在、可执行且与当前架构匹配
+fn inspect_binary(path: &std::path::Path, name: &str) -> BinaryCapability {
+    use std::io::Read;
+
+    let mut cap = BinaryCapability {
+        installed: false,
+        executable: false,
+        arch_ok: None,
+        available: false,
+        path: path.to_string_lossy().to_string(),
+        error: None,
     };
 
-    let pending_restart = state.sing_box_pending_restart.load(Ordering::Relaxed);
-    Json(ApiResponse::success(
-        if running { "running" } else { "stopped" },
-        StatusData {
-            running,
-            pid,
-            uptime_secs,
-            pending_restart,
-        },
-    ))
+    let meta = match fs::metadata(path) {
+        Ok(m) if m.is_file() => m,
+        _ => {
+            cap.error = Some(format!("{} 未安装: {}", name, cap.path));
+            return cap;
+        }
+    };
+    cap.installed = true;
+    cap.executable = meta.permissions().mode() & 0o111 != 0;
+
+    let mut header = [0u8; 20];
+    let is_elf = fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok()
+        && header[..4] == *b"\x7fELF";
+    if is_elf {
+        // EI_DATA: 1 = little endian, 2 = big endian
+        let machine = if header[5] == 2 {
+            u16::from_be_bytes([header[18], header[19]])
+        } else {
+            u16::from_le_bytes([header[18], header[19]])
+        };
+        cap.arch_ok = expected_elf_machine().map(|expected| expected == machine);
+    }
+
+    cap.error = if cap.arch_ok == Some(false) {
+        Some(format!(
+            "{} 与当前架构 {} 不匹配，请重新安装",
+            name,
+            std::env::consts::ARCH
+        ))
+    } else if !cap.executable {
+        Some(format!("{} 没有可执行权限: {}", name, cap.path))
+    } else {
+        None
+    };
+    cap.available = cap.error.is_none();
+    cap
+}
+
+/// 启动前检查二进制是否可用，返回可读的错误而不是原始的 spawn 失败
+fn ensure_binary_runnable(path: &std::path::Path, name: &str) -> Result<(), String> {
+    if process_stub_mode() {
+        return Ok(());
+    }
+    match inspect_binary(path, name).error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
-/// GET /api/binaries/status - Check if sing-box, gotty and ivnc binaries exist
+/// GET /api/binaries/status - Check if sing-box, gotty and ivnc binaries exist and can run on this arch
 async fn get_binaries_status() -> Json<ApiResponse<serde_json::Value>> {
     let current_dir = std::env::current_dir().unwrap_or_default();
-    let sing_box_exists = current_dir.join("sing-box").exists();
-    let gotty_exists = current_dir.join("gotty").exists();
-    let ivnc_exists = current_dir.join("ivnc").exists();
-
-    let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
 
     Json(ApiResponse::success("ok", json!({
-        "sing_box": {
-            "installed": sing_box_exists,
-            "path": current_dir.join("sing-box").to_string_lossy()
-        },
-        "gotty": {
-            "installed": gotty_exists,
-            "path": current_dir.join("gotty").to_string_lossy()
-        },
-        "ivnc": {
-            "installed": ivnc_exists,
-            "path": current_dir.join("ivnc").to_string_lossy()
-        },
-        "arch": arch
+        "sing_box": inspect_binary(&current_dir.join("sing-box"), "sing-box"),
+        "gotty": inspect_binary(&current_dir.join("gotty"), "gotty"),
+        "ivnc": inspect_binary(&current_dir.join("ivnc"), "ivnc"),
+        "arch": binary_arch().unwrap_or(std::env::consts::ARCH),
+        "target_arch": std::env::consts::ARCH,
+        "arch_supported": binary_arch().is_some()
     })))
 }
 
@@ -2383,7 +4006,9 @@ async fn install_sing_box() -> Json<ApiResponse<serde_json::Value>> {
         Err(e) => return Json(ApiResponse::error(format!("获取当前目录失败: {}", e))),
     };
 
-    let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
+    let Some(arch) = binary_arch() else {
+        return Json(ApiResponse::error(unsupported_arch_message("sing-box")));
+    };
     let client = reqwest::Client::new();
     let release_url = "https://api.github.com/repos/SagerNet/sing-box/releases/latest";
 
@@ -2484,7 +4109,9 @@ async fn install_gotty() -> Json<ApiResponse<serde_json::Value>> {
         Err(e) => return Json(ApiResponse::error(format!("获取当前目录失败: {}", e))),
     };
 
-    let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
+    let Some(arch) = binary_arch() else {
+        return Json(ApiResponse::error(unsupported_arch_message("gotty")));
+    };
     let url = format!(
         "https://github.com/Xiechengqi/gotty/releases/download/latest/gotty-linux-{}",
         arch
@@ -2530,7 +4157,9 @@ async fn install_ivnc() -> Json<ApiResponse<serde_json::Value>> {
         Err(e) => return Json(ApiResponse::error(format!("获取当前目录失败: {}", e))),
     };
 
-    let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
+    let Some(arch) = binary_arch() else {
+        return Json(ApiResponse::error(unsupported_arch_message("ivnc")));
+    };
     let client = reqwest::Client::new();
     let download_url = format!(
         "https://github.com/Xiechengqi/iVnc/releases/latest/download/ivnc-linux-{}",
@@ -2631,7 +4260,10 @@ async fn perform_sing_box_upgrade(log_tx: tokio::sync::mpsc::Sender<UpgradeLogEn
     // Step 1: Get latest release info
     send_log(1, "获取最新版本信息...", "info", None).await;
 
-    let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
+    let Some(arch) = binary_arch() else {
+        send_log(1, &unsupported_arch_message("sing-box"), "error", None).await;
+        return;
+    };
     let client = reqwest::Client::new();
     let release_url = "https://api.github.com/repos/SagerNet/sing-box/releases/latest";
 
@@ -3051,7 +4683,10 @@ async fn perform_ivnc_upgrade(log_tx: tokio::sync::mpsc::Sender<UpgradeLogEntry>
     // Step 1: Get latest release
     send_log(1, "获取最新版本信息...", "info", None).await;
 
-    let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
+    let Some(arch) = binary_arch() else {
+        send_log(1, &unsupported_arch_message("ivnc"), "error", None).await;
+        return;
+    };
     let client = reqwest::Client::new();
     let download_url = format!(
         "https://github.com/Xiechengqi/iVnc/releases/latest/download/ivnc-linux-{}",
@@ -3140,65 +4775,110 @@ async fn perform_ivnc_upgrade(log_tx: tokio::sync::mpsc::Sender<UpgradeLogEntry>
     send_log(4, "iVNC 更新完成", "success", None).await;
 }
 
-async fn refresh_system_metrics(state: &AppState) -> Result<(), String> {
+/// 调用 machine_info 并捕获 panic/错误：部分内核上 system_status/graphics_status 会 panic，
+/// 单项失败只让该项为空，不影响其它指标；同一项只记录一次日志
+fn guarded_metric<T>(name: &'static str, f: impl FnOnce() -> Result<T, String>) -> Option<T> {
+    let result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(panic) => Err(panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".to_string())),
+    };
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let first = METRIC_FAILURES_LOGGED
+                .lock()
+                .map(|mut logged| logged.insert(name))
+                .unwrap_or(false);
+            if first {
+                log_warning!("Metric '{}' unavailable, reporting null: {}", name, e);
+            }
+            None
+        }
+    }
+}
+
+/// 刷新 system info/status 缓存；`record` 为 true 且开启 metrics 时写入历史
+async fn refresh_system_metrics(state: &AppState, record: bool) -> Result<(), String> {
     let mut machine = state.system_monitor.machine.lock().await;
-    let mut info = machine.system_info();
-    let status = machine
-        .system_status()
-        .map_err(|e| format!("Failed to read system status: {}", e))?;
-    let graphics = machine.graphics_status();
+    let mut info = guarded_metric("system_info", || Ok(machine.system_info()));
+    let status = guarded_metric("system_status", || {
+        machine.system_status().map_err(|e| e.to_string())
+    });
+    let graphics = guarded_metric("graphics_status", || Ok(machine.graphics_status()));
     drop(machine);
 
-    if info.processor.brand.trim().is_empty() {
-        if let Some(fallback) = read_cpu_brand_fallback(&info) {
-            info.processor.brand = fallback;
+    if let Some(info) = info.as_mut() {
+        if info.processor.brand.trim().is_empty() {
+            if let Some(fallback) = read_cpu_brand_fallback(info) {
+                info.processor.brand = fallback;
+            }
         }
     }
 
     let sample_period_secs = state.metrics_config.sample_interval_secs.max(1);
     let (primary_disk_used, primary_disk_total) =
-        select_primary_disk(&info).unwrap_or((0, 0));
-    let gpu_percent = average_gpu_percent(&graphics);
+        info.as_ref().and_then(select_primary_disk).unwrap_or((0, 0));
+    let gpu_percent = graphics.as_deref().and_then(average_gpu_percent);
 
-    let mut seen_mounts: HashSet<String> = HashSet::new();
-    let disks_usage = info
-        .disks
-        .iter()
-        .filter(|disk| {
-            let key = if disk.mount_point.is_empty() {
-                &disk.name
-            } else {
-                &disk.mount_point
-            };
-            seen_mounts.insert(key.to_string())
-        })
-        .map(|disk| {
-            json!({
-                "name": disk.name,
-                "used": disk.size.saturating_sub(disk.available),
-                "total": disk.size
+    let disks_usage = info.as_ref().map(|info| {
+        let mut seen_mounts: HashSet<String> = HashSet::new();
+        info.disks
+            .iter()
+            .filter(|disk| {
+                let key = if disk.mount_point.is_empty() {
+                    &disk.name
+                } else {
+                    &disk.mount_point
+                };
+                seen_mounts.insert(key.to_string())
             })
-        })
-        .collect::<Vec<_>>();
+            .map(|disk| {
+                json!({
+                    "name": disk.name,
+                    "used": disk.size.saturating_sub(disk.available),
+                    "total": disk.size
+                })
+            })
+            .collect::<Vec<_>>()
+    });
 
-    let info_value = serde_json::to_value(&info)
-        .map_err(|e| format!("Failed to serialize system info: {}", e))?;
+    // system_info 不可用时保留上次的 info 缓存
+    if let Some(info) = info.as_ref() {
+        let info_value = serde_json::to_value(info)
+            .map_err(|e| format!("Failed to serialize system info: {}", e))?;
+        *state.system_monitor.info_cache.lock().await = Some(info_value);
+    }
     let uptime_secs = read_uptime_secs();
+    let (net_rx_rate, net_tx_rate) = sample_net_rates(state).await;
+    let swap = read_swap_kb();
+    let load_avg = read_load_avg();
     let status_value = json!({
         "timestamp": chrono::Utc::now().timestamp(),
         "samplePeriodSecs": sample_period_secs,
-        "cpuPercent": status.cpu,
-        "memoryUsedKb": status.memory,
+        "cpuPercent": status.as_ref().map(|s| s.cpu),
+        "memoryUsedKb": status.as_ref().map(|s| s.memory),
         "uptimeSecs": uptime_secs,
         "graphics": graphics,
         "disks": disks_usage,
-        "nvidiaAvailable": !graphics.is_empty()
+        "netRxBytesPerSec": net_rx_rate,
+        "netTxBytesPerSec": net_tx_rate,
+        "swapUsedKb": swap.map(|(used, _)| used),
+        "swapTotalKb": swap.map(|(_, total)| total),
+        "loadAvg": load_avg,
+        "nvidiaAvailable": graphics.as_ref().map(|g| !g.is_empty()).unwrap_or(false)
     });
 
-    *state.system_monitor.info_cache.lock().await = Some(info_value);
     *state.system_monitor.status_cache.lock().await = Some(status_value);
+    *state.system_monitor.refreshed_at.lock().await = Some(Instant::now());
+    // 顺带推进自身 CPU 采样窗口，使 /api/self/metrics 反映最近一个采样周期
+    let _ = sample_self_metrics(state).await;
 
-    if state.metrics_config.enabled {
+    // CPU/内存不可用时不写入历史（表结构要求非空）
+    if let (true, Some(status)) = (record && state.metrics_config.enabled, status) {
         let record = MetricsRecord {
             timestamp: chrono::Utc::now().timestamp(),
             cpu_percent: status.cpu,
@@ -3206,13 +4886,86 @@ async fn refresh_system_metrics(state: &AppState) -> Result<(), String> {
             gpu_percent,
             disk_used_bytes: primary_disk_used,
             disk_total_bytes: primary_disk_total,
+            net_rx_bytes_per_sec: net_rx_rate,
+            net_tx_bytes_per_sec: net_tx_rate,
+            swap_used_kb: swap.map(|(used, _)| used),
+            swap_total_kb: swap.map(|(_, total)| total),
+            load_avg,
         };
-        write_metrics_record(&state.metrics_config, record).await?;
+        write_metrics_record(state, record).await?;
     }
 
     Ok(())
 }
 
+/// 读取 /proc/net/dev 中所有网卡的累计收发字节数（跳过 lo 与 sing-box 的 TUN 网卡，避免重复计算）
+fn read_net_dev_totals() -> Option<(u64, u64)> {
+    let contents = fs::read_to_string("/proc/net/dev").ok()?;
+    let mut rx_total = 0u64;
+    let mut tx_total = 0u64;
+    // 前两行为表头
+    for line in contents.lines().skip(2) {
+        let Some((iface, counters)) = line.split_once(':') else {
+            continue;
+        };
+        let iface = iface.trim();
+        if iface == "lo" || iface == "sing-tun" {
+            continue;
+        }
+        let fields: Vec<u64> = counters
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        if fields.len() < 9 {
+            continue;
+        }
+        rx_total = rx_total.saturating_add(fields[0]);
+        tx_total = tx_total.saturating_add(fields[8]);
+    }
+    Some((rx_total, tx_total))
+}
+
+/// 与上一次采样相比的网络收发速率（字节/秒）；首次采样或计数器回绕时为 None
+async fn sample_net_rates(state: &AppState) -> (Option<u64>, Option<u64>) {
+    let Some((rx, tx)) = read_net_dev_totals() else {
+        return (None, None);
+    };
+    let now = Instant::now();
+    let prev = state.system_monitor.net_prev.lock().await.replace((now, rx, tx));
+    let Some((prev_at, prev_rx, prev_tx)) = prev else {
+        return (None, None);
+    };
+    let secs = now.duration_since(prev_at).as_secs_f64();
+    if secs <= 0.0 || rx < prev_rx || tx < prev_tx {
+        return (None, None);
+    }
+    (
+        Some(((rx - prev_rx) as f64 / secs) as u64),
+        Some(((tx - prev_tx) as f64 / secs) as u64),
+    )
+}
+
+/// 从 /proc/meminfo 读取 swap 使用量与总量（KB）
+fn read_swap_kb() -> Option<(u64, u64)> {
+    let contents = fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| {
+        contents
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|rest| rest.split_whitespace().next()?.parse::<u64>().ok())
+    };
+    let total = field("SwapTotal")?;
+    let free = field("SwapFree")?;
+    Some((total.saturating_sub(free), total))
+}
+
+/// 从 /proc/loadavg 读取 1/5/15 分钟平均负载
+fn read_load_avg() -> Option<[f64; 3]> {
+    let contents = fs::read_to_string("/proc/loadavg").ok()?;
+    let mut parts = contents.split_whitespace().map(|v| v.parse::<f64>().ok());
+    Some([parts.next()??, parts.next()??, parts.next()??])
+}
+
 fn read_uptime_secs() -> Option<u64> {
     let contents = fs::read_to_string("/proc/uptime").ok()?;
     let first = contents.split_whitespace().next()?;
@@ -3260,15 +5013,39 @@ fn read_cpu_brand_fallback(info: &machine_info::SystemInfo) -> Option<String> {
     hardware.or(processor)
 }
 
+/// 历史指标中的一个桶；没有样本的桶（停机等）所有字段为 null，前端据此断开曲线
 #[derive(Serialize)]
 struct MetricsPoint {
     timestamp: i64,
-    cpu_percent: i32,
-    memory_used_kb: i32,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu_percent: Option<i32>,
+    memory_used_kb: Option<i32>,
     gpu_percent: Option<i32>,
-    disk_used_bytes: u64,
-    disk_total_bytes: u64,
+    disk_used_bytes: Option<u64>,
+    disk_total_bytes: Option<u64>,
+    net_rx_bytes_per_sec: Option<u64>,
+    net_tx_bytes_per_sec: Option<u64>,
+    swap_used_kb: Option<u64>,
+    swap_total_kb: Option<u64>,
+    /// 1/5/15 分钟平均负载
+    load_avg: Option<[f64; 3]>,
+}
+
+impl MetricsPoint {
+    fn gap(timestamp: i64) -> Self {
+        MetricsPoint {
+            timestamp,
+            cpu_percent: None,
+            memory_used_kb: None,
+            gpu_percent: None,
+            disk_used_bytes: None,
+            disk_total_bytes: None,
+            net_rx_bytes_per_sec: None,
+            net_tx_bytes_per_sec: None,
+            swap_used_kb: None,
+            swap_total_kb: None,
+            load_avg: None,
+        }
+    }
 }
 
 struct MetricsRecord {
@@ -3278,12 +5055,100 @@ struct MetricsRecord {
     gpu_percent: Option<i32>,
     disk_used_bytes: u64,
     disk_total_bytes: u64,
+    net_rx_bytes_per_sec: Option<u64>,
+    net_tx_bytes_per_sec: Option<u64>,
+    swap_used_kb: Option<u64>,
+    swap_total_kb: Option<u64>,
+    load_avg: Option<[f64; 3]>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct MetricsQuery {
     range: Option<String>,
     step: Option<String>,
+    /// 每个桶内的聚合函数：avg（默认）/ max / min / p95
+    agg: Option<String>,
+    /// 为没有样本的桶补 null 点（默认 true）
+    fill_gaps: Option<bool>,
+}
+
+/// 历史指标按桶聚合时使用的函数
+#[derive(Clone, Copy, PartialEq)]
+enum MetricsAgg {
+    Avg,
+    Max,
+    Min,
+    P95,
+}
+
+/// 受 `agg` 影响的列及其是否为整数列；磁盘、swap 等容量类数值始终取桶内最后一个样本
+const METRICS_AGG_COLUMNS: &[(&str, bool)] = &[
+    ("cpu_percent", true),
+    ("gpu_percent", true),
+    ("memory_used_kb", true),
+    ("net_rx_bytes_per_sec", true),
+    ("net_tx_bytes_per_sec", true),
+    ("load_avg_1", false),
+    ("load_avg_5", false),
+    ("load_avg_15", false),
+];
+
+impl MetricsAgg {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricsAgg::Avg => "avg",
+            MetricsAgg::Max => "max",
+            MetricsAgg::Min => "min",
+            MetricsAgg::P95 => "p95",
+        }
+    }
+
+    fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("avg") {
+            "avg" => Ok(MetricsAgg::Avg),
+            "max" => Ok(MetricsAgg::Max),
+            "min" => Ok(MetricsAgg::Min),
+            "p95" => Ok(MetricsAgg::P95),
+            other => Err(format!(
+                "Invalid agg '{}', expected one of: avg, max, min, p95",
+                other
+            )),
+        }
+    }
+
+    /// 对 bucketed 表别名 b 中某列的聚合表达式
+    fn column_sql(self, column: &str, integer: bool) -> String {
+        match self {
+            // avg 保持原有行为：内存取桶内最后一个样本
+            MetricsAgg::Avg if column == "memory_used_kb" => "b2.memory_used_kb".to_string(),
+            MetricsAgg::Avg if integer => format!("CAST(AVG(b.{0}) AS INTEGER)", column),
+            MetricsAgg::Avg => format!("AVG(b.{0})", column),
+            MetricsAgg::Max => format!("MAX(b.{0})", column),
+            MetricsAgg::Min => format!("MIN(b.{0})", column),
+            // 桶内按值排序（NULL 排最后），取排名 >= ceil(0.95 * 非空样本数) 的最小值
+            MetricsAgg::P95 => format!(
+                "MIN(CASE WHEN b.{0}_rank >= (b.{0}_n * 95 + 99) / 100 THEN b.{0} END)",
+                column
+            ),
+        }
+    }
+
+    /// p95 需要的窗口列（其余聚合函数不需要）
+    fn window_columns_sql(self) -> String {
+        if self != MetricsAgg::P95 {
+            return String::new();
+        }
+        METRICS_AGG_COLUMNS
+            .iter()
+            .map(|(column, _)| {
+                format!(
+                    ",\n                    ROW_NUMBER() OVER (PARTITION BY (timestamp / ?1) * ?1 ORDER BY {0} IS NULL, {0}) AS {0}_rank,\n                    COUNT({0}) OVER (PARTITION BY (timestamp / ?1) * ?1) AS {0}_n",
+                    column
+                )
+            })
+            .collect()
+    }
 }
 
 fn parse_duration_to_secs(input: &str) -> Option<i64> {
@@ -3308,8 +5173,13 @@ fn default_step_label(range_secs: i64) -> String {
         "1m".to_string()
     } else if range_secs <= 86400 {
         "5m".to_string()
-    } else {
+    } else if range_secs <= 7 * 86400 {
         "15m".to_string()
+    } else if range_secs <= 30 * 86400 {
+        // 超过原始保留期的部分来自小时汇总表，步长不宜小于 1h
+        "1h".to_string()
+    } else {
+        "1d".to_string()
     }
 }
 
@@ -3330,9 +5200,12 @@ fn average_gpu_percent(graphics: &[machine_info::GraphicsUsage]) -> Option<i32>
     Some((sum / graphics.len() as u32) as i32)
 }
 
-fn init_metrics_db(path: &str) -> Result<(), String> {
+/// 打开指标库并完成建表/迁移。WAL + synchronous=NORMAL 让采样写入与查询互不阻塞
+fn open_metrics_db(path: &str) -> Result<Connection, String> {
     let conn = Connection::open(path)
         .map_err(|e| format!("Failed to open metrics db: {}", e))?;
+    conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")
+        .map_err(|e| format!("Failed to configure metrics db: {}", e))?;
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS system_metrics (
             timestamp INTEGER NOT NULL,
@@ -3345,31 +5218,136 @@ fn init_metrics_db(path: &str) -> Result<(), String> {
         CREATE INDEX IF NOT EXISTS idx_system_metrics_ts ON system_metrics(timestamp);",
     )
     .map_err(|e| format!("Failed to init metrics db: {}", e))?;
-    Ok(())
+    // 旧库升级：后续新增的列均可为空
+    for (column, sql_type) in METRICS_EXTRA_COLUMNS {
+        ensure_metrics_column(&conn, column, sql_type)?;
+    }
+    // 小时级汇总表：原始样本过期删除前先聚合到这里，用于长期查询
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS system_metrics_hourly (
+            bucket_ts INTEGER PRIMARY KEY,
+            cpu_percent INTEGER NOT NULL,
+            memory_used_kb INTEGER NOT NULL,
+            gpu_percent INTEGER,
+            disk_used_bytes INTEGER NOT NULL,
+            disk_total_bytes INTEGER NOT NULL,
+            net_rx_bytes_per_sec INTEGER,
+            net_tx_bytes_per_sec INTEGER,
+            swap_used_kb INTEGER,
+            swap_total_kb INTEGER,
+            load_avg_1 REAL,
+            load_avg_5 REAL,
+            load_avg_15 REAL
+        );",
+    )
+    .map_err(|e| format!("Failed to init metrics db: {}", e))?;
+    // 同步任务运行历史，与指标共用一个库
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sync_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sync_id TEXT NOT NULL,
+            started_at_ms INTEGER NOT NULL,
+            finished_at_ms INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            error TEXT,
+            files_transferred INTEGER NOT NULL,
+            bytes_sent INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sync_runs_sync ON sync_runs(sync_id, started_at_ms);",
+    )
+    .map_err(|e| format!("Failed to init metrics db: {}", e))?;
+    Ok(conn)
 }
 
-fn insert_metrics_record(path: &str, record: &MetricsRecord) -> Result<(), String> {
-    let conn = Connection::open(path)
-        .map_err(|e| format!("Failed to open metrics db: {}", e))?;
-    conn.execute(
-        "INSERT INTO system_metrics (timestamp, cpu_percent, memory_used_kb, gpu_percent, disk_used_bytes, disk_total_bytes)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![
-            record.timestamp,
+/// 在阻塞线程中使用 AppState 里常驻的指标库连接（首次使用时打开）
+async fn with_metrics_db<T, F>(state: &AppState, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+{
+    let db = state.metrics_db.clone();
+    let storage_path = state.metrics_config.storage_path.clone();
+    spawn_blocking(move || {
+        let mut guard = db.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = Some(open_metrics_db(&storage_path)?);
+        }
+        let conn = guard.as_ref().expect("metrics db opened above");
+        f(conn)
+    })
+    .await
+    .map_err(|e| format!("Metrics task failed: {}", e))?
+}
+
+const METRICS_CHECKPOINT_INTERVAL_SECS: u64 = 3600;
+/// 每隔多少次 checkpoint 做一次 VACUUM（默认约一天一次）
+const METRICS_VACUUM_EVERY_CHECKPOINTS: u64 = 24;
+
+/// 截断 WAL 文件；`vacuum` 为 true 时先 VACUUM 回收裁剪后留下的空闲页
+fn maintain_metrics_db(conn: &Connection, vacuum: bool) -> Result<(), String> {
+    if vacuum {
+        conn.execute_batch("VACUUM;")
+            .map_err(|e| format!("Failed to vacuum metrics db: {}", e))?;
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+        .map_err(|e| format!("Failed to checkpoint metrics db: {}", e))
+}
+
+const METRICS_ROLLUP_SECS: i64 = 3600;
+
+/// 建表之后新增的 system_metrics 列（均可为空）
+const METRICS_EXTRA_COLUMNS: &[(&str, &str)] = &[
+    ("net_rx_bytes_per_sec", "INTEGER"),
+    ("net_tx_bytes_per_sec", "INTEGER"),
+    ("swap_used_kb", "INTEGER"),
+    ("swap_total_kb", "INTEGER"),
+    ("load_avg_1", "REAL"),
+    ("load_avg_5", "REAL"),
+    ("load_avg_15", "REAL"),
+];
+
+fn ensure_metrics_column(conn: &Connection, column: &str, sql_type: &str) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT 1 FROM pragma_table_info('system_metrics') WHERE name = ?1")
+        .map_err(|e| format!("Failed to inspect metrics db: {}", e))?;
+    let exists = stmt
+        .exists(params![column])
+        .map_err(|e| format!("Failed to inspect metrics db: {}", e))?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE system_metrics ADD COLUMN {} {}",
+            column, sql_type
+        ))
+        .map_err(|e| format!("Failed to migrate metrics db: {}", e))?;
+    }
+    Ok(())
+}
+
+fn insert_metrics_record(conn: &Connection, record: &MetricsRecord) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO system_metrics (timestamp, cpu_percent, memory_used_kb, gpu_percent, disk_used_bytes, disk_total_bytes, net_rx_bytes_per_sec, net_tx_bytes_per_sec, swap_used_kb, swap_total_kb, load_avg_1, load_avg_5, load_avg_15)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            record.timestamp,
             record.cpu_percent,
             record.memory_used_kb,
             record.gpu_percent,
             record.disk_used_bytes as i64,
             record.disk_total_bytes as i64,
+            record.net_rx_bytes_per_sec.map(|v| v as i64),
+            record.net_tx_bytes_per_sec.map(|v| v as i64),
+            record.swap_used_kb.map(|v| v as i64),
+            record.swap_total_kb.map(|v| v as i64),
+            record.load_avg.map(|l| l[0]),
+            record.load_avg.map(|l| l[1]),
+            record.load_avg.map(|l| l[2]),
         ],
     )
     .map_err(|e| format!("Failed to insert metrics: {}", e))?;
     Ok(())
 }
 
-fn prune_metrics(path: &str, cutoff_ts: i64) -> Result<(), String> {
-    let conn = Connection::open(path)
-        .map_err(|e| format!("Failed to open metrics db: {}", e))?;
+fn prune_metrics(conn: &Connection, cutoff_ts: i64) -> Result<(), String> {
     conn.execute(
         "DELETE FROM system_metrics WHERE timestamp < ?1",
         params![cutoff_ts],
@@ -3378,37 +5356,197 @@ fn prune_metrics(path: &str, cutoff_ts: i64) -> Result<(), String> {
     Ok(())
 }
 
+/// 把已结束、尚未汇总的整小时原始样本聚合进 system_metrics_hourly。
+/// 内存/磁盘/swap 取该小时最后一个样本（SQLite 的 MAX() 裸列语义），其余取平均值。
+fn rollup_metrics_hourly(conn: &Connection, now_ts: i64) -> Result<usize, String> {
+    let current_hour = (now_ts / METRICS_ROLLUP_SECS) * METRICS_ROLLUP_SECS;
+    conn.execute(
+        "INSERT OR IGNORE INTO system_metrics_hourly (bucket_ts, cpu_percent, memory_used_kb, gpu_percent, disk_used_bytes, disk_total_bytes, net_rx_bytes_per_sec, net_tx_bytes_per_sec, swap_used_kb, swap_total_kb, load_avg_1, load_avg_5, load_avg_15)
+         SELECT bucket_ts, cpu_percent, memory_used_kb, gpu_percent, disk_used_bytes, disk_total_bytes, net_rx_bytes_per_sec, net_tx_bytes_per_sec, swap_used_kb, swap_total_kb, load_avg_1, load_avg_5, load_avg_15
+         FROM (
+             SELECT
+                 (timestamp / ?1) * ?1 AS bucket_ts,
+                 MAX(timestamp) AS latest_ts,
+                 CAST(AVG(cpu_percent) AS INTEGER) AS cpu_percent,
+                 memory_used_kb,
+                 CAST(AVG(gpu_percent) AS INTEGER) AS gpu_percent,
+                 disk_used_bytes,
+                 disk_total_bytes,
+                 CAST(AVG(net_rx_bytes_per_sec) AS INTEGER) AS net_rx_bytes_per_sec,
+                 CAST(AVG(net_tx_bytes_per_sec) AS INTEGER) AS net_tx_bytes_per_sec,
+                 swap_used_kb,
+                 swap_total_kb,
+                 AVG(load_avg_1) AS load_avg_1,
+                 AVG(load_avg_5) AS load_avg_5,
+                 AVG(load_avg_15) AS load_avg_15
+             FROM system_metrics
+             WHERE timestamp >= (SELECT COALESCE(MAX(bucket_ts) + ?1, 0) FROM system_metrics_hourly)
+               AND timestamp < ?2
+             GROUP BY bucket_ts
+         )",
+        params![METRICS_ROLLUP_SECS, current_hour],
+    )
+    .map_err(|e| format!("Failed to roll up metrics: {}", e))
+}
+
+fn prune_metrics_rollup(conn: &Connection, cutoff_ts: i64) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM system_metrics_hourly WHERE bucket_ts < ?1",
+        params![cutoff_ts],
+    )
+    .map_err(|e| format!("Failed to prune metrics rollup: {}", e))?;
+    Ok(())
+}
+
+fn reset_metrics(conn: &Connection) -> Result<usize, String> {
+    let mut removed = conn
+        .execute("DELETE FROM system_metrics", [])
+        .map_err(|e| format!("Failed to reset metrics: {}", e))?;
+    removed += conn
+        .execute("DELETE FROM system_metrics_hourly", [])
+        .map_err(|e| format!("Failed to reset metrics: {}", e))?;
+    maintain_metrics_db(conn, true)?;
+    Ok(removed)
+}
+
+/// 每个同步任务最多保留的运行记录条数
+const SYNC_HISTORY_KEEP_PER_SYNC: i64 = 1000;
+const SYNC_HISTORY_DEFAULT_LIMIT: usize = 50;
+const SYNC_HISTORY_MAX_LIMIT: usize = 500;
+
+fn insert_sync_run(conn: &Connection, record: &sync::SyncRunRecord) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO sync_runs (sync_id, started_at_ms, finished_at_ms, status, error, files_transferred, bytes_sent)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            record.sync_id,
+            record.started_at_ms,
+            record.finished_at_ms,
+            record.status,
+            record.error,
+            record.files_transferred as i64,
+            record.bytes_sent as i64,
+        ],
+    )
+    .map_err(|e| format!("Failed to insert sync run: {}", e))?;
+    conn.execute(
+        "DELETE FROM sync_runs WHERE sync_id = ?1 AND id NOT IN (
+             SELECT id FROM sync_runs WHERE sync_id = ?1 ORDER BY id DESC LIMIT ?2
+         )",
+        params![record.sync_id, SYNC_HISTORY_KEEP_PER_SYNC],
+    )
+    .map_err(|e| format!("Failed to prune sync runs: {}", e))?;
+    Ok(())
+}
+
+fn load_sync_runs(
+    conn: &Connection,
+    sync_id: &str,
+    limit: usize,
+) -> Result<Vec<sync::SyncRunRecord>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT sync_id, started_at_ms, finished_at_ms, status, error, files_transferred, bytes_sent
+             FROM sync_runs WHERE sync_id = ?1 ORDER BY started_at_ms DESC, id DESC LIMIT ?2",
+        )
+        .map_err(|e| format!("Failed to query sync runs: {}", e))?;
+    let rows = stmt
+        .query_map(params![sync_id, limit as i64], |row| {
+            Ok(sync::SyncRunRecord {
+                sync_id: row.get(0)?,
+                started_at_ms: row.get(1)?,
+                finished_at_ms: row.get(2)?,
+                status: row.get(3)?,
+                error: row.get(4)?,
+                files_transferred: row.get::<_, i64>(5)?.max(0) as u64,
+                bytes_sent: row.get::<_, i64>(6)?.max(0) as u64,
+            })
+        })
+        .map_err(|e| format!("Failed to query sync runs: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read sync runs: {}", e))
+}
+
+fn delete_sync_runs(conn: &Connection, sync_id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM sync_runs WHERE sync_id = ?1", params![sync_id])
+        .map_err(|e| format!("Failed to delete sync runs: {}", e))?;
+    Ok(())
+}
+
 async fn write_metrics_record(
-    config: &MetricsConfig,
+    state: &AppState,
     record: MetricsRecord,
 ) -> Result<(), String> {
-    let storage_path = config.storage_path.clone();
+    let config = &state.metrics_config;
     let retention_days = config.retention_days;
     let cutoff_ts = record.timestamp - (retention_days as i64 * 86400);
-    spawn_blocking(move || {
-        init_metrics_db(&storage_path)?;
-        insert_metrics_record(&storage_path, &record)?;
+    let rollup_retention_days = config.rollup_retention_days;
+    let rollup_cutoff_ts = record.timestamp - (rollup_retention_days as i64 * 86400);
+    with_metrics_db(state, move |conn| {
+        insert_metrics_record(conn, &record)?;
+        // 先汇总再删除，保证过期的原始样本已经进入小时表
+        rollup_metrics_hourly(conn, record.timestamp)?;
         if retention_days > 0 {
-            prune_metrics(&storage_path, cutoff_ts)?;
+            prune_metrics(conn, cutoff_ts)?;
+        }
+        if rollup_retention_days > 0 {
+            prune_metrics_rollup(conn, rollup_cutoff_ts)?;
         }
-        Ok::<(), String>(())
+        Ok(())
     })
     .await
-    .map_err(|e| format!("Metrics task failed: {}", e))??;
-    Ok(())
 }
 
 fn load_metrics_series(
-    path: &str,
+    conn: &Connection,
     start_ts: i64,
     end_ts: i64,
     step_secs: i64,
+    agg: MetricsAgg,
+    fill_gaps: bool,
 ) -> Result<Vec<MetricsPoint>, String> {
-    let conn = Connection::open(path)
-        .map_err(|e| format!("Failed to open metrics db: {}", e))?;
-    let mut stmt = conn
-        .prepare(
-            "WITH bucketed AS (
+    let mut points = Vec::new();
+    for_each_metrics_point(conn, start_ts, end_ts, step_secs, agg, fill_gaps, |point| {
+        points.push(point);
+        true
+    })?;
+    Ok(points)
+}
+
+/// 按桶逐行读取历史指标，`on_point` 返回 false 时提前结束（用于流式导出）。
+/// 早于最旧原始样本的时间段自动改读小时汇总表；`fill_gaps` 时为空桶补 `MetricsPoint::gap`。
+fn for_each_metrics_point(
+    conn: &Connection,
+    start_ts: i64,
+    end_ts: i64,
+    step_secs: i64,
+    agg: MetricsAgg,
+    fill_gaps: bool,
+    mut on_point: impl FnMut(MetricsPoint) -> bool,
+) -> Result<(), String> {
+    let sql = format!(
+        "WITH raw_start AS (
+                SELECT COALESCE(MIN(timestamp), ?3 + 1) AS ts FROM system_metrics
+            ),
+            samples AS (
+                SELECT
+                    bucket_ts AS timestamp,
+                    cpu_percent,
+                    memory_used_kb,
+                    gpu_percent,
+                    disk_used_bytes,
+                    disk_total_bytes,
+                    net_rx_bytes_per_sec,
+                    net_tx_bytes_per_sec,
+                    swap_used_kb,
+                    swap_total_kb,
+                    load_avg_1,
+                    load_avg_5,
+                    load_avg_15
+                FROM system_metrics_hourly
+                WHERE bucket_ts >= ?2 AND bucket_ts <= ?3
+                  AND bucket_ts + ?4 <= (SELECT ts FROM raw_start)
+                UNION ALL
                 SELECT
                     timestamp,
                     cpu_percent,
@@ -3416,10 +5554,34 @@ fn load_metrics_series(
                     gpu_percent,
                     disk_used_bytes,
                     disk_total_bytes,
-                    (timestamp / ?1) * ?1 AS bucket_ts
+                    net_rx_bytes_per_sec,
+                    net_tx_bytes_per_sec,
+                    swap_used_kb,
+                    swap_total_kb,
+                    load_avg_1,
+                    load_avg_5,
+                    load_avg_15
                 FROM system_metrics
                 WHERE timestamp >= ?2 AND timestamp <= ?3
             ),
+            bucketed AS (
+                SELECT
+                    timestamp,
+                    cpu_percent,
+                    memory_used_kb,
+                    gpu_percent,
+                    disk_used_bytes,
+                    disk_total_bytes,
+                    net_rx_bytes_per_sec,
+                    net_tx_bytes_per_sec,
+                    swap_used_kb,
+                    swap_total_kb,
+                    load_avg_1,
+                    load_avg_5,
+                    load_avg_15,
+                    (timestamp / ?1) * ?1 AS bucket_ts{windows}
+                FROM samples
+            ),
             latest_in_bucket AS (
                 SELECT bucket_ts, MAX(timestamp) AS latest_ts
                 FROM bucketed
@@ -3427,36 +5589,88 @@ fn load_metrics_series(
             )
             SELECT
                 b.bucket_ts AS timestamp,
-                CAST(AVG(b.cpu_percent) AS INTEGER) AS cpu_percent,
-                CAST(AVG(b.gpu_percent) AS INTEGER) AS gpu_percent,
-                b2.memory_used_kb AS memory_used_kb,
+                {cpu} AS cpu_percent,
+                {gpu} AS gpu_percent,
+                {mem} AS memory_used_kb,
                 b2.disk_used_bytes AS disk_used_bytes,
-                b2.disk_total_bytes AS disk_total_bytes
+                b2.disk_total_bytes AS disk_total_bytes,
+                {rx} AS net_rx_bytes_per_sec,
+                {tx} AS net_tx_bytes_per_sec,
+                b2.swap_used_kb AS swap_used_kb,
+                b2.swap_total_kb AS swap_total_kb,
+                {l1} AS load_avg_1,
+                {l5} AS load_avg_5,
+                {l15} AS load_avg_15
             FROM bucketed b
             JOIN latest_in_bucket l ON b.bucket_ts = l.bucket_ts
             JOIN bucketed b2 ON b2.bucket_ts = l.bucket_ts AND b2.timestamp = l.latest_ts
-            GROUP BY b.bucket_ts, b2.memory_used_kb, b2.disk_used_bytes, b2.disk_total_bytes
+            GROUP BY b.bucket_ts, b2.memory_used_kb, b2.disk_used_bytes, b2.disk_total_bytes, b2.swap_used_kb, b2.swap_total_kb
             ORDER BY b.bucket_ts ASC",
-        )
+        windows = agg.window_columns_sql(),
+        cpu = agg.column_sql("cpu_percent", true),
+        gpu = agg.column_sql("gpu_percent", true),
+        mem = agg.column_sql("memory_used_kb", true),
+        rx = agg.column_sql("net_rx_bytes_per_sec", true),
+        tx = agg.column_sql("net_tx_bytes_per_sec", true),
+        l1 = agg.column_sql("load_avg_1", false),
+        l5 = agg.column_sql("load_avg_5", false),
+        l15 = agg.column_sql("load_avg_15", false),
+    );
+    let mut stmt = conn
+        .prepare(&sql)
         .map_err(|e| format!("Failed to prepare metrics query: {}", e))?;
     let rows = stmt
-        .query_map(params![step_secs, start_ts, end_ts], |row| {
+        .query_map(params![step_secs, start_ts, end_ts, METRICS_ROLLUP_SECS], |row| {
             Ok(MetricsPoint {
                 timestamp: row.get(0)?,
                 cpu_percent: row.get(1)?,
                 gpu_percent: row.get(2)?,
                 memory_used_kb: row.get(3)?,
-                disk_used_bytes: row.get::<_, i64>(4)? as u64,
-                disk_total_bytes: row.get::<_, i64>(5)? as u64,
+                disk_used_bytes: row.get::<_, Option<i64>>(4)?.map(|v| v as u64),
+                disk_total_bytes: row.get::<_, Option<i64>>(5)?.map(|v| v as u64),
+                net_rx_bytes_per_sec: row.get::<_, Option<i64>>(6)?.map(|v| v as u64),
+                net_tx_bytes_per_sec: row.get::<_, Option<i64>>(7)?.map(|v| v as u64),
+                swap_used_kb: row.get::<_, Option<i64>>(8)?.map(|v| v as u64),
+                swap_total_kb: row.get::<_, Option<i64>>(9)?.map(|v| v as u64),
+                load_avg: match (
+                    row.get::<_, Option<f64>>(10)?,
+                    row.get::<_, Option<f64>>(11)?,
+                    row.get::<_, Option<f64>>(12)?,
+                ) {
+                    (Some(l1), Some(l5), Some(l15)) => Some([l1, l5, l15]),
+                    _ => None,
+                },
             })
         })
         .map_err(|e| format!("Failed to load metrics: {}", e))?;
 
-    let mut points = Vec::new();
+    // 桶起点与 SQL 中的 (timestamp / step) * step 对齐
+    let mut next_ts = (start_ts / step_secs) * step_secs;
     for row in rows {
-        points.push(row.map_err(|e| format!("Failed to parse metrics row: {}", e))?);
+        let point = row.map_err(|e| format!("Failed to parse metrics row: {}", e))?;
+        if fill_gaps {
+            while next_ts < point.timestamp {
+                if !on_point(MetricsPoint::gap(next_ts)) {
+                    return Ok(());
+                }
+                next_ts += step_secs;
+            }
+            next_ts = point.timestamp + step_secs;
+        }
+        if !on_point(point) {
+            return Ok(());
+        }
     }
-    Ok(points)
+    if fill_gaps {
+        let last_ts = (end_ts / step_secs) * step_secs;
+        while next_ts <= last_ts {
+            if !on_point(MetricsPoint::gap(next_ts)) {
+                break;
+            }
+            next_ts += step_secs;
+        }
+    }
+    Ok(())
 }
 
 fn detect_os_id() -> String {
@@ -3615,18 +5829,32 @@ async fn get_tools_status() -> Json<ApiResponse<serde_json::Value>> {
     Json(ApiResponse::success("Tools status", data))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/system/info",
+    tag = "system",
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_system_info(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    {
+    if state.system_monitor.is_fresh(state.metrics_config.cache_ttl_secs).await {
         let cache = state.system_monitor.info_cache.lock().await;
         if let Some(value) = cache.as_ref() {
             return Json(ApiResponse::success("System info", value.clone()));
         }
     }
 
-    if let Err(e) = refresh_system_metrics(&state).await {
-        return Json(ApiResponse::error(e));
+    // 缓存缺失或过期：按需刷新（不写历史），失败时退回旧缓存
+    if let Err(e) = refresh_system_metrics(&state, false).await {
+        let cache = state.system_monitor.info_cache.lock().await;
+        return match cache.as_ref() {
+            Some(value) => Json(ApiResponse::success("System info", value.clone())),
+            None => Json(ApiResponse::error(e)),
+        };
     }
 
     let cache = state.system_monitor.info_cache.lock().await;
@@ -3636,27 +5864,155 @@ async fn get_system_info(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/system/status",
+    tag = "system",
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_system_status(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    {
+    match load_system_status(&state).await {
+        Ok(value) => Json(ApiResponse::success("System status", value)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// /proc/<pid>/stat 中 CPU 时间的单位（USER_HZ，Linux 用户态 ABI 固定为 100）
+const PROC_CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+#[derive(Serialize)]
+struct SelfMetrics {
+    pid: u32,
+    rss_kb: Option<u64>,
+    threads: Option<u64>,
+    open_fds: Option<usize>,
+    cpu_time_secs: Option<f64>,
+    /// 距上一次采样的 CPU 占用（单核 100%），首次采样为 None
+    cpu_percent: Option<f64>,
+    tokio_alive_tasks: usize,
+    tokio_workers: usize,
+}
+
+/// 读取 /proc/<proc>/status 中的数值字段（如 VmRSS、Threads）
+fn read_proc_status_field(proc_dir: &str, name: &str) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", proc_dir)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .and_then(|rest| rest.split_whitespace().next()?.parse::<u64>().ok())
+}
+
+/// 读取 /proc/<proc>/stat 中累计的用户态 + 内核态 CPU 时间（clock ticks）
+fn read_proc_cpu_ticks(proc_dir: &str) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", proc_dir)).ok()?;
+    // comm 字段可能包含空格，从最后一个 ')' 之后开始解析；utime/stime 为第 14/15 个字段
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    Some(utime + stime)
+}
+
+/// 两次采样之间的 CPU 占用（单核 100%）
+fn cpu_percent_between(prev: Option<(Instant, u64)>, now: Instant, ticks: u64) -> Option<f64> {
+    let (at, prev_ticks) = prev?;
+    let secs = now.duration_since(at).as_secs_f64();
+    (secs > 0.0 && ticks >= prev_ticks)
+        .then(|| (ticks - prev_ticks) as f64 / PROC_CLOCK_TICKS_PER_SEC / secs * 100.0)
+}
+
+/// 读取 miao 进程自身的资源占用（/proc/self/*）
+async fn sample_self_metrics(state: &AppState) -> SelfMetrics {
+    let rss_kb = read_proc_status_field("self", "VmRSS");
+    let threads = read_proc_status_field("self", "Threads");
+
+    let cpu_ticks = read_proc_cpu_ticks("self");
+    let cpu_percent = match cpu_ticks {
+        Some(ticks) => {
+            let now = Instant::now();
+            let prev = state.system_monitor.self_cpu_prev.lock().await.replace((now, ticks));
+            cpu_percent_between(prev, now, ticks)
+        }
+        None => None,
+    };
+
+    let runtime = tokio::runtime::Handle::current().metrics();
+    SelfMetrics {
+        pid: std::process::id(),
+        rss_kb,
+        threads,
+        open_fds: fs::read_dir("/proc/self/fd").ok().map(|dir| dir.count()),
+        cpu_time_secs: cpu_ticks.map(|t| t as f64 / PROC_CLOCK_TICKS_PER_SEC),
+        cpu_percent,
+        tokio_alive_tasks: runtime.num_alive_tasks(),
+        tokio_workers: runtime.num_workers(),
+    }
+}
+
+/// GET /api/self/metrics - miao 自身的内存、CPU、文件描述符与 tokio 任务数
+async fn get_self_metrics(State(state): State<Arc<AppState>>) -> Json<ApiResponse<SelfMetrics>> {
+    Json(ApiResponse::success("Self metrics", sample_self_metrics(&state).await))
+}
+
+/// 读取系统状态缓存；缺失或过期时按需刷新（不写历史），失败时退回旧缓存
+async fn load_system_status(state: &AppState) -> Result<serde_json::Value, String> {
+    if state.system_monitor.is_fresh(state.metrics_config.cache_ttl_secs).await {
         let cache = state.system_monitor.status_cache.lock().await;
         if let Some(value) = cache.as_ref() {
-            return Json(ApiResponse::success("System status", value.clone()));
+            return Ok(value.clone());
         }
     }
 
-    if let Err(e) = refresh_system_metrics(&state).await {
-        return Json(ApiResponse::error(e));
-    }
-
+    let refreshed = refresh_system_metrics(state, false).await;
     let cache = state.system_monitor.status_cache.lock().await;
-    match cache.as_ref() {
-        Some(value) => Json(ApiResponse::success("System status", value.clone())),
-        None => Json(ApiResponse::error("System status not available")),
+    match (cache.as_ref(), refreshed) {
+        (Some(value), _) => Ok(value.clone()),
+        (None, Err(e)) => Err(e),
+        (None, Ok(())) => Err("System status not available".to_string()),
     }
 }
 
+/// GET /api/system/status/sse - 以 SSE 周期推送系统状态（WebSocket 不可用时的替代）
+async fn system_status_sse(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<SseStatusQuery>,
+) -> Result<Sse<impl futures_util::Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if verify_token(&q.token).is_err() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let period = q
+        .interval_secs
+        .unwrap_or(SYSTEM_STATUS_SSE_INTERVAL_SECS)
+        .max(1);
+    let mut ticker = tokio::time::interval(Duration::from_secs(period));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    let stream = futures_util::stream::unfold((state, ticker), |(state, mut ticker)| async move {
+        ticker.tick().await;
+        let event = match load_system_status(&state).await {
+            Ok(value) => Event::default().data(value.to_string()),
+            Err(e) => Event::default().event("error").data(e),
+        };
+        Some((Ok(event), (state, ticker)))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/metrics",
+    tag = "system",
+    params(MetricsQuery),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_system_metrics(
     State(state): State<Arc<AppState>>,
     Query(query): Query<MetricsQuery>,
@@ -3683,20 +6039,21 @@ async fn get_system_metrics(
         return Json(ApiResponse::error("Step must be <= range"));
     }
 
+    let agg = match MetricsAgg::parse(query.agg.as_deref()) {
+        Ok(agg) => agg,
+        Err(err) => return Json(ApiResponse::error(err)),
+    };
+    let fill_gaps = query.fill_gaps.unwrap_or(true);
+
     let end_ts = chrono::Utc::now().timestamp();
     let start_ts = end_ts - range_secs;
-    let storage_path = state.metrics_config.storage_path.clone();
-
-    let result = spawn_blocking(move || {
-        init_metrics_db(&storage_path)?;
-        load_metrics_series(&storage_path, start_ts, end_ts, step_secs)
+    let result = with_metrics_db(&state, move |conn| {
+        load_metrics_series(conn, start_ts, end_ts, step_secs, agg, fill_gaps)
     })
-    .await
-    .map_err(|e| format!("Metrics task failed: {}", e));
+    .await;
 
     let series = match result {
-        Ok(Ok(series)) => series,
-        Ok(Err(err)) => return Json(ApiResponse::error(err)),
+        Ok(series) => series,
         Err(err) => return Json(ApiResponse::error(err)),
     };
 
@@ -3705,27 +6062,181 @@ async fn get_system_metrics(
         json!({
             "range": range_label,
             "step": step_label,
+            "agg": agg.as_str(),
             "series": series
         }),
     ))
 }
 
+#[derive(Deserialize)]
+struct MetricsExportQuery {
+    range: Option<String>,
+    step: Option<String>,
+    agg: Option<String>,
+    fill_gaps: Option<bool>,
+    format: Option<String>,
+}
+
+const METRICS_CSV_HEADER: &str =
+    "timestamp,cpu_percent,memory_used_kb,gpu_percent,disk_used_bytes,disk_total_bytes,net_rx_bytes_per_sec,net_tx_bytes_per_sec,swap_used_kb,swap_total_kb,load_avg_1,load_avg_5,load_avg_15\n";
+const METRICS_CSV_CHUNK_BYTES: usize = 16 * 1024;
+
+fn metrics_csv_line(point: &MetricsPoint) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{}\n",
+        point.timestamp,
+        point.cpu_percent.map(|v| v.to_string()).unwrap_or_default(),
+        point.memory_used_kb.map(|v| v.to_string()).unwrap_or_default(),
+        point.gpu_percent.map(|v| v.to_string()).unwrap_or_default(),
+        point.disk_used_bytes.map(|v| v.to_string()).unwrap_or_default(),
+        point.disk_total_bytes.map(|v| v.to_string()).unwrap_or_default(),
+        point.net_rx_bytes_per_sec.map(|v| v.to_string()).unwrap_or_default(),
+        point.net_tx_bytes_per_sec.map(|v| v.to_string()).unwrap_or_default(),
+        point.swap_used_kb.map(|v| v.to_string()).unwrap_or_default(),
+        point.swap_total_kb.map(|v| v.to_string()).unwrap_or_default(),
+        point.load_avg.map(|l| format!("{:.2},{:.2},{:.2}", l[0], l[1], l[2])).unwrap_or_else(|| ",,".to_string())
+    )
+}
+
+/// GET /api/metrics/export - 以 CSV 流式导出历史指标（range/step/agg/fill_gaps 同 /api/system/metrics）
+async fn export_system_metrics(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MetricsExportQuery>,
+) -> Response {
+    let reject = |status: StatusCode, msg: &str| {
+        (status, Json(ApiResponse::<()>::error(msg))).into_response()
+    };
+    if !state.metrics_config.enabled {
+        return reject(StatusCode::BAD_REQUEST, "Metrics storage is disabled");
+    }
+    let format = query.format.unwrap_or_else(|| "csv".to_string());
+    if !format.eq_ignore_ascii_case("csv") {
+        return reject(StatusCode::BAD_REQUEST, "Unsupported format (only csv)");
+    }
+
+    let range_label = query.range.unwrap_or_else(|| "1h".to_string());
+    let range_secs = match parse_duration_to_secs(&range_label) {
+        Some(value) if value > 0 => value,
+        _ => return reject(StatusCode::BAD_REQUEST, "Invalid range"),
+    };
+    let step_label = query
+        .step
+        .unwrap_or_else(|| default_step_label(range_secs));
+    let step_secs = match parse_duration_to_secs(&step_label) {
+        Some(value) if value > 0 => value,
+        _ => return reject(StatusCode::BAD_REQUEST, "Invalid step"),
+    };
+    if step_secs > range_secs {
+        return reject(StatusCode::BAD_REQUEST, "Step must be <= range");
+    }
+    let agg = match MetricsAgg::parse(query.agg.as_deref()) {
+        Ok(agg) => agg,
+        Err(err) => return reject(StatusCode::BAD_REQUEST, &err),
+    };
+    let fill_gaps = query.fill_gaps.unwrap_or(true);
+
+    let end_ts = chrono::Utc::now().timestamp();
+    let start_ts = end_ts - range_secs;
+    let storage_path = state.metrics_config.storage_path.clone();
+
+    // 导出可能持续很久（受客户端读取速度限制），单独开一个连接，
+    // WAL 模式下不会阻塞常驻连接上的采样写入
+    let conn = match spawn_blocking(move || open_metrics_db(&storage_path)).await {
+        Ok(Ok(conn)) => conn,
+        Ok(Err(e)) => return reject(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        Err(e) => {
+            return reject(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Metrics task failed: {}", e),
+            )
+        }
+    };
+
+    // 在阻塞线程里逐行读取并分块发送，避免大范围导出时整体驻留内存
+    let (tx, rx) = tokio::sync::mpsc::channel::<String>(8);
+    spawn_blocking(move || {
+        let mut chunk = String::from(METRICS_CSV_HEADER);
+        let result = for_each_metrics_point(&conn, start_ts, end_ts, step_secs, agg, fill_gaps, |point| {
+            chunk.push_str(&metrics_csv_line(&point));
+            if chunk.len() < METRICS_CSV_CHUNK_BYTES {
+                return true;
+            }
+            tx.blocking_send(std::mem::take(&mut chunk)).is_ok()
+        });
+        if let Err(e) = result {
+            log_error!("Metrics export failed: {}", e);
+            return;
+        }
+        if !chunk.is_empty() {
+            let _ = tx.blocking_send(chunk);
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (Ok::<_, Infallible>(chunk), rx))
+    });
+    let filename = format!("miao-metrics-{}.csv", range_label);
+    (
+        StatusCode::OK,
+        [
+            (axum::http::header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        axum::body::Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+/// POST /api/system/metrics/reset - 清空历史指标数据
+#[utoipa::path(
+    post,
+    path = "/api/system/metrics/reset",
+    tag = "system",
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn reset_system_metrics(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    if !state.metrics_config.enabled {
+        return Json(ApiResponse::error("Metrics storage is disabled"));
+    }
+
+    match with_metrics_db(&state, reset_metrics).await {
+        Ok(removed) => {
+            log_info!("Metrics history reset, {} rows removed", removed);
+            Json(ApiResponse::success(
+                "Metrics history reset",
+                json!({ "removed": removed }),
+            ))
+        }
+        Err(err) => Json(ApiResponse::error(err)),
+    }
+}
+
 async fn get_terminal_runtime_status(id: &str) -> TerminalRuntimeStatus {
     let mut lock = GOTTY_PROCESSES.lock().await;
     if let Some(proc) = lock.get_mut(id) {
         match proc.child.try_wait() {
-            Ok(Some(_)) => {
+            Ok(Some(status)) => {
                 lock.remove(id);
                 TerminalRuntimeStatus {
                     running: false,
                     pid: None,
                     uptime_secs: None,
+                    last_exit: Some(record_process_exit(terminal_process_key(id), status)),
                 }
             }
             Ok(None) => TerminalRuntimeStatus {
                 running: true,
                 pid: proc.child.id(),
                 uptime_secs: Some(proc.started_at.elapsed().as_secs()),
+                last_exit: last_process_exit(&terminal_process_key(id)),
             },
             Err(_) => {
                 lock.remove(id);
@@ -3733,6 +6244,7 @@ async fn get_terminal_runtime_status(id: &str) -> TerminalRuntimeStatus {
                     running: false,
                     pid: None,
                     uptime_secs: None,
+                    last_exit: last_process_exit(&terminal_process_key(id)),
                 }
             }
         }
@@ -3741,6 +6253,7 @@ async fn get_terminal_runtime_status(id: &str) -> TerminalRuntimeStatus {
             running: false,
             pid: None,
             uptime_secs: None,
+            last_exit: last_process_exit(&terminal_process_key(id)),
         }
     }
 }
@@ -3749,18 +6262,20 @@ async fn get_app_runtime_status(id: &str) -> AppRuntimeStatus {
     let mut lock = APP_PROCESSES.lock().await;
     if let Some(proc) = lock.get_mut(id) {
         match proc.child.try_wait() {
-            Ok(Some(_)) => {
+            Ok(Some(status)) => {
                 lock.remove(id);
                 AppRuntimeStatus {
                     running: false,
                     pid: None,
                     uptime_secs: None,
+                    last_exit: Some(record_process_exit(app_process_key(id), status)),
                 }
             }
             Ok(None) => AppRuntimeStatus {
                 running: true,
                 pid: proc.child.id(),
                 uptime_secs: Some(proc.started_at.elapsed().as_secs()),
+                last_exit: last_process_exit(&app_process_key(id)),
             },
             Err(_) => {
                 lock.remove(id);
@@ -3768,6 +6283,7 @@ async fn get_app_runtime_status(id: &str) -> AppRuntimeStatus {
                     running: false,
                     pid: None,
                     uptime_secs: None,
+                    last_exit: last_process_exit(&app_process_key(id)),
                 }
             }
         }
@@ -3776,6 +6292,7 @@ async fn get_app_runtime_status(id: &str) -> AppRuntimeStatus {
             running: false,
             pid: None,
             uptime_secs: None,
+            last_exit: last_process_exit(&app_process_key(id)),
         }
     }
 }
@@ -3792,6 +6309,7 @@ fn build_terminal_item(cfg: TerminalNodeConfig, status: TerminalRuntimeStatus) -
         auth_username: cfg.auth_username,
         auth_password: cfg.auth_password,
         extra_args: cfg.extra_args,
+        startup_check_ms: cfg.startup_check_ms,
         status,
     }
 }
@@ -3807,6 +6325,7 @@ fn build_app_item(cfg: AppConfig, status: AppRuntimeStatus) -> AppItem {
         command: cfg.command,
         args: cfg.args,
         env: cfg.env,
+        startup_check_ms: cfg.startup_check_ms,
         status,
     }
 }
@@ -3821,44 +6340,259 @@ async fn get_terminals(State(state): State<Arc<AppState>>) -> Json<ApiResponse<T
     Json(ApiResponse::success("Terminals", TerminalListResponse { items }))
 }
 
-// iVnc API endpoints
-async fn get_ivnc_status(State(state): State<Arc<AppState>>) -> Json<ApiResponse<IVncStatus>> {
-    let installed = check_ivnc_installed();
-    let version = get_ivnc_version();
-    let config = state.ivnc_config.lock().await.clone();
-
-    let process_guard = state.ivnc_process.lock().await;
-    let (running, pid, uptime_secs) = if let Some(proc) = process_guard.as_ref() {
-        let uptime = proc.started_at.elapsed().as_secs();
-        (true, Some(proc.pid), Some(uptime))
+/// (running, pid, uptime_secs) of the iVnc child; reaps it if it has exited.
+async fn get_ivnc_runtime_status(state: &AppState) -> (bool, Option<u32>, Option<u64>) {
+    let mut process_guard = state.ivnc_process.lock().await;
+    if let Some(proc) = process_guard.as_mut() {
+        match proc.child.try_wait() {
+            Ok(Some(status)) => {
+                *process_guard = None;
+                record_process_exit(ivnc_process_key(), status);
+                (false, None, None)
+            }
+            _ => {
+                let uptime = proc.started_at.elapsed().as_secs();
+                (true, Some(proc.pid), Some(uptime))
+            }
+        }
     } else {
         (false, None, None)
-    };
+    }
+}
 
-    Json(ApiResponse::success("iVnc 状态", IVncStatus {
-        installed,
-        version,
-        running,
-        pid,
-        uptime_secs,
-        port: config.port,
-    }))
+#[derive(Serialize)]
+struct ManagedProcessItem {
+    #[serde(rename = "type")]
+    kind: &'static str,  // sing-box / terminal / app / ivnc
+    id: String,
+    name: Option<String>,
+    state: &'static str, // running / stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uptime_secs: Option<u64>,
+    /// 距上一次查询的 CPU 占用（单核 100%），首次查询为 None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu_percent: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rss_kb: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_exit: Option<ProcessExitInfo>,
 }
 
-async fn start_ivnc(State(state): State<Arc<AppState>>) -> Result<Json<ApiResponse<()>>, (StatusCode, String)> {
-    if !check_ivnc_installed() {
-        return Err((StatusCode::BAD_REQUEST, "iVnc 未安装".to_string()));
+impl ManagedProcessItem {
+    fn new(
+        kind: &'static str,
+        id: String,
+        name: Option<String>,
+        (running, pid, uptime_secs): (bool, Option<u32>, Option<u64>),
+        last_exit: Option<ProcessExitInfo>,
+    ) -> Self {
+        Self {
+            kind,
+            id,
+            name,
+            state: if running { "running" } else { "stopped" },
+            pid,
+            uptime_secs,
+            cpu_percent: None,
+            rss_kb: None,
+            last_exit,
+        }
     }
+}
 
-    if state.ivnc_process.lock().await.is_some() {
-        return Err((StatusCode::BAD_REQUEST, "iVnc 已在运行".to_string()));
-    }
+/// 有数量上限的资源类型
+#[derive(Clone, Copy)]
+enum ResourceKind {
+    Tunnels,
+    Syncs,
+    Terminals,
+    Apps,
+    VncSessions,
+}
 
-    let config = state.ivnc_config.lock().await.clone();
-    generate_ivnc_config(&config)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+impl ResourceKind {
+    const ALL: [ResourceKind; 5] = [
+        ResourceKind::Tunnels,
+        ResourceKind::Syncs,
+        ResourceKind::Terminals,
+        ResourceKind::Apps,
+        ResourceKind::VncSessions,
+    ];
 
-    let log_path = get_ivnc_log_path();
+    fn key(self) -> &'static str {
+        match self {
+            ResourceKind::Tunnels => "tunnels",
+            ResourceKind::Syncs => "syncs",
+            ResourceKind::Terminals => "terminals",
+            ResourceKind::Apps => "apps",
+            ResourceKind::VncSessions => "vnc_sessions",
+        }
+    }
+
+    fn limit(self, config: &Config) -> usize {
+        match self {
+            ResourceKind::Tunnels => config.max_tunnels.unwrap_or(DEFAULT_MAX_TUNNELS),
+            ResourceKind::Syncs => config.max_syncs.unwrap_or(DEFAULT_MAX_SYNCS),
+            ResourceKind::Terminals => config.max_terminals.unwrap_or(DEFAULT_MAX_TERMINALS),
+            ResourceKind::Apps => config.max_apps.unwrap_or(DEFAULT_MAX_APPS),
+            ResourceKind::VncSessions => config.max_vnc_sessions.unwrap_or(DEFAULT_MAX_VNC_SESSIONS),
+        }
+    }
+
+    /// 配置中已有的数量；iVnc 会话数取决于运行状态，由调用方提供
+    fn count(self, config: &Config) -> usize {
+        match self {
+            ResourceKind::Tunnels => config
+                .tcp_tunnels
+                .iter()
+                .filter(|t| t.managed_by.is_none())
+                .count(),
+            ResourceKind::Syncs => config.syncs.len(),
+            ResourceKind::Terminals => config.terminals.len(),
+            ResourceKind::Apps => config.apps.len(),
+            ResourceKind::VncSessions => 0,
+        }
+    }
+}
+
+/// 新建资源前检查是否已达上限
+fn ensure_resource_capacity(config: &Config, kind: ResourceKind, used: usize) -> Result<(), String> {
+    let limit = kind.limit(config);
+    if used >= limit {
+        return Err(format!(
+            "Limit reached: at most {} {} allowed (max_{} in config.yaml)",
+            limit,
+            kind.key(),
+            kind.key()
+        ));
+    }
+    Ok(())
+}
+
+/// GET /api/limits - 各类资源的当前数量与上限
+async fn get_resource_limits(State(state): State<Arc<AppState>>) -> Json<ApiResponse<serde_json::Value>> {
+    let vnc_running = state.ivnc_process.lock().await.is_some() as usize;
+    let config = state.config.lock().await;
+    let mut limits = serde_json::Map::new();
+    for kind in ResourceKind::ALL {
+        let used = match kind {
+            ResourceKind::VncSessions => vnc_running,
+            _ => kind.count(&config),
+        };
+        limits.insert(
+            kind.key().to_string(),
+            json!({ "used": used, "max": kind.limit(&config) }),
+        );
+    }
+    Json(ApiResponse::success("Resource limits", serde_json::Value::Object(limits)))
+}
+
+/// GET /api/processes - 所有托管子进程（sing-box、终端、应用、iVnc）的运行状态
+async fn get_processes(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<Vec<ManagedProcessItem>>> {
+    let (terminals, apps) = {
+        let config = state.config.lock().await;
+        (config.terminals.clone(), config.apps.clone())
+    };
+
+    let mut items = Vec::with_capacity(terminals.len() + apps.len() + 2);
+    items.push(ManagedProcessItem::new(
+        "sing-box",
+        sing_box_process_key(),
+        Some("sing-box".to_string()),
+        get_sing_box_runtime_status().await,
+        last_process_exit(&sing_box_process_key()),
+    ));
+    for t in terminals {
+        let status = get_terminal_runtime_status(&t.id).await;
+        items.push(ManagedProcessItem::new(
+            "terminal",
+            t.id,
+            t.name,
+            (status.running, status.pid, status.uptime_secs),
+            status.last_exit,
+        ));
+    }
+    for app in apps {
+        let status = get_app_runtime_status(&app.id).await;
+        items.push(ManagedProcessItem::new(
+            "app",
+            app.id,
+            app.name,
+            (status.running, status.pid, status.uptime_secs),
+            status.last_exit,
+        ));
+    }
+    items.push(ManagedProcessItem::new(
+        "ivnc",
+        ivnc_process_key(),
+        Some("iVnc".to_string()),
+        get_ivnc_runtime_status(&state).await,
+        last_process_exit(&ivnc_process_key()),
+    ));
+
+    // 按 pid 读取 /proc 计算 CPU/RSS，并清理已退出进程的采样记录
+    {
+        let now = Instant::now();
+        let mut prev_samples = state.system_monitor.child_cpu_prev.lock().await;
+        let mut live = HashMap::new();
+        for item in items.iter_mut() {
+            let Some(pid) = item.pid else {
+                continue;
+            };
+            let proc_dir = pid.to_string();
+            item.rss_kb = read_proc_status_field(&proc_dir, "VmRSS");
+            if let Some(ticks) = read_proc_cpu_ticks(&proc_dir) {
+                item.cpu_percent = cpu_percent_between(prev_samples.get(&pid).copied(), now, ticks);
+                live.insert(pid, (now, ticks));
+            }
+        }
+        *prev_samples = live;
+    }
+
+    Json(ApiResponse::success("Processes", items))
+}
+
+// iVnc API endpoints
+async fn get_ivnc_status(State(state): State<Arc<AppState>>) -> Json<ApiResponse<IVncStatus>> {
+    let installed = check_ivnc_installed();
+    let version = get_ivnc_version();
+    let config = state.ivnc_config.lock().await.clone();
+
+    let (running, pid, uptime_secs) = get_ivnc_runtime_status(&state).await;
+
+    Json(ApiResponse::success("iVnc 状态", IVncStatus {
+        installed,
+        version,
+        running,
+        pid,
+        uptime_secs,
+        port: config.port,
+        last_exit: last_process_exit(&ivnc_process_key()),
+    }))
+}
+
+async fn start_ivnc(State(state): State<Arc<AppState>>) -> Result<Json<ApiResponse<()>>, (StatusCode, String)> {
+    if !check_ivnc_installed() && !process_stub_mode() {
+        return Err((StatusCode::BAD_REQUEST, "iVnc 未安装".to_string()));
+    }
+    ensure_binary_runnable(&get_ivnc_binary_path(), "ivnc")
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    if state.ivnc_process.lock().await.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "iVnc 已在运行".to_string()));
+    }
+    ensure_resource_capacity(&*state.config.lock().await, ResourceKind::VncSessions, 0)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let config = state.ivnc_config.lock().await.clone();
+    generate_ivnc_config(&config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let log_path = get_ivnc_log_path();
     if let Some(parent) = log_path.parent() {
         fs::create_dir_all(parent)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("创建日志目录失败: {}", e)))?;
@@ -3869,9 +6603,14 @@ async fn start_ivnc(State(state): State<Arc<AppState>>) -> Result<Json<ApiRespon
         .open(&log_path)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("打开日志文件失败: {}", e)))?;
 
-    let child = tokio::process::Command::new(get_ivnc_binary_path())
-        .arg("-c")
-        .arg(get_ivnc_config_path())
+    let mut command = if process_stub_mode() {
+        stub_process_command("ivnc")
+    } else {
+        let mut command = tokio::process::Command::new(get_ivnc_binary_path());
+        command.arg("-c").arg(get_ivnc_config_path());
+        command
+    };
+    let child = command
         .stdout(log_file.try_clone().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("克隆文件句柄失败: {}", e)))?)
         .stderr(log_file)
         .spawn()
@@ -4001,6 +6740,8 @@ async fn create_app(
 
     {
         let mut config_guard = state.config.lock().await;
+        ensure_resource_capacity(&config_guard, ResourceKind::Apps, ResourceKind::Apps.count(&config_guard))
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
         config_guard.apps.push(cfg.clone());
         if let Err(e) = save_config(&config_guard).await {
             return Err((
@@ -4274,6 +7015,182 @@ async fn restart_service(
     Ok(Json(ApiResponse::success_no_data("sing-box restarted")))
 }
 
+#[derive(Serialize)]
+struct ServiceApplyResponse {
+    /// `sing-box check` 是否通过
+    valid: bool,
+    check_error: Option<String>,
+    subscription_errors: Vec<String>,
+    was_running: bool,
+    /// 配置已生效（sing-box 已启动/重启）
+    applied: bool,
+}
+
+/// POST /api/service/apply - 重新生成配置并校验，通过后启动或重启 sing-box，应用所有待生效的修改
+async fn apply_service(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<ServiceApplyResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    // 取消尚未执行的防抖 regenerate，这里会立即生成
+    state.regenerate_generation.fetch_add(1, Ordering::SeqCst);
+    let config_clone = regenerate_config(state.clone())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e))))?;
+
+    let subscription_errors = collect_subscription_errors(&state).await;
+    let was_running = sing_box_running().await;
+
+    if let Err(e) = check_sing_box_config(&state.sing_box_home).await {
+        log_warning!("Apply aborted, sing-box config check failed: {}", e);
+        return Ok(Json(ApiResponse::success(
+            "Config validation failed, changes not applied",
+            ServiceApplyResponse {
+                valid: false,
+                check_error: Some(e),
+                subscription_errors,
+                was_running,
+                applied: false,
+            },
+        )));
+    }
+
+    if was_running {
+        stop_sing_internal().await;
+        sleep(Duration::from_millis(500)).await;
+    }
+    start_sing_internal(&state.sing_box_home)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e))))?;
+    let _ = apply_saved_selections(&config_clone).await;
+    state.sing_box_pending_restart.store(false, Ordering::Relaxed);
+
+    Ok(Json(ApiResponse::success(
+        if was_running { "Changes applied, sing-box restarted" } else { "Changes applied, sing-box started" },
+        ServiceApplyResponse {
+            valid: true,
+            check_error: None,
+            subscription_errors,
+            was_running,
+            applied: true,
+        },
+    )))
+}
+
+async fn collect_subscription_errors(state: &AppState) -> Vec<String> {
+    let status = state.subscription_status.lock().await;
+    let mut errors: Vec<String> = status
+        .iter()
+        .filter_map(|(id, r)| r.error.as_ref().map(|e| format!("{}: {}", id, e)))
+        .collect();
+    errors.sort();
+    errors
+}
+
+#[derive(Serialize)]
+struct ServiceRegenerateResponse {
+    /// `sing-box check` 是否通过
+    valid: bool,
+    check_error: Option<String>,
+    subscription_errors: Vec<String>,
+    config_path: String,
+    /// sing-box 正在运行，新配置需重启（/api/service/apply）后生效
+    pending_restart: bool,
+}
+
+/// POST /api/service/regenerate - 重新生成并校验 config.json，不启动/重启 sing-box
+async fn regenerate_service_config(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<ServiceRegenerateResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    // 取消尚未执行的防抖 regenerate，这里会立即生成
+    state.regenerate_generation.fetch_add(1, Ordering::SeqCst);
+    regenerate_config(state.clone())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e))))?;
+
+    let subscription_errors = collect_subscription_errors(&state).await;
+    let check_error = check_sing_box_config(&state.sing_box_home).await.err();
+    let pending_restart = sing_box_running().await;
+    if pending_restart {
+        state.sing_box_pending_restart.store(true, Ordering::Relaxed);
+    }
+
+    Ok(Json(ApiResponse::success(
+        if check_error.is_none() { "Config regenerated" } else { "Config regenerated but validation failed" },
+        ServiceRegenerateResponse {
+            valid: check_error.is_none(),
+            check_error,
+            subscription_errors,
+            config_path: PathBuf::from(&state.sing_box_home)
+                .join("config.json")
+                .to_string_lossy()
+                .to_string(),
+            pending_restart,
+        },
+    )))
+}
+
+#[derive(Serialize)]
+struct RegeneratePreviewResponse {
+    outbound_tags: Vec<String>,
+    proxy_outbounds: Vec<String>,
+    proxy_default: Option<String>,
+    files: Vec<SubFileStatus>,
+    errors: Vec<String>,
+}
+
+/// POST /api/service/regenerate/preview - Build config from cached subscriptions without fetching, writing or restarting
+async fn preview_regenerate(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<RegeneratePreviewResponse>> {
+    let config_clone = { state.config.lock().await.clone() };
+    let (loaded, status_map) = if SUBSCRIPTIONS_ENABLED {
+        load_subscriptions(&config_clone, &state.subscriptions_root, false).await
+    } else {
+        (
+            LoadedSubscriptions {
+                files: vec![],
+                outbounds: vec![],
+                node_names: vec![],
+                dir_error: None,
+            },
+            HashMap::new(),
+        )
+    };
+
+    let mut errors: Vec<String> = vec![];
+    for sub in config_clone.subscriptions.iter().filter(|s| s.enabled) {
+        if let Some(err) = status_map.get(&sub.id).and_then(|r| r.error.as_ref()) {
+            errors.push(format!("{}: {}", sub.id, err));
+        }
+    }
+
+    let sing_box_config = build_singbox_config(&config_clone, &loaded);
+    let tags_of = |v: &serde_json::Value| -> Vec<String> {
+        v.as_array()
+            .map(|arr| arr.iter().filter_map(|t| t.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    };
+    let outbound_tags: Vec<String> = sing_box_config["outbounds"]
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|o| o.get("tag").and_then(|v| v.as_str()).map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    let proxy = &sing_box_config["outbounds"][0];
+
+    Json(ApiResponse::success(
+        "Regenerate preview",
+        RegeneratePreviewResponse {
+            outbound_tags,
+            proxy_outbounds: tags_of(&proxy["outbounds"]),
+            proxy_default: proxy.get("default").and_then(|v| v.as_str()).map(String::from),
+            files: loaded.files,
+            errors,
+        },
+    ))
+}
+
 fn normalize_terminal_request(
     req: TerminalUpsertRequest,
     id: String,
@@ -4343,6 +7260,9 @@ fn normalize_terminal_request(
             .filter(|v| !v.is_empty())
             .collect();
     }
+    if let Some(ms) = req.startup_check_ms {
+        cfg.startup_check_ms = Some(ms.min(MAX_STARTUP_CHECK_MS));
+    }
 
     if cfg.command.trim().is_empty() {
         return Err("terminal command is required".to_string());
@@ -4418,6 +7338,9 @@ fn normalize_app_request(
         }
         cfg.env = normalized;
     }
+    if let Some(ms) = req.startup_check_ms {
+        cfg.startup_check_ms = Some(ms.min(MAX_STARTUP_CHECK_MS));
+    }
 
     if cfg.command.trim().is_empty() {
         return Err("应用启动命令不能为空".to_string());
@@ -4485,6 +7408,12 @@ async fn create_terminal(
 
     {
         let mut config_guard = state.config.lock().await;
+        ensure_resource_capacity(
+            &config_guard,
+            ResourceKind::Terminals,
+            ResourceKind::Terminals.count(&config_guard),
+        )
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
         if let Some(err) = terminal_bind_conflict(&cfg.id, &cfg, &config_guard.terminals) {
             return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(err))));
         }
@@ -4567,6 +7496,7 @@ async fn update_terminal(
             }
             let _ = save_config(&config_guard).await;
         }
+        bump_terminal_restart_generation(&cfg.id);
         let _ = stop_terminal_internal(&cfg.id).await;
         if let Err(e) = start_terminal_internal(&cfg.id, &cfg).await {
             return Err((
@@ -4688,6 +7618,7 @@ async fn restart_terminal(
         }
         t.clone()
     };
+    bump_terminal_restart_generation(&id);
     let _ = stop_terminal_internal(&id).await;
     if let Err(e) = start_terminal_internal(&cfg.id, &cfg).await {
         return Err((
@@ -4728,6 +7659,7 @@ async fn restart_terminal_by_port(
         t.clone()
     };
     let id = cfg.id.clone();
+    bump_terminal_restart_generation(&id);
     let _ = stop_terminal_internal(&id).await;
     if let Err(e) = start_terminal_internal(&id, &cfg).await {
         return Err((
@@ -4739,91 +7671,330 @@ async fn restart_terminal_by_port(
 }
 
 /// POST /api/connectivity - Test connectivity to a single site
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ConnectivityRequest {
     url: String,
-}
-
+    #[serde(default)]
+    method: ConnectivityMethod,
+    #[serde(default)]
+    expected_status: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/connectivity",
+    tag = "connectivity",
+    request_body = ConnectivityRequest,
+    responses(
+        (status = 200, description = "OK", body = ConnectivityApiResponse),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn test_connectivity(
     Json(req): Json<ConnectivityRequest>,
 ) -> Json<ApiResponse<ConnectivityResult>> {
+    let client = match build_connectivity_client() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to create client: {}", e)));
+        }
+    };
+
+    let expected_status = match normalize_expected_status(req.expected_status) {
+        Ok(v) => v,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    let result = probe_connectivity(
+        &client,
+        String::new(),
+        req.url,
+        req.method,
+        expected_status.as_deref(),
+    )
+    .await;
+    Json(ApiResponse::success("Test completed", result))
+}
+
+fn normalize_expected_status(raw: Option<String>) -> Result<Option<String>, String> {
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+    let value = raw.trim().to_ascii_lowercase();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let valid = match value.strip_suffix("xx") {
+        Some(class) => matches!(class, "1" | "2" | "3" | "4" | "5"),
+        None => value.parse::<u16>().map(|c| (100..=599).contains(&c)).unwrap_or(false),
+    };
+    if !valid {
+        return Err(format!(
+            "Invalid expected_status '{}': use a code like 204 or a class like 2xx",
+            raw
+        ));
+    }
+    Ok(Some(value))
+}
+
+fn status_matches(expected: Option<&str>, status: reqwest::StatusCode) -> bool {
+    let Some(expected) = expected else {
+        return status.is_success() || status.is_redirection();
+    };
+    let code = status.as_u16();
+    match expected.strip_suffix("xx") {
+        Some(class) => class.parse::<u16>().map(|c| code / 100 == c).unwrap_or(false),
+        None => expected.parse::<u16>().map(|c| c == code).unwrap_or(false),
+    }
+}
+
+fn build_connectivity_client() -> Result<reqwest::Client, reqwest::Error> {
     // 使用 sing-box 的 mixed 代理（如果可用）或直连
     // TUN 模式下，系统流量会自动经过代理
-    let client = match reqwest::Client::builder()
+    reqwest::Client::builder()
         .timeout(Duration::from_secs(10))
         .danger_accept_invalid_certs(false)
         .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            return Json(ApiResponse::error(format!("Failed to create client: {}", e)));
-        }
+}
+
+async fn probe_connectivity(
+    client: &reqwest::Client,
+    name: String,
+    url: String,
+    method: ConnectivityMethod,
+    expected_status: Option<&str>,
+) -> ConnectivityResult {
+    // 断言 3xx 时不能跟随重定向，否则只能看到最终状态码
+    let no_redirect_client;
+    let client = if expected_status.map(|e| e.starts_with('3')).unwrap_or(false) {
+        no_redirect_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_else(|_| client.clone());
+        &no_redirect_client
+    } else {
+        client
     };
 
     let start = Instant::now();
-    let result = match client.get(&req.url).send().await {
+    let request = match method {
+        ConnectivityMethod::Head => client.head(&url),
+        ConnectivityMethod::Get => client.get(&url),
+    };
+    match request.send().await {
         Ok(resp) => {
-            // 检查是否成功（2xx 或 3xx 状态码）
-            let success = resp.status().is_success() || resp.status().is_redirection();
+            // 默认 2xx 或 3xx 视为成功，指定 expected_status 时按其判断
+            let success = status_matches(expected_status, resp.status());
             ConnectivityResult {
-                name: String::new(),
-                url: req.url,
+                name,
+                url,
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 success,
+                status_code: Some(resp.status().as_u16()),
             }
         }
         Err(e) => {
-            log_info!("Connectivity test failed for {}: {}", req.url, e);
+            log_info!("Connectivity test failed for {}: {}", url, e);
             ConnectivityResult {
-                name: String::new(),
-                url: req.url,
+                name,
+                url,
                 latency_ms: None,
                 success: false,
+                status_code: None,
             }
         }
-    };
+    }
+}
 
-    Json(ApiResponse::success("Test completed", result))
+#[derive(Deserialize)]
+struct ConnectivityPresetRequest {
+    name: String,
+    url: String,
+    #[serde(default)]
+    method: ConnectivityMethod,
+    #[serde(default)]
+    expected_status: Option<String>,
 }
 
-// ============================================================================
-// Setup APIs (first run)
-// ============================================================================
+fn normalize_connectivity_preset(
+    req: ConnectivityPresetRequest,
+    id: String,
+) -> Result<ConnectivityPreset, String> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Preset name is required".to_string());
+    }
+    let url = req.url.trim().to_string();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("Preset url must start with http:// or https://".to_string());
+    }
+    let expected_status = normalize_expected_status(req.expected_status)?;
+    Ok(ConnectivityPreset {
+        id,
+        name,
+        url,
+        method: req.method,
+        expected_status,
+    })
+}
 
-async fn setup_status(State(state): State<Arc<AppState>>) -> Json<ApiResponse<SetupStatusResponse>> {
-    Json(ApiResponse::success(
-        "Setup status",
-        SetupStatusResponse {
-            initialized: !state.setup_required.load(Ordering::Relaxed),
-        },
-    ))
+/// GET /api/connectivity/presets - List saved connectivity presets
+async fn list_connectivity_presets(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<Vec<ConnectivityPreset>>> {
+    let presets = { state.config.lock().await.connectivity_presets.clone() };
+    Json(ApiResponse::success("Connectivity presets", presets))
 }
 
-async fn setup_init(
+/// POST /api/connectivity/presets - Add a connectivity preset
+async fn create_connectivity_preset(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<SetupInitRequest>,
-) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
-    if !state.setup_required.load(Ordering::Relaxed) {
+    Json(req): Json<ConnectivityPresetRequest>,
+) -> Result<Json<ApiResponse<ConnectivityPreset>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let preset = normalize_connectivity_preset(req, generate_connectivity_preset_id())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+    let mut config = state.config.lock().await;
+    config.connectivity_presets.push(preset.clone());
+    if let Err(e) = save_config(&config).await {
         return Err((
-            StatusCode::CONFLICT,
-            Json(ApiResponse::error("Already initialized")),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to save config: {}", e))),
         ));
     }
+    Ok(Json(ApiResponse::success("Preset created", preset)))
+}
 
-    let password = req.password.trim();
-    if password.is_empty() {
+/// PUT /api/connectivity/presets/{id} - Update a connectivity preset
+async fn update_connectivity_preset(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<ConnectivityPresetRequest>,
+) -> Result<Json<ApiResponse<ConnectivityPreset>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let preset = normalize_connectivity_preset(req, id.clone())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+    let mut config = state.config.lock().await;
+    let Some(existing) = config.connectivity_presets.iter_mut().find(|p| p.id == id) else {
+        return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Preset not found"))));
+    };
+    *existing = preset.clone();
+    if let Err(e) = save_config(&config).await {
         return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ApiResponse::error("Password is required")),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to save config: {}", e))),
         ));
     }
+    Ok(Json(ApiResponse::success("Preset updated", preset)))
+}
 
-    let mut new_config = {
+/// DELETE /api/connectivity/presets/{id} - Remove a connectivity preset
+async fn delete_connectivity_preset(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let mut config = state.config.lock().await;
+    let before = config.connectivity_presets.len();
+    config.connectivity_presets.retain(|p| p.id != id);
+    if config.connectivity_presets.len() == before {
+        return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Preset not found"))));
+    }
+    if let Err(e) = save_config(&config).await {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to save config: {}", e))),
+        ));
+    }
+    Ok(Json(ApiResponse::success_no_data("Preset deleted")))
+}
+
+/// POST /api/connectivity/run-presets - Test all saved presets concurrently
+async fn run_connectivity_presets(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<Vec<ConnectivityResult>>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let presets = { state.config.lock().await.connectivity_presets.clone() };
+    let client = build_connectivity_client().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to create client: {}", e))),
+        )
+    })?;
+
+    let mut tasks = Vec::with_capacity(presets.len());
+    for preset in presets {
+        let client = client.clone();
+        tasks.push(tokio::spawn(async move {
+            probe_connectivity(
+                &client,
+                preset.name,
+                preset.url,
+                preset.method,
+                preset.expected_status.as_deref(),
+            )
+            .await
+        }));
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        if let Ok(result) = task.await {
+            results.push(result);
+        }
+    }
+    Ok(Json(ApiResponse::success("Presets tested", results)))
+}
+
+// ============================================================================
+// Setup APIs (first run)
+// ============================================================================
+
+async fn setup_status(State(state): State<Arc<AppState>>) -> Json<ApiResponse<SetupStatusResponse>> {
+    Json(ApiResponse::success(
+        "Setup status",
+        SetupStatusResponse {
+            initialized: !state.setup_required.load(Ordering::Relaxed),
+        },
+    ))
+}
+
+async fn setup_init(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SetupInitRequest>,
+) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
+    if !state.setup_required.load(Ordering::Relaxed) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error("Already initialized")),
+        ));
+    }
+
+    let password = req.password.trim();
+    if password.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error("Password is required")),
+        ));
+    }
+    // argon2 哈希耗时较长，放到阻塞线程池避免卡住运行时
+    let password = password.to_string();
+    let password_hash = match tokio::task::spawn_blocking(move || hash_password(&password)).await {
+        Ok(Ok(hash)) => hash,
+        Ok(Err(e)) => return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e)))),
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!("Password hashing failed: {}", e))),
+            ))
+        }
+    };
+
+    let mut new_config = {
         let config = state.config.lock().await;
         let mut c = config.clone();
-        c.password = Some(password.to_string());
+        c.password = Some(password_hash);
         c.nodes = vec![];
         c.selections = HashMap::new();
+        c.selection_keys = HashMap::new();
         c
     };
     new_config.port = Some(DEFAULT_PORT);
@@ -4858,6 +8029,8 @@ async fn setup_init(
 
 const CLASH_HTTP_BASE: &str = "http://127.0.0.1:6262";
 const CLASH_WS_BASE: &str = "ws://127.0.0.1:6262";
+/// 与 sing-box Clash API 的默认测速地址一致
+const DEFAULT_PROXY_CHECK_URL: &str = "https://www.gstatic.com/generate_204";
 
 async fn clash_get_proxies() -> Result<Json<ApiResponse<serde_json::Value>>, (StatusCode, Json<ApiResponse<()>>)> {
     let client = reqwest::Client::new();
@@ -4956,6 +8129,9 @@ async fn clash_switch_proxy(
     Path(group): Path<String>,
     Json(req): Json<ClashSwitchRequest>,
 ) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
+    validate_selector_choice(&state, &group, &req.name)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
     switch_selector_and_save(&state, &group, &req.name)
         .await
         .map_err(|e| (StatusCode::BAD_GATEWAY, Json(ApiResponse::error(e))))?;
@@ -4968,10 +8144,37 @@ struct DelayQuery {
     url: Option<String>,
 }
 
+fn validate_proxy_check_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(format!("proxy check URL must be http(s) with a host: {}", url));
+    }
+    Ok(())
+}
+
+/// 生效的测速地址：config.yaml 的 `proxy_check_url` 有效时优先，否则默认值
+fn effective_proxy_check_url(config: &Config) -> String {
+    match config.proxy_check_url.as_deref().map(str::trim) {
+        Some(url) if !url.is_empty() => match validate_proxy_check_url(url) {
+            Ok(()) => url.to_string(),
+            Err(e) => {
+                log_warning!("{}, falling back to {}", e, DEFAULT_PROXY_CHECK_URL);
+                DEFAULT_PROXY_CHECK_URL.to_string()
+            }
+        },
+        _ => DEFAULT_PROXY_CHECK_URL.to_string(),
+    }
+}
+
 async fn clash_test_delay(
+    State(state): State<Arc<AppState>>,
     Path(node): Path<String>,
     Query(q): Query<DelayQuery>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let check_url = match q.url {
+        Some(url) => url,
+        None => effective_proxy_check_url(&*state.config.lock().await),
+    };
     let client = reqwest::Client::new();
     let mut req = client.get(format!(
         "{}/proxies/{}/delay",
@@ -4981,9 +8184,7 @@ async fn clash_test_delay(
     if let Some(timeout) = q.timeout {
         req = req.query(&[("timeout", timeout.to_string())]);
     }
-    if let Some(url) = q.url {
-        req = req.query(&[("url", url)]);
-    }
+    req = req.query(&[("url", check_url)]);
 
     let resp = req
         .send()
@@ -5026,7 +8227,100 @@ struct BatchDelayItem {
     success: bool,
 }
 
+/// 每个节点保留的最近测速次数
+const NODE_DELAY_HISTORY_LEN: usize = 20;
+/// 批量测速结果持久化文件（与 config.yaml 同目录），重启后不丢失
+const NODE_DELAYS_FILE: &str = "node_delays.json";
+/// 持久化的节点数上限，超出时丢弃最久未测速的节点
+const MAX_PERSISTED_NODE_DELAYS: usize = 2000;
+
+/// Latest delay test result for a node (from batch delay tests)
+#[derive(Clone, Serialize, Deserialize)]
+struct NodeDelaySample {
+    delay: Option<u64>,
+    tested_at: i64,
+    /// 最近 NODE_DELAY_HISTORY_LEN 次结果（旧 -> 新），None 表示该次失败
+    #[serde(default)]
+    history: VecDeque<Option<u64>>,
+}
+
+impl NodeDelaySample {
+    fn record(&mut self, delay: Option<u64>, tested_at: i64) {
+        self.delay = delay;
+        self.tested_at = tested_at;
+        if self.history.len() >= NODE_DELAY_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(delay);
+    }
+
+    /// 成功样本的平均延迟与标准差（抖动），少于两个成功样本时抖动为 None
+    fn delay_stats(&self) -> (Option<f64>, Option<f64>) {
+        let ok: Vec<f64> = self.history.iter().flatten().map(|d| *d as f64).collect();
+        if ok.is_empty() {
+            return (None, None);
+        }
+        let mean = ok.iter().sum::<f64>() / ok.len() as f64;
+        if ok.len() < 2 {
+            return (Some(mean), None);
+        }
+        let variance = ok.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / ok.len() as f64;
+        (Some(mean), Some(variance.sqrt()))
+    }
+}
+
+fn node_delays_path() -> PathBuf {
+    StdPath::new(CONFIG_FILE).with_file_name(NODE_DELAYS_FILE)
+}
+
+async fn load_node_delays() -> HashMap<String, NodeDelaySample> {
+    let path = node_delays_path();
+    let mut delays: HashMap<String, NodeDelaySample> = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log_warning!("Ignoring unreadable {}: {}", path.display(), e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    };
+    for sample in delays.values_mut() {
+        while sample.history.len() > NODE_DELAY_HISTORY_LEN {
+            sample.history.pop_front();
+        }
+    }
+    delays
+}
+
+/// 保存测速结果：在锁内序列化快照，释放锁后写临时文件再 rename，
+/// 写到一半崩溃也不会截断已有记录
+async fn save_node_delays(state: &AppState) {
+    let _save_guard = NODE_DELAYS_SAVE_LOCK.lock().await;
+    let json = {
+        let delays = state.node_delays.lock().await;
+        let mut entries: Vec<(&String, &NodeDelaySample)> = delays.iter().collect();
+        if entries.len() > MAX_PERSISTED_NODE_DELAYS {
+            entries.sort_by(|a, b| b.1.tested_at.cmp(&a.1.tested_at));
+            entries.truncate(MAX_PERSISTED_NODE_DELAYS);
+        }
+        let capped: HashMap<&String, &NodeDelaySample> = entries.into_iter().collect();
+        serde_json::to_string(&capped)
+    };
+    let path = node_delays_path();
+    let tmp = path.with_extension("json.tmp");
+    let result = match json {
+        Ok(json) => match tokio::fs::write(&tmp, json).await {
+            Ok(()) => tokio::fs::rename(&tmp, &path).await,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        log_warning!("Failed to persist {}: {}", path.display(), e);
+    }
+}
+
 async fn clash_test_batch_delay(
+    State(state): State<Arc<AppState>>,
     Json(req): Json<BatchDelayRequest>,
 ) -> Result<Json<ApiResponse<BatchDelayResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
     let client = reqwest::Client::builder()
@@ -5034,6 +8328,11 @@ async fn clash_test_batch_delay(
         .build()
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(format!("Failed to create client: {}", e)))))?;
 
+    let check_url = match req.url.clone() {
+        Some(url) => url,
+        None => effective_proxy_check_url(&*state.config.lock().await),
+    };
+
     // 并行测试所有节点延迟
     let mut tasks = Vec::with_capacity(req.nodes.len());
 
@@ -5041,27 +8340,22 @@ async fn clash_test_batch_delay(
         let client = client.clone();
         let node = node.clone();
         let timeout = req.timeout;
-        let url = req.url.clone();
+        let url = check_url.clone();
 
         tasks.push(tokio::spawn(async move {
-            let mut result_url = format!("{}/proxies/{}/delay", CLASH_HTTP_BASE,
-                percent_encoding::utf8_percent_encode(&node, percent_encoding::NON_ALPHANUMERIC));
-
             let mut params: Vec<(&str, String)> = Vec::new();
             if let Some(t) = timeout {
                 params.push(("timeout", t.to_string()));
             }
-            if let Some(ref test_url) = url {
-                params.push(("url", test_url.clone()));
-            }
+            params.push(("url", url));
 
-            if !params.is_empty() {
-                let query_string: String = params.iter()
-                    .map(|(k, v)| format!("{}={}", k, percent_encoding::utf8_percent_encode(v, percent_encoding::NON_ALPHANUMERIC)))
-                    .collect::<Vec<_>>()
-                    .join("&");
-                result_url = format!("{}?{}", result_url, query_string);
-            }
+            let query_string: String = params.iter()
+                .map(|(k, v)| format!("{}={}", k, percent_encoding::utf8_percent_encode(v, percent_encoding::NON_ALPHANUMERIC)))
+                .collect::<Vec<_>>()
+                .join("&");
+            let result_url = format!("{}/proxies/{}/delay?{}", CLASH_HTTP_BASE,
+                percent_encoding::utf8_percent_encode(&node, percent_encoding::NON_ALPHANUMERIC),
+                query_string);
 
             let resp = client.get(&result_url).send().await;
 
@@ -5106,6 +8400,24 @@ async fn clash_test_batch_delay(
         }
     }
 
+    {
+        let now = chrono::Utc::now().timestamp();
+        // 只记录当前存在的节点，避免任意名字撑大 node_delays
+        let known = state.node_type_by_tag.lock().await;
+        let mut delays = state.node_delays.lock().await;
+        for item in results.iter().filter(|item| known.contains_key(&item.node)) {
+            delays
+                .entry(item.node.clone())
+                .or_insert_with(|| NodeDelaySample {
+                    delay: None,
+                    tested_at: now,
+                    history: VecDeque::with_capacity(NODE_DELAY_HISTORY_LEN),
+                })
+                .record(item.delay, now);
+        }
+    }
+    save_node_delays(&state).await;
+
     Ok(Json(ApiResponse::success("Batch delay test completed", BatchDelayResponse {
         results,
         total: req.nodes.len(),
@@ -5113,6 +8425,197 @@ async fn clash_test_batch_delay(
     })))
 }
 
+#[derive(Serialize)]
+struct FastestNode {
+    tag: String,
+    delay: u64,
+    tested_at: i64,
+}
+
+#[derive(Serialize)]
+struct NodeDelayTrend {
+    tag: String,
+    delay: Option<u64>,
+    tested_at: i64,
+    /// 最近几次测速结果（旧 -> 新），null 表示失败，可直接画 sparkline
+    recent: Vec<Option<u64>>,
+    failures: usize,
+    avg_delay: Option<f64>,
+    /// 成功样本延迟的标准差（毫秒）
+    jitter: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct NodeHealthSummary {
+    total: usize,
+    healthy: usize,
+    unhealthy: usize,
+    unknown: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fastest: Option<FastestNode>,
+    nodes: Vec<NodeDelayTrend>,
+}
+
+/// GET /api/nodes/health-summary - Node health counts and per-node delay trend from recent delay tests
+async fn get_node_health_summary(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<NodeHealthSummary>> {
+    let tags: Vec<String> = { state.node_type_by_tag.lock().await.keys().cloned().collect() };
+    let delays = state.node_delays.lock().await;
+
+    let mut summary = NodeHealthSummary {
+        total: tags.len(),
+        healthy: 0,
+        unhealthy: 0,
+        unknown: 0,
+        fastest: None,
+        nodes: Vec::new(),
+    };
+    for tag in tags {
+        if let Some(sample) = delays.get(&tag) {
+            let (avg_delay, jitter) = sample.delay_stats();
+            summary.nodes.push(NodeDelayTrend {
+                tag: tag.clone(),
+                delay: sample.delay,
+                tested_at: sample.tested_at,
+                recent: sample.history.iter().copied().collect(),
+                failures: sample.history.iter().filter(|d| d.is_none()).count(),
+                avg_delay,
+                jitter,
+            });
+        }
+        match delays.get(&tag) {
+            Some(NodeDelaySample { delay: Some(delay), tested_at, .. }) => {
+                summary.healthy += 1;
+                if summary.fastest.as_ref().map(|f| *delay < f.delay).unwrap_or(true) {
+                    summary.fastest = Some(FastestNode {
+                        tag,
+                        delay: *delay,
+                        tested_at: *tested_at,
+                    });
+                }
+            }
+            Some(_) => summary.unhealthy += 1,
+            None => summary.unknown += 1,
+        }
+    }
+
+    summary.nodes.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+    Json(ApiResponse::success("Node health summary", summary))
+}
+
+#[derive(Serialize)]
+struct ProxyMemberOverview {
+    tag: String,
+    delay: Option<u64>,      // 最近一次测速结果，None 表示失败或未测
+    tested_at: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ProxyGroupOverview {
+    name: String,
+    #[serde(rename = "type")]
+    group_type: String,
+    now: Option<String>,
+    members: Vec<ProxyMemberOverview>,
+}
+
+#[derive(Serialize)]
+struct ProxyOverviewResponse {
+    node_count: usize,
+    manual_node_count: usize,
+    selections: HashMap<String, String>,
+    groups: Vec<ProxyGroupOverview>,
+    active_node: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clash_error: Option<String>,
+}
+
+/// GET /api/proxy/overview - 节点数量、分组当前选择、成员健康状况，一次返回
+async fn get_proxy_overview(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<ProxyOverviewResponse>> {
+    let (manual_node_count, selections) = {
+        let config = state.config.lock().await;
+        (config.nodes.len(), config.selections.clone())
+    };
+    let node_count = state.node_type_by_tag.lock().await.len();
+
+    // sing-box 未运行时 Clash API 不可用，仍返回本地信息
+    let clash_result: Result<serde_json::Value, String> = async {
+        let resp = reqwest::Client::new()
+            .get(format!("{}/proxies", CLASH_HTTP_BASE))
+            .timeout(Duration::from_secs(3))
+            .send()
+            .await
+            .map_err(|e| format!("Clash API request failed: {}", e))?;
+        resp.json::<serde_json::Value>()
+            .await
+            .map_err(|e| format!("Clash API parse failed: {}", e))
+    }
+    .await;
+
+    let (proxies, clash_error) = match clash_result {
+        Ok(json) => (json.get("proxies").cloned().unwrap_or_default(), None),
+        Err(e) => (serde_json::Value::Null, Some(e)),
+    };
+
+    let delays = state.node_delays.lock().await;
+    let mut groups: Vec<ProxyGroupOverview> = Vec::new();
+    if let Some(map) = proxies.as_object() {
+        for (name, item) in map {
+            let group_type = item.get("type").and_then(|v| v.as_str()).unwrap_or_default();
+            if !matches!(group_type, "Selector" | "URLTest" | "Fallback") {
+                continue;
+            }
+            let members = item
+                .get("all")
+                .and_then(|v| v.as_array())
+                .map(|all| {
+                    all.iter()
+                        .filter_map(|m| m.as_str())
+                        .map(|tag| {
+                            let sample = delays.get(tag);
+                            ProxyMemberOverview {
+                                tag: tag.to_string(),
+                                delay: sample.and_then(|s| s.delay),
+                                tested_at: sample.map(|s| s.tested_at),
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            groups.push(ProxyGroupOverview {
+                name: name.clone(),
+                group_type: group_type.to_string(),
+                now: item.get("now").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                members,
+            });
+        }
+    }
+    drop(delays);
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let active_node = groups
+        .iter()
+        .find(|g| g.name == "proxy")
+        .and_then(|g| g.now.clone())
+        .or_else(|| selections.get("proxy").cloned());
+
+    Json(ApiResponse::success(
+        "Proxy overview",
+        ProxyOverviewResponse {
+            node_count,
+            manual_node_count,
+            selections,
+            groups,
+            active_node,
+            clash_error,
+        },
+    ))
+}
+
 async fn get_selections(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<SelectionsResponse>> {
@@ -5143,35 +8646,124 @@ async fn clash_ws_logs(
         return Err(StatusCode::UNAUTHORIZED);
     }
     let level = q.level.unwrap_or_else(|| "info".to_string());
-    Ok(ws.on_upgrade(move |socket| handle_logs_websocket(socket, level)))
+    let events: Option<Vec<String>> = q.events.map(|raw| {
+        raw.split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect()
+    });
+    Ok(ws.on_upgrade(move |socket| handle_logs_websocket(socket, level, events)))
 }
 
-async fn handle_logs_websocket(mut socket: WebSocket, min_level: String) {
-    let mut rx = LOG_BROADCAST.subscribe();
+/// Level + event filter for a raw log buffer line; unparseable lines always pass
+fn log_message_passes(msg: &str, min_level: &str, events: &Option<Vec<String>>) -> bool {
+    let Ok(entry) = serde_json::from_str::<serde_json::Value>(msg) else {
+        return true;
+    };
+    if let Some(level) = entry.get("level").and_then(|v| v.as_str()) {
+        if !level_passes(level, min_level) {
+            return false;
+        }
+    }
+    event_passes(&entry, events)
+}
 
-    // Helper to check if log level passes the filter
-    fn level_passes(log_level: &str, min_level: &str) -> bool {
-        let level_priority = |l: &str| match l.to_lowercase().as_str() {
-            "debug" => 0,
-            "info" => 1,
-            "warning" => 2,
-            "error" => 3,
-            _ => 1,
-        };
-        level_priority(log_level) >= level_priority(min_level)
+fn lagged_log_warning(dropped: u64) -> String {
+    use chrono::FixedOffset;
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let time_str = Utc::now().with_timezone(&utc8).format("%Y-%m-%d %H:%M:%S").to_string();
+    serde_json::json!({
+        "time": time_str,
+        "level": "warning",
+        "message": format!("Dropped {} log messages (client too slow)", dropped)
+    })
+    .to_string()
+}
+
+/// Check if log level passes the minimum level filter
+fn level_passes(log_level: &str, min_level: &str) -> bool {
+    let level_priority = |l: &str| match l.to_lowercase().as_str() {
+        "debug" => 0,
+        "info" => 1,
+        "warning" => 2,
+        "error" => 3,
+        _ => 1,
+    };
+    level_priority(log_level) >= level_priority(min_level)
+}
+
+/// GET /api/logs/sse - 与 /api/clash/ws/logs 相同的日志流（历史 + 实时），SSE 封装
+async fn logs_sse(
+    Query(q): Query<WsAuthQuery>,
+) -> Result<Sse<impl futures_util::Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if verify_token(&q.token).is_err() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let min_level = q.level.unwrap_or_else(|| "info".to_string());
+    let events: Option<Vec<String>> = q.events.map(|raw| {
+        raw.split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect()
+    });
+    // 先订阅再取历史，避免两者之间的日志丢失
+    let rx = LOG_BROADCAST.subscribe();
+    let history: VecDeque<String> = {
+        let buffer = LOG_BUFFER.lock().expect("log buffer lock poisoned");
+        buffer.iter().cloned().collect()
+    };
+
+    let stream = futures_util::stream::unfold(
+        (history, rx, min_level, events),
+        |(mut history, mut rx, min_level, events)| async move {
+            loop {
+                let msg = match history.pop_front() {
+                    Some(msg) => msg,
+                    None => match rx.recv().await {
+                        Ok(msg) => msg,
+                        Err(broadcast::error::RecvError::Lagged(n)) => lagged_log_warning(n),
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    },
+                };
+                if log_message_passes(&msg, &min_level, &events) {
+                    let event = Event::default().data(msg);
+                    return Some((Ok(event), (history, rx, min_level, events)));
+                }
+            }
+        },
+    );
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// With an event filter only structured events pass; each filter is either a
+/// subsystem (`tunnel`) or a fully qualified event (`tunnel.state_changed`).
+fn event_passes(entry: &serde_json::Value, filters: &Option<Vec<String>>) -> bool {
+    let Some(filters) = filters else {
+        return true;
+    };
+    if entry.get("type").and_then(|v| v.as_str()) != Some("event") {
+        return false;
     }
+    let subsystem = entry.get("subsystem").and_then(|v| v.as_str()).unwrap_or("");
+    let event = entry.get("event").and_then(|v| v.as_str()).unwrap_or("");
+    let qualified = format!("{}.{}", subsystem, event);
+    filters.is_empty() || filters.iter().any(|f| f == subsystem || *f == qualified)
+}
+
+async fn handle_logs_websocket(
+    mut socket: WebSocket,
+    min_level: String,
+    events: Option<Vec<String>>,
+) {
+    let mut rx = LOG_BROADCAST.subscribe();
 
     let history: Vec<String> = {
         let buffer = LOG_BUFFER.lock().expect("log buffer lock poisoned");
         buffer.iter().cloned().collect()
     };
     for msg in history {
-        if let Ok(entry) = serde_json::from_str::<serde_json::Value>(&msg) {
-            if let Some(level) = entry.get("level").and_then(|v| v.as_str()) {
-                if !level_passes(level, &min_level) {
-                    continue;
-                }
-            }
+        if !log_message_passes(&msg, &min_level, &events) {
+            continue;
         }
         if socket.send(Message::Text(msg.into())).await.is_err() {
             return;
@@ -5183,13 +8775,8 @@ async fn handle_logs_websocket(mut socket: WebSocket, min_level: String) {
             result = rx.recv() => {
                 match result {
                     Ok(msg) => {
-                        // Parse JSON to check level filter
-                        if let Ok(entry) = serde_json::from_str::<serde_json::Value>(&msg) {
-                            if let Some(level) = entry.get("level").and_then(|v| v.as_str()) {
-                                if !level_passes(level, &min_level) {
-                                    continue;
-                                }
-                            }
+                        if !log_message_passes(&msg, &min_level, &events) {
+                            continue;
                         }
                         if socket.send(Message::Text(msg.into())).await.is_err() {
                             break;
@@ -5197,15 +8784,7 @@ async fn handle_logs_websocket(mut socket: WebSocket, min_level: String) {
                     }
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         // Client is too slow, some messages were dropped
-                        use chrono::FixedOffset;
-                        let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
-                        let time_str = Utc::now().with_timezone(&utc8).format("%Y-%m-%d %H:%M:%S").to_string();
-                        let warning = serde_json::json!({
-                            "time": time_str,
-                            "level": "warning",
-                            "message": format!("Dropped {} log messages (client too slow)", n)
-                        });
-                        let _ = socket.send(Message::Text(warning.to_string().into())).await;
+                        let _ = socket.send(Message::Text(lagged_log_warning(n).into())).await;
                     }
                     Err(broadcast::error::RecvError::Closed) => {
                         break;
@@ -5355,18 +8934,218 @@ async fn proxy_websocket(mut client_socket: WebSocket, upstream_url: String) {
 // Version and Upgrade APIs
 // ============================================================================
 
-#[derive(Serialize)]
-struct VersionInfo {
-    current: String,
-    latest: Option<String>,
-    has_update: bool,
-    download_url: Option<String>,
+const TLS_DIR: &str = "tls";
+const TLS_SELF_SIGNED_CERT: &str = "miao-self-signed.crt";
+const TLS_SELF_SIGNED_KEY: &str = "miao-self-signed.key";
+const TLS_SELF_SIGNED_DEFAULT_DAYS: u32 = 3650;
+const TLS_SELF_SIGNED_MAX_DAYS: u32 = 36500;
+/// 证书/私钥文件变化检测间隔（秒）
+const TLS_RELOAD_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// 同时配置了证书与私钥时返回 (cert, key)；只配置了一个视为错误
+fn tls_paths(config: &Config) -> Result<Option<(String, String)>, String> {
+    let cert = config.tls_cert_path.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    let key = config.tls_key_path.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok(Some((cert.to_string(), key.to_string()))),
+        (None, None) => Ok(None),
+        _ => Err("tls_cert_path and tls_key_path must be set together".to_string()),
+    }
 }
 
-#[derive(Deserialize)]
-struct GitHubRelease {
-    tag_name: String,
-    assets: Vec<GitHubAsset>,
+fn tls_files_mtime(cert: &str, key: &str) -> Option<(std::time::SystemTime, std::time::SystemTime)> {
+    let cert = fs::metadata(cert).and_then(|m| m.modified()).ok()?;
+    let key = fs::metadata(key).and_then(|m| m.modified()).ok()?;
+    Some((cert, key))
+}
+
+/// 定期检查证书/私钥的修改时间，变化后热加载（续期无需重启）；加载失败时保留旧证书
+fn spawn_tls_reloader(tls_config: axum_server::tls_rustls::RustlsConfig, cert: String, key: String) {
+    tokio::spawn(async move {
+        let mut last = tls_files_mtime(&cert, &key);
+        let mut interval = tokio::time::interval(Duration::from_secs(TLS_RELOAD_CHECK_INTERVAL_SECS));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let current = tls_files_mtime(&cert, &key);
+            if current.is_none() || current == last {
+                continue;
+            }
+            match tls_config.reload_from_pem_file(&cert, &key).await {
+                Ok(()) => {
+                    last = current;
+                    log_info!("TLS certificate reloaded from {}", cert);
+                }
+                // 证书与私钥可能分两次写入，下一轮再试
+                Err(e) => log_warning!("Failed to reload TLS certificate {}: {}", cert, e),
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct TlsSelfSignedRequest {
+    /// Subject CN，默认 "miao"
+    #[serde(default)]
+    common_name: Option<String>,
+    /// DNS 名或 IP；默认 CN + localhost + 127.0.0.1 + ::1
+    #[serde(default)]
+    san: Option<Vec<String>>,
+    #[serde(default)]
+    days: Option<u32>,
+    /// 写入 config.yaml 的 tls_cert_path / tls_key_path
+    #[serde(default)]
+    apply: bool,
+    /// 已存在时覆盖
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Serialize)]
+struct TlsSelfSignedResponse {
+    cert_path: String,
+    key_path: String,
+    common_name: String,
+    san: Vec<String>,
+    not_after: String,
+    fingerprint_sha256: String,
+    applied: bool,
+}
+
+/// POST /api/tls/self-signed - 在数据目录生成自签名证书/私钥，可选写入 tls_cert_path / tls_key_path
+async fn generate_tls_self_signed(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<TlsSelfSignedRequest>,
+) -> Result<Json<ApiResponse<TlsSelfSignedResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    use chrono::Datelike;
+    use sha2::{Digest, Sha256};
+
+    let bad_request = |msg: String| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(msg)));
+    let common_name = req
+        .common_name
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| "miao".to_string());
+    let mut san: Vec<String> = req
+        .san
+        .unwrap_or_else(|| {
+            vec![
+                common_name.clone(),
+                "localhost".to_string(),
+                "127.0.0.1".to_string(),
+                "::1".to_string(),
+            ]
+        })
+        .into_iter()
+        .map(|s| s.trim().trim_start_matches('[').trim_end_matches(']').to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    san.dedup();
+    if san.is_empty() {
+        return Err(bad_request("san must contain at least one name".to_string()));
+    }
+    let days = req.days.unwrap_or(TLS_SELF_SIGNED_DEFAULT_DAYS);
+    if days == 0 || days > TLS_SELF_SIGNED_MAX_DAYS {
+        return Err(bad_request(format!("days must be 1-{}", TLS_SELF_SIGNED_MAX_DAYS)));
+    }
+
+    let dir = PathBuf::from(TLS_DIR);
+    let cert_path = dir.join(TLS_SELF_SIGNED_CERT);
+    let key_path = dir.join(TLS_SELF_SIGNED_KEY);
+    if !req.overwrite && (cert_path.exists() || key_path.exists()) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error(format!(
+                "{} already exists, set overwrite to replace it",
+                cert_path.display()
+            ))),
+        ));
+    }
+
+    let mut params = rcgen::CertificateParams::new(san.clone())
+        .map_err(|e| bad_request(format!("Invalid san: {}", e)))?;
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, common_name.clone());
+    // 提前一天生效，容忍客户端时钟偏差
+    let not_before = Utc::now() - chrono::Duration::days(1);
+    let not_after = Utc::now() + chrono::Duration::days(i64::from(days));
+    params.not_before =
+        rcgen::date_time_ymd(not_before.year(), not_before.month() as u8, not_before.day() as u8);
+    params.not_after =
+        rcgen::date_time_ymd(not_after.year(), not_after.month() as u8, not_after.day() as u8);
+    let internal = |e: String| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e)));
+    let key_pair = rcgen::KeyPair::generate()
+        .map_err(|e| internal(format!("Failed to generate key: {}", e)))?;
+    let cert = params
+        .self_signed(&key_pair)
+        .map_err(|e| internal(format!("Failed to sign certificate: {}", e)))?;
+    let fingerprint_sha256 = Sha256::digest(cert.der().as_ref())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":");
+
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| internal(format!("Failed to create {}: {}", dir.display(), e)))?;
+    tokio::fs::write(&key_path, key_pair.serialize_pem())
+        .await
+        .map_err(|e| internal(format!("Failed to write {}: {}", key_path.display(), e)))?;
+    if let Err(e) = tokio::fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600)).await {
+        log_warning!("Failed to chmod 600 {}: {}", key_path.display(), e);
+    }
+    tokio::fs::write(&cert_path, cert.pem())
+        .await
+        .map_err(|e| internal(format!("Failed to write {}: {}", cert_path.display(), e)))?;
+
+    // 写入绝对路径，避免 --data-dir 变化后失效
+    let absolute = |p: &PathBuf| {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(p))
+            .unwrap_or_else(|_| p.clone())
+            .to_string_lossy()
+            .into_owned()
+    };
+    let cert_path = absolute(&cert_path);
+    let key_path = absolute(&key_path);
+
+    if req.apply {
+        let mut config = state.config.lock().await;
+        config.tls_cert_path = Some(cert_path.clone());
+        config.tls_key_path = Some(key_path.clone());
+        if let Err(e) = save_config(&config).await {
+            return Err(internal(format!("Failed to save config: {}", e)));
+        }
+    }
+    log_info!("Generated self-signed TLS certificate {} (CN={})", cert_path, common_name);
+
+    Ok(Json(ApiResponse::success(
+        "Self-signed certificate generated",
+        TlsSelfSignedResponse {
+            cert_path,
+            key_path,
+            common_name,
+            san,
+            not_after: not_after.format("%Y-%m-%d").to_string(),
+            fingerprint_sha256,
+            applied: req.apply,
+        },
+    )))
+}
+
+#[derive(Serialize)]
+struct VersionInfo {
+    current: String,
+    latest: Option<String>,
+    has_update: bool,
+    download_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitHubRelease {
+    tag_name: String,
+    assets: Vec<GitHubAsset>,
 }
 
 #[derive(Deserialize)]
@@ -5375,6 +9154,11 @@ struct GitHubAsset {
     browser_download_url: String,
 }
 
+/// GET /api/openapi.json - OpenAPI document for the documented endpoints
+async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(openapi::ApiDoc::openapi())
+}
+
 /// GET /api/version - Get current version and check for updates
 async fn get_version() -> Json<ApiResponse<VersionInfo>> {
     let current = format!("v{}", VERSION);
@@ -6027,7 +9811,30 @@ fn build_subscription_source_response(
     root: &StdPath,
 ) -> SubscriptionSourceResponse {
     match &sub.source {
-        SubscriptionSource::Url { url } => SubscriptionSourceResponse::Url { url: url.clone() },
+        SubscriptionSource::Url { url, headers, user_agent } => SubscriptionSourceResponse::Url {
+            url: url.clone(),
+            headers: redact_subscription_headers(headers),
+            user_agent: user_agent.clone(),
+        },
+        SubscriptionSource::Git { repo, branch, credentials } => SubscriptionSourceResponse::Git {
+            repo: repo.clone(),
+            branch: branch.clone(),
+            credentials: credentials.as_ref().map(redact_git_credentials),
+            workdir: root.join(&sub.id).display().to_string(),
+        },
+    }
+}
+
+fn redact_git_credentials(credentials: &GitCredentials) -> GitCredentials {
+    match credentials {
+        GitCredentials::Token { username, .. } => GitCredentials::Token {
+            token: REDACTED_SECRET.to_string(),
+            username: username.clone(),
+        },
+        GitCredentials::KeyPath { path, known_hosts_path } => GitCredentials::KeyPath {
+            path: path.clone(),
+            known_hosts_path: known_hosts_path.clone(),
+        },
     }
 }
 
@@ -6041,12 +9848,29 @@ fn build_subscription_item(
         name: sub.name.clone(),
         enabled: sub.enabled,
         source: build_subscription_source_response(sub, root),
+        refresh_interval_secs: sub.refresh_interval_secs,
         updated_at: runtime.and_then(|value| value.updated_at),
         last_error: runtime.and_then(|value| value.error.clone()),
+        status: runtime.map(|value| {
+            if value.stale {
+                "stale".to_string()
+            } else if value.error.is_some() {
+                "error".to_string()
+            } else {
+                "ok".to_string()
+            }
+        }),
+        attempts: runtime.map(|value| value.attempts),
         files: runtime.map(|value| value.files.clone()).unwrap_or_default(),
     }
 }
 
+/// 0 表示关闭；过短的间隔提升到最小值，避免频繁请求订阅源
+fn normalize_subscription_refresh_interval(secs: Option<u64>) -> Option<u64> {
+    secs.filter(|s| *s > 0)
+        .map(|s| s.max(MIN_SUBSCRIPTION_REFRESH_INTERVAL_SECS))
+}
+
 fn normalize_subscription_name(name: Option<String>) -> Option<String> {
     name.and_then(|value| {
         let trimmed = value.trim().to_string();
@@ -6058,15 +9882,167 @@ fn normalize_subscription_name(name: Option<String>) -> Option<String> {
     })
 }
 
-fn validate_subscription_source(input: &SubscriptionSourceInput) -> Result<SubscriptionSource, String> {
+/// 订阅请求头中视为敏感的字段，返回给前端时打码
+fn is_sensitive_header(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    matches!(lower.as_str(), "authorization" | "proxy-authorization" | "cookie")
+        || lower.contains("token")
+        || lower.contains("secret")
+        || lower.contains("key")
+}
+
+/// 逐跳（hop-by-hop）及由 HTTP 客户端自行管理的请求头，不允许在订阅中设置
+fn is_forbidden_subscription_header(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "connection"
+            | "keep-alive"
+            | "proxy-connection"
+            | "te"
+            | "trailer"
+            | "transfer-encoding"
+            | "upgrade"
+            | "host"
+            | "content-length"
+    )
+}
+
+fn redact_subscription_headers(headers: &HashMap<String, String>) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let shown = if is_sensitive_header(name) {
+                REDACTED_SECRET.to_string()
+            } else {
+                value.clone()
+            };
+            (name.clone(), shown)
+        })
+        .collect()
+}
+
+/// `existing` 为更新前的订阅来源：请求中回传的打码值会保留原值
+fn validate_subscription_source(
+    input: &SubscriptionSourceInput,
+    existing: Option<&SubscriptionSource>,
+) -> Result<SubscriptionSource, String> {
     match input {
-        SubscriptionSourceInput::Url { url } => {
+        SubscriptionSourceInput::Url { url, headers, user_agent } => {
             let trimmed = url.trim();
             if trimmed.is_empty() {
-                Err("订阅内容不能为空".to_string())
-            } else {
-                Ok(SubscriptionSource::Url { url: trimmed.to_string() })
+                return Err("订阅内容不能为空".to_string());
+            }
+            let (old_headers, old_user_agent) = match existing {
+                Some(SubscriptionSource::Url { headers, user_agent, .. }) => {
+                    (Some(headers), user_agent.clone())
+                }
+                _ => (None, None),
+            };
+            let headers = match headers {
+                // 未传 headers 时沿用原配置
+                None => old_headers.cloned().unwrap_or_default(),
+                Some(input) => {
+                    let mut out = HashMap::new();
+                    for (name, value) in input {
+                        let name = name.trim().to_string();
+                        if name.is_empty() {
+                            continue;
+                        }
+                        if is_forbidden_subscription_header(&name) {
+                            return Err(format!("不允许设置请求头: {}", name));
+                        }
+                        let value = if value == REDACTED_SECRET {
+                            match old_headers.and_then(|h| h.get(&name)) {
+                                Some(old) => old.clone(),
+                                None => continue,
+                            }
+                        } else {
+                            value.trim().to_string()
+                        };
+                        reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                            .map_err(|_| format!("无效的请求头名称: {}", name))?;
+                        reqwest::header::HeaderValue::from_str(&value)
+                            .map_err(|_| format!("请求头 {} 的值无效", name))?;
+                        out.insert(name, value);
+                    }
+                    out
+                }
+            };
+            let user_agent = match user_agent {
+                None => old_user_agent,
+                Some(ua) => normalize_subscription_name(Some(ua.clone())),
+            };
+            Ok(SubscriptionSource::Url {
+                url: trimmed.to_string(),
+                headers,
+                user_agent,
+            })
+        }
+        SubscriptionSourceInput::Git { repo, branch, credentials } => {
+            let repo = repo.trim();
+            if !looks_like_git_url(repo) {
+                return Err("仓库地址需为 http(s)://、ssh:// 或 git@host:path 格式".to_string());
             }
+            let branch = match branch.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(b) => {
+                    // 分支名会作为 git 参数传入，拒绝以 - 开头的值
+                    if b.starts_with('-') || b.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                        return Err(format!("无效的分支名: {}", b));
+                    }
+                    Some(b.to_string())
+                }
+            };
+            let old_credentials = match existing {
+                Some(SubscriptionSource::Git { credentials, .. }) => credentials.clone(),
+                _ => None,
+            };
+            let credentials = match credentials {
+                None => old_credentials,
+                Some(GitCredentials::Token { token, username }) => {
+                    let token = if token == REDACTED_SECRET {
+                        match old_credentials {
+                            Some(GitCredentials::Token { token, .. }) => token,
+                            _ => return Err("仓库 token 不能为空".to_string()),
+                        }
+                    } else {
+                        token.trim().to_string()
+                    };
+                    if token.is_empty() {
+                        None
+                    } else {
+                        Some(GitCredentials::Token {
+                            token,
+                            username: normalize_subscription_name(username.clone()),
+                        })
+                    }
+                }
+                Some(GitCredentials::KeyPath { path, known_hosts_path }) => {
+                    let path = path.trim();
+                    if path.is_empty() {
+                        None
+                    } else {
+                        if !fs::metadata(path).map(|m| m.is_file()).unwrap_or(false) {
+                            return Err(format!("私钥文件不存在: {}", path));
+                        }
+                        let known_hosts_path = normalize_known_hosts_path(known_hosts_path.clone());
+                        if let Some(known_hosts) = known_hosts_path.as_deref() {
+                            if !StdPath::new(known_hosts).is_file() {
+                                return Err(format!("known_hosts file not found: {}", known_hosts));
+                            }
+                        }
+                        Some(GitCredentials::KeyPath {
+                            path: path.to_string(),
+                            known_hosts_path,
+                        })
+                    }
+                }
+            };
+            Ok(SubscriptionSource::Git {
+                repo: repo.to_string(),
+                branch,
+                credentials,
+            })
         }
     }
 }
@@ -6101,13 +10077,14 @@ async fn create_subscription(
             Json(ApiResponse::error("订阅功能已停用")),
         ));
     }
-    let source = validate_subscription_source(&req.source)
+    let source = validate_subscription_source(&req.source, None)
         .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
     let cfg = SubscriptionConfig {
         id: generate_subscription_id(),
         name: normalize_subscription_name(req.name),
         enabled: req.enabled.unwrap_or(true),
         source,
+        refresh_interval_secs: normalize_subscription_refresh_interval(req.refresh_interval_secs),
     };
 
     {
@@ -6148,25 +10125,29 @@ async fn update_subscription(
             Json(ApiResponse::error("订阅功能已停用")),
         ));
     }
-    let source = validate_subscription_source(&req.source)
-        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
-
     let updated = {
         let mut config = state.config.lock().await;
         let Some(pos) = config.subscriptions.iter().position(|s| s.id == id) else {
             return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Subscription not found"))));
         };
         let existing = config.subscriptions[pos].clone();
+        let source = validate_subscription_source(&req.source, Some(&existing.source))
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
         let name = if req.name.is_some() {
             normalize_subscription_name(req.name)
         } else {
             existing.name
         };
+        let refresh_interval_secs = match req.refresh_interval_secs {
+            Some(secs) => normalize_subscription_refresh_interval(Some(secs)),
+            None => existing.refresh_interval_secs,
+        };
         let cfg = SubscriptionConfig {
             id: existing.id,
             name,
             enabled: req.enabled.unwrap_or(existing.enabled),
             source,
+            refresh_interval_secs,
         };
         config.subscriptions[pos] = cfg.clone();
         if let Err(e) = save_config(&config).await {
@@ -6219,9 +10200,7 @@ async fn delete_subscription(
         removed
     };
 
-    if let SubscriptionSource::Url { .. } = removed.source {
-        let _ = remove_path_if_exists(&state.subscriptions_root.join(&removed.id)).await;
-    }
+    let _ = remove_path_if_exists(&state.subscriptions_root.join(&removed.id)).await;
 
     if let Err(e) = regenerate_and_restart(state.clone()).await {
         eprintln!("❌ Failed to regenerate and restart: {}", e);
@@ -6287,6 +10266,15 @@ async fn reload_subscriptions(
 // ============================================================================
 
 /// GET /api/nodes - Get all manual nodes
+#[utoipa::path(
+    get,
+    path = "/api/nodes",
+    tag = "nodes",
+    responses(
+        (status = 200, description = "OK", body = NodeListApiResponse),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_nodes(State(state): State<Arc<AppState>>) -> Json<ApiResponse<Vec<NodeInfo>>> {
     let config = state.config.lock().await;
 
@@ -6312,6 +10300,18 @@ async fn get_nodes(State(state): State<Arc<AppState>>) -> Json<ApiResponse<Vec<N
 }
 
 /// GET /api/nodes/{tag} - Get a manual node detail (without password)
+#[utoipa::path(
+    get,
+    path = "/api/nodes/{tag}",
+    tag = "nodes",
+    params(("tag" = String, Path, description = "Node tag")),
+    responses(
+        (status = 200, description = "OK", body = NodeDetailApiResponse),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_node(
     State(state): State<Arc<AppState>>,
     Path(tag): Path<String>,
@@ -6352,6 +10352,38 @@ async fn get_node(
             .get("user")
             .and_then(|u| u.as_str())
             .map(|u| u.to_string());
+        let up_mbps = v.get("up_mbps").and_then(|m| m.as_u64()).map(|m| m as u32);
+        let down_mbps = v.get("down_mbps").and_then(|m| m.as_u64()).map(|m| m as u32);
+        let insecure = v
+            .get("tls")
+            .and_then(|t| t.get("insecure"))
+            .and_then(|i| i.as_bool());
+        let alpn = v
+            .get("tls")
+            .and_then(|t| t.get("alpn"))
+            .and_then(|a| serde_json::from_value::<Vec<String>>(a.clone()).ok());
+        let utls_fingerprint = v
+            .get("tls")
+            .and_then(|t| t.get("utls"))
+            .and_then(|u| u.get("fingerprint"))
+            .and_then(|f| f.as_str())
+            .map(|f| f.to_string());
+        let reality = v
+            .get("tls")
+            .and_then(|t| t.get("reality"))
+            .and_then(|r| serde_json::from_value::<Reality>(r.clone()).ok());
+        let ech = v
+            .get("tls")
+            .and_then(|t| t.get("ech"))
+            .and_then(|e| serde_json::from_value::<Ech>(e.clone()).ok());
+        let uuid = v
+            .get("uuid")
+            .and_then(|u| u.as_str())
+            .map(|u| u.to_string());
+        let flow = v
+            .get("flow")
+            .and_then(|f| f.as_str())
+            .map(|f| f.to_string());
 
         return Ok(Json(ApiResponse::success(
             "Node detail",
@@ -6363,6 +10395,15 @@ async fn get_node(
                 sni,
                 cipher,
                 user,
+                up_mbps,
+                down_mbps,
+                insecure,
+                alpn,
+                utls_fingerprint,
+                reality,
+                ech,
+                uuid,
+                flow,
             },
         )));
     }
@@ -6370,11 +10411,36 @@ async fn get_node(
     Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Node not found"))))
 }
 
-/// POST /api/nodes - Add a node (Hysteria2/AnyTLS/Shadowsocks)
+const DEFAULT_HY2_UP_MBPS: u32 = 40;
+const DEFAULT_HY2_DOWN_MBPS: u32 = 350;
+const DEFAULT_SS_METHOD: &str = "2022-blake3-aes-128-gcm";
+
+fn validate_hysteria2_mbps(up: Option<u32>, down: Option<u32>) -> Result<(), String> {
+    if up == Some(0) || down == Some(0) {
+        return Err("up_mbps/down_mbps must be positive".to_string());
+    }
+    Ok(())
+}
+
+/// POST /api/nodes - Add a node (Hysteria2/AnyTLS/Shadowsocks/VLESS/Trojan/SSH)
+#[utoipa::path(
+    post,
+    path = "/api/nodes",
+    tag = "nodes",
+    request_body = NodeRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn add_node(
     State(state): State<Arc<AppState>>,
     Json(req): Json<NodeRequest>,
 ) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
+    validate_hysteria2_mbps(req.up_mbps, req.down_mbps)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
     {
         let mut config = state.config.lock().await;
 
@@ -6392,6 +10458,14 @@ async fn add_node(
 
         // Build node based on type
         let node_type = req.node_type.as_deref().unwrap_or("hysteria2");
+        let alpn = req.alpn.clone().and_then(normalize_alpn);
+        let utls_fingerprint = normalize_utls_fingerprint(node_type, req.utls_fingerprint.clone())
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+        let (reality, ech) =
+            validate_tls_security(node_type, req.sni.as_deref(), req.reality.clone(), req.ech.clone())
+                .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+        // REALITY needs a uTLS client hello
+        let utls_fingerprint = utls_fingerprint.or_else(|| reality.as_ref().map(|_| "chrome".to_string()));
         let node_json = match node_type {
             "ssh" => {
                 let mut node = serde_json::Map::new();
@@ -6461,7 +10535,11 @@ async fn add_node(
                     tls: Tls {
                         enabled: true,
                         server_name: req.sni,
-                        insecure: true,
+                        insecure: req.insecure.unwrap_or(true),
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
                     },
                 };
                 serde_json::to_string(&node)
@@ -6472,11 +10550,61 @@ async fn add_node(
                     tag: req.tag,
                     server: req.server,
                     server_port: req.server_port,
-                    method: req.cipher.unwrap_or_else(|| "2022-blake3-aes-128-gcm".to_string()),
+                    method: req.cipher.unwrap_or_else(|| DEFAULT_SS_METHOD.to_string()),
                     password: req.password.unwrap_or_default(),
                 };
                 serde_json::to_string(&node)
             }
+            "vless" => {
+                let uuid = normalize_vless_uuid(req.uuid)
+                    .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+                let flow = normalize_vless_flow(req.flow)
+                    .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+                let node = Vless {
+                    outbound_type: "vless".to_string(),
+                    tag: req.tag,
+                    server: req.server,
+                    server_port: req.server_port,
+                    uuid,
+                    flow,
+                    tls: Tls {
+                        enabled: true,
+                        server_name: req.sni,
+                        insecure: req.insecure.unwrap_or(true),
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
+                    },
+                };
+                serde_json::to_string(&node)
+            }
+            "trojan" => {
+                let password = req.password.unwrap_or_default();
+                if password.is_empty() {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::error("Trojan node requires password")),
+                    ));
+                }
+                let node = Trojan {
+                    outbound_type: "trojan".to_string(),
+                    tag: req.tag,
+                    server: req.server,
+                    server_port: req.server_port,
+                    password,
+                    tls: Tls {
+                        enabled: true,
+                        server_name: req.sni,
+                        insecure: req.insecure.unwrap_or(true),
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
+                    },
+                };
+                serde_json::to_string(&node)
+            }
             _ => {
                 // Default to Hysteria2
                 let node = Hysteria2 {
@@ -6485,12 +10613,16 @@ async fn add_node(
                     server: req.server,
                     server_port: req.server_port,
                     password: req.password.unwrap_or_default(),
-                    up_mbps: 40,
-                    down_mbps: 350,
+                    up_mbps: req.up_mbps.unwrap_or(DEFAULT_HY2_UP_MBPS),
+                    down_mbps: req.down_mbps.unwrap_or(DEFAULT_HY2_DOWN_MBPS),
                     tls: Tls {
                         enabled: true,
                         server_name: req.sni,
-                        insecure: true,
+                        insecure: req.insecure.unwrap_or(true),
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
                     },
                 };
                 serde_json::to_string(&node)
@@ -6522,12 +10654,7 @@ async fn add_node(
             .sing_box_pending_restart
             .store(false, Ordering::Relaxed);
     }
-    let state_clone = state.clone();
-    tokio::spawn(async move {
-        if let Err(e) = regenerate_config(state_clone).await {
-            log_error!("Background regenerate failed: {}", e);
-        }
-    });
+    schedule_regenerate(state.clone());
 
     Ok(Json(ApiResponse::success_no_data(if running {
         "Node added, restart required"
@@ -6537,11 +10664,26 @@ async fn add_node(
 }
 
 /// PUT /api/nodes/{tag} - Update a manual node by tag (password optional)
+#[utoipa::path(
+    put,
+    path = "/api/nodes/{tag}",
+    tag = "nodes",
+    params(("tag" = String, Path, description = "Node tag")),
+    request_body = NodeUpdateRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn update_node(
     State(state): State<Arc<AppState>>,
     Path(original_tag): Path<String>,
     Json(req): Json<NodeUpdateRequest>,
 ) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
+    validate_hysteria2_mbps(req.up_mbps, req.down_mbps)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
     {
         let mut config = state.config.lock().await;
 
@@ -6617,6 +10759,59 @@ async fn update_node(
             .to_string();
         let password = password.unwrap_or(existing_password);
 
+        let insecure = req
+            .insecure
+            .or_else(|| {
+                existing
+                    .get("tls")
+                    .and_then(|t| t.get("insecure"))
+                    .and_then(|i| i.as_bool())
+            })
+            .unwrap_or(true);
+
+        let existing_tls = existing.get("tls");
+        let alpn = match req.alpn.clone() {
+            Some(list) => normalize_alpn(list),
+            None => existing_tls
+                .and_then(|t| t.get("alpn"))
+                .and_then(|a| serde_json::from_value::<Vec<String>>(a.clone()).ok()),
+        };
+        let utls_fingerprint = match req.utls_fingerprint.clone() {
+            Some(fp) => Some(fp),
+            // Keep the stored fingerprint only while the node stays a uTLS-capable type
+            None if supports_utls(node_type) => existing_tls
+                .and_then(|t| t.get("utls"))
+                .and_then(|u| u.get("fingerprint"))
+                .and_then(|f| f.as_str())
+                .map(|f| f.to_string()),
+            None => None,
+        };
+        let utls_fingerprint = normalize_utls_fingerprint(node_type, utls_fingerprint)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+        let reality = req.reality.clone().or_else(|| {
+            if !supports_reality(node_type) {
+                return None;
+            }
+            existing_tls
+                .and_then(|t| t.get("reality"))
+                .and_then(|r| serde_json::from_value::<Reality>(r.clone()).ok())
+        });
+        let ech = req.ech.clone().or_else(|| {
+            existing_tls
+                .and_then(|t| t.get("ech"))
+                .and_then(|e| serde_json::from_value::<Ech>(e.clone()).ok())
+        });
+        let sni_for_check = req.sni.clone().or_else(|| {
+            existing_tls
+                .and_then(|t| t.get("server_name"))
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string())
+        });
+        let (reality, ech) = validate_tls_security(node_type, sni_for_check.as_deref(), reality, ech)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+        let utls_fingerprint = utls_fingerprint.or_else(|| reality.as_ref().map(|_| "chrome".to_string()));
+
         let node_json = match node_type {
             "ssh" => {
                 let mut node = serde_json::Map::new();
@@ -6656,7 +10851,11 @@ async fn update_node(
                     tls: Tls {
                         enabled: true,
                         server_name: sni,
-                        insecure: true,
+                        insecure,
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
                     },
                 };
                 serde_json::to_string(&node)
@@ -6666,7 +10865,7 @@ async fn update_node(
                     .cipher
                     .clone()
                     .or_else(|| existing.get("method").and_then(|m| m.as_str()).map(|m| m.to_string()))
-                    .unwrap_or_else(|| "2022-blake3-aes-128-gcm".to_string());
+                    .unwrap_or_else(|| DEFAULT_SS_METHOD.to_string());
                 let node = Shadowsocks {
                     outbound_type: "shadowsocks".to_string(),
                     tag: new_tag,
@@ -6677,6 +10876,59 @@ async fn update_node(
                 };
                 serde_json::to_string(&node)
             }
+            "vless" => {
+                let uuid = normalize_vless_uuid(req.uuid.clone().or_else(|| {
+                    existing.get("uuid").and_then(|u| u.as_str()).map(|u| u.to_string())
+                }))
+                .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+                let flow = normalize_vless_flow(req.flow.clone().or_else(|| {
+                    existing.get("flow").and_then(|f| f.as_str()).map(|f| f.to_string())
+                }))
+                .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+                let node = Vless {
+                    outbound_type: "vless".to_string(),
+                    tag: new_tag,
+                    server,
+                    server_port,
+                    uuid,
+                    flow,
+                    tls: Tls {
+                        enabled: true,
+                        server_name: sni_for_check,
+                        insecure,
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
+                    },
+                };
+                serde_json::to_string(&node)
+            }
+            "trojan" => {
+                if password.is_empty() {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::error("Trojan node requires password")),
+                    ));
+                }
+                let node = Trojan {
+                    outbound_type: "trojan".to_string(),
+                    tag: new_tag,
+                    server,
+                    server_port,
+                    password,
+                    tls: Tls {
+                        enabled: true,
+                        server_name: sni_for_check,
+                        insecure,
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
+                    },
+                };
+                serde_json::to_string(&node)
+            }
             _ => {
                 let sni = req
                     .sni
@@ -6694,12 +10946,28 @@ async fn update_node(
                     server,
                     server_port,
                     password,
-                    up_mbps: existing.get("up_mbps").and_then(|v| v.as_u64()).unwrap_or(40) as u32,
-                    down_mbps: existing.get("down_mbps").and_then(|v| v.as_u64()).unwrap_or(350) as u32,
+                    up_mbps: req.up_mbps.unwrap_or_else(|| {
+                        existing
+                            .get("up_mbps")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as u32)
+                            .unwrap_or(DEFAULT_HY2_UP_MBPS)
+                    }),
+                    down_mbps: req.down_mbps.unwrap_or_else(|| {
+                        existing
+                            .get("down_mbps")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as u32)
+                            .unwrap_or(DEFAULT_HY2_DOWN_MBPS)
+                    }),
                     tls: Tls {
                         enabled: true,
                         server_name: sni,
-                        insecure: true,
+                        insecure,
+                        alpn,
+                        utls: utls_from_fingerprint(utls_fingerprint),
+                        reality,
+                        ech,
                     },
                 };
                 serde_json::to_string(&node)
@@ -6731,12 +10999,7 @@ async fn update_node(
             .sing_box_pending_restart
             .store(false, Ordering::Relaxed);
     }
-    let state_clone = state.clone();
-    tokio::spawn(async move {
-        if let Err(e) = regenerate_config(state_clone).await {
-            log_error!("Background regenerate failed: {}", e);
-        }
-    });
+    schedule_regenerate(state.clone());
 
     Ok(Json(ApiResponse::success_no_data(if running {
         "Node updated, restart required"
@@ -6746,6 +11009,18 @@ async fn update_node(
 }
 
 /// DELETE /api/nodes - Delete a node by tag
+#[utoipa::path(
+    delete,
+    path = "/api/nodes",
+    tag = "nodes",
+    request_body = DeleteNodeRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn delete_node(
     State(state): State<Arc<AppState>>,
     Json(req): Json<DeleteNodeRequest>,
@@ -6787,12 +11062,7 @@ async fn delete_node(
             .sing_box_pending_restart
             .store(false, Ordering::Relaxed);
     }
-    let state_clone = state.clone();
-    tokio::spawn(async move {
-        if let Err(e) = regenerate_config(state_clone).await {
-            log_error!("Background regenerate failed: {}", e);
-        }
-    });
+    schedule_regenerate(state.clone());
 
     Ok(Json(ApiResponse::success_no_data(if running {
         "Node deleted, restart required"
@@ -6801,32 +11071,542 @@ async fn delete_node(
     })))
 }
 
-/// POST /api/node-test - Test a node connectivity (TCP connect only)
-async fn test_node(
-    Json(req): Json<NodeTestRequest>,
-) -> Result<Json<ApiResponse<NodeTestResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
-    let timeout_ms = req.timeout_ms.unwrap_or(3000);
-    let addr = format!("{}:{}", req.server, req.server_port);
+#[derive(Deserialize, ToSchema)]
+struct NodeImportRequest {
+    /// Newline-separated share links (ss://, vmess://, hysteria2:// / hy2://);
+    /// a base64-encoded list is accepted as well
+    links: String,
+}
 
-    let started = Instant::now();
-    let connect = tokio::time::timeout(
-        Duration::from_millis(timeout_ms),
-        tokio::net::TcpStream::connect(addr),
-    )
-    .await;
+#[derive(Serialize, ToSchema)]
+struct NodeImportResult {
+    /// 1-based line number in the (decoded) link list
+    line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
-    match connect {
-        Ok(Ok(stream)) => {
-            drop(stream);
-            Ok(Json(ApiResponse::success(
-                "Connected",
-                NodeTestResponse {
-                    latency_ms: started.elapsed().as_millis(),
-                },
-            )))
-        }
-        Ok(Err(e)) => Err((
-            StatusCode::BAD_GATEWAY,
+#[derive(Serialize, ToSchema)]
+struct NodeImportResponse {
+    imported: usize,
+    failed: usize,
+    results: Vec<NodeImportResult>,
+}
+
+/// Parse one share link into (tag, sing-box outbound)
+fn parse_share_link(link: &str) -> Result<(String, serde_json::Value), String> {
+    let scheme = link.split_once("://").map(|(s, _)| s.to_ascii_lowercase());
+    match scheme.as_deref() {
+        Some("ss") => parse_single_ss_url(link).ok_or_else(|| "Invalid ss:// link".to_string()),
+        Some("vmess") => parse_vmess_link(link),
+        Some("hysteria2") | Some("hy2") => parse_hysteria2_link(link),
+        Some(other) => Err(format!("Unsupported link scheme: {}://", other)),
+        None => Err("Not a share link".to_string()),
+    }
+}
+
+/// vmess://BASE64(JSON) (v2rayN format)
+fn parse_vmess_link(link: &str) -> Result<(String, serde_json::Value), String> {
+    let decoded = base64_decode_lenient(&link["vmess://".len()..])
+        .map_err(|_| "Invalid vmess:// link: payload is not base64".to_string())?;
+    let v: serde_json::Value = serde_json::from_slice(&decoded)
+        .map_err(|e| format!("Invalid vmess:// link: {}", e))?;
+    // 端口/alter_id 在不同客户端里可能是字符串或数字
+    let field = |key: &str| -> String {
+        match v.get(key) {
+            Some(serde_json::Value::String(s)) => s.trim().to_string(),
+            Some(serde_json::Value::Number(n)) => n.to_string(),
+            _ => String::new(),
+        }
+    };
+
+    let server = field("add");
+    if server.is_empty() {
+        return Err("Invalid vmess:// link: missing add".to_string());
+    }
+    let server_port = field("port")
+        .parse::<u16>()
+        .map_err(|_| "Invalid vmess:// link: bad port".to_string())?;
+    let uuid = field("id");
+    if uuid.is_empty() {
+        return Err("Invalid vmess:// link: missing id".to_string());
+    }
+    let name = field("ps");
+    let tag = if name.is_empty() { join_host_port(&server, server_port) } else { name };
+    let security = Some(field("scy")).filter(|s| !s.is_empty()).unwrap_or_else(|| "auto".to_string());
+
+    let mut node = serde_json::Map::new();
+    node.insert("type".to_string(), json!("vmess"));
+    node.insert("tag".to_string(), json!(tag));
+    node.insert("server".to_string(), json!(server));
+    node.insert("server_port".to_string(), json!(server_port));
+    node.insert("uuid".to_string(), json!(uuid));
+    node.insert("security".to_string(), json!(security));
+    node.insert("alter_id".to_string(), json!(field("aid").parse::<u32>().unwrap_or(0)));
+
+    let host = field("host");
+    let path = field("path");
+    if field("tls") == "tls" {
+        let sni = Some(field("sni")).filter(|s| !s.is_empty()).or_else(|| Some(host.clone()).filter(|h| !h.is_empty()));
+        let mut tls = serde_json::Map::new();
+        tls.insert("enabled".to_string(), json!(true));
+        if let Some(sni) = sni {
+            tls.insert("server_name".to_string(), json!(sni));
+        }
+        tls.insert("insecure".to_string(), json!(false));
+        if let Some(alpn) = normalize_alpn(field("alpn").split(',').map(|s| s.to_string()).collect()) {
+            tls.insert("alpn".to_string(), json!(alpn));
+        }
+        if let Some(fp) = Some(field("fp")).filter(|s| !s.is_empty()) {
+            tls.insert("utls".to_string(), json!({ "enabled": true, "fingerprint": fp }));
+        }
+        node.insert("tls".to_string(), serde_json::Value::Object(tls));
+    }
+    match field("net").as_str() {
+        "" | "tcp" => {}
+        "ws" => {
+            let mut transport = serde_json::Map::new();
+            transport.insert("type".to_string(), json!("ws"));
+            if !path.is_empty() {
+                transport.insert("path".to_string(), json!(path));
+            }
+            if !host.is_empty() {
+                transport.insert("headers".to_string(), json!({ "Host": host }));
+            }
+            node.insert("transport".to_string(), serde_json::Value::Object(transport));
+        }
+        "grpc" => {
+            node.insert(
+                "transport".to_string(),
+                json!({ "type": "grpc", "service_name": path }),
+            );
+        }
+        "h2" | "http" => {
+            let mut transport = serde_json::Map::new();
+            transport.insert("type".to_string(), json!("http"));
+            if !path.is_empty() {
+                transport.insert("path".to_string(), json!(path));
+            }
+            if !host.is_empty() {
+                transport.insert("host".to_string(), json!([host]));
+            }
+            node.insert("transport".to_string(), serde_json::Value::Object(transport));
+        }
+        other => return Err(format!("Unsupported vmess network: {}", other)),
+    }
+
+    Ok((tag, serde_json::Value::Object(node)))
+}
+
+/// hysteria2://password@host:port/?sni=...&insecure=1#name (hy2:// is an alias)
+fn parse_hysteria2_link(link: &str) -> Result<(String, serde_json::Value), String> {
+    let url = reqwest::Url::parse(link).map_err(|e| format!("Invalid hysteria2:// link: {}", e))?;
+    let server = url
+        .host_str()
+        .map(|h| h.trim_start_matches('[').trim_end_matches(']').to_string())
+        .filter(|h| !h.is_empty())
+        .ok_or_else(|| "Invalid hysteria2:// link: missing host".to_string())?;
+    let server_port = url.port().unwrap_or(443);
+    // 密码可能写成 user:pass 形式的 userinfo
+    let password = match url.password() {
+        Some(pass) => format!("{}:{}", url_decode(url.username()), url_decode(pass)),
+        None => url_decode(url.username()),
+    };
+    if password.is_empty() {
+        return Err("Invalid hysteria2:// link: missing password".to_string());
+    }
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    let param = |key: &str| params.get(key).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+    let name = url.fragment().map(url_decode).unwrap_or_default();
+    let tag = if name.is_empty() { join_host_port(&server, server_port) } else { name };
+    let node = Hysteria2 {
+        outbound_type: "hysteria2".to_string(),
+        tag: tag.clone(),
+        server,
+        server_port,
+        password,
+        up_mbps: DEFAULT_HY2_UP_MBPS,
+        down_mbps: DEFAULT_HY2_DOWN_MBPS,
+        tls: Tls {
+            enabled: true,
+            server_name: param("sni").or_else(|| param("peer")),
+            insecure: matches!(param("insecure").as_deref(), Some("1") | Some("true")),
+            alpn: param("alpn").and_then(|a| normalize_alpn(a.split(',').map(|s| s.to_string()).collect())),
+            utls: None,
+            reality: None,
+            ech: None,
+        },
+    };
+    let mut value = serde_json::to_value(&node).map_err(|e| e.to_string())?;
+    if let Some(obfs) = param("obfs") {
+        value["obfs"] = json!({
+            "type": obfs,
+            "password": param("obfs-password").unwrap_or_default(),
+        });
+    }
+    Ok((tag, value))
+}
+
+/// POST /api/nodes/import - Import manual nodes from share links (ss/vmess/hysteria2)
+#[utoipa::path(
+    post,
+    path = "/api/nodes/import",
+    tag = "nodes",
+    request_body = NodeImportRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn import_nodes(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<NodeImportRequest>,
+) -> Result<Json<ApiResponse<NodeImportResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let mut text = req.links.trim().to_string();
+    // 整段 base64 的订阅内容
+    if !text.contains("://") {
+        if let Ok(decoded) = base64_decode_lenient(&text) {
+            text = String::from_utf8_lossy(&decoded).into_owned();
+        }
+    }
+    if text.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error("No links provided"))));
+    }
+
+    let mut results = Vec::new();
+    let imported = {
+        let mut config = state.config.lock().await;
+        let mut tags: HashSet<String> = config
+            .nodes
+            .iter()
+            .filter_map(|n| serde_json::from_str::<serde_json::Value>(n).ok())
+            .filter_map(|v| v.get("tag").and_then(|t| t.as_str()).map(|t| t.to_string()))
+            .collect();
+        let mut new_nodes = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = parse_share_link(line).and_then(|(tag, node)| {
+                if !tags.insert(tag.clone()) {
+                    return Err(format!("Node with tag {} already exists", tag));
+                }
+                serde_json::to_string(&node)
+                    .map(|json| (tag, json))
+                    .map_err(|e| format!("Failed to serialize node: {}", e))
+            });
+            match parsed {
+                Ok((tag, json)) => {
+                    new_nodes.push(json);
+                    results.push(NodeImportResult {
+                        line: idx + 1,
+                        tag: Some(tag),
+                        success: true,
+                        error: None,
+                    });
+                }
+                Err(e) => results.push(NodeImportResult {
+                    line: idx + 1,
+                    tag: None,
+                    success: false,
+                    error: Some(e),
+                }),
+            }
+        }
+
+        let imported = new_nodes.len();
+        if imported > 0 {
+            config.nodes.extend(new_nodes);
+            if let Err(e) = save_config(&config).await {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse::error(format!("Failed to save config: {}", e))),
+                ));
+            }
+        }
+        imported
+    };
+
+    let failed = results.len() - imported;
+    let running = sing_box_running().await;
+    if imported > 0 {
+        state.sing_box_pending_restart.store(running, Ordering::Relaxed);
+        schedule_regenerate(state.clone());
+    }
+
+    let message = format!("Imported {} node(s), {} failed", imported, failed);
+    Ok(Json(ApiResponse::success(
+        message,
+        NodeImportResponse {
+            imported,
+            failed,
+            results,
+        },
+    )))
+}
+
+#[derive(Deserialize)]
+struct NodeExportQuery {
+    /// singbox (default) | clash
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// sing-box outbound -> Clash (mihomo) proxy; None for types Clash cannot express
+fn singbox_outbound_to_clash(v: &serde_json::Value) -> Option<serde_json::Value> {
+    let str_field = |obj: &serde_json::Value, key: &str| {
+        obj.get(key).and_then(|s| s.as_str()).map(|s| s.to_string())
+    };
+    let tls = v.get("tls");
+    let tls_str = |key: &str| tls.and_then(|t| str_field(t, key));
+
+    let mut proxy = serde_json::Map::new();
+    proxy.insert("name".to_string(), json!(str_field(v, "tag")?));
+    proxy.insert("server".to_string(), json!(str_field(v, "server")?));
+    proxy.insert("port".to_string(), json!(v.get("server_port")?.as_u64()?));
+
+    let node_type = str_field(v, "type")?;
+    match node_type.as_str() {
+        "shadowsocks" => {
+            proxy.insert("type".to_string(), json!("ss"));
+            proxy.insert("cipher".to_string(), json!(str_field(v, "method")?));
+            proxy.insert("password".to_string(), json!(str_field(v, "password")?));
+        }
+        "hysteria2" | "anytls" | "trojan" => {
+            proxy.insert("type".to_string(), json!(node_type));
+            proxy.insert("password".to_string(), json!(str_field(v, "password")?));
+            if node_type == "hysteria2" {
+                if let Some(up) = v.get("up_mbps").and_then(|m| m.as_u64()) {
+                    proxy.insert("up".to_string(), json!(format!("{} Mbps", up)));
+                }
+                if let Some(down) = v.get("down_mbps").and_then(|m| m.as_u64()) {
+                    proxy.insert("down".to_string(), json!(format!("{} Mbps", down)));
+                }
+                if let Some(obfs) = v.get("obfs") {
+                    if let Some(t) = str_field(obfs, "type") {
+                        proxy.insert("obfs".to_string(), json!(t));
+                    }
+                    if let Some(pw) = str_field(obfs, "password") {
+                        proxy.insert("obfs-password".to_string(), json!(pw));
+                    }
+                }
+            }
+        }
+        "vless" => {
+            proxy.insert("type".to_string(), json!("vless"));
+            proxy.insert("uuid".to_string(), json!(str_field(v, "uuid")?));
+            if let Some(flow) = str_field(v, "flow") {
+                proxy.insert("flow".to_string(), json!(flow));
+            }
+        }
+        "vmess" => {
+            proxy.insert("type".to_string(), json!("vmess"));
+            proxy.insert("uuid".to_string(), json!(str_field(v, "uuid")?));
+            proxy.insert(
+                "alterId".to_string(),
+                json!(v.get("alter_id").and_then(|a| a.as_u64()).unwrap_or(0)),
+            );
+            proxy.insert(
+                "cipher".to_string(),
+                json!(str_field(v, "security").unwrap_or_else(|| "auto".to_string())),
+            );
+            if let Some(transport) = v.get("transport") {
+                match str_field(transport, "type").as_deref() {
+                    Some("ws") => {
+                        proxy.insert("network".to_string(), json!("ws"));
+                        let mut opts = serde_json::Map::new();
+                        if let Some(path) = str_field(transport, "path") {
+                            opts.insert("path".to_string(), json!(path));
+                        }
+                        if let Some(headers) = transport.get("headers") {
+                            opts.insert("headers".to_string(), headers.clone());
+                        }
+                        proxy.insert("ws-opts".to_string(), serde_json::Value::Object(opts));
+                    }
+                    Some("grpc") => {
+                        proxy.insert("network".to_string(), json!("grpc"));
+                        proxy.insert(
+                            "grpc-opts".to_string(),
+                            json!({ "grpc-service-name": str_field(transport, "service_name").unwrap_or_default() }),
+                        );
+                    }
+                    Some("http") => {
+                        proxy.insert("network".to_string(), json!("h2"));
+                        let mut opts = serde_json::Map::new();
+                        if let Some(path) = str_field(transport, "path") {
+                            opts.insert("path".to_string(), json!(path));
+                        }
+                        if let Some(host) = transport.get("host") {
+                            opts.insert("host".to_string(), host.clone());
+                        }
+                        proxy.insert("h2-opts".to_string(), serde_json::Value::Object(opts));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        "ssh" => {
+            // 私钥路径只在本机有效，只导出密码认证的 SSH 节点
+            proxy.insert("type".to_string(), json!("ssh"));
+            proxy.insert("username".to_string(), json!(str_field(v, "user")?));
+            proxy.insert("password".to_string(), json!(str_field(v, "password")?));
+        }
+        _ => return None,
+    }
+
+    if tls.and_then(|t| t.get("enabled")).and_then(|e| e.as_bool()).unwrap_or(false) {
+        if matches!(node_type.as_str(), "vless" | "vmess") {
+            proxy.insert("tls".to_string(), json!(true));
+        }
+        if let Some(sni) = tls_str("server_name") {
+            let key = if matches!(node_type.as_str(), "vless" | "vmess") { "servername" } else { "sni" };
+            proxy.insert(key.to_string(), json!(sni));
+        }
+        let insecure = tls.and_then(|t| t.get("insecure")).and_then(|i| i.as_bool()).unwrap_or(false);
+        proxy.insert("skip-cert-verify".to_string(), json!(insecure));
+        if let Some(alpn) = tls.and_then(|t| t.get("alpn")) {
+            proxy.insert("alpn".to_string(), alpn.clone());
+        }
+        if let Some(fp) = tls.and_then(|t| t.get("utls")).and_then(|u| str_field(u, "fingerprint")) {
+            proxy.insert("client-fingerprint".to_string(), json!(fp));
+        }
+        if let Some(reality) = tls.and_then(|t| t.get("reality")) {
+            let mut opts = serde_json::Map::new();
+            if let Some(pk) = str_field(reality, "public_key") {
+                opts.insert("public-key".to_string(), json!(pk));
+            }
+            if let Some(sid) = str_field(reality, "short_id") {
+                opts.insert("short-id".to_string(), json!(sid));
+            }
+            proxy.insert("reality-opts".to_string(), serde_json::Value::Object(opts));
+        }
+    }
+
+    Some(serde_json::Value::Object(proxy))
+}
+
+/// GET /api/nodes/export?format=singbox|clash - 导出手动节点为订阅文件（支持 ?token= 认证，便于其它设备直接订阅）
+async fn export_nodes(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<NodeExportQuery>,
+) -> Response {
+    let format = q.format.as_deref().map(|f| f.trim().to_lowercase()).unwrap_or_default();
+    let outbounds: Vec<serde_json::Value> = {
+        let config = state.config.lock().await;
+        config
+            .nodes
+            .iter()
+            .filter_map(|n| serde_json::from_str::<serde_json::Value>(n).ok())
+            .collect()
+    };
+
+    let (body, content_type, filename) = match format.as_str() {
+        "" | "singbox" | "sing-box" => {
+            // 与订阅解析的 sing-box JSON 格式一致（顶层 outbounds）
+            match serde_json::to_string_pretty(&json!({ "outbounds": outbounds })) {
+                Ok(body) => (body, "application/json; charset=utf-8", "miao-nodes.json"),
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::<()>::error(format!("Failed to serialize nodes: {}", e))),
+                    )
+                        .into_response()
+                }
+            }
+        }
+        "clash" => {
+            let proxies: Vec<serde_json::Value> =
+                outbounds.iter().filter_map(singbox_outbound_to_clash).collect();
+            let skipped = outbounds.len() - proxies.len();
+            match serde_yaml::to_string(&json!({ "proxies": proxies })) {
+                Ok(yaml) => {
+                    let body = if skipped > 0 {
+                        format!("# {} node(s) skipped: not representable in Clash\n{}", skipped, yaml)
+                    } else {
+                        yaml
+                    };
+                    (body, "text/yaml; charset=utf-8", "miao-nodes.yaml")
+                }
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::<()>::error(format!("Failed to serialize nodes: {}", e))),
+                    )
+                        .into_response()
+                }
+            }
+        }
+        other => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<()>::error(format!(
+                    "Unsupported format '{}', expected singbox or clash",
+                    other
+                ))),
+            )
+                .into_response()
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (axum::http::header::CONTENT_TYPE, content_type.to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// POST /api/node-test - Test a node connectivity (TCP connect only)
+#[utoipa::path(
+    post,
+    path = "/api/node-test",
+    tag = "nodes",
+    request_body = NodeTestRequest,
+    responses(
+        (status = 200, description = "OK", body = NodeTestApiResponse),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn test_node(
+    Json(req): Json<NodeTestRequest>,
+) -> Result<Json<ApiResponse<NodeTestResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let timeout_ms = req.timeout_ms.unwrap_or(3000);
+    let addr = join_host_port(&req.server, req.server_port);
+
+    let started = Instant::now();
+    let connect = tokio::time::timeout(
+        Duration::from_millis(timeout_ms),
+        tokio::net::TcpStream::connect(addr),
+    )
+    .await;
+
+    match connect {
+        Ok(Ok(stream)) => {
+            drop(stream);
+            Ok(Json(ApiResponse::success(
+                "Connected",
+                NodeTestResponse {
+                    latency_ms: started.elapsed().as_millis(),
+                },
+            )))
+        }
+        Ok(Err(e)) => Err((
+            StatusCode::BAD_GATEWAY,
             Json(ApiResponse::error(format!("Connect failed: {}", e))),
         )),
         Err(_) => Err((
@@ -6836,16 +11616,21 @@ async fn test_node(
     }
 }
 
+/// 生效的 DNS 候选：config.yaml 的 `dns_candidates` 优先，否则内置默认
+fn effective_dns_candidates(config: &Config) -> Vec<String> {
+    let raw_candidates = config
+        .dns_candidates
+        .clone()
+        .unwrap_or_else(default_dns_candidates);
+    normalize_dns_candidates(raw_candidates)
+}
+
 /// GET /api/dns/status - Get current DNS status
 async fn get_dns_status(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<serde_json::Value>> {
     let config = state.config.lock().await;
-    let raw_candidates = config
-        .dns_candidates
-        .clone()
-        .unwrap_or_else(default_dns_candidates);
-    let candidates = normalize_dns_candidates(raw_candidates);
+    let candidates = effective_dns_candidates(&config);
     let configured = config.dns_active.as_deref().unwrap_or(DEFAULT_DNS_ACTIVE);
     let active = sanitize_dns_active(configured);
 
@@ -6855,18 +11640,69 @@ async fn get_dns_status(
     })))
 }
 
+/// GET /api/dns/defaults - 内置 DNS 默认值与当前生效值（`dns_candidates` / `dns_active` 可在 config.yaml 覆盖）
+async fn get_dns_defaults(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let config = state.config.lock().await;
+    let configured = config.dns_active.as_deref().unwrap_or(DEFAULT_DNS_ACTIVE);
+    Json(ApiResponse::success("DNS defaults", json!({
+        "supported_tags": DNS_SUPPORTED_TAGS,
+        "fallback": DEFAULT_DNS_ACTIVE,
+        "builtin": {
+            "active": DEFAULT_DNS_ACTIVE,
+            "candidates": default_dns_candidates(),
+        },
+        "configured": {
+            "active": config.dns_active,
+            "candidates": config.dns_candidates,
+        },
+        "effective": {
+            "active": sanitize_dns_active(configured),
+            "candidates": effective_dns_candidates(&config),
+        },
+    })))
+}
+
+/// GET /api/proxy/defaults - 新建节点与隧道时使用的默认值
+async fn get_proxy_defaults() -> Json<ApiResponse<serde_json::Value>> {
+    let backoff = default_tcp_tunnel_backoff();
+    Json(ApiResponse::success("Proxy defaults", json!({
+        "node": {
+            "type": "hysteria2",
+            "tls_insecure": true,
+            "hysteria2_up_mbps": DEFAULT_HY2_UP_MBPS,
+            "hysteria2_down_mbps": DEFAULT_HY2_DOWN_MBPS,
+            "shadowsocks_method": DEFAULT_SS_METHOD,
+            "ssh_port": default_ssh_port(),
+        },
+        "inbounds": {
+            "socks": "127.0.0.1:1080",
+            "tun_interface": "sing-tun",
+        },
+        "selector_builtins": SELECTOR_BUILTIN_OUTBOUNDS,
+        "tcp_tunnel": {
+            "local_addr": default_local_addr(),
+            "remote_bind_addr": default_remote_bind_addr(),
+            "ssh_port": default_ssh_port(),
+            "connect_timeout_ms": default_connect_timeout_ms(),
+            "keepalive_interval_ms": default_keepalive_interval_ms(),
+            "reconnect_backoff_ms": {
+                "base_ms": backoff.base_ms,
+                "max_ms": backoff.max_ms,
+            },
+        },
+    })))
+}
+
 async fn switch_dns_active(
     State(state): State<Arc<AppState>>,
     Json(req): Json<DnsSwitchRequest>,
 ) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
-    let raw_candidates = {
+    let candidates = {
         let config = state.config.lock().await;
-        config
-            .dns_candidates
-            .clone()
-            .unwrap_or_else(default_dns_candidates)
+        effective_dns_candidates(&config)
     };
-    let candidates = normalize_dns_candidates(raw_candidates);
 
     if !candidates.iter().any(|c| c == &req.tag) {
         return Err((
@@ -6877,13 +11713,22 @@ async fn switch_dns_active(
 
     {
         let mut config = state.config.lock().await;
-        config.dns_active = Some(req.tag.clone());
+        let old = config.dns_active.replace(req.tag.clone());
         if let Err(e) = save_config(&config).await {
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ApiResponse::error(format!("Failed to save config: {}", e))),
             ));
         }
+        if old.as_deref() != Some(req.tag.as_str()) {
+            notify_webhook(
+                &config,
+                "dns_switched",
+                old,
+                Some(req.tag.clone()),
+                "DNS switched via API",
+            );
+        }
     }
 
     if is_sing_running().await {
@@ -6895,118 +11740,718 @@ async fn switch_dns_active(
         })?;
     }
 
-    Ok(Json(ApiResponse::success_no_data("DNS switched")))
+    Ok(Json(ApiResponse::success_no_data("DNS switched")))
+}
+
+fn redact_tunnel_auth(auth: &TcpTunnelAuth) -> TcpTunnelAuthPublic {
+    match auth {
+        TcpTunnelAuth::Password { password } => TcpTunnelAuthPublic::Password {
+            password: password.clone(),
+        },
+        TcpTunnelAuth::PrivateKeyPath { path, .. } => TcpTunnelAuthPublic::PrivateKeyPath {
+            path: path.clone(),
+        },
+    }
+}
+
+fn redact_sync_auth(auth: &TcpTunnelAuth) -> SyncAuthPublic {
+    match auth {
+        TcpTunnelAuth::Password { password } => SyncAuthPublic::Password {
+            password: if password.is_empty() { "".to_string() } else { "******".to_string() },
+        },
+        TcpTunnelAuth::PrivateKeyPath { path, .. } => SyncAuthPublic::PrivateKeyPath {
+            path: path.clone(),
+        },
+    }
+}
+
+fn generate_tunnel_id() -> String {
+    format!("t-{}", uuid::Uuid::new_v4())
+}
+
+fn generate_sync_id() -> String {
+    format!("sync-{}", uuid::Uuid::new_v4())
+}
+
+fn generate_connectivity_preset_id() -> String {
+    format!("conn-{}", uuid::Uuid::new_v4())
+}
+
+fn generate_subscription_id() -> String {
+    format!("sub-{}", uuid::Uuid::new_v4())
+}
+
+fn generate_terminal_id() -> String {
+    format!("term-{}", uuid::Uuid::new_v4())
+}
+
+fn generate_app_id() -> String {
+    format!("app-{}", uuid::Uuid::new_v4())
+}
+
+fn normalize_display_value(value: &str) -> String {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        ":0".to_string()
+    } else if trimmed.starts_with(':') {
+        trimmed.to_string()
+    } else {
+        format!(":{}", trimmed)
+    }
+}
+
+fn terminal_legacy_is_default(cfg: &TerminalConfigLegacy) -> bool {
+    cfg.enabled == false
+        && cfg.addr == default_terminal_addr()
+        && cfg.port == default_terminal_port()
+        && cfg.command == default_terminal_command()
+        && cfg.command_args.is_empty()
+        && cfg.auth_username.is_none()
+        && cfg.auth_password.is_none()
+        && (cfg.extra_args.is_empty() || cfg.extra_args == default_terminal_extra_args())
+}
+
+fn terminal_node_default(id: String) -> TerminalNodeConfig {
+    let mut cfg = TerminalNodeConfig::default();
+    cfg.id = id;
+    cfg
+}
+
+fn terminal_bind_conflict(
+    id: &str,
+    cfg: &TerminalNodeConfig,
+    terminals: &[TerminalNodeConfig],
+) -> Option<String> {
+    let addr = if cfg.addr.trim().is_empty() {
+        "127.0.0.1"
+    } else {
+        cfg.addr.as_str()
+    };
+    let port = cfg.port;
+    for t in terminals {
+        if t.id == id {
+            continue;
+        }
+        if t.port != port {
+            continue;
+        }
+        let other_addr = if t.addr.trim().is_empty() {
+            "127.0.0.1"
+        } else {
+            t.addr.as_str()
+        };
+        let conflicts = addr == other_addr || addr == "0.0.0.0" || other_addr == "0.0.0.0";
+        if conflicts {
+            let name = t.name.clone().unwrap_or_else(|| t.id.clone());
+            return Some(format!("terminal port already in use by {}", name));
+        }
+    }
+    None
+}
+
+/// 有序的配置迁移：(升级到的版本, 说明, 迁移函数)。新增迁移只在末尾追加。
+const CONFIG_MIGRATIONS: &[(u32, &str, fn(&mut Config))] = &[
+    (1, "legacy single `terminal` moved into `terminals`", migrate_terminals),
+];
+/// 当前配置结构版本（= 最后一个迁移的版本）
+const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// 按版本顺序执行尚未应用的迁移，返回已应用的版本列表
+fn run_config_migrations(config: &mut Config) -> Vec<u32> {
+    if config.schema_version > CONFIG_SCHEMA_VERSION {
+        log_warning!(
+            "config.yaml schema_version {} is newer than supported {}; unknown fields may be dropped on save",
+            config.schema_version,
+            CONFIG_SCHEMA_VERSION
+        );
+        return vec![];
+    }
+    let mut applied = vec![];
+    for (version, description, migrate) in CONFIG_MIGRATIONS {
+        if *version <= config.schema_version {
+            continue;
+        }
+        migrate(config);
+        config.schema_version = *version;
+        log_info!("Config migrated to schema v{}: {}", version, description);
+        applied.push(*version);
+    }
+    applied
+}
+
+#[derive(Serialize)]
+struct ConfigMigrationInfo {
+    version: u32,
+    description: &'static str,
+}
+
+#[derive(Serialize)]
+struct ConfigVersionResponse {
+    schema_version: u32,
+    latest_schema_version: u32,
+    app_version: String,
+    migrations: Vec<ConfigMigrationInfo>,
+}
+
+/// GET /api/config/version - 配置结构版本与已知迁移
+async fn get_config_version(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<ConfigVersionResponse>> {
+    let schema_version = state.config.lock().await.schema_version;
+    Json(ApiResponse::success(
+        "Config version",
+        ConfigVersionResponse {
+            schema_version,
+            latest_schema_version: CONFIG_SCHEMA_VERSION,
+            app_version: format!("v{}", VERSION),
+            migrations: CONFIG_MIGRATIONS
+                .iter()
+                .map(|(version, description, _)| ConfigMigrationInfo {
+                    version: *version,
+                    description: *description,
+                })
+                .collect(),
+        },
+    ))
+}
+
+#[derive(Deserialize)]
+struct ConfigExportQuery {
+    #[serde(default)]
+    include_secrets: bool,
+}
+
+/// 导出/导入用的配置包；导入时只要求 `config`
+#[derive(Serialize, Deserialize)]
+struct ConfigBundle {
+    #[serde(default)]
+    app_version: String,
+    #[serde(default)]
+    exported_at_ms: i64,
+    #[serde(default)]
+    secrets_included: bool,
+    config: Config,
+}
+
+#[derive(Serialize)]
+struct ConfigImportResponse {
+    schema_version: u32,
+    migrations_applied: Vec<u32>,
+    /// 配置已保存，但重新生成/重启 sing-box 失败时的错误
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restart_error: Option<String>,
+}
+
+/// GET /api/config/export - 导出完整配置；默认脱敏，`?include_secrets=true` 时包含明文密钥
+async fn export_config(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ConfigExportQuery>,
+) -> Json<ApiResponse<ConfigBundle>> {
+    let mut config = state.config.lock().await.clone();
+    // JWT 签名密钥只属于本机，不随配置迁移
+    config.jwt_secret = None;
+    if q.include_secrets {
+        log_warning!("Config exported with plaintext secrets");
+    } else {
+        secrets::redact_config_secrets(&mut config);
+    }
+    Json(ApiResponse::success(
+        "Config exported",
+        ConfigBundle {
+            app_version: format!("v{}", VERSION),
+            exported_at_ms: Utc::now().timestamp_millis(),
+            secrets_included: q.include_secrets,
+            config,
+        },
+    ))
+}
+
+/// POST /api/config/import - 校验并替换整份配置，重新应用隧道/同步/终端/应用并重启 sing-box
+async fn import_config(
+    State(state): State<Arc<AppState>>,
+    Json(bundle): Json<ConfigBundle>,
+) -> Result<Json<ApiResponse<ConfigImportResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let mut imported = bundle.config;
+    if imported.schema_version > CONFIG_SCHEMA_VERSION {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error(format!(
+                "Config schema_version {} is newer than supported {}",
+                imported.schema_version, CONFIG_SCHEMA_VERSION
+            ))),
+        ));
+    }
+    // 与启动时相同的迁移与规范化，旧版本导出的配置也能直接导入
+    let migrations_applied = run_config_migrations(&mut imported);
+    normalize_terminals(&mut imported);
+    if SUBSCRIPTIONS_ENABLED {
+        normalize_subscriptions(&mut imported);
+    }
+    validate_imported_config(&imported)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+    let previous = {
+        let mut config = state.config.lock().await;
+        check_imported_capacity(&imported, &config)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+        restore_redacted_secrets(&mut imported, &config);
+        let unresolved = secrets::count_redacted_secrets(&mut imported);
+        if unresolved > 0 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::error(format!(
+                    "{} secret(s) are redacted and have no matching item in the current config; export with include_secrets=true",
+                    unresolved
+                ))),
+            ));
+        }
+        imported.jwt_secret = config.jwt_secret.clone();
+        if let Err(e) = save_config(&imported).await {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!("Failed to save config: {}", e))),
+            ));
+        }
+        std::mem::replace(&mut *config, imported)
+    };
+
+    apply_tunnels_from_config(&state).await;
+    apply_full_tunnel_sets_from_config(&state).await;
+    let config_snapshot = state.config.lock().await.clone();
+    let schema_version = config_snapshot.schema_version;
+    state.sync_manager.apply_config(&config_snapshot.syncs).await;
+    reconcile_imported_processes(&previous, &config_snapshot).await;
+    let restart_error = regenerate_and_restart(state.clone()).await.err();
+    match &restart_error {
+        Some(e) => log_warning!("Config imported, but sing-box restart failed: {}", e),
+        None => log_info!("Config imported (app version {})", bundle.app_version),
+    }
+
+    Ok(Json(ApiResponse::success(
+        "Config imported",
+        ConfigImportResponse {
+            schema_version,
+            migrations_applied,
+            restart_error,
+        },
+    )))
+}
+
+/// 导入前的结构校验：各类条目的 id 必须非空且唯一
+fn validate_imported_config(config: &Config) -> Result<(), String> {
+    fn check<'a>(kind: &str, ids: impl Iterator<Item = &'a str>) -> Result<(), String> {
+        let mut seen = HashSet::new();
+        for id in ids {
+            if id.trim().is_empty() {
+                return Err(format!("{} with empty id", kind));
+            }
+            if !seen.insert(id) {
+                return Err(format!("Duplicate {} id: {}", kind, id));
+            }
+        }
+        Ok(())
+    }
+    check("tcp_tunnels", config.tcp_tunnels.iter().map(|t| t.id.as_str()))?;
+    check("tcp_tunnel_sets", config.tcp_tunnel_sets.iter().map(|s| s.id.as_str()))?;
+    check("syncs", config.syncs.iter().map(|s| s.id.as_str()))?;
+    check("hosts", config.hosts.iter().map(|h| h.id.as_str()))?;
+    check("terminals", config.terminals.iter().map(|t| t.id.as_str()))?;
+    check("subscriptions", config.subscriptions.iter().map(|s| s.id.as_str()))?;
+    check("apps", config.apps.iter().map(|a| a.id.as_str()))?;
+    Ok(())
+}
+
+/// 导入的条目数不能超过当前实例的 max_* 上限，与逐个新建时的检查一致
+fn check_imported_capacity(imported: &Config, current: &Config) -> Result<(), String> {
+    for kind in ResourceKind::ALL {
+        if matches!(kind, ResourceKind::VncSessions) {
+            continue;
+        }
+        let count = kind.count(imported);
+        let limit = kind.limit(current);
+        if count > limit {
+            return Err(format!(
+                "Imported config has {} {}, limit is {} (max_{} in config.yaml)",
+                count,
+                kind.key(),
+                limit,
+                kind.key()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 导入后让终端/应用进程与新配置一致：删除、停用或配置有变的先停止，启用但未运行的再启动
+async fn reconcile_imported_processes(previous: &Config, config: &Config) {
+    for old in &previous.terminals {
+        let keep = config
+            .terminals
+            .iter()
+            .any(|t| t.id == old.id && t.enabled && t == old);
+        if !keep {
+            // 仍启用的终端会在下面按新配置重新启动，对已连接的代理来说是一次重启
+            if config.terminals.iter().any(|t| t.id == old.id && t.enabled) {
+                bump_terminal_restart_generation(&old.id);
+            }
+            let _ = stop_terminal_internal(&old.id).await;
+        }
+    }
+    for old in &previous.apps {
+        let keep = config
+            .apps
+            .iter()
+            .any(|a| a.id == old.id && a.enabled && a == old);
+        if !keep {
+            let _ = stop_app_internal(&old.id).await;
+        }
+    }
+
+    for terminal in config.terminals.iter().filter(|t| t.enabled) {
+        if get_terminal_runtime_status(&terminal.id).await.running {
+            continue;
+        }
+        if let Err(e) = start_terminal_internal(&terminal.id, terminal).await {
+            log_warning!("Failed to start imported terminal {}: {}", terminal.id, e);
+        }
+    }
+    for app in config.apps.iter().filter(|a| a.enabled) {
+        if get_app_runtime_status(&app.id).await.running {
+            continue;
+        }
+        if let Err(e) = start_app_internal(app, config).await {
+            log_warning!("Failed to start imported app {}: {}", app.id, e);
+        }
+    }
+}
+
+fn restore_redacted(value: &mut String, existing: Option<&String>) {
+    if *value == REDACTED_SECRET {
+        if let Some(old) = existing {
+            *value = old.clone();
+        }
+    }
+}
+
+fn restore_tunnel_auth_secrets(auth: &mut TcpTunnelAuth, existing: Option<&TcpTunnelAuth>) {
+    match (auth, existing) {
+        (TcpTunnelAuth::Password { password }, Some(TcpTunnelAuth::Password { password: old })) => {
+            restore_redacted(password, Some(old));
+        }
+        (
+            TcpTunnelAuth::PrivateKeyPath { passphrase: Some(passphrase), .. },
+            Some(TcpTunnelAuth::PrivateKeyPath { passphrase: old, .. }),
+        ) => restore_redacted(passphrase, old.as_ref()),
+        _ => {}
+    }
+}
+
+fn restore_host_auth_secrets(auth: &mut HostAuth, existing: Option<&HostAuth>) {
+    match (auth, existing) {
+        (HostAuth::Password { password: Some(password) }, Some(HostAuth::Password { password: old })) => {
+            restore_redacted(password, old.as_ref());
+        }
+        (
+            HostAuth::PrivateKeyPath { passphrase: Some(passphrase), .. },
+            Some(HostAuth::PrivateKeyPath { passphrase: old, .. }),
+        ) => restore_redacted(passphrase, old.as_ref()),
+        _ => {}
+    }
+}
+
+/// 脱敏导出再导入时，把占位符还原为当前配置中同 id 条目的原值
+fn restore_redacted_secrets(imported: &mut Config, current: &Config) {
+    if let Some(password) = imported.password.as_mut() {
+        restore_redacted(password, current.password.as_ref());
+    }
+    if let Some(auth) = imported.default_ssh_auth.as_mut() {
+        restore_tunnel_auth_secrets(auth, current.default_ssh_auth.as_ref());
+    }
+    for host in imported.hosts.iter_mut() {
+        let old = current.hosts.iter().find(|h| h.id == host.id);
+        restore_host_auth_secrets(&mut host.auth, old.map(|h| &h.auth));
+    }
+    for tunnel in imported.tcp_tunnels.iter_mut() {
+        let old = current.tcp_tunnels.iter().find(|t| t.id == tunnel.id);
+        restore_tunnel_auth_secrets(&mut tunnel.auth, old.map(|t| &t.auth));
+    }
+    for set in imported.tcp_tunnel_sets.iter_mut() {
+        let old = current.tcp_tunnel_sets.iter().find(|s| s.id == set.id);
+        restore_tunnel_auth_secrets(&mut set.auth, old.map(|s| &s.auth));
+    }
+    for sync in imported.syncs.iter_mut() {
+        let old = current.syncs.iter().find(|s| s.id == sync.id);
+        restore_tunnel_auth_secrets(&mut sync.ssh.auth, old.map(|s| &s.ssh.auth));
+    }
+    if let Some(password) = imported.terminal.as_mut().and_then(|t| t.auth_password.as_mut()) {
+        restore_redacted(password, current.terminal.as_ref().and_then(|t| t.auth_password.as_ref()));
+    }
+    for terminal in imported.terminals.iter_mut() {
+        let old = current.terminals.iter().find(|t| t.id == terminal.id);
+        if let Some(password) = terminal.auth_password.as_mut() {
+            restore_redacted(password, old.and_then(|t| t.auth_password.as_ref()));
+        }
+    }
+    for node in imported.nodes.iter_mut() {
+        secrets::restore_node_secrets(node, &current.nodes);
+    }
+    for sub in imported.subscriptions.iter_mut() {
+        let old_source = current
+            .subscriptions
+            .iter()
+            .find(|s| s.id == sub.id)
+            .map(|s| &s.source);
+        match &mut sub.source {
+            SubscriptionSource::Url { headers, .. } => {
+                let old_headers = match old_source {
+                    Some(SubscriptionSource::Url { headers, .. }) => Some(headers),
+                    _ => None,
+                };
+                // 无法还原的脱敏请求头直接丢弃，与订阅编辑接口一致
+                headers.retain(|name, value| {
+                    if *value != REDACTED_SECRET {
+                        return true;
+                    }
+                    match old_headers.and_then(|h| h.get(name)) {
+                        Some(old) => {
+                            *value = old.clone();
+                            true
+                        }
+                        None => false,
+                    }
+                });
+            }
+            SubscriptionSource::Git { credentials, .. } => {
+                if let Some(GitCredentials::Token { token, .. }) = credentials.as_mut() {
+                    let old_token = match old_source {
+                        Some(SubscriptionSource::Git {
+                            credentials: Some(GitCredentials::Token { token, .. }),
+                            ..
+                        }) => Some(token),
+                        _ => None,
+                    };
+                    restore_redacted(token, old_token);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ConfigProblem {
+    section: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    message: String,
 }
 
-fn redact_tunnel_auth(auth: &TcpTunnelAuth) -> TcpTunnelAuthPublic {
-    match auth {
-        TcpTunnelAuth::Password { password } => TcpTunnelAuthPublic::Password {
-            password: password.clone(),
-        },
-        TcpTunnelAuth::PrivateKeyPath { path, .. } => TcpTunnelAuthPublic::PrivateKeyPath {
-            path: path.clone(),
-        },
-    }
+#[derive(Serialize)]
+struct ConfigValidationResponse {
+    valid: bool,
+    problems: Vec<ConfigProblem>,
 }
 
-fn redact_sync_auth(auth: &TcpTunnelAuth) -> SyncAuthPublic {
-    match auth {
-        TcpTunnelAuth::Password { password } => SyncAuthPublic::Password {
-            password: if password.is_empty() { "".to_string() } else { "******".to_string() },
-        },
-        TcpTunnelAuth::PrivateKeyPath { path, .. } => SyncAuthPublic::PrivateKeyPath {
-            path: path.clone(),
-        },
+/// POST /api/config/validate - 对完整或部分配置运行所有校验规则，只返回问题列表，不落盘也不改运行状态
+async fn validate_config(
+    State(state): State<Arc<AppState>>,
+    Json(mut candidate): Json<Config>,
+) -> Json<ApiResponse<ConfigValidationResponse>> {
+    {
+        let current = state.config.lock().await;
+        // 部分配置：未提供的列表沿用当前配置，便于校验跨条目的冲突与引用
+        if candidate.terminals.is_empty() {
+            candidate.terminals = current.terminals.clone();
+        }
+        if candidate.apps.is_empty() {
+            candidate.apps = current.apps.clone();
+        }
+        if candidate.syncs.is_empty() {
+            candidate.syncs = current.syncs.clone();
+        }
+        if candidate.tcp_tunnels.is_empty() {
+            candidate.tcp_tunnels = current.tcp_tunnels.clone();
+        }
+        if candidate.tcp_tunnel_sets.is_empty() {
+            candidate.tcp_tunnel_sets = current.tcp_tunnel_sets.clone();
+        }
+        if candidate.hosts.is_empty() {
+            candidate.hosts = current.hosts.clone();
+        }
+        if candidate.default_ssh_auth.is_none() {
+            candidate.default_ssh_auth = current.default_ssh_auth.clone();
+        }
+        restore_redacted_secrets(&mut candidate, &current);
     }
-}
+    run_config_migrations(&mut candidate);
+    normalize_terminals(&mut candidate);
 
-fn generate_tunnel_id() -> String {
-    format!("t-{}", uuid::Uuid::new_v4())
+    // 含私钥解析等阻塞检查
+    let problems = tokio::task::spawn_blocking(move || collect_config_problems(&candidate))
+        .await
+        .unwrap_or_else(|e| {
+            vec![ConfigProblem {
+                section: "config",
+                id: None,
+                message: format!("Validation failed: {}", e),
+            }]
+        });
+    let message = if problems.is_empty() {
+        "Config is valid".to_string()
+    } else {
+        format!("{} problem(s) found", problems.len())
+    };
+    Json(ApiResponse::success(
+        message,
+        ConfigValidationResponse {
+            valid: problems.is_empty(),
+            problems,
+        },
+    ))
 }
 
-fn generate_sync_id() -> String {
-    format!("sync-{}", uuid::Uuid::new_v4())
-}
+/// 与各 upsert 接口一致的校验规则，汇总全部问题而不是遇到第一个就返回
+fn collect_config_problems(config: &Config) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    let mut push = |section: &'static str, id: Option<&str>, message: String| {
+        problems.push(ConfigProblem {
+            section,
+            id: id.map(|s| s.to_string()),
+            message,
+        });
+    };
 
-fn generate_subscription_id() -> String {
-    format!("sub-{}", uuid::Uuid::new_v4())
-}
+    if let Err(e) = validate_imported_config(config) {
+        push("config", None, e);
+    }
+    if let Some(auth) = config.default_ssh_auth.as_ref() {
+        if let Err(e) = validate_tunnel_auth_key(auth) {
+            push("default_ssh_auth", None, e);
+        }
+    }
+    if let Some(url) = config.proxy_check_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        if let Err(e) = validate_proxy_check_url(url.trim()) {
+            push("proxy_check_url", None, e);
+        }
+    }
+    if let Some(url) = config.notify_webhook_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        let ok = reqwest::Url::parse(url.trim())
+            .map(|u| matches!(u.scheme(), "http" | "https"))
+            .unwrap_or(false);
+        if !ok {
+            push("notify_webhook_url", None, format!("invalid webhook URL: {}", url));
+        }
+    }
 
-fn generate_terminal_id() -> String {
-    format!("term-{}", uuid::Uuid::new_v4())
-}
+    for t in &config.terminals {
+        let id = Some(t.id.as_str());
+        if t.command.trim().is_empty() {
+            push("terminals", id, "terminal command is required".to_string());
+        }
+        if t.port == 0 {
+            push("terminals", id, "terminal port is required".to_string());
+        } else if let Some(e) = terminal_bind_conflict(&t.id, t, &config.terminals) {
+            push("terminals", id, e);
+        }
+    }
 
-fn generate_app_id() -> String {
-    format!("app-{}", uuid::Uuid::new_v4())
-}
+    for a in &config.apps {
+        if a.command.trim().is_empty() {
+            push("apps", Some(a.id.as_str()), "应用启动命令不能为空".to_string());
+        }
+    }
 
-fn normalize_display_value(value: &str) -> String {
-    let trimmed = value.trim();
-    if trimmed.is_empty() {
-        ":0".to_string()
-    } else if trimmed.starts_with(':') {
-        trimmed.to_string()
-    } else {
-        format!(":{}", trimmed)
+    for h in &config.hosts {
+        let id = Some(h.id.as_str());
+        if h.host.trim().is_empty() {
+            push("hosts", id, "SSH host is required".to_string());
+        }
+        if h.username.trim().is_empty() {
+            push("hosts", id, "SSH username is required".to_string());
+        }
+        if let Err(e) = validate_host_auth_key(&h.auth) {
+            push("hosts", id, e);
+        }
     }
-}
 
-fn terminal_legacy_is_default(cfg: &TerminalConfigLegacy) -> bool {
-    cfg.enabled == false
-        && cfg.addr == default_terminal_addr()
-        && cfg.port == default_terminal_port()
-        && cfg.command == default_terminal_command()
-        && cfg.command_args.is_empty()
-        && cfg.auth_username.is_none()
-        && cfg.auth_password.is_none()
-        && (cfg.extra_args.is_empty() || cfg.extra_args == default_terminal_extra_args())
-}
+    for t in &config.tcp_tunnels {
+        let id = Some(t.id.as_str());
+        if let Err((code, e)) = tcp_tunnel::validate(t) {
+            push("tcp_tunnels", id, format!("{}: {}", code, e));
+        }
+        if let Err(e) = validate_tunnel_auth_key(&t.auth) {
+            push("tcp_tunnels", id, e);
+        }
+    }
 
-fn terminal_node_default(id: String) -> TerminalNodeConfig {
-    let mut cfg = TerminalNodeConfig::default();
-    cfg.id = id;
-    cfg
-}
+    for set in &config.tcp_tunnel_sets {
+        let id = Some(set.id.as_str());
+        if let Err(e) = validate_host_key_settings(
+            set.strict_host_key_checking,
+            &set.host_key_fingerprint,
+            set.known_hosts_path.as_deref(),
+        ) {
+            push("tcp_tunnel_sets", id, e);
+        }
+        if let Err(e) = validate_tunnel_set_port_mapping(set, &config.tcp_tunnels) {
+            push("tcp_tunnel_sets", id, e);
+        }
+        if let Err(e) = validate_tunnel_auth_key(&set.auth) {
+            push("tcp_tunnel_sets", id, e);
+        }
+    }
 
-fn terminal_bind_conflict(
-    id: &str,
-    cfg: &TerminalNodeConfig,
-    terminals: &[TerminalNodeConfig],
-) -> Option<String> {
-    let addr = if cfg.addr.trim().is_empty() {
-        "127.0.0.1"
-    } else {
-        cfg.addr.as_str()
-    };
-    let port = cfg.port;
-    for t in terminals {
-        if t.id == id {
-            continue;
+    for sync in &config.syncs {
+        let id = Some(sync.id.as_str());
+        if sync.local_paths.iter().all(|p| p.path.trim().is_empty()) {
+            push("syncs", id, "Local paths are required".to_string());
         }
-        if t.port != port {
-            continue;
+        if sync.local_paths.len() > 1 && sync.remote_path.is_some() {
+            push("syncs", id, "Multiple local paths cannot set remote path".to_string());
         }
-        let other_addr = if t.addr.trim().is_empty() {
-            "127.0.0.1"
-        } else {
-            t.addr.as_str()
-        };
-        let conflicts = addr == other_addr || addr == "0.0.0.0" || other_addr == "0.0.0.0";
-        if conflicts {
-            let name = t.name.clone().unwrap_or_else(|| t.id.clone());
-            return Some(format!("terminal port already in use by {}", name));
+        if let Err(e) = normalize_sync_schedule(sync.schedule.clone()) {
+            push("syncs", id, e);
+        }
+        if sync.ssh.host.trim().is_empty() {
+            push("syncs", id, "SSH host is required".to_string());
+        }
+        if sync.ssh.username.trim().is_empty() {
+            push("syncs", id, "SSH username is required".to_string());
+        }
+        if let Err(e) = validate_tunnel_auth_key(&sync.ssh.auth) {
+            push("syncs", id, e);
+        }
+        if let Err(e) = validate_sync_via_tunnel(
+            config,
+            sync.via_tunnel_id.clone(),
+            sync.ssh.host.trim(),
+            sync.ssh.port,
+        ) {
+            push("syncs", id, e);
+        }
+        if let Some(host_id) = sync.host_id.as_ref() {
+            if !config.hosts.iter().any(|h| h.id == *host_id) {
+                push("syncs", id, format!("Host {} not found", host_id));
+            }
         }
     }
-    None
+
+    problems
+}
+
+/// 每次加载都执行的终端 id 补全（与版本无关）
+fn normalize_terminals(config: &mut Config) -> bool {
+    let mut changed = false;
+    for t in &mut config.terminals {
+        if t.id.trim().is_empty() {
+            t.id = generate_terminal_id();
+            changed = true;
+        }
+    }
+    changed
 }
 
 fn migrate_terminals(config: &mut Config) {
     if !config.terminals.is_empty() {
-        for t in &mut config.terminals {
-            if t.id.trim().is_empty() {
-                t.id = generate_terminal_id();
-            }
-        }
         config.terminal = None;
         return;
     }
@@ -7046,6 +12491,7 @@ fn normalize_subscriptions(config: &mut Config) -> bool {
 }
 
 fn normalize_tcp_tunnel(req: TcpTunnelUpsertRequest, id: String) -> Result<TcpTunnelConfig, String> {
+    let direction = req.direction.unwrap_or_default();
     let local_addr = req.local_addr.unwrap_or_else(default_local_addr);
     let local_port = req.local_port.unwrap_or(22);
     let remote_bind_addr = req.remote_bind_addr.unwrap_or_else(default_remote_bind_addr);
@@ -7056,6 +12502,7 @@ fn normalize_tcp_tunnel(req: TcpTunnelUpsertRequest, id: String) -> Result<TcpTu
     let auth = req.auth.ok_or("auth is required")?;
     let strict_host_key_checking = req.strict_host_key_checking.unwrap_or(true);
     let host_key_fingerprint = req.host_key_fingerprint.unwrap_or_default();
+    let known_hosts_path = normalize_known_hosts_path(req.known_hosts_path);
     let allow_public_bind = req.allow_public_bind.unwrap_or(false);
     let connect_timeout_ms = req.connect_timeout_ms.unwrap_or_else(default_connect_timeout_ms);
     let keepalive_interval_ms = req
@@ -7067,17 +12514,32 @@ fn normalize_tcp_tunnel(req: TcpTunnelUpsertRequest, id: String) -> Result<TcpTu
     if remote_port == 0 {
         return Err("remote_port must be > 0".to_string());
     }
-    if remote_bind_addr == "0.0.0.0" && !allow_public_bind {
-        return Err("allow_public_bind must be true when remote_bind_addr is 0.0.0.0".to_string());
-    }
-    if strict_host_key_checking && host_key_fingerprint.trim().is_empty() {
-        return Err("host_key_fingerprint is required when strict_host_key_checking is true".to_string());
+    match direction {
+        TcpTunnelDirection::Reverse => {
+            if remote_bind_addr == "0.0.0.0" && !allow_public_bind {
+                return Err("allow_public_bind must be true when remote_bind_addr is 0.0.0.0".to_string());
+            }
+        }
+        TcpTunnelDirection::Forward => {
+            if local_port == 0 {
+                return Err("local_port must be > 0 for forward tunnels".to_string());
+            }
+            if local_addr == "0.0.0.0" && !allow_public_bind {
+                return Err("allow_public_bind must be true when local_addr is 0.0.0.0".to_string());
+            }
+        }
     }
+    validate_host_key_settings(
+        strict_host_key_checking,
+        &host_key_fingerprint,
+        known_hosts_path.as_deref(),
+    )?;
 
     Ok(TcpTunnelConfig {
         id,
         name: req.name,
         enabled,
+        direction,
         local_addr,
         local_port,
         remote_bind_addr,
@@ -7088,20 +12550,78 @@ fn normalize_tcp_tunnel(req: TcpTunnelUpsertRequest, id: String) -> Result<TcpTu
         auth,
         strict_host_key_checking,
         host_key_fingerprint,
+        known_hosts_path,
         allow_public_bind,
         connect_timeout_ms,
         keepalive_interval_ms,
         reconnect_backoff_ms,
         managed_by: None,
+        host_id: req.host_id,
     })
 }
 
+/// 端口列表去重、排序并去掉 0
+fn normalize_port_list(ports: Vec<u16>) -> Vec<u16> {
+    let mut ports: Vec<u16> = ports.into_iter().filter(|p| *p != 0).collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// 保存时校验端口映射。冲突检查只针对已配置隧道在同一 ssh_host 上占用的远端端口；
+/// 远端机器上其他程序的监听无法在本机扫描到，由 ssh 转发失败时在隧道状态中报告。
+/// 本机监听端口是映射的来源而不是目标，因此不参与冲突检查。
+fn validate_tunnel_set_port_mapping(
+    set: &TcpTunnelSetConfig,
+    tunnels: &[TcpTunnelConfig],
+) -> Result<(), String> {
+    if set.include_ports_enabled {
+        let both: Vec<String> = set
+            .include_ports
+            .iter()
+            .filter(|p| set.exclude_ports.contains(p))
+            .map(|p| p.to_string())
+            .collect();
+        if !both.is_empty() {
+            return Err(format!(
+                "ports {} are in both include_ports and exclude_ports",
+                both.join(", ")
+            ));
+        }
+    }
+    if set.port_strategy != TcpTunnelSetPortStrategy::Same && set.port_base == 0 {
+        return Err("port_base is required when port_strategy is offset or auto".to_string());
+    }
+    if set.port_strategy != TcpTunnelSetPortStrategy::Offset || !set.include_ports_enabled {
+        return Ok(());
+    }
+
+    // With a fixed include list the offset mapping is fully known up front,
+    // so check it against tunnels that do not belong to this set.
+    let others: Vec<TcpTunnelConfig> = tunnels
+        .iter()
+        .filter(|t| {
+            !matches!(
+                &t.managed_by,
+                Some(TcpTunnelManagedBy::FullTunnel { set_id, .. }) if set_id == &set.id
+            )
+        })
+        .cloned()
+        .collect();
+    let claimed = full_tunnel::claimed_remote_ports(&others, &set.ssh_host);
+    for p in set.include_ports.iter() {
+        full_tunnel::resolve_remote_port(set, *p, &claimed)?;
+    }
+    Ok(())
+}
+
 async fn sing_box_running() -> bool {
     let mut lock = SING_PROCESS.lock().await;
     if let Some(ref mut proc) = *lock {
         match proc.child.try_wait() {
-            Ok(Some(_)) => {
+            Ok(Some(status)) => {
                 *lock = None;
+                record_process_exit(sing_box_process_key(), status);
                 false
             }
             Ok(None) => true,
@@ -7119,9 +12639,30 @@ async fn apply_tunnels_from_config(state: &Arc<AppState>) {
 
 async fn apply_full_tunnel_sets_from_config(state: &Arc<AppState>) {
     let sets = { state.config.lock().await.tcp_tunnel_sets.clone() };
+    let conflicts = full_tunnel::all_set_conflicts(&sets);
+    for s in sets.iter().filter(|s| s.enabled) {
+        if let Some(conflicts) = conflicts.get(&s.id) {
+            log_warning!(
+                "TCP tunnel set {} overlaps with {} on {}; conflicting ports will be skipped",
+                s.id,
+                conflicts.join(", "),
+                s.ssh_host
+            );
+        }
+    }
     state.full_tunnel.sync_from_config(state.clone(), sets).await;
 }
 
+fn tunnel_set_conflict_error(conflicts: &[String]) -> (StatusCode, Json<ApiResponse<()>>) {
+    (
+        StatusCode::CONFLICT,
+        Json(ApiResponse::error(format!(
+            "Set overlaps with {} (same ssh_host and remote ports)",
+            conflicts.join(", ")
+        ))),
+    )
+}
+
 fn build_tcp_tunnel_item(
     t: TcpTunnelConfig,
     status: tcp_tunnel::TunnelRuntimeStatus,
@@ -7130,6 +12671,7 @@ fn build_tcp_tunnel_item(
         id: t.id,
         name: t.name,
         enabled: t.enabled,
+        direction: t.direction,
         local_addr: t.local_addr,
         local_port: t.local_port,
         remote_bind_addr: t.remote_bind_addr,
@@ -7140,14 +12682,25 @@ fn build_tcp_tunnel_item(
         auth: redact_tunnel_auth(&t.auth),
         strict_host_key_checking: t.strict_host_key_checking,
         host_key_fingerprint: t.host_key_fingerprint,
+        known_hosts_path: t.known_hosts_path,
         allow_public_bind: t.allow_public_bind,
         connect_timeout_ms: t.connect_timeout_ms,
         keepalive_interval_ms: t.keepalive_interval_ms,
         reconnect_backoff_ms: t.reconnect_backoff_ms,
+        host_id: t.host_id,
         status,
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/tcp-tunnels",
+    tag = "tunnels",
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_tcp_tunnels(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<TcpTunnelListResponse>> {
@@ -7173,6 +12726,102 @@ async fn get_tcp_tunnels(
     ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/tcp-tunnels/{id}/effective",
+    tag = "tunnels",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn get_tcp_tunnel_effective(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<TcpTunnelEffectiveResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let config = state.config.lock().await;
+    let Some(tunnel) = config.tcp_tunnels.iter().find(|t| t.id == id) else {
+        return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Tunnel not found"))));
+    };
+
+    let tunnel_settings = TcpTunnelSshSettings {
+        ssh_host: tunnel.ssh_host.clone(),
+        ssh_port: tunnel.ssh_port,
+        username: tunnel.username.clone(),
+        auth: Some(redact_tunnel_auth(&tunnel.auth)),
+    };
+    let host = tunnel
+        .host_id
+        .as_ref()
+        .and_then(|host_id| config.hosts.iter().find(|h| h.id == *host_id));
+    let host_missing = tunnel.host_id.is_some() && host.is_none();
+
+    let mut drift = Vec::new();
+    let mut host_error = None;
+    let host_settings = host.map(|host| {
+        if host.host != tunnel.ssh_host {
+            drift.push("ssh_host".to_string());
+        }
+        if host.port != tunnel.ssh_port {
+            drift.push("ssh_port".to_string());
+        }
+        if host.username != tunnel.username {
+            drift.push("username".to_string());
+        }
+        let auth = match resolve_host_auth(host) {
+            Ok(auth) => {
+                if auth != tunnel.auth {
+                    drift.push("auth".to_string());
+                }
+                Some(redact_tunnel_auth(&auth))
+            }
+            Err(e) => {
+                drift.push("auth".to_string());
+                host_error = Some(e);
+                None
+            }
+        };
+        TcpTunnelSshSettings {
+            ssh_host: host.host.clone(),
+            ssh_port: host.port,
+            username: host.username.clone(),
+            auth,
+        }
+    });
+    if host_missing {
+        host_error = Some("Host not found".to_string());
+    }
+
+    let in_sync = drift.is_empty() && !host_missing;
+    Ok(Json(ApiResponse::success(
+        "Tunnel effective settings",
+        TcpTunnelEffectiveResponse {
+            id: tunnel.id.clone(),
+            host_id: tunnel.host_id.clone(),
+            tunnel: tunnel_settings,
+            host: host_settings,
+            host_missing,
+            host_error,
+            drift,
+            in_sync,
+        },
+    )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnels",
+    tag = "tunnels",
+    request_body = TcpTunnelUpsertRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn create_tcp_tunnel(
     State(state): State<Arc<AppState>>,
     Json(req): Json<TcpTunnelUpsertRequest>,
@@ -7202,6 +12851,9 @@ async fn create_tcp_tunnel(
         req.username = Some(host.username.clone());
         req.auth = Some(auth);
     }
+    if req.auth.is_none() {
+        req.auth = state.config.lock().await.default_ssh_auth.clone();
+    }
 
     let cfg = normalize_tcp_tunnel(req, id.clone())
         .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
@@ -7216,6 +12868,9 @@ async fn create_tcp_tunnel(
         }
         _ => {}
     }
+    check_tunnel_auth_key(&cfg.auth)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
     {
         let mut config = state.config.lock().await;
@@ -7225,6 +12880,8 @@ async fn create_tcp_tunnel(
                 Json(ApiResponse::error("Tunnel id already exists")),
             ));
         }
+        ensure_resource_capacity(&config, ResourceKind::Tunnels, ResourceKind::Tunnels.count(&config))
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
         config.tcp_tunnels.push(cfg.clone());
         if let Err(e) = save_config(&config).await {
             return Err((
@@ -7244,6 +12901,7 @@ async fn create_tcp_tunnel(
                 id: cfg.id,
                 name: cfg.name,
                 enabled: cfg.enabled,
+                direction: cfg.direction,
                 local_addr: cfg.local_addr,
                 local_port: cfg.local_port,
                 remote_bind_addr: cfg.remote_bind_addr,
@@ -7254,16 +12912,31 @@ async fn create_tcp_tunnel(
                 auth: redact_tunnel_auth(&cfg.auth),
                 strict_host_key_checking: cfg.strict_host_key_checking,
                 host_key_fingerprint: cfg.host_key_fingerprint,
+                known_hosts_path: cfg.known_hosts_path,
                 allow_public_bind: cfg.allow_public_bind,
                 connect_timeout_ms: cfg.connect_timeout_ms,
                 keepalive_interval_ms: cfg.keepalive_interval_ms,
                 reconnect_backoff_ms: cfg.reconnect_backoff_ms,
+                host_id: cfg.host_id,
                 status,
             },
         },
     )))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/tcp-tunnels/{id}",
+    tag = "tunnels",
+    params(("id" = String, Path, description = "Resource id")),
+    request_body = TcpTunnelUpsertRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn update_tcp_tunnel(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7305,9 +12978,16 @@ async fn update_tcp_tunnel(
         req.username = Some(host.username.clone());
         req.auth = Some(auth);
     }
+    // 未指定方向时沿用原隧道的方向
+    if req.direction.is_none() {
+        req.direction = Some(existing.direction);
+    }
 
     let mut cfg = normalize_tcp_tunnel(req, id.clone())
         .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+    if cfg.host_id.is_none() {
+        cfg.host_id = existing.host_id.clone();
+    }
 
     // Support "leave blank to keep unchanged" for private-key secrets on update.
     match (&existing.auth, &mut cfg.auth) {
@@ -7341,6 +13021,9 @@ async fn update_tcp_tunnel(
         }
         _ => {}
     }
+    check_tunnel_auth_key(&cfg.auth)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
     {
         let mut config = state.config.lock().await;
@@ -7365,6 +13048,7 @@ async fn update_tcp_tunnel(
                 id: cfg.id,
                 name: cfg.name,
                 enabled: cfg.enabled,
+                direction: cfg.direction,
                 local_addr: cfg.local_addr,
                 local_port: cfg.local_port,
                 remote_bind_addr: cfg.remote_bind_addr,
@@ -7375,16 +13059,30 @@ async fn update_tcp_tunnel(
                 auth: redact_tunnel_auth(&cfg.auth),
                 strict_host_key_checking: cfg.strict_host_key_checking,
                 host_key_fingerprint: cfg.host_key_fingerprint,
+                known_hosts_path: cfg.known_hosts_path,
                 allow_public_bind: cfg.allow_public_bind,
                 connect_timeout_ms: cfg.connect_timeout_ms,
                 keepalive_interval_ms: cfg.keepalive_interval_ms,
                 reconnect_backoff_ms: cfg.reconnect_backoff_ms,
+                host_id: cfg.host_id,
                 status,
             },
         },
     )))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/tcp-tunnels/{id}",
+    tag = "tunnels",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn delete_tcp_tunnel(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7408,6 +13106,18 @@ async fn delete_tcp_tunnel(
     Ok(Json(ApiResponse::success_no_data("Tunnel deleted")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnels/{id}/start",
+    tag = "tunnels",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn start_tcp_tunnel(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7429,6 +13139,18 @@ async fn start_tcp_tunnel(
     Ok(Json(ApiResponse::success_no_data("Tunnel started")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnels/{id}/stop",
+    tag = "tunnels",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn stop_tcp_tunnel(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7450,6 +13172,18 @@ async fn stop_tcp_tunnel(
     Ok(Json(ApiResponse::success_no_data("Tunnel stopped")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnels/{id}/restart",
+    tag = "tunnels",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn restart_tcp_tunnel(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7535,6 +13269,7 @@ async fn get_tcp_tunnel_overview(
             ssh_host: t.ssh_host,
             ssh_port: t.ssh_port,
             username: t.username,
+            direction: Some(t.direction),
             remote_bind_addr: t.remote_bind_addr,
             remote_port: Some(t.remote_port),
             local_addr: Some(t.local_addr),
@@ -7542,6 +13277,7 @@ async fn get_tcp_tunnel_overview(
             auth: Some(redact_tunnel_auth(&t.auth)),
             strict_host_key_checking: Some(t.strict_host_key_checking),
             host_key_fingerprint: Some(t.host_key_fingerprint),
+            known_hosts_path: t.known_hosts_path,
             allow_public_bind: Some(t.allow_public_bind),
             connect_timeout_ms: Some(t.connect_timeout_ms),
             keepalive_interval_ms: Some(t.keepalive_interval_ms),
@@ -7576,12 +13312,14 @@ async fn get_tcp_tunnel_overview(
             ssh_port: s.ssh_port,
             username: s.username,
             remote_bind_addr: s.remote_bind_addr,
+            direction: None,
             remote_port: None,
             local_addr: None,
             local_port: None,
             auth: None,
             strict_host_key_checking: None,
             host_key_fingerprint: None,
+            known_hosts_path: None,
             allow_public_bind: None,
             connect_timeout_ms: None,
             keepalive_interval_ms: None,
@@ -7589,26 +13327,101 @@ async fn get_tcp_tunnel_overview(
             status,
         });
     }
-
-    Json(ApiResponse::success(
-        "TCP tunnel overview",
-        TcpTunnelOverviewResponse { supported, items },
-    ))
+
+    Json(ApiResponse::success(
+        "TCP tunnel overview",
+        TcpTunnelOverviewResponse { supported, items },
+    ))
+}
+
+/// GET /api/tcp-tunnels/status/ws - 推送隧道运行状态变更（先发一次全量快照，之后逐条推送）
+async fn tcp_tunnel_status_ws(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<WsAuthQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    if verify_token(&q.token).is_err() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(ws.on_upgrade(move |socket| handle_tcp_tunnel_status_websocket(socket, state)))
+}
+
+async fn handle_tcp_tunnel_status_websocket(mut socket: WebSocket, state: Arc<AppState>) {
+    // 先订阅再取快照，避免两者之间的变更丢失
+    let mut rx = tcp_tunnel::subscribe_status_updates();
+
+    let ids: Vec<String> = {
+        let config = state.config.lock().await;
+        config.tcp_tunnels.iter().map(|t| t.id.clone()).collect()
+    };
+    let mut tunnels: Vec<tcp_tunnel::TunnelStatusUpdate> = Vec::with_capacity(ids.len());
+    let at_ms = Utc::now().timestamp_millis();
+    for id in ids {
+        let status = state.tcp_tunnel.get_status(&id).await.unwrap_or_default();
+        tunnels.push(tcp_tunnel::TunnelStatusUpdate { id, at_ms, status });
+    }
+    let snapshot = serde_json::json!({ "type": "snapshot", "tunnels": tunnels });
+    if socket.send(Message::Text(snapshot.to_string().into())).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            result = rx.recv() => {
+                match result {
+                    Ok(update) => {
+                        let msg = serde_json::json!({ "type": "update", "tunnel": update });
+                        if socket.send(Message::Text(msg.to_string().into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        // 客户端过慢丢了更新，提示其重新拉取 overview
+                        let msg = serde_json::json!({ "type": "lagged", "dropped": n });
+                        if socket.send(Message::Text(msg.to_string().into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            msg = socket.recv() => {
+                match msg {
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(Message::Ping(data))) => {
+                        let _ = socket.send(Message::Pong(data)).await;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/tcp-tunnel-sets",
+    tag = "tunnel-sets",
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_tcp_tunnel_sets(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<TcpTunnelSetListResponse>> {
     let supported = state.tcp_tunnel.supported();
     let sets = { state.config.lock().await.tcp_tunnel_sets.clone() };
 
+    let mut all_conflicts = full_tunnel::all_set_conflicts(&sets);
     let mut items: Vec<TcpTunnelSetListItem> = Vec::with_capacity(sets.len());
     for s in sets {
         let mut status = tcp_tunnel::TunnelRuntimeStatus::default();
         let st = state.full_tunnel.get_status(&s.id).await;
+        let conflicts = all_conflicts.remove(&s.id).unwrap_or_default();
         status.state = if !s.enabled {
             tcp_tunnel::TunnelState::Stopped
-        } else if st.last_error.is_some() {
+        } else if st.last_error.is_some() || !conflicts.is_empty() {
             tcp_tunnel::TunnelState::Error
         } else {
             tcp_tunnel::TunnelState::Forwarding
@@ -7619,6 +13432,12 @@ async fn get_tcp_tunnel_sets(
                 message: e,
                 at_ms: chrono::Utc::now().timestamp_millis(),
             });
+        } else if !conflicts.is_empty() {
+            status.last_error = Some(tcp_tunnel::TunnelErrorInfo {
+                code: "PORT_CONFLICT".to_string(),
+                message: format!("Overlaps with {}", conflicts.join(", ")),
+                at_ms: chrono::Utc::now().timestamp_millis(),
+            });
         }
         items.push(TcpTunnelSetListItem {
             id: s.id,
@@ -7634,6 +13453,7 @@ async fn get_tcp_tunnel_sets(
             include_ports: s.include_ports,
             exclude_ports: s.exclude_ports,
             connect_timeout_ms: s.connect_timeout_ms,
+            conflicts,
             status,
         });
     }
@@ -7644,17 +13464,34 @@ async fn get_tcp_tunnel_sets(
     ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/tcp-tunnel-sets/{id}",
+    tag = "tunnel-sets",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<TcpTunnelSetDetailResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
-    let set = {
+    let (set, conflicts) = {
         let config = state.config.lock().await;
-        config
+        let set = config
             .tcp_tunnel_sets
             .iter()
             .find(|s| s.id == id)
-            .cloned()
+            .cloned();
+        let conflicts = set
+            .as_ref()
+            .map(|s| full_tunnel::find_set_conflicts(s, &config.tcp_tunnel_sets))
+            .unwrap_or_default();
+        (set, conflicts)
     };
     let Some(set) = set else {
         return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Set not found"))));
@@ -7672,6 +13509,7 @@ async fn get_tcp_tunnel_set(
             auth: redact_tunnel_auth(&set.auth),
             strict_host_key_checking: set.strict_host_key_checking,
             host_key_fingerprint: set.host_key_fingerprint,
+            known_hosts_path: set.known_hosts_path,
             include_ports_enabled: set.include_ports_enabled,
             include_ports: set.include_ports,
             exclude_ports: set.exclude_ports,
@@ -7680,6 +13518,11 @@ async fn get_tcp_tunnel_set(
             connect_timeout_ms: set.connect_timeout_ms,
             start_batch_size: set.start_batch_size,
             start_batch_interval_ms: set.start_batch_interval_ms,
+            port_strategy: set.port_strategy,
+            port_base: set.port_base,
+            scan_method: set.scan_method,
+            skip_loopback: set.skip_loopback,
+            conflicts,
         },
     )))
 }
@@ -7724,16 +13567,33 @@ async fn get_tcp_tunnel_set_tunnels(
     )))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnel-sets/{id}/start",
+    tag = "tunnel-sets",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn start_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
     {
         let mut config = state.config.lock().await;
-        let Some(s) = config.tcp_tunnel_sets.iter_mut().find(|s| s.id == id) else {
+        let Some(pos) = config.tcp_tunnel_sets.iter().position(|s| s.id == id) else {
             return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Set not found"))));
         };
-        s.enabled = true;
+        let conflicts =
+            full_tunnel::find_set_conflicts(&config.tcp_tunnel_sets[pos], &config.tcp_tunnel_sets);
+        if !conflicts.is_empty() {
+            return Err(tunnel_set_conflict_error(&conflicts));
+        }
+        config.tcp_tunnel_sets[pos].enabled = true;
         for t in config.tcp_tunnels.iter_mut() {
             if let Some(TcpTunnelManagedBy::FullTunnel { set_id, .. }) = &t.managed_by {
                 if set_id == &id {
@@ -7753,6 +13613,18 @@ async fn start_tcp_tunnel_set(
     Ok(Json(ApiResponse::success_no_data("Set started")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnel-sets/{id}/stop",
+    tag = "tunnel-sets",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn stop_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7782,6 +13654,18 @@ async fn stop_tcp_tunnel_set(
     Ok(Json(ApiResponse::success_no_data("Set stopped")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnel-sets/{id}/restart",
+    tag = "tunnel-sets",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn restart_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7789,10 +13673,15 @@ async fn restart_tcp_tunnel_set(
     // For now, "restart" means set enabled=true (controller will handle actual runtime when implemented).
     {
         let mut config = state.config.lock().await;
-        let Some(s) = config.tcp_tunnel_sets.iter_mut().find(|s| s.id == id) else {
+        let Some(pos) = config.tcp_tunnel_sets.iter().position(|s| s.id == id) else {
             return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Set not found"))));
         };
-        s.enabled = true;
+        let conflicts =
+            full_tunnel::find_set_conflicts(&config.tcp_tunnel_sets[pos], &config.tcp_tunnel_sets);
+        if !conflicts.is_empty() {
+            return Err(tunnel_set_conflict_error(&conflicts));
+        }
+        config.tcp_tunnel_sets[pos].enabled = true;
         for t in config.tcp_tunnels.iter_mut() {
             if let Some(TcpTunnelManagedBy::FullTunnel { set_id, .. }) = &t.managed_by {
                 if set_id == &id {
@@ -7812,6 +13701,19 @@ async fn restart_tcp_tunnel_set(
     Ok(Json(ApiResponse::success_no_data("Set restarted")))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/tcp-tunnel-sets/{id}",
+    tag = "tunnel-sets",
+    params(("id" = String, Path, description = "Resource id")),
+    request_body = TcpTunnelSetCreateRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn update_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -7866,6 +13768,9 @@ async fn update_tcp_tunnel_set(
         }
         _ => {}
     }
+    check_tunnel_auth_key(&auth)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
     let ssh_host = req.ssh_host.ok_or_else(|| {
         (StatusCode::BAD_REQUEST, Json(ApiResponse::error("ssh_host is required")))
@@ -7888,14 +13793,16 @@ async fn update_tcp_tunnel_set(
     let host_key_fingerprint = req
         .host_key_fingerprint
         .unwrap_or_else(|| existing.host_key_fingerprint.clone());
-    if strict_host_key_checking && host_key_fingerprint.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ApiResponse::error(
-                "host_key_fingerprint is required when strict_host_key_checking is true",
-            )),
-        ));
-    }
+    let known_hosts_path = match req.known_hosts_path {
+        Some(path) => normalize_known_hosts_path(Some(path)),
+        None => existing.known_hosts_path.clone(),
+    };
+    validate_host_key_settings(
+        strict_host_key_checking,
+        &host_key_fingerprint,
+        known_hosts_path.as_deref(),
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
     let name = match req.name {
         Some(n) => {
@@ -7922,11 +13829,16 @@ async fn update_tcp_tunnel_set(
         auth: auth.clone(),
         strict_host_key_checking,
         host_key_fingerprint: host_key_fingerprint.clone(),
+        known_hosts_path,
         include_ports_enabled: req
             .include_ports_enabled
             .unwrap_or(existing.include_ports_enabled),
-        include_ports: req.include_ports.unwrap_or_else(|| existing.include_ports.clone()),
-        exclude_ports: req.exclude_ports.unwrap_or_else(|| existing.exclude_ports.clone()),
+        include_ports: normalize_port_list(
+            req.include_ports.unwrap_or_else(|| existing.include_ports.clone()),
+        ),
+        exclude_ports: normalize_port_list(
+            req.exclude_ports.unwrap_or_else(|| existing.exclude_ports.clone()),
+        ),
         scan_interval_ms: req.scan_interval_ms.unwrap_or(existing.scan_interval_ms),
         debounce_ms: req.debounce_ms.unwrap_or(existing.debounce_ms),
         connect_timeout_ms: req
@@ -7936,15 +13848,40 @@ async fn update_tcp_tunnel_set(
         start_batch_interval_ms: req
             .start_batch_interval_ms
             .unwrap_or(existing.start_batch_interval_ms),
+        port_strategy: req.port_strategy.unwrap_or(existing.port_strategy),
+        port_base: req.port_base.unwrap_or(existing.port_base),
+        scan_method: req.scan_method.unwrap_or(existing.scan_method),
+        skip_loopback: req.skip_loopback.unwrap_or(existing.skip_loopback),
     };
+    let remap = updated.port_strategy != existing.port_strategy
+        || updated.port_base != existing.port_base;
 
     {
         let mut config = state.config.lock().await;
         let Some(pos) = config.tcp_tunnel_sets.iter().position(|s| s.id == id) else {
             return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Set not found"))));
         };
+        if let Err(e) = validate_tunnel_set_port_mapping(&updated, &config.tcp_tunnels) {
+            return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))));
+        }
+        if updated.enabled {
+            let conflicts = full_tunnel::find_set_conflicts(&updated, &config.tcp_tunnel_sets);
+            if !conflicts.is_empty() {
+                return Err(tunnel_set_conflict_error(&conflicts));
+            }
+        }
         config.tcp_tunnel_sets[pos] = updated.clone();
 
+        // Mapping changed: drop managed tunnels so the next scan re-creates them.
+        if remap {
+            config.tcp_tunnels.retain(|t| {
+                !matches!(
+                    &t.managed_by,
+                    Some(TcpTunnelManagedBy::FullTunnel { set_id, .. }) if set_id == &id
+                )
+            });
+        }
+
         for t in config.tcp_tunnels.iter_mut() {
             if let Some(TcpTunnelManagedBy::FullTunnel { set_id, .. }) = &t.managed_by {
                 if set_id == &id {
@@ -7956,6 +13893,7 @@ async fn update_tcp_tunnel_set(
                     t.auth = auth.clone();
                     t.strict_host_key_checking = updated.strict_host_key_checking;
                     t.host_key_fingerprint = updated.host_key_fingerprint.clone();
+                    t.known_hosts_path = updated.known_hosts_path.clone();
                     t.allow_public_bind = updated.remote_bind_addr == "0.0.0.0";
                     t.connect_timeout_ms = updated.connect_timeout_ms;
                 }
@@ -7978,6 +13916,18 @@ async fn update_tcp_tunnel_set(
     )))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/tcp-tunnel-sets",
+    tag = "tunnel-sets",
+    request_body = TcpTunnelSetCreateRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn create_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Json(req): Json<TcpTunnelSetCreateRequest>,
@@ -7988,9 +13938,10 @@ async fn create_tcp_tunnel_set(
     let ssh_port = req.ssh_port.unwrap_or_else(default_ssh_port);
     let strict_host_key_checking = req.strict_host_key_checking.unwrap_or(true);
     let host_key_fingerprint = req.host_key_fingerprint.unwrap_or_default();
+    let known_hosts_path = normalize_known_hosts_path(req.known_hosts_path);
     let include_ports_enabled = req.include_ports_enabled.unwrap_or(false);
-    let include_ports = req.include_ports.unwrap_or_default();
-    let exclude_ports = req.exclude_ports.unwrap_or_default();
+    let include_ports = normalize_port_list(req.include_ports.unwrap_or_default());
+    let exclude_ports = normalize_port_list(req.exclude_ports.unwrap_or_default());
     let scan_interval_ms = req.scan_interval_ms.unwrap_or(3_000);
     let debounce_ms = req.debounce_ms.unwrap_or(8_000);
     let connect_timeout_ms = req
@@ -8002,15 +13953,15 @@ async fn create_tcp_tunnel_set(
     let start_batch_interval_ms = req
         .start_batch_interval_ms
         .unwrap_or_else(default_tunnel_set_start_batch_interval_ms);
+    let port_strategy = req.port_strategy.unwrap_or_default();
+    let port_base = req.port_base.unwrap_or(0);
 
-    if strict_host_key_checking && host_key_fingerprint.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ApiResponse::error(
-                "host_key_fingerprint is required when strict_host_key_checking is true",
-            )),
-        ));
-    }
+    validate_host_key_settings(
+        strict_host_key_checking,
+        &host_key_fingerprint,
+        known_hosts_path.as_deref(),
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
     let ssh_host = req.ssh_host.ok_or_else(|| {
         (StatusCode::BAD_REQUEST, Json(ApiResponse::error("ssh_host is required")))
@@ -8018,9 +13969,12 @@ async fn create_tcp_tunnel_set(
     let username = req.username.ok_or_else(|| {
         (StatusCode::BAD_REQUEST, Json(ApiResponse::error("username is required")))
     })?;
-    let auth = req.auth.ok_or_else(|| {
-        (StatusCode::BAD_REQUEST, Json(ApiResponse::error("auth is required")))
-    })?;
+    let auth = match req.auth {
+        Some(auth) => auth,
+        None => state.config.lock().await.default_ssh_auth.clone().ok_or_else(|| {
+            (StatusCode::BAD_REQUEST, Json(ApiResponse::error("auth is required")))
+        })?,
+    };
 
     match &auth {
         TcpTunnelAuth::PrivateKeyPath { path, .. } if path.is_empty() => {
@@ -8031,29 +13985,48 @@ async fn create_tcp_tunnel_set(
         }
         _ => {}
     }
+    check_tunnel_auth_key(&auth)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+    let set = TcpTunnelSetConfig {
+        id,
+        name: req.name,
+        enabled,
+        remote_bind_addr,
+        ssh_host,
+        ssh_port,
+        username,
+        auth,
+        strict_host_key_checking,
+        host_key_fingerprint,
+        known_hosts_path,
+        include_ports_enabled,
+        include_ports,
+        exclude_ports,
+        scan_interval_ms,
+        debounce_ms,
+        connect_timeout_ms,
+        start_batch_size,
+        start_batch_interval_ms,
+        port_strategy,
+        port_base,
+        scan_method: req.scan_method.unwrap_or_default(),
+        skip_loopback: req.skip_loopback.unwrap_or(false),
+    };
 
     {
         let mut config = state.config.lock().await;
-        config.tcp_tunnel_sets.push(TcpTunnelSetConfig {
-            id,
-            name: req.name,
-            enabled,
-            remote_bind_addr,
-            ssh_host,
-            ssh_port,
-            username,
-            auth,
-            strict_host_key_checking,
-            host_key_fingerprint,
-            include_ports_enabled,
-            include_ports,
-            exclude_ports,
-            scan_interval_ms,
-            debounce_ms,
-            connect_timeout_ms,
-            start_batch_size,
-            start_batch_interval_ms,
-        });
+        if let Err(e) = validate_tunnel_set_port_mapping(&set, &config.tcp_tunnels) {
+            return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))));
+        }
+        if set.enabled {
+            let conflicts = full_tunnel::find_set_conflicts(&set, &config.tcp_tunnel_sets);
+            if !conflicts.is_empty() {
+                return Err(tunnel_set_conflict_error(&conflicts));
+            }
+        }
+        config.tcp_tunnel_sets.push(set);
         if let Err(e) = save_config(&config).await {
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -8102,6 +14075,45 @@ async fn copy_tcp_tunnel_set(
     Ok(Json(ApiResponse::success_no_data("Set copied")))
 }
 
+/// POST /api/tcp-tunnel-sets/{id}/scan-now - 立即扫描本机监听端口并返回诊断信息
+async fn scan_tcp_tunnel_set_now(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<TcpTunnelSetScanResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let (set, mut managed_ports) = {
+        let config = state.config.lock().await;
+        let Some(set) = config.tcp_tunnel_sets.iter().find(|s| s.id == id).cloned() else {
+            return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Set not found"))));
+        };
+        let managed: Vec<u16> = config
+            .tcp_tunnels
+            .iter()
+            .filter_map(|t| match &t.managed_by {
+                Some(TcpTunnelManagedBy::FullTunnel { set_id, managed_port }) if set_id == &id => {
+                    Some(*managed_port)
+                }
+                _ => None,
+            })
+            .collect();
+        (set, managed)
+    };
+    managed_ports.sort_unstable();
+
+    let report = state.full_tunnel.scan_now(&set).await;
+    let message = if report.error.is_some() { "Scan failed" } else { "Scan completed" };
+    Ok(Json(ApiResponse::success(
+        message,
+        TcpTunnelSetScanResponse {
+            method: report.method.map(|m| m.to_string()),
+            raw_ports: report.raw_ports,
+            candidate_ports: report.candidate_ports,
+            managed_ports,
+            error: report.error,
+            triggered: report.triggered,
+        },
+    )))
+}
+
 async fn test_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8122,6 +14134,7 @@ async fn test_tcp_tunnel_set(
         id: "test".to_string(),
         name: None,
         enabled: true,
+        direction: TcpTunnelDirection::Reverse,
         local_addr: "127.0.0.1".to_string(),
         local_port: 0,
         remote_bind_addr: set.remote_bind_addr.clone(),
@@ -8132,11 +14145,13 @@ async fn test_tcp_tunnel_set(
         auth: set.auth.clone(),
         strict_host_key_checking: set.strict_host_key_checking,
         host_key_fingerprint: set.host_key_fingerprint.clone(),
+        known_hosts_path: set.known_hosts_path.clone(),
         allow_public_bind: set.remote_bind_addr == "0.0.0.0",
         connect_timeout_ms: set.connect_timeout_ms,
         keepalive_interval_ms: default_keepalive_interval_ms(),
         reconnect_backoff_ms: default_tcp_tunnel_backoff(),
         managed_by: None,
+        host_id: None,
     };
 
     let start = std::time::Instant::now();
@@ -8155,6 +14170,18 @@ async fn test_tcp_tunnel_set(
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/tcp-tunnel-sets/{id}",
+    tag = "tunnel-sets",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn delete_tcp_tunnel_set(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8282,6 +14309,8 @@ async fn copy_tcp_tunnel(
         let Some(existing) = config.tcp_tunnels.iter().find(|t| t.id == id).cloned() else {
             return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Tunnel not found"))));
         };
+        ensure_resource_capacity(&config, ResourceKind::Tunnels, ResourceKind::Tunnels.count(&config))
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
         let mut cloned = existing.clone();
         cloned.id = generate_tunnel_id();
@@ -8313,6 +14342,7 @@ async fn copy_tcp_tunnel(
             id: cfg.id,
             name: cfg.name,
             enabled: cfg.enabled,
+            direction: cfg.direction,
             local_addr: cfg.local_addr,
             local_port: cfg.local_port,
             remote_bind_addr: cfg.remote_bind_addr,
@@ -8323,15 +14353,26 @@ async fn copy_tcp_tunnel(
             auth: redact_tunnel_auth(&cfg.auth),
             strict_host_key_checking: cfg.strict_host_key_checking,
             host_key_fingerprint: cfg.host_key_fingerprint,
+            known_hosts_path: cfg.known_hosts_path,
             allow_public_bind: cfg.allow_public_bind,
             connect_timeout_ms: cfg.connect_timeout_ms,
             keepalive_interval_ms: cfg.keepalive_interval_ms,
             reconnect_backoff_ms: cfg.reconnect_backoff_ms,
+            host_id: cfg.host_id,
             status,
         },
     )))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/syncs",
+    tag = "syncs",
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn get_syncs(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<SyncListResponse>> {
@@ -8344,6 +14385,18 @@ async fn get_syncs(
     Json(ApiResponse::success("ok", SyncListResponse { items }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/syncs",
+    tag = "syncs",
+    request_body = SyncUpsertRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn create_sync(
     State(state): State<Arc<AppState>>,
     Json(req): Json<SyncUpsertRequest>,
@@ -8407,6 +14460,16 @@ async fn create_sync(
     let auth = req.auth.ok_or_else(|| {
         (StatusCode::BAD_REQUEST, Json(ApiResponse::error("SSH auth is required")))
     })?;
+    check_tunnel_auth_key(&auth)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+    let port = req.ssh_port.unwrap_or(default_ssh_port());
+    let via_tunnel_id = {
+        let config = state.config.lock().await;
+        validate_sync_via_tunnel(&config, req.via_tunnel_id, &host, port)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?
+    };
 
     let options = normalize_sync_options(req.options);
     let cfg = SyncConfig {
@@ -8417,16 +14480,22 @@ async fn create_sync(
         remote_path,
         ssh: SyncSshConfig {
             host,
-            port: req.ssh_port.unwrap_or(default_ssh_port()),
+            port,
             username,
             auth,
         },
         options,
         schedule,
+        via_tunnel_id,
+        host_id: req.host_id,
+        pre_command: normalize_sync_command(req.pre_command),
+        post_command: normalize_sync_command(req.post_command),
     };
 
     let syncs_snapshot = {
         let mut config = state.config.lock().await;
+        ensure_resource_capacity(&config, ResourceKind::Syncs, ResourceKind::Syncs.count(&config))
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
         config.syncs.push(cfg.clone());
         if let Err(e) = save_config(&config).await {
             return Err((
@@ -8447,6 +14516,19 @@ async fn create_sync(
     )))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/syncs/{id}",
+    tag = "syncs",
+    params(("id" = String, Path, description = "Resource id")),
+    request_body = SyncUpsertRequest,
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn update_sync(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8524,6 +14606,12 @@ async fn update_sync(
         let auth = req.auth.ok_or_else(|| {
             (StatusCode::BAD_REQUEST, Json(ApiResponse::error("auth is required")))
         })?;
+        check_tunnel_auth_key(&auth)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+        let port = req.ssh_port.unwrap_or(default_ssh_port());
+        let via_tunnel_id = validate_sync_via_tunnel(&config, req.via_tunnel_id, &host, port)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
         let cfg = SyncConfig {
             id: id.clone(),
@@ -8533,12 +14621,16 @@ async fn update_sync(
             remote_path,
             ssh: SyncSshConfig {
                 host,
-                port: req.ssh_port.unwrap_or(default_ssh_port()),
+                port,
                 username,
                 auth,
             },
             options: normalize_sync_options(req.options),
             schedule,
+            via_tunnel_id,
+            host_id: req.host_id.or_else(|| existing.host_id.clone()),
+            pre_command: normalize_sync_command(req.pre_command),
+            post_command: normalize_sync_command(req.post_command),
         };
         config.syncs[pos] = cfg.clone();
         if let Err(e) = save_config(&config).await {
@@ -8560,6 +14652,18 @@ async fn update_sync(
     )))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/syncs/{id}",
+    tag = "syncs",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn delete_sync(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8581,9 +14685,25 @@ async fn delete_sync(
     };
     state.sync_manager.apply_config(&syncs_snapshot).await;
     let _ = state.sync_manager.stop(&id).await;
+    let history_id = id.clone();
+    if let Err(e) = with_metrics_db(&state, move |conn| delete_sync_runs(conn, &history_id)).await {
+        log_warning!("Failed to delete history of sync {}: {}", id, e);
+    }
     Ok(Json(ApiResponse::success_no_data("Sync deleted")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/syncs/{id}/start",
+    tag = "syncs",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn start_sync(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8595,6 +14715,9 @@ async fn start_sync(
         };
         sync.clone()
     };
+    check_sync_reachable(&state, &cfg)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
     if let Err(e) = state.sync_manager.start(cfg.clone()).await {
         return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))));
     }
@@ -8615,6 +14738,18 @@ async fn start_sync(
     Ok(Json(ApiResponse::success_no_data("Sync started")))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/syncs/{id}/stop",
+    tag = "syncs",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn stop_sync(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8639,6 +14774,18 @@ async fn stop_sync(
 }
 
 // Run sync once (without modifying enabled state)
+#[utoipa::path(
+    post,
+    path = "/api/syncs/{id}/run",
+    tag = "syncs",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn run_sync(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8654,8 +14801,11 @@ async fn run_sync(
     // Check if already running
     let status = state.sync_manager.get_status(&id).await;
     if status.state == SyncState::Running {
-        return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error("Sync is already running"))));
+        return Err((StatusCode::CONFLICT, Json(ApiResponse::error("Sync is already running"))));
     }
+    check_sync_reachable(&state, &cfg)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
 
     if let Err(e) = state.sync_manager.start(cfg.clone()).await {
         return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))));
@@ -8664,19 +14814,79 @@ async fn run_sync(
     Ok(Json(ApiResponse::success_no_data("Sync started")))
 }
 
+// Preview what a run would upload/delete without touching the remote
+#[utoipa::path(
+    post,
+    path = "/api/syncs/{id}/dry-run",
+    tag = "syncs",
+    params(("id" = String, Path, description = "Resource id")),
+    responses(
+        (status = 200, description = "OK", body = ApiMessage),
+        (status = "4XX", description = "Request rejected", body = ApiMessage),
+        (status = "5XX", description = "Internal error", body = ApiMessage),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn dry_run_sync(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<SyncDryRunResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let cfg = {
+        let config = state.config.lock().await;
+        let Some(sync) = config.syncs.iter().find(|s| s.id == id) else {
+            return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Sync not found"))));
+        };
+        sync.clone()
+    };
+    check_sync_reachable(&state, &cfg)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+
+    let paths = state.sync_manager.dry_run(&cfg).await;
+    let count = |kind: SyncChangeType| {
+        paths
+            .iter()
+            .flat_map(|p| p.changes.iter())
+            .filter(|c| c.change_type == kind)
+            .count()
+    };
+    Ok(Json(ApiResponse::success(
+        "Dry run completed",
+        SyncDryRunResponse {
+            created: count(SyncChangeType::Created),
+            updated: count(SyncChangeType::Updated),
+            deleted: count(SyncChangeType::Deleted),
+            paths,
+        },
+    )))
+}
+
 // Toggle schedule enabled/disabled
 async fn toggle_schedule_sync(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<SyncScheduleToggleResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
-    let new_enabled = {
-        let mut config = state.config.lock().await;
-        let Some(sync) = config.syncs.iter_mut().find(|s| s.id == id) else {
+    let cfg = {
+        let config = state.config.lock().await;
+        let Some(sync) = config.syncs.iter().find(|s| s.id == id) else {
             return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Sync not found"))));
         };
         if sync.schedule.is_none() || sync.schedule.as_ref().unwrap().cron.trim().is_empty() {
             return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error("No schedule configured"))));
         }
+        sync.clone()
+    };
+    // Only enabling needs the host to be reachable
+    if !cfg.enabled {
+        check_sync_reachable(&state, &cfg)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(e))))?;
+    }
+    let new_enabled = {
+        let mut config = state.config.lock().await;
+        let Some(sync) = config.syncs.iter_mut().find(|s| s.id == id) else {
+            return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Sync not found"))));
+        };
         sync.enabled = !sync.enabled;
         sync.enabled
     };
@@ -8718,6 +14928,29 @@ async fn get_sync_logs(
     Ok(Json(ApiResponse::success("Logs retrieved", logs)))
 }
 
+/// GET /api/syncs/{id}/history - Recent runs with status, files transferred and bytes sent
+async fn get_sync_history(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<SyncHistoryQuery>,
+) -> Result<Json<ApiResponse<Vec<sync::SyncRunRecord>>>, (StatusCode, Json<ApiResponse<()>>)> {
+    {
+        let config = state.config.lock().await;
+        if !config.syncs.iter().any(|s| s.id == id) {
+            return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Sync not found"))));
+        }
+    }
+
+    let limit = q
+        .limit
+        .unwrap_or(SYNC_HISTORY_DEFAULT_LIMIT)
+        .clamp(1, SYNC_HISTORY_MAX_LIMIT);
+    let runs = with_metrics_db(&state, move |conn| load_sync_runs(conn, &id, limit))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e))))?;
+    Ok(Json(ApiResponse::success("Sync history retrieved", runs)))
+}
+
 async fn get_sing_box_logs(
     Query(q): Query<SingBoxLogsQuery>,
 ) -> Json<ApiResponse<Vec<LogEntry>>> {
@@ -8738,6 +14971,91 @@ async fn get_sing_box_logs(
     Json(ApiResponse::success("Logs retrieved", logs))
 }
 
+/// 日志条目中的 `time` 为 UTC+8 的 `%Y-%m-%d %H:%M:%S`，转换为 unix 秒
+fn parse_log_time(time: &str) -> Option<i64> {
+    use chrono::{FixedOffset, NaiveDateTime, TimeZone};
+    let utc8 = FixedOffset::east_opt(8 * 3600)?;
+    let naive = NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%d %H:%M:%S").ok()?;
+    utc8.from_local_datetime(&naive).single().map(|t| t.timestamp())
+}
+
+/// GET /api/logs - 分页查询内存日志缓冲，支持 level / since / contains / events 过滤
+async fn get_logs(
+    Query(q): Query<LogsQuery>,
+) -> Result<Json<ApiResponse<LogsPageResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let min_level = q.level.unwrap_or_else(|| "debug".to_string());
+    let since = match q.since.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => None,
+        Some(raw) => match raw.parse::<i64>().ok().or_else(|| parse_log_time(raw)) {
+            Some(ts) => Some(ts),
+            None => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::error(
+                        "Invalid since: expected unix seconds or 'YYYY-MM-DD HH:MM:SS' (UTC+8)",
+                    )),
+                ))
+            }
+        },
+    };
+    let contains = q
+        .contains
+        .map(|c| c.to_lowercase())
+        .filter(|c| !c.is_empty());
+    let events: Option<Vec<String>> = q.events.map(|raw| {
+        raw.split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect()
+    });
+    let limit = q.limit.unwrap_or(LOGS_DEFAULT_LIMIT).clamp(1, LOGS_MAX_LIMIT);
+
+    let history: Vec<String> = {
+        let buffer = LOG_BUFFER.lock().expect("log buffer lock poisoned");
+        buffer.iter().cloned().collect()
+    };
+    let mut matched: Vec<serde_json::Value> = history
+        .iter()
+        .filter_map(|msg| serde_json::from_str::<serde_json::Value>(msg).ok())
+        .filter(|entry| {
+            let level = entry.get("level").and_then(|v| v.as_str()).unwrap_or("info");
+            if !level_passes(level, &min_level) || !event_passes(entry, &events) {
+                return false;
+            }
+            if let Some(since) = since {
+                let ts = entry
+                    .get("time")
+                    .and_then(|v| v.as_str())
+                    .and_then(parse_log_time);
+                if !ts.is_some_and(|ts| ts >= since) {
+                    return false;
+                }
+            }
+            if let Some(needle) = contains.as_deref() {
+                let message = entry.get("message").and_then(|v| v.as_str()).unwrap_or("");
+                if !message.to_lowercase().contains(needle) {
+                    return false;
+                }
+            }
+            true
+        })
+        .collect();
+
+    let total = matched.len();
+    if matched.len() > limit {
+        matched = matched.split_off(matched.len() - limit);
+    }
+
+    Ok(Json(ApiResponse::success(
+        "Logs retrieved",
+        LogsPageResponse {
+            truncated: total > matched.len(),
+            total,
+            entries: matched,
+        },
+    )))
+}
+
 async fn get_app_logs(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -8809,38 +15127,152 @@ async fn get_terminal_logs(
         }
     }
 
-    Ok(Json(ApiResponse::success("Logs retrieved", logs)))
+    Ok(Json(ApiResponse::success("Logs retrieved", logs)))
+}
+
+// WebSocket handler for sync logs
+async fn sync_ws_logs(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    // Verify sync exists
+    {
+        let config = state.config.lock().await;
+        if !config.syncs.iter().any(|s| s.id == id) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    let rx = match state.sync_manager.subscribe_logs(&id).await {
+        Some(rx) => rx,
+        None => return Err(StatusCode::NOT_FOUND),
+    };
+
+    Ok(ws.on_upgrade(move |socket| handle_sync_logs_websocket(socket, rx)))
+}
+
+/// GET /api/syncs/{id}/progress - 推送同步传输进度（percent/bytes_transferred/speed/eta）
+async fn sync_ws_progress(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<WsAuthQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    if verify_token(&q.token).is_err() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    {
+        let config = state.config.lock().await;
+        if !config.syncs.iter().any(|s| s.id == id) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    let (last, rx) = state.sync_manager.subscribe_progress(&id).await;
+
+    Ok(ws.on_upgrade(move |socket| handle_sync_progress_websocket(socket, last, rx)))
+}
+
+async fn terminal_ws_logs(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<WsAuthQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    if verify_token(&q.token).is_err() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    {
+        let config = state.config.lock().await;
+        if !config.terminals.iter().any(|t| t.id == id) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    Ok(ws.on_upgrade(move |socket| handle_terminal_logs_websocket(socket, id)))
+}
+
+/// gotty 断开后等待其重新监听的最长时间
+const TERMINAL_PROXY_RECONNECT_MS: u64 = 15_000;
+const TERMINAL_PROXY_RETRY_MS: u64 = 300;
+
+fn terminal_restart_generation(id: &str) -> u64 {
+    TERMINAL_RESTART_GENERATIONS.lock().unwrap().get(id).copied().unwrap_or(0)
+}
+
+/// 重启前调用（先于停止进程），让已连接的代理知道接下来的断开需要重连
+fn bump_terminal_restart_generation(id: &str) {
+    *TERMINAL_RESTART_GENERATIONS
+        .lock()
+        .unwrap()
+        .entry(id.to_string())
+        .or_insert(0) += 1;
+}
+
+/// 终端代理票据的有效期；票据写在 iframe 地址里，因此只对一个终端有效且只能用一次
+const TERMINAL_TICKET_TTL_SECS: u64 = 60;
+
+fn issue_terminal_ticket(id: &str) -> String {
+    let ticket = uuid::Uuid::new_v4().simple().to_string();
+    let now = Instant::now();
+    let mut tickets = TERMINAL_TICKETS.lock().unwrap();
+    tickets.retain(|_, (_, expires_at)| *expires_at > now);
+    tickets.insert(
+        ticket.clone(),
+        (id.to_string(), now + Duration::from_secs(TERMINAL_TICKET_TTL_SECS)),
+    );
+    ticket
+}
+
+/// 校验并作废票据：过期、属于其它终端或已用过的票据都无效
+fn consume_terminal_ticket(ticket: &str, id: &str) -> bool {
+    match TERMINAL_TICKETS.lock().unwrap().remove(ticket) {
+        Some((terminal_id, expires_at)) => terminal_id == id && expires_at > Instant::now(),
+        None => false,
+    }
+}
+
+#[derive(Serialize)]
+struct TerminalTicketResponse {
+    ticket: String,
+    expires_in: u64,
 }
 
-// WebSocket handler for sync logs
-async fn sync_ws_logs(
+/// POST /api/terminals/{id}/ticket - 签发内嵌终端连接 WebSocket 代理用的一次性票据
+async fn create_terminal_ticket(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    ws: WebSocketUpgrade,
-) -> Result<Response, StatusCode> {
-    // Verify sync exists
+) -> Result<Json<ApiResponse<TerminalTicketResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
     {
         let config = state.config.lock().await;
-        if !config.syncs.iter().any(|s| s.id == id) {
-            return Err(StatusCode::NOT_FOUND);
+        if !config.terminals.iter().any(|t| t.id == id) {
+            return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Terminal not found"))));
         }
     }
+    Ok(Json(ApiResponse::success(
+        "Terminal ticket issued",
+        TerminalTicketResponse {
+            ticket: issue_terminal_ticket(&id),
+            expires_in: TERMINAL_TICKET_TTL_SECS,
+        },
+    )))
+}
 
-    let rx = match state.sync_manager.subscribe_logs(&id).await {
-        Some(rx) => rx,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
-
-    Ok(ws.on_upgrade(move |socket| handle_sync_logs_websocket(socket, rx)))
+#[derive(Deserialize)]
+struct TerminalProxyQuery {
+    ticket: String,
 }
 
-async fn terminal_ws_logs(
+/// GET /api/terminals/{id}/ws - 代理 gotty 的 WebSocket，后端重启时保持浏览器连接并自动重连；
+/// 用 POST /api/terminals/{id}/ticket 签发的票据认证，不接受登录 token
+async fn terminal_ws_proxy(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Query(q): Query<WsAuthQuery>,
+    Query(q): Query<TerminalProxyQuery>,
     ws: WebSocketUpgrade,
 ) -> Result<Response, StatusCode> {
-    if verify_token(&q.token).is_err() {
+    if !consume_terminal_ticket(&q.ticket, &id) {
         return Err(StatusCode::UNAUTHORIZED);
     }
     {
@@ -8850,7 +15282,164 @@ async fn terminal_ws_logs(
         }
     }
 
-    Ok(ws.on_upgrade(move |socket| handle_terminal_logs_websocket(socket, id)))
+    Ok(ws
+        .protocols(["webtty"])
+        .on_upgrade(move |socket| handle_terminal_proxy_websocket(socket, state, id)))
+}
+
+type TerminalUpstream = tokio_tungstenite::WebSocketStream<
+    tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+>;
+
+/// 每次连接都重新读取终端配置，重启后端口或凭据变化也能跟上
+async fn connect_terminal_upstream(state: &AppState, id: &str) -> Result<TerminalUpstream, String> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+    let cfg = {
+        let config = state.config.lock().await;
+        config
+            .terminals
+            .iter()
+            .find(|t| t.id == id)
+            .cloned()
+            .ok_or_else(|| "Terminal not found".to_string())?
+    };
+    let host = match cfg.addr.trim() {
+        "" | "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "::1",
+        other => other,
+    };
+    let url = format!("ws://{}/ws", join_host_port(host, cfg.port));
+    let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+    request
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", HeaderValue::from_static("webtty"));
+    if let (Some(user), Some(pass)) = (&cfg.auth_username, &cfg.auth_password) {
+        let credential = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pass));
+        let value = HeaderValue::from_str(&format!("Basic {}", credential)).map_err(|e| e.to_string())?;
+        request.headers_mut().insert("Authorization", value);
+    }
+    let (upstream, _) = connect_async(request).await.map_err(|e| e.to_string())?;
+    Ok(upstream)
+}
+
+async fn handle_terminal_proxy_websocket(socket: WebSocket, state: Arc<AppState>, id: String) {
+    use tokio_tungstenite::tungstenite::Message as UpstreamMessage;
+
+    let (mut client_tx, mut client_rx) = socket.split();
+    // gotty 要求连接后的第一条消息为初始化消息（AuthToken/Arguments），重连后需要重放；
+    // 以 '3' 开头的是窗口大小消息，重放最后一次以恢复终端尺寸。
+    // 其余输入在后端不可用期间直接丢弃：补发到新 shell 里会被当作命令执行
+    let mut init_msg: Option<String> = None;
+    let mut last_resize: Option<String> = None;
+    let mut attached_before = false;
+
+    loop {
+        let deadline = Instant::now() + Duration::from_millis(TERMINAL_PROXY_RECONNECT_MS);
+        let upstream = loop {
+            match connect_terminal_upstream(&state, &id).await {
+                Ok(upstream) => break upstream,
+                Err(e) => {
+                    if Instant::now() >= deadline {
+                        log_warning!("Terminal {} websocket upstream unavailable: {}", id, e);
+                        let _ = client_tx.send(Message::Close(None)).await;
+                        return;
+                    }
+                }
+            }
+            // 等待期间继续接收客户端消息，避免浏览器端超时
+            tokio::select! {
+                _ = sleep(Duration::from_millis(TERMINAL_PROXY_RETRY_MS)) => {}
+                msg = client_rx.next() => match msg {
+                    Some(Ok(Message::Text(t))) => {
+                        let t = t.to_string();
+                        if init_msg.is_none() {
+                            init_msg = Some(t);
+                        } else if t.starts_with('3') {
+                            last_resize = Some(t);
+                        }
+                    }
+                    Some(Ok(Message::Binary(_))) | Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => {}
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                },
+            }
+        };
+        // 连上之后记录当前重启代数：之后的断开只有在代数变化（发生了重启）时才重连
+        let generation = terminal_restart_generation(&id);
+
+        let (mut upstream_tx, mut upstream_rx) = upstream.split();
+        let mut replay = Vec::new();
+        if let Some(init) = &init_msg {
+            replay.push(UpstreamMessage::Text(init.clone()));
+        }
+        if attached_before {
+            if let Some(resize) = &last_resize {
+                replay.push(UpstreamMessage::Text(resize.clone()));
+            }
+        }
+        let mut replay_failed = false;
+        for msg in replay {
+            if upstream_tx.send(msg).await.is_err() {
+                replay_failed = true;
+                break;
+            }
+        }
+        if !replay_failed {
+            if attached_before {
+                log_info!("Terminal {} websocket reattached to backend", id);
+            }
+            attached_before = true;
+
+            // 转发直到任意一端断开；发送失败的输入直接丢弃
+            loop {
+                tokio::select! {
+                    msg = client_rx.next() => {
+                        let forward = match msg {
+                            Some(Ok(Message::Text(t))) => {
+                                let t = t.to_string();
+                                if init_msg.is_none() {
+                                    init_msg = Some(t.clone());
+                                } else if t.starts_with('3') {
+                                    last_resize = Some(t.clone());
+                                }
+                                UpstreamMessage::Text(t)
+                            }
+                            Some(Ok(Message::Binary(b))) => UpstreamMessage::Binary(b.to_vec()),
+                            Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+                            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                                let _ = upstream_tx.send(UpstreamMessage::Close(None)).await;
+                                return;
+                            }
+                        };
+                        if upstream_tx.send(forward).await.is_err() {
+                            break;
+                        }
+                    }
+                    msg = upstream_rx.next() => match msg {
+                        Some(Ok(UpstreamMessage::Text(t))) => {
+                            if client_tx.send(Message::Text(t.into())).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(UpstreamMessage::Binary(b))) => {
+                            if client_tx.send(Message::Binary(axum::body::Bytes::from(b))).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(UpstreamMessage::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    },
+                }
+            }
+        }
+
+        // 后端断开：不是重启引起的（例如用户执行了 exit）就结束会话，不再悄悄拉起新 shell
+        if terminal_restart_generation(&id) == generation {
+            let _ = client_tx.send(Message::Close(None)).await;
+            return;
+        }
+    }
 }
 
 async fn sing_box_ws_logs(
@@ -8922,6 +15511,46 @@ async fn handle_sync_logs_websocket(mut socket: WebSocket, mut rx: broadcast::Re
     }
 }
 
+async fn handle_sync_progress_websocket(
+    mut socket: WebSocket,
+    last: Option<sync::SyncProgress>,
+    mut rx: broadcast::Receiver<sync::SyncProgress>,
+) {
+    if let Some(progress) = last {
+        let json = serde_json::to_string(&progress).unwrap_or_default();
+        if socket.send(Message::Text(json.into())).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            result = rx.recv() => {
+                match result {
+                    Ok(progress) => {
+                        let json = serde_json::to_string(&progress).unwrap_or_default();
+                        if socket.send(Message::Text(json.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    // 进度帧只关心最新值，落后时直接跳过
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            msg = socket.recv() => {
+                match msg {
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(Message::Ping(data))) => {
+                        let _ = socket.send(Message::Pong(data)).await;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
 async fn handle_sing_box_logs_websocket(mut socket: WebSocket) {
     let mut rx = SING_LOG_BROADCAST.subscribe();
 
@@ -9118,6 +15747,11 @@ struct SyncLogsQuery {
     limit: Option<usize>,
 }
 
+#[derive(Deserialize)]
+struct SyncHistoryQuery {
+    limit: Option<usize>,
+}
+
 #[derive(Deserialize)]
 struct TerminalLogsQuery {
     limit: Option<usize>,
@@ -9128,6 +15762,31 @@ struct SingBoxLogsQuery {
     limit: Option<usize>,
 }
 
+const LOGS_DEFAULT_LIMIT: usize = 200;
+const LOGS_MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct LogsQuery {
+    /// 最低级别：debug / info / warning / error
+    level: Option<String>,
+    /// unix 秒，或 `YYYY-MM-DD HH:MM:SS`（UTC+8）
+    since: Option<String>,
+    limit: Option<usize>,
+    /// 消息子串（不区分大小写）
+    contains: Option<String>,
+    /// Comma separated event filter, same as the WS `events` param
+    events: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LogsPageResponse {
+    /// 按时间顺序，返回最新的 `limit` 条
+    entries: Vec<serde_json::Value>,
+    /// 过滤后匹配总数
+    total: usize,
+    truncated: bool,
+}
+
 #[derive(Deserialize)]
 struct AppLogsQuery {
     limit: Option<usize>,
@@ -9142,8 +15801,14 @@ struct SyncScheduleToggleResponse {
 // ============================================================================
 // Save config to config.yaml
 async fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let yaml = serde_yaml::to_string(config)?;
-    tokio::fs::write("config.yaml", yaml).await?;
+    let yaml = if config.encrypt_secrets.unwrap_or(false) {
+        let mut sealed = config.clone();
+        secrets::encrypt_config_secrets(&mut sealed)?;
+        serde_yaml::to_string(&sealed)?
+    } else {
+        serde_yaml::to_string(config)?
+    };
+    tokio::fs::write(CONFIG_FILE, yaml).await?;
     Ok(())
 }
 
@@ -9182,6 +15847,10 @@ fn normalize_sync_remote_path(remote_path: Option<String>) -> Option<String> {
     })
 }
 
+fn normalize_sync_command(command: Option<String>) -> Option<String> {
+    command.map(|c| c.trim().to_string()).filter(|c| !c.is_empty())
+}
+
 fn normalize_sync_options(mut options: SyncOptions) -> SyncOptions {
     options.exclude = options
         .exclude
@@ -9216,70 +15885,396 @@ async fn build_sync_local_paths(paths: &[String]) -> Result<Vec<SyncLocalPath>,
             kind,
         });
     }
-    if items.is_empty() {
-        return Err("Local paths are required".to_string());
+    if items.is_empty() {
+        return Err("Local paths are required".to_string());
+    }
+    Ok(items)
+}
+
+fn normalize_sync_schedule(schedule: Option<SyncSchedule>) -> Result<Option<SyncSchedule>, String> {
+    let Some(mut schedule) = schedule else {
+        return Ok(None);
+    };
+    if schedule.timezone.trim().is_empty() {
+        schedule.timezone = default_schedule_timezone();
+    }
+    if schedule.cron.trim().is_empty() {
+        return Err("Cron expression is required".to_string());
+    }
+    let expr = schedule.cron.trim();
+    let cron_expr = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    let _ = cron::Schedule::from_str(&cron_expr)
+        .map_err(|e| format!("Invalid cron expression: {}", e))?;
+    schedule.cron = schedule.cron.trim().to_string();
+    Ok(Some(schedule))
+}
+
+fn build_sync_item(cfg: &SyncConfig, status: SyncRuntimeStatus) -> SyncItem {
+    SyncItem {
+        id: cfg.id.clone(),
+        name: cfg.name.clone(),
+        enabled: cfg.enabled,
+        local_paths: cfg.local_paths.clone(),
+        remote_path: cfg.remote_path.clone(),
+        ssh: SyncSshInfo {
+            host: cfg.ssh.host.clone(),
+            port: cfg.ssh.port,
+            username: cfg.ssh.username.clone(),
+        },
+        auth: redact_sync_auth(&cfg.ssh.auth),
+        options: cfg.options.clone(),
+        schedule: cfg.schedule.clone(),
+        via_tunnel_id: cfg.via_tunnel_id.clone(),
+        host_id: cfg.host_id.clone(),
+        pre_command: cfg.pre_command.clone(),
+        post_command: cfg.post_command.clone(),
+        status,
+    }
+}
+
+/// `via_tunnel_id` 必须指向同一 SSH 主机（host + port）的隧道
+fn validate_sync_via_tunnel(
+    config: &Config,
+    via_tunnel_id: Option<String>,
+    host: &str,
+    port: u16,
+) -> Result<Option<String>, String> {
+    let Some(tunnel_id) = via_tunnel_id.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()) else {
+        return Ok(None);
+    };
+    let tunnel = config
+        .tcp_tunnels
+        .iter()
+        .find(|t| t.id == tunnel_id)
+        .ok_or_else(|| format!("Tunnel {} not found", tunnel_id))?;
+    if tunnel.ssh_host.trim() != host || tunnel.ssh_port != port {
+        return Err(format!(
+            "Tunnel {} connects to {}, not {}",
+            tunnel_id,
+            join_host_port(&tunnel.ssh_host, tunnel.ssh_port),
+            join_host_port(host, port)
+        ));
+    }
+    Ok(Some(tunnel_id))
+}
+
+/// 运行或启用调度前检查 SSH 主机可达：有 `via_tunnel_id` 时要求该隧道处于 forwarding，
+/// 否则直接 TCP 探测 SSH 端口
+async fn check_sync_reachable(state: &AppState, cfg: &SyncConfig) -> Result<(), String> {
+    if let Some(tunnel_id) = cfg.via_tunnel_id.as_ref() {
+        let status = state.tcp_tunnel.get_status(tunnel_id).await.unwrap_or_default();
+        if status.state != tcp_tunnel::TunnelState::Forwarding {
+            return Err(format!(
+                "Tunnel {} to {} is not forwarding, sync host unreachable",
+                tunnel_id,
+                join_host_port(&cfg.ssh.host, cfg.ssh.port)
+            ));
+        }
+        return Ok(());
+    }
+    let addr = join_host_port(&cfg.ssh.host, cfg.ssh.port);
+    match tokio::time::timeout(
+        Duration::from_secs(SYNC_REACHABILITY_TIMEOUT_SECS),
+        tokio::net::TcpStream::connect(&addr),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("SSH host {} unreachable: {}", addr, e)),
+        Err(_) => Err(format!("SSH host {} unreachable: timed out", addr)),
+    }
+}
+
+fn resolve_host_auth(host: &HostConfig) -> Result<TcpTunnelAuth, String> {
+    match &host.auth {
+        HostAuth::Password { password } => {
+            let Some(pwd) = password.clone().filter(|p| !p.trim().is_empty()) else {
+                return Err("Host password is required".to_string());
+            };
+            Ok(TcpTunnelAuth::Password { password: pwd })
+        }
+        HostAuth::PrivateKeyPath { path, passphrase } => {
+            let resolved_path = resolve_private_key_path(path)
+                .map_err(|_| "Host private key path is required".to_string())?;
+            Ok(TcpTunnelAuth::PrivateKeyPath {
+                path: resolved_path,
+                passphrase: passphrase.clone(),
+            })
+        }
+    }
+}
+
+/// Save inline SSH credentials as a managed host, reusing an existing host with
+/// the same host+port+username and the same credentials. A host that matches the
+/// endpoint but authenticates differently is left alone and a new host is created,
+/// so the caller is never bound to credentials other than its own.
+/// Returns (host_id, created).
+fn promote_ssh_to_host(
+    config: &mut Config,
+    name: Option<String>,
+    host: &str,
+    port: u16,
+    username: &str,
+    auth: &TcpTunnelAuth,
+) -> (String, bool) {
+    let host_auth = host_auth_from_tunnel_auth(auth);
+    if let Some(existing) = config.hosts.iter().find(|h| {
+        h.host == host && h.port == port && h.username == username && h.auth == host_auth
+    }) {
+        return (existing.id.clone(), false);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let id = uuid::Uuid::new_v4().to_string();
+    config.hosts.push(HostConfig {
+        id: id.clone(),
+        name,
+        host: host.to_string(),
+        port,
+        username: username.to_string(),
+        auth: host_auth,
+        group_id: None,
+        tags: vec![],
+        description: None,
+        enabled: true,
+        connection_timeout_ms: default_connection_timeout_ms(),
+        keepalive_interval_ms: default_keepalive_interval_ms(),
+        created_at: Some(now),
+        updated_at: Some(now),
+        last_connected_at: None,
+        last_test_result: None,
+        jump_host_id: None,
+    });
+    (id, true)
+}
+
+#[derive(Serialize)]
+struct SaveAsHostResponse {
+    host_id: String,
+    created: bool,
+}
+
+/// POST /api/tcp-tunnels/{id}/save-host - Save the tunnel's SSH credentials as a host
+async fn save_tcp_tunnel_as_host(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<SaveAsHostResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let mut config = state.config.lock().await;
+    let Some(tunnel) = config.tcp_tunnels.iter().find(|t| t.id == id).cloned() else {
+        return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Tunnel not found"))));
+    };
+    let (host_id, created) = promote_ssh_to_host(
+        &mut config,
+        tunnel.name.clone(),
+        &tunnel.ssh_host,
+        tunnel.ssh_port,
+        &tunnel.username,
+        &tunnel.auth,
+    );
+    if created {
+        save_config(&config).await.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!("Failed to save config: {}", e))),
+            )
+        })?;
+    }
+    Ok(Json(ApiResponse::success(
+        if created { "Host created" } else { "Host already exists" },
+        SaveAsHostResponse { host_id, created },
+    )))
+}
+
+#[derive(Serialize)]
+struct HostResyncSummary {
+    tunnels_updated: usize,
+    syncs_updated: usize,
+    syncs_restarted: usize,
+}
+
+/// 将主机当前的 host/port/username/auth 重新写入引用它的隧道与同步任务，
+/// 然后重新应用隧道配置并重启受影响且正在运行的同步任务
+async fn resync_host_items(
+    state: &Arc<AppState>,
+    host_id: &str,
+) -> Result<HostResyncSummary, String> {
+    let (tunnels_updated, changed_syncs, syncs_snapshot) = {
+        let mut config = state.config.lock().await;
+        let host = config
+            .hosts
+            .iter()
+            .find(|h| h.id == host_id)
+            .ok_or("Host not found")?;
+        let auth = resolve_host_auth(host)?;
+        let (addr, port, username) = (host.host.clone(), host.port, host.username.clone());
+
+        let mut tunnels_updated = 0;
+        for t in config
+            .tcp_tunnels
+            .iter_mut()
+            .filter(|t| t.host_id.as_deref() == Some(host_id))
+        {
+            if t.ssh_host == addr && t.ssh_port == port && t.username == username && t.auth == auth {
+                continue;
+            }
+            t.ssh_host = addr.clone();
+            t.ssh_port = port;
+            t.username = username.clone();
+            t.auth = auth.clone();
+            tunnels_updated += 1;
+        }
+
+        let mut changed_syncs = Vec::new();
+        for s in config
+            .syncs
+            .iter_mut()
+            .filter(|s| s.host_id.as_deref() == Some(host_id))
+        {
+            if s.ssh.host == addr && s.ssh.port == port && s.ssh.username == username && s.ssh.auth == auth {
+                continue;
+            }
+            s.ssh.host = addr.clone();
+            s.ssh.port = port;
+            s.ssh.username = username.clone();
+            s.ssh.auth = auth.clone();
+            changed_syncs.push(s.clone());
+        }
+
+        if tunnels_updated > 0 || !changed_syncs.is_empty() {
+            save_config(&config)
+                .await
+                .map_err(|e| format!("Failed to save config: {}", e))?;
+        }
+        (tunnels_updated, changed_syncs, config.syncs.clone())
+    };
+
+    if tunnels_updated > 0 {
+        apply_tunnels_from_config(state).await;
+    }
+    let mut syncs_restarted = 0;
+    if !changed_syncs.is_empty() {
+        state.sync_manager.apply_config(&syncs_snapshot).await;
+        for cfg in &changed_syncs {
+            if state.sync_manager.get_status(&cfg.id).await.state != SyncState::Running {
+                continue;
+            }
+            let _ = state.sync_manager.stop(&cfg.id).await;
+            if let Err(e) = state.sync_manager.start(cfg.clone()).await {
+                log_warning!("Sync {} restart after host resync failed: {}", cfg.id, e);
+                continue;
+            }
+            syncs_restarted += 1;
+        }
     }
-    Ok(items)
+
+    log_info!(
+        "Host {} resynced: {} tunnel(s), {} sync(s) updated",
+        host_id,
+        tunnels_updated,
+        changed_syncs.len()
+    );
+    Ok(HostResyncSummary {
+        tunnels_updated,
+        syncs_updated: changed_syncs.len(),
+        syncs_restarted,
+    })
 }
 
-fn normalize_sync_schedule(schedule: Option<SyncSchedule>) -> Result<Option<SyncSchedule>, String> {
-    let Some(mut schedule) = schedule else {
-        return Ok(None);
+/// POST /api/syncs/{id}/save-host - Save the sync's SSH credentials as a host
+async fn save_sync_as_host(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<SaveAsHostResponse>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let mut config = state.config.lock().await;
+    let Some(sync) = config.syncs.iter().find(|s| s.id == id).cloned() else {
+        return Err((StatusCode::NOT_FOUND, Json(ApiResponse::error("Sync not found"))));
     };
-    if schedule.timezone.trim().is_empty() {
-        schedule.timezone = default_schedule_timezone();
-    }
-    if schedule.cron.trim().is_empty() {
-        return Err("Cron expression is required".to_string());
+    let (host_id, created) = promote_ssh_to_host(
+        &mut config,
+        sync.name.clone(),
+        &sync.ssh.host,
+        sync.ssh.port,
+        &sync.ssh.username,
+        &sync.ssh.auth,
+    );
+    if created {
+        save_config(&config).await.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!("Failed to save config: {}", e))),
+            )
+        })?;
     }
-    let expr = schedule.cron.trim();
-    let cron_expr = if expr.split_whitespace().count() == 5 {
-        format!("0 {}", expr)
-    } else {
-        expr.to_string()
-    };
-    let _ = cron::Schedule::from_str(&cron_expr)
-        .map_err(|e| format!("Invalid cron expression: {}", e))?;
-    schedule.cron = schedule.cron.trim().to_string();
-    Ok(Some(schedule))
+    Ok(Json(ApiResponse::success(
+        if created { "Host created" } else { "Host already exists" },
+        SaveAsHostResponse { host_id, created },
+    )))
 }
 
-fn build_sync_item(cfg: &SyncConfig, status: SyncRuntimeStatus) -> SyncItem {
-    SyncItem {
-        id: cfg.id.clone(),
-        name: cfg.name.clone(),
-        enabled: cfg.enabled,
-        local_paths: cfg.local_paths.clone(),
-        remote_path: cfg.remote_path.clone(),
-        ssh: SyncSshInfo {
-            host: cfg.ssh.host.clone(),
-            port: cfg.ssh.port,
-            username: cfg.ssh.username.clone(),
-        },
-        auth: redact_sync_auth(&cfg.ssh.auth),
-        options: cfg.options.clone(),
-        schedule: cfg.schedule.clone(),
-        status,
+/// proxy 组只能选择已知节点或内置出站（direct / block），其他组交给 Clash API 校验
+async fn validate_selector_choice(state: &AppState, group: &str, desired: &str) -> Result<(), String> {
+    if group == "proxy" {
+        let is_builtin = SELECTOR_BUILTIN_OUTBOUNDS
+            .iter()
+            .any(|tag| tag.eq_ignore_ascii_case(desired));
+        let is_node = state
+            .node_type_by_tag
+            .lock()
+            .await
+            .keys()
+            .any(|tag| tag.eq_ignore_ascii_case(desired));
+        if !is_builtin && !is_node {
+            return Err(format!(
+                "{} is not a node or one of the built-in outbounds ({})",
+                desired,
+                SELECTOR_BUILTIN_OUTBOUNDS.join(", ")
+            ));
+        }
     }
+    Ok(())
 }
 
-fn resolve_host_auth(host: &HostConfig) -> Result<TcpTunnelAuth, String> {
-    match &host.auth {
-        HostAuth::Password { password } => {
-            let Some(pwd) = password.clone().filter(|p| !p.trim().is_empty()) else {
-                return Err("Host password is required".to_string());
-            };
-            Ok(TcpTunnelAuth::Password { password: pwd })
-        }
-        HostAuth::PrivateKeyPath { path, passphrase } => {
-            let resolved_path = resolve_private_key_path(path)
-                .map_err(|_| "Host private key path is required".to_string())?;
-            Ok(TcpTunnelAuth::PrivateKeyPath {
-                path: resolved_path,
-                passphrase: passphrase.clone(),
-            })
+/// 向 `notify_webhook_url` 异步 POST 事件：`{event, old, new, reason, timestamp}`。
+/// 发送失败只记日志，不影响调用方。
+fn notify_webhook(
+    config: &Config,
+    event: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+    reason: impl Into<String>,
+) {
+    let Some(url) = config
+        .notify_webhook_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .map(str::to_string)
+    else {
+        return;
+    };
+    let payload = serde_json::json!({
+        "event": event,
+        "old": old,
+        "new": new,
+        "reason": reason.into(),
+        "timestamp": Utc::now().timestamp_millis(),
+    });
+    tokio::spawn(async move {
+        let result = HTTP_CLIENT
+            .post(&url)
+            .timeout(Duration::from_secs(NOTIFY_WEBHOOK_TIMEOUT_SECS))
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        if let Err(e) = result {
+            log_warning!("Webhook notification ({}) failed: {}", event, e);
         }
-    }
+    });
 }
 
 async fn switch_selector_and_save(
@@ -9287,30 +16282,108 @@ async fn switch_selector_and_save(
     group: &str,
     desired: &str,
 ) -> Result<(), String> {
-    let client = reqwest::Client::new();
-
-    clash_switch_selector_resilient(&client, group, desired).await?;
+    clash_switch_selector_resilient(&HTTP_CLIENT, group, desired).await?;
 
     {
         let mut config = state.config.lock().await;
-        config
+        let old = config
             .selections
             .insert(group.to_string(), desired.to_string());
+        // 稳定 key 由随后的 regenerate 按新节点重新计算
+        let old_key = config.selection_keys.remove(group);
         if let Err(e) = save_config(&config).await {
+            // 保存失败时回滚内存中的选择，不发送通知
+            match &old {
+                Some(name) => config.selections.insert(group.to_string(), name.clone()),
+                None => config.selections.remove(group),
+            };
+            if let Some(key) = old_key {
+                config.selection_keys.insert(group.to_string(), key);
+            }
             return Err(format!("Failed to save config: {}", e));
         }
+        if old.as_deref() != Some(desired) {
+            notify_webhook(
+                &config,
+                "proxy_switched",
+                old,
+                Some(desired.to_string()),
+                format!("selector {} switched via API", group),
+            );
+        }
     }
 
-    let state_clone = state.clone();
-    tokio::spawn(async move {
-        if let Err(e) = regenerate_config(state_clone).await {
-            log_error!("Background regenerate failed: {}", e);
-        }
-    });
+    schedule_regenerate(state.clone());
 
     Ok(())
 }
 
+/// 节点稳定 key：type + server + server_port 的哈希，节点改名后不变
+fn node_stable_key(outbound: &serde_json::Value) -> Option<String> {
+    use sha2::{Digest, Sha256};
+    let typ = outbound.get("type").and_then(|v| v.as_str())?;
+    let server = outbound.get("server").and_then(|v| v.as_str())?;
+    let port = outbound.get("server_port").and_then(|v| v.as_u64())?;
+    let digest = Sha256::digest(format!("{}|{}|{}", typ, server.to_ascii_lowercase(), port));
+    let hex: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    Some(format!("n-{}", hex))
+}
+
+/// stable key -> 当前 tag（手动节点优先，其次订阅节点）
+fn build_node_tag_by_key(config: &Config, subs: &LoadedSubscriptions) -> HashMap<String, String> {
+    let manual = config
+        .nodes
+        .iter()
+        .filter_map(|node_str| serde_json::from_str::<serde_json::Value>(node_str).ok());
+    let mut tag_by_key = HashMap::new();
+    for outbound in manual.chain(subs.outbounds.iter().cloned()) {
+        let Some(tag) = outbound.get("tag").and_then(|t| t.as_str()) else {
+            continue;
+        };
+        if let Some(key) = node_stable_key(&outbound) {
+            tag_by_key.entry(key).or_insert_with(|| tag.to_string());
+        }
+    }
+    tag_by_key
+}
+
+/// 按稳定 key 修正 selections：节点改名后选择跟随到新名字；尚无 key 的选择
+/// （旧配置或刚切换）按当前名字补上 key。找不到 key 时保留原名字，由
+/// clash_switch_selector_resilient 按名字匹配兜底。返回是否有改动。
+fn sync_selection_keys(config: &mut Config, subs: &LoadedSubscriptions) -> bool {
+    let tag_by_key = build_node_tag_by_key(config, subs);
+    let key_by_tag: HashMap<&str, &str> = tag_by_key
+        .iter()
+        .map(|(key, tag)| (tag.as_str(), key.as_str()))
+        .collect();
+    let mut changed = false;
+    let groups: Vec<String> = config.selections.keys().cloned().collect();
+    for group in groups {
+        let name = config.selections[&group].clone();
+        match config.selection_keys.get(&group) {
+            Some(key) => {
+                if let Some(tag) = tag_by_key.get(key) {
+                    if *tag != name {
+                        log_info!("Selection {} follows renamed node: {} -> {}", group, name, tag);
+                        config.selections.insert(group.clone(), tag.clone());
+                        changed = true;
+                    }
+                }
+            }
+            None => {
+                if let Some(key) = key_by_tag.get(name.as_str()) {
+                    config.selection_keys.insert(group.clone(), key.to_string());
+                    changed = true;
+                }
+            }
+        }
+    }
+    let before = config.selection_keys.len();
+    let selections = &config.selections;
+    config.selection_keys.retain(|group, _| selections.contains_key(group));
+    changed || config.selection_keys.len() != before
+}
+
 fn build_node_type_map(config: &Config, subs: &LoadedSubscriptions) -> HashMap<String, String> {
     let mut node_type_by_tag = HashMap::new();
 
@@ -9351,8 +16424,10 @@ fn default_dns_candidates() -> Vec<String> {
     ]
 }
 
+const DNS_SUPPORTED_TAGS: [&str; 3] = ["dns-direct", "doh-cf", "doh-google"];
+
 fn is_supported_dns_tag(tag: &str) -> bool {
-    matches!(tag, "dns-direct" | "doh-cf" | "doh-google")
+    DNS_SUPPORTED_TAGS.contains(&tag)
 }
 
 fn sanitize_dns_active(configured: &str) -> String {
@@ -9445,6 +16520,14 @@ async fn apply_saved_selections(config: &Config) -> Result<(), String> {
 
         if let Some(e) = last_err {
             log_error!("Failed to restore selection for {}: {}", group, e);
+            // sing-box 保持默认选择，实际使用的节点已不是保存的那个
+            notify_webhook(
+                config,
+                "proxy_restore_failed",
+                Some(name.clone()),
+                None,
+                format!("selector {}: {}", group, e),
+            );
         }
     }
 
@@ -9455,9 +16538,26 @@ async fn apply_saved_selections(config: &Config) -> Result<(), String> {
 async fn regenerate_config(state: Arc<AppState>) -> Result<Config, String> {
     let config_clone = { state.config.lock().await.clone() };
     let loaded = load_subscriptions_and_update_state(&state, &config_clone).await;
-    {
+    let config_clone = {
+        let mut config = state.config.lock().await;
+        if sync_selection_keys(&mut config, &loaded) {
+            if let Err(e) = save_config(&config).await {
+                log_warning!("Failed to save selection keys: {}", e);
+            }
+        }
+        config.clone()
+    };
+    let pruned = {
         let mut node_type_by_tag = state.node_type_by_tag.lock().await;
         *node_type_by_tag = build_node_type_map(&config_clone, &loaded);
+        // 已删除节点的测速记录不再计入健康统计
+        let mut delays = state.node_delays.lock().await;
+        let before = delays.len();
+        delays.retain(|tag, _| node_type_by_tag.contains_key(tag));
+        delays.len() != before
+    };
+    if pruned {
+        save_node_delays(&state).await;
     }
 
     gen_config(&config_clone, &state.sing_box_home, &loaded)
@@ -9467,6 +16567,30 @@ async fn regenerate_config(state: Arc<AppState>) -> Result<Config, String> {
     Ok(config_clone)
 }
 
+/// Debounced background regenerate: a burst of edits within
+/// `regenerate_debounce_ms` results in a single regenerate after the last one.
+fn schedule_regenerate(state: Arc<AppState>) {
+    let generation = state.regenerate_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    tokio::spawn(async move {
+        let debounce_ms = {
+            let config = state.config.lock().await;
+            config
+                .regenerate_debounce_ms
+                .unwrap_or(DEFAULT_REGENERATE_DEBOUNCE_MS)
+        };
+        if debounce_ms > 0 {
+            sleep(Duration::from_millis(debounce_ms)).await;
+        }
+        // 窗口内又有新的修改，交给最新的那次处理
+        if state.regenerate_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if let Err(e) = regenerate_config(state).await {
+            log_error!("Background regenerate failed: {}", e);
+        }
+    });
+}
+
 /// Regenerate sing-box config and restart the service
 async fn regenerate_and_restart(state: Arc<AppState>) -> Result<(), String> {
     let config_clone = regenerate_config(state.clone()).await?;
@@ -9483,9 +16607,11 @@ async fn regenerate_and_restart(state: Arc<AppState>) -> Result<(), String> {
     Ok(())
 }
 
+/// `fetch` 为 false 时只读取 `sub/` 下已有的缓存，不联网也不写盘（用于预览）
 async fn load_subscriptions(
     config: &Config,
     root: &StdPath,
+    fetch: bool,
 ) -> (LoadedSubscriptions, HashMap<String, SubscriptionRuntime>) {
     let mut status_map: HashMap<String, SubscriptionRuntime> = HashMap::new();
     let mut merged_by_tag: HashMap<String, serde_json::Value> = HashMap::new();
@@ -9493,11 +16619,59 @@ async fn load_subscriptions(
     let mut files: Vec<SubFileStatus> = vec![];
     let mut dir_error: Option<String> = None;
     let now_ts = chrono::Utc::now().timestamp();
+    let fetch_timeout = Duration::from_millis(
+        config
+            .subscription_fetch_timeout_ms
+            .unwrap_or(DEFAULT_SUBSCRIPTION_FETCH_TIMEOUT_MS)
+            .max(1),
+    );
+
+    // Fetch and parse subscriptions concurrently (bounded); `buffered` keeps
+    // results in config order so the merge below stays deterministic.
+    let results: Vec<(
+        &SubscriptionConfig,
+        u32,
+        Result<(LoadedSubscriptions, Option<String>), String>,
+    )> =
+        futures_util::stream::iter(config.subscriptions.iter().filter(|s| s.enabled))
+            .map(|sub| async move {
+                let git_checkout = matches!(sub.source, SubscriptionSource::Git { .. });
+                if !fetch {
+                    let dir = root.join(&sub.id);
+                    let loaded = if subscription_has_cached_copy(sub, &dir).await {
+                        Ok((load_subscription_dir(&dir, Some(&sub.id), git_checkout).await, None))
+                    } else {
+                        Err("Subscription has not been fetched yet".to_string())
+                    };
+                    return (sub, 0, loaded);
+                }
+                let (attempts, prepared) = prepare_subscription_dir(sub, root, fetch_timeout).await;
+                let loaded = match prepared {
+                    Ok(dir) => Ok((load_subscription_dir(&dir, Some(&sub.id), git_checkout).await, None)),
+                    Err(err) => {
+                        // Keep serving the last good copy instead of dropping the nodes.
+                        let dir = root.join(&sub.id);
+                        if subscription_has_cached_copy(sub, &dir).await {
+                            log_warning!(
+                                "Subscription {} fetch failed, using cached copy: {}",
+                                sub.id,
+                                err
+                            );
+                            Ok((load_subscription_dir(&dir, Some(&sub.id), git_checkout).await, Some(err)))
+                        } else {
+                            Err(err)
+                        }
+                    }
+                };
+                (sub, attempts, loaded)
+            })
+            .buffered(SUBSCRIPTION_LOAD_CONCURRENCY)
+            .collect()
+            .await;
 
-    for sub in config.subscriptions.iter().filter(|s| s.enabled) {
-        match prepare_subscription_dir(sub, root).await {
-            Ok(dir) => {
-                let loaded = load_subscription_dir(&dir, Some(&sub.id)).await;
+    for (sub, attempts, result) in results {
+        match result {
+            Ok((loaded, stale_error)) => {
                 if dir_error.is_none() {
                     dir_error = loaded.dir_error.clone();
                 }
@@ -9519,8 +16693,10 @@ async fn load_subscriptions(
                     sub.id.clone(),
                     SubscriptionRuntime {
                         files: loaded.files,
-                        error: loaded.dir_error.clone(),
+                        stale: stale_error.is_some(),
+                        error: stale_error.or(loaded.dir_error.clone()),
                         updated_at: Some(now_ts),
+                        attempts,
                     },
                 );
             }
@@ -9532,8 +16708,10 @@ async fn load_subscriptions(
                     sub.id.clone(),
                     SubscriptionRuntime {
                         files: vec![],
+                        stale: false,
                         error: Some(err),
                         updated_at: None,
+                        attempts,
                     },
                 );
             }
@@ -9570,7 +16748,7 @@ async fn load_subscriptions_and_update_state(
             dir_error: None,
         };
     }
-    let (loaded, status_map) = load_subscriptions(config, &state.subscriptions_root).await;
+    let (loaded, status_map) = load_subscriptions(config, &state.subscriptions_root, true).await;
     {
         let mut guard = state.subscription_status.lock().await;
         *guard = status_map;
@@ -9578,6 +16756,91 @@ async fn load_subscriptions_and_update_state(
     loaded
 }
 
+/// 后台定时刷新设置了 `refresh_interval_secs` 的订阅：内容与已加载的缓存逐字节相同时只更新状态，
+/// 有变化才重新生成配置并重启 sing-box，避免无谓的代理中断
+fn spawn_subscription_refresher(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        // 启动时已经拉取过一次，从现在开始计时
+        let mut last_refresh: HashMap<String, Instant> = HashMap::new();
+        loop {
+            sleep(Duration::from_secs(SUBSCRIPTION_REFRESH_TICK_SECS)).await;
+            if state.setup_required.load(Ordering::Relaxed) {
+                continue;
+            }
+            let config = { state.config.lock().await.clone() };
+            let now = Instant::now();
+            last_refresh.retain(|id, _| config.subscriptions.iter().any(|s| s.id == *id));
+            let due: Vec<&SubscriptionConfig> = config
+                .subscriptions
+                .iter()
+                .filter(|s| s.enabled)
+                .filter(|s| {
+                    let Some(interval) = s.refresh_interval_secs else {
+                        return false;
+                    };
+                    let last = *last_refresh.entry(s.id.clone()).or_insert(now);
+                    now.duration_since(last) >= Duration::from_secs(interval)
+                })
+                .collect();
+            if due.is_empty() {
+                continue;
+            }
+
+            let timeout = Duration::from_millis(
+                config
+                    .subscription_fetch_timeout_ms
+                    .unwrap_or(DEFAULT_SUBSCRIPTION_FETCH_TIMEOUT_MS)
+                    .max(1),
+            );
+            let mut changed = Vec::new();
+            for sub in due {
+                last_refresh.insert(sub.id.clone(), Instant::now());
+                let dir = state.subscriptions_root.join(&sub.id);
+                let before = subscription_content_marker(sub, &dir).await;
+                let (attempts, result) =
+                    prepare_subscription_dir(sub, &state.subscriptions_root, timeout).await;
+                let after = match &result {
+                    Ok(_) => subscription_content_marker(sub, &dir).await,
+                    Err(_) => None,
+                };
+
+                let mut status = state.subscription_status.lock().await;
+                let runtime = status.entry(sub.id.clone()).or_insert_with(|| SubscriptionRuntime {
+                    files: vec![],
+                    error: None,
+                    updated_at: None,
+                    attempts: 0,
+                    stale: false,
+                });
+                runtime.attempts = attempts;
+                match result {
+                    Ok(_) => {
+                        runtime.updated_at = Some(chrono::Utc::now().timestamp());
+                        runtime.error = None;
+                        runtime.stale = false;
+                        if after.is_none() || before != after {
+                            changed.push(sub.id.clone());
+                        }
+                    }
+                    Err(e) => {
+                        log_warning!("Scheduled refresh of subscription {} failed: {}", sub.id, e);
+                        runtime.stale = before.is_some();
+                        runtime.error = Some(e);
+                    }
+                }
+            }
+
+            if changed.is_empty() {
+                continue;
+            }
+            log_info!("Subscriptions changed ({}), regenerating config", changed.join(", "));
+            if let Err(e) = regenerate_and_restart(state.clone()).await {
+                log_error!("Failed to apply refreshed subscriptions: {}", e);
+            }
+        }
+    });
+}
+
 /// Check if sing-box binary exists in current working directory
 fn check_sing_box() -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
     let current_dir = std::env::current_dir()?;
@@ -9669,6 +16932,38 @@ async fn check_and_install_openwrt_dependencies(
     Ok(())
 }
 
+/// Run `sing-box check` against the generated config.json
+async fn check_sing_box_config(sing_box_home: &str) -> Result<(), String> {
+    if process_stub_mode() {
+        return Ok(());
+    }
+    let sing_box_path = PathBuf::from(sing_box_home).join("sing-box");
+    let config_path = PathBuf::from(sing_box_home).join("config.json");
+    if !sing_box_path.exists() {
+        return Err(format!("sing-box 二进制文件不存在: {:?}", sing_box_path));
+    }
+    let output = tokio::process::Command::new(&sing_box_path)
+        .current_dir(sing_box_home)
+        .arg("check")
+        .arg("-c")
+        .arg(&config_path)
+        .output()
+        .await
+        .map_err(|e| format!("执行 sing-box check 失败: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Err(if !stderr.is_empty() {
+        stderr
+    } else if !stdout.is_empty() {
+        stdout
+    } else {
+        format!("sing-box check exited with {}", output.status)
+    })
+}
+
 async fn start_sing_internal(
     sing_box_home: &str,
 ) -> Result<(), String> {
@@ -9683,15 +16978,16 @@ async fn start_sing_internal(
     let config_path = PathBuf::from(sing_box_home).join("config.json");
 
     // Check if sing-box binary exists
-    if !sing_box_path.exists() {
+    if !sing_box_path.exists() && !process_stub_mode() {
         return Err(format!(
             "sing-box 二进制文件不存在: {:?}。请重新编译项目: bash ./build.sh",
             sing_box_path
         ));
     }
+    ensure_binary_runnable(&sing_box_path, "sing-box")?;
 
     // Check if config file exists
-    if !config_path.exists() {
+    if !config_path.exists() && !process_stub_mode() {
         return Err(format!(
             "sing-box 配置文件不存在: {:?}。请检查订阅或手动节点配置是否正确",
             config_path
@@ -9716,6 +17012,7 @@ async fn start_sing_internal(
     // Wait a short moment to check if process exits immediately
     sleep(Duration::from_millis(500)).await;
     if let Some(exit_status) = child.try_wait().map_err(|e| format!("等待进程失败: {}", e))? {
+        record_process_exit(sing_box_process_key(), exit_status);
         let code = exit_status.code().unwrap_or(-1);
         // Try to read config for more details
         let config_content = tokio::fs::read_to_string(&config_path).await.ok();
@@ -9741,6 +17038,9 @@ async fn start_sing_internal(
         started_at: Instant::now(),
     });
     drop(lock); // Release lock before connectivity check
+    if process_stub_mode() {
+        return Ok(());
+    }
 
     // Wait for sing-box to fully initialize
     sleep(Duration::from_secs(5)).await;
@@ -9826,7 +17126,12 @@ async fn start_terminal_internal(
         lock.remove(id);
     }
 
-    let gotty_path = check_gotty()?;
+    let gotty_path = if process_stub_mode() {
+        PathBuf::from("gotty")
+    } else {
+        check_gotty()?
+    };
+    ensure_binary_runnable(&gotty_path, "gotty")?;
     log_info!("Starting gotty from: {:?}", gotty_path);
 
     // 创建自定义 index.html，注入 miao 端口和 gotty 端口
@@ -9861,15 +17166,21 @@ async fn start_terminal_internal(
         command.arg(arg);
     }
 
-    let mut child = spawn_with_gotty_log_capture(&mut command, format!("gotty-{}", id))?;
+    let process_name = format!("gotty-{}", id);
+    let spawned_at = utc8_now_string();
+    let mut child = spawn_with_gotty_log_capture(&mut command, process_name.clone())?;
     let pid = child.id();
     log_info!("gotty process spawned with PID: {:?}", pid);
 
-    sleep(Duration::from_millis(300)).await;
-    if let Some(exit_status) = child.try_wait().map_err(|e| format!("等待进程失败: {}", e))? {
-        let code = exit_status.code().unwrap_or(-1);
-        return Err(format!("gotty exited immediately with code {}", code).into());
-    }
+    wait_for_startup(
+        &mut child,
+        terminal_process_key(id),
+        "gotty",
+        (&*GOTTY_LOG_BUFFER, process_name.as_str(), spawned_at.as_str()),
+        config.startup_check_ms.unwrap_or(DEFAULT_STARTUP_CHECK_MS),
+        Some((config.addr.as_str(), config.port)),
+    )
+    .await?;
 
     lock.insert(
         id.to_string(),
@@ -10080,15 +17391,21 @@ async fn start_app_internal(
         command.env(k, v);
     }
 
-    let mut child = spawn_with_app_log_capture(&mut command, format!("app-{}", app.id))?;
+    let process_name = format!("app-{}", app.id);
+    let spawned_at = utc8_now_string();
+    let mut child = spawn_with_app_log_capture(&mut command, process_name.clone())?;
     let pid = child.id();
     log_info!("app process spawned with PID: {:?}", pid);
 
-    sleep(Duration::from_millis(300)).await;
-    if let Some(exit_status) = child.try_wait().map_err(|e| format!("等待进程失败: {}", e))? {
-        let code = exit_status.code().unwrap_or(-1);
-        return Err(format!("app exited immediately with code {}", code).into());
-    }
+    wait_for_startup(
+        &mut child,
+        app_process_key(&app.id),
+        "app",
+        (&*APP_LOG_BUFFER, process_name.as_str(), spawned_at.as_str()),
+        app.startup_check_ms.unwrap_or(DEFAULT_STARTUP_CHECK_MS),
+        None,
+    )
+    .await?;
 
     let mut lock = APP_PROCESSES.lock().await;
     lock.insert(
@@ -10124,22 +17441,14 @@ async fn stop_app_internal(id: &str) -> Result<(), String> {
     Ok(())
 }
 
-async fn gen_config(
-    config: &Config,
-    sing_box_home: &str,
-    subs: &LoadedSubscriptions,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// Build the sing-box config JSON (outbounds + proxy selector) without writing it.
+fn build_singbox_config(config: &Config, subs: &LoadedSubscriptions) -> serde_json::Value {
     let my_outbounds: Vec<serde_json::Value> = subs.outbounds.clone();
     let my_names: Vec<String> = my_outbounds
         .iter()
         .filter_map(|o| o.get("tag").and_then(|v| v.as_str()).map(String::from))
         .collect();
 
-    let total_nodes = my_outbounds.len();
-    if total_nodes == 0 {
-        eprintln!("⚠️  Warning: No proxy nodes available. Generating minimal config.");
-    }
-
     let mut sing_box_config = get_config_template();
     if let Some(dns) = sing_box_config.get_mut("dns") {
         let configured = config.dns_active.as_deref().unwrap_or(DEFAULT_DNS_ACTIVE);
@@ -10153,10 +17462,12 @@ async fn gen_config(
                     .into_iter()
                     .map(serde_json::Value::String),
             );
-            // Ensure selector has at least one outbound
-            if arr.is_empty() {
-                arr.push(serde_json::Value::String("direct".to_string()));
-            }
+            // 内置出站放在最后：没有节点时 direct 成为默认选择
+            arr.extend(
+                SELECTOR_BUILTIN_OUTBOUNDS
+                    .iter()
+                    .map(|tag| serde_json::Value::String(tag.to_string())),
+            );
         }
     }
     if let Some(arr) = sing_box_config["outbounds"].as_array_mut() {
@@ -10189,6 +17500,18 @@ async fn gen_config(
             }
         }
     }
+    sing_box_config
+}
+
+async fn gen_config(
+    config: &Config,
+    sing_box_home: &str,
+    subs: &LoadedSubscriptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if subs.outbounds.is_empty() {
+        eprintln!("⚠️  Warning: No proxy nodes available. Generating minimal config.");
+    }
+    let sing_box_config = build_singbox_config(config, subs);
     let config_output_loc = format!("{}/config.json", sing_box_home);
     tokio::fs::write(
         &config_output_loc,
@@ -10247,7 +17570,8 @@ fn get_config_template() -> serde_json::Value {
         "outbounds": [
             {"type": "selector", "tag": "proxy", "outbounds": []},
             {"type": "selector", "tag": "_dns", "outbounds": ["proxy", "direct"]},
-            {"type": "direct", "tag": "direct"}
+            {"type": "direct", "tag": "direct"},
+            {"type": "block", "tag": "block"}
         ],
         "route": {
             "final": "proxy",
@@ -10277,10 +17601,10 @@ fn parse_subscription_text(
         }
     }
 
-    // Try parsing as Shadowsocks URL list (base64 encoded)
-    if let Some(ss_result) = try_parse_ss_urls(text) {
-        log_info!("Detected Shadowsocks URL format subscription");
-        return ss_result;
+    // Try parsing as a share link list (plain or base64 encoded)
+    if let Some(links_result) = try_parse_share_links(text) {
+        log_info!("Detected share link format subscription");
+        return links_result;
     }
 
     // Fall back to YAML parsing (Clash format)
@@ -10324,6 +17648,10 @@ fn parse_subscription_text(
                             .and_then(|s| s.as_str())
                             .map(|s| s.to_string()),
                         insecure: true,
+                        alpn: None,
+                        utls: None,
+                        reality: None,
+                        ech: None,
                     },
                 };
                 node_names.push(name.to_string());
@@ -10354,6 +17682,10 @@ fn parse_subscription_text(
                             .get("skip-cert-verify")
                             .and_then(|v| v.as_bool())
                             .unwrap_or(false),
+                        alpn: None,
+                        utls: None,
+                        reality: None,
+                        ech: None,
                     },
                 };
                 node_names.push(name.to_string());
@@ -10372,7 +17704,21 @@ async fn load_subscription_file(
     parse_subscription_text(&text)
 }
 
-async fn load_subscription_dir(sub_dir: &StdPath, subscription_id: Option<&str>) -> LoadedSubscriptions {
+/// Git 仓库里只有这些扩展名的文件会被当作订阅，README/LICENSE 等一律跳过
+const GIT_SUBSCRIPTION_EXTENSIONS: [&str; 4] = ["yaml", "yml", "json", "txt"];
+
+fn has_subscription_extension(file_name: &str) -> bool {
+    StdPath::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| GIT_SUBSCRIPTION_EXTENSIONS.iter().any(|known| e.eq_ignore_ascii_case(known)))
+}
+
+async fn load_subscription_dir(
+    sub_dir: &StdPath,
+    subscription_id: Option<&str>,
+    git_checkout: bool,
+) -> LoadedSubscriptions {
     let mut file_paths: Vec<(String, PathBuf)> = vec![];
     let mut dir_error: Option<String> = None;
 
@@ -10389,6 +17735,13 @@ async fn load_subscription_dir(sub_dir: &StdPath, subscription_id: Option<&str>)
                             continue;
                         }
                         let file_name = ent.file_name().to_string_lossy().to_string();
+                        // Git 工作区里的 .gitignore 等隐藏文件不是订阅
+                        if file_name.starts_with('.') {
+                            continue;
+                        }
+                        if git_checkout && !has_subscription_extension(&file_name) {
+                            continue;
+                        }
                         file_paths.push((file_name, ent.path()));
                     }
                     Ok(None) => break,
@@ -10505,6 +17858,10 @@ fn parse_singbox_json(
                         enabled: true,
                         server_name,
                         insecure: true,
+                        alpn: None,
+                        utls: None,
+                        reality: None,
+                        ech: None,
                     },
                 };
 
@@ -10526,30 +17883,46 @@ fn parse_singbox_json(
     Ok((node_names, result_outbounds))
 }
 
-/// Try to detect if the text is base64 encoded SS URLs
-fn try_parse_ss_urls(text: &str) -> Option<Result<(Vec<String>, Vec<serde_json::Value>), Box<dyn std::error::Error + Send + Sync>>> {
+/// Schemes recognised as share links (some are recognised but not convertible)
+const SHARE_LINK_SCHEMES: &[&str] = &[
+    "ss", "ssr", "vmess", "vless", "trojan", "hysteria", "hysteria2", "hy2", "tuic", "anytls",
+];
+
+fn is_share_link(line: &str) -> bool {
+    line.split_once("://")
+        .map(|(scheme, _)| SHARE_LINK_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// A provider body that is a newline-separated share link list, or such a list
+/// base64-encoded. Returns None when the text is neither (e.g. Clash YAML).
+fn try_parse_share_links(text: &str) -> Option<Result<(Vec<String>, Vec<serde_json::Value>), Box<dyn std::error::Error + Send + Sync>>> {
     let trimmed = text.trim();
     if trimmed.is_empty() {
         return None;
     }
 
-    // First check if it's already decoded SS URLs
-    if trimmed.contains("ss://") {
-        return Some(parse_ss_url_list(trimmed));
+    let first_line = trimmed.lines().next().unwrap_or("").trim();
+    if is_share_link(first_line) {
+        return Some(parse_share_link_list(trimmed));
     }
 
-    // Try to decode as base64
-    match base64_decode(trimmed) {
-        Ok(decoded) => {
-            let decoded_str = String::from_utf8_lossy(&decoded);
-            // Check if decoded content contains ss:// URLs
-            if decoded_str.contains("ss://") {
-                Some(parse_ss_url_list(&decoded_str))
-            } else {
-                None
-            }
-        }
-        Err(_) => None,
+    // base64 blob: only base64 characters (line breaks allowed)
+    let compact: String = trimmed.split_whitespace().collect();
+    let is_base64 = compact
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_'));
+    if !is_base64 {
+        return None;
+    }
+    let decoded = match base64_decode_lenient(&compact) {
+        Ok(decoded) => String::from_utf8_lossy(&decoded).into_owned(),
+        Err(_) => return None,
+    };
+    if decoded.lines().any(|line| is_share_link(line.trim())) {
+        Some(parse_share_link_list(&decoded))
+    } else {
+        Some(Err("Base64 subscription payload contains no share links".into()))
     }
 }
 
@@ -10564,25 +17937,53 @@ fn base64_decode(input: &str) -> Result<Vec<u8>, base64::DecodeError> {
     }
 }
 
-/// Parse a list of SS URLs (base64 decoded content)
-fn parse_ss_url_list(content: &str) -> Result<(Vec<String>, Vec<serde_json::Value>), Box<dyn std::error::Error + Send + Sync>> {
+/// Base64 decode that also accepts unpadded input (common in share links)
+fn base64_decode_lenient(input: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+    base64_decode(input).or_else(|_| {
+        let unpadded = input.trim().trim_end_matches('=');
+        base64::Engine::decode(&STANDARD_NO_PAD, unpadded)
+            .or_else(|_| base64::Engine::decode(&URL_SAFE_NO_PAD, unpadded))
+    })
+}
+
+/// Convert every share link via the same parser as POST /api/nodes/import;
+/// unusable links are skipped, an error is returned only if none converted
+fn parse_share_link_list(content: &str) -> Result<(Vec<String>, Vec<serde_json::Value>), Box<dyn std::error::Error + Send + Sync>> {
     let mut node_names = vec![];
     let mut outbounds = vec![];
+    let mut first_error: Option<String> = None;
+    let mut failed = 0;
 
-    // Split by newlines and parse each SS URL
     for line in content.lines() {
         let line = line.trim();
-        if line.is_empty() || !line.starts_with("ss://") {
+        if line.is_empty() {
             continue;
         }
-
-        if let Some((name, ss)) = parse_single_ss_url(line) {
-            node_names.push(name.clone());
-            outbounds.push(ss);
+        match parse_share_link(line) {
+            Ok((name, outbound)) => {
+                node_names.push(name);
+                outbounds.push(outbound);
+            }
+            Err(e) => {
+                failed += 1;
+                first_error.get_or_insert(e);
+            }
         }
     }
 
-    log_info!("Parsed {} nodes from Shadowsocks URLs", node_names.len());
+    if outbounds.is_empty() {
+        return Err(format!(
+            "No usable share links ({} failed: {})",
+            failed,
+            first_error.unwrap_or_else(|| "empty list".to_string())
+        )
+        .into());
+    }
+    if failed > 0 {
+        log_warning!("Skipped {} unsupported or invalid share links", failed);
+    }
+    log_info!("Parsed {} nodes from share links", node_names.len());
     Ok((node_names, outbounds))
 }
 
@@ -10607,7 +18008,8 @@ fn parse_single_ss_url(url: &str) -> Option<(String, serde_json::Value)> {
 
     // Parse server:port
     let (server, port) = match server_part.rsplit_once(':') {
-        Some((s, p)) => (s, p.parse::<u16>().ok()?),
+        // IPv6 servers arrive bracketed (`[2001:db8::1]:8388`)
+        Some((s, p)) => (s.trim_start_matches('[').trim_end_matches(']'), p.parse::<u16>().ok()?),
         None => return None,
     };
 
@@ -10633,7 +18035,7 @@ fn parse_single_ss_url(url: &str) -> Option<(String, serde_json::Value)> {
     // Create shadowsocks outbound
     let ss = Shadowsocks {
         outbound_type: "shadowsocks".to_string(),
-        tag: if name.is_empty() { format!("{}:{}", server, port) } else { name },
+        tag: if name.is_empty() { join_host_port(server, port) } else { name },
         server: server.to_string(),
         server_port: port,
         method,
@@ -10772,17 +18174,35 @@ async fn sync_git_repo(repo: &str, target: &StdPath) -> Result<(), String> {
     ))
 }
 
-async fn fetch_subscription_url(url: &str, dest_dir: &StdPath) -> Result<PathBuf, String> {
+async fn fetch_subscription_url(
+    url: &str,
+    headers: &HashMap<String, String>,
+    user_agent: Option<&str>,
+    dest_dir: &StdPath,
+    timeout: Duration,
+) -> Result<PathBuf, String> {
     tokio::fs::create_dir_all(dest_dir)
         .await
         .map_err(|e| format!("Failed to create dir {}: {}", dest_dir.display(), e))?;
 
-    let target = dest_dir.join("subscription.yaml");
+    let target = subscription_cache_file(dest_dir);
 
     // Check if it's a URL or direct content
     if url.starts_with("http://") || url.starts_with("https://") {
         // Download from URL
-        let resp = reqwest::get(url)
+        let mut builder = reqwest::Client::builder().timeout(timeout);
+        if let Some(ua) = user_agent {
+            builder = builder.user_agent(ua);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let mut request = client.get(url);
+        for (name, value) in headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let resp = request
+            .send()
             .await
             .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
         if !resp.status().is_success() {
@@ -10792,11 +18212,17 @@ async fn fetch_subscription_url(url: &str, dest_dir: &StdPath) -> Result<PathBuf
             .bytes()
             .await
             .map_err(|e| format!("Failed to read response: {}", e))?;
-        tokio::fs::write(&target, bytes)
+        // Write next to the dir and rename, so a failed write never clobbers
+        // the last good copy (and no temp file is picked up by the loader).
+        let tmp = dest_dir.with_extension("download");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        tokio::fs::rename(&tmp, &target)
             .await
             .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
     } else {
-        // Direct content (will be decoded by try_parse_ss_urls if needed)
+        // Direct content (will be decoded by try_parse_share_links if needed)
         tokio::fs::write(&target, url.as_bytes())
             .await
             .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
@@ -10805,15 +18231,194 @@ async fn fetch_subscription_url(url: &str, dest_dir: &StdPath) -> Result<PathBuf
     Ok(target)
 }
 
+/// Last successfully fetched content of a URL subscription.
+fn subscription_cache_file(dir: &StdPath) -> PathBuf {
+    dir.join("subscription.yaml")
+}
+
+/// Whether a previous fetch left something usable in `dir`.
+async fn subscription_has_cached_copy(sub: &SubscriptionConfig, dir: &StdPath) -> bool {
+    let marker = match &sub.source {
+        SubscriptionSource::Url { .. } => subscription_cache_file(dir),
+        SubscriptionSource::Git { .. } => dir.join(".git"),
+    };
+    tokio::fs::metadata(marker).await.is_ok()
+}
+
+/// Identifies the fetched content so a refresh can tell whether anything changed:
+/// the cached file for URL sources, the checked-out commit for Git sources.
+async fn subscription_content_marker(sub: &SubscriptionConfig, dir: &StdPath) -> Option<Vec<u8>> {
+    match &sub.source {
+        SubscriptionSource::Url { .. } => tokio::fs::read(subscription_cache_file(dir)).await.ok(),
+        SubscriptionSource::Git { .. } => {
+            if tokio::fs::metadata(dir.join(".git")).await.is_err() {
+                return None;
+            }
+            run_git(&["rev-parse", "HEAD"], Some(dir), None, Duration::from_secs(10))
+                .await
+                .ok()
+                .map(String::into_bytes)
+        }
+    }
+}
+
+/// Run a git command non-interactively, passing credentials through the
+/// environment so they never show up in argv or `.git/config`.
+async fn run_git(
+    args: &[&str],
+    cwd: Option<&StdPath>,
+    credentials: Option<&GitCredentials>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut cmd = tokio::process::Command::new("git");
+    cmd.args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+    match credentials {
+        Some(GitCredentials::Token { token, username }) => {
+            let user = username.as_deref().unwrap_or("x-access-token");
+            let basic = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", user, token));
+            cmd.env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+                .env("GIT_CONFIG_VALUE_0", format!("Authorization: Basic {}", basic));
+        }
+        Some(GitCredentials::KeyPath { path, known_hosts_path }) => {
+            let quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
+            // 与隧道的 known_hosts_path 一致：不接受首次见到的未知主机密钥
+            let mut ssh = format!(
+                "ssh -i {} -o IdentitiesOnly=yes -o BatchMode=yes -o StrictHostKeyChecking=yes",
+                quote(path)
+            );
+            if let Some(known_hosts) = known_hosts_path {
+                ssh.push_str(&format!(" -o UserKnownHostsFile={}", quote(known_hosts)));
+            }
+            cmd.env("GIT_SSH_COMMAND", ssh);
+        }
+        None => {}
+    }
+
+    let name = args.first().copied().unwrap_or("git");
+    let output = tokio::time::timeout(timeout, cmd.output())
+        .await
+        .map_err(|_| format!("git {} timed out", name))?
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Bring `dest_dir` to the tip of `branch` (remote default branch when unset).
+/// An existing clean checkout of the same repo gets a shallow fetch + checkout;
+/// anything else (dirty tree, other remote, failed fetch) is re-cloned.
+async fn fetch_subscription_git(
+    repo: &str,
+    branch: Option<&str>,
+    credentials: Option<&GitCredentials>,
+    dest_dir: &StdPath,
+    timeout: Duration,
+) -> Result<(), String> {
+    let timeout = timeout.max(Duration::from_secs(SUBSCRIPTION_GIT_MIN_TIMEOUT_SECS));
+
+    if tokio::fs::metadata(dest_dir.join(".git")).await.is_ok() {
+        let dir = Some(dest_dir);
+        let clean = run_git(&["status", "--porcelain"], dir, None, timeout)
+            .await
+            .map(|out| out.is_empty())
+            .unwrap_or(false);
+        let same_remote = run_git(&["remote", "get-url", "origin"], dir, None, timeout)
+            .await
+            .map(|url| url == repo)
+            .unwrap_or(false);
+        if clean && same_remote {
+            let refspec = branch.unwrap_or("HEAD");
+            let updated = async {
+                run_git(&["fetch", "--depth", "1", "origin", refspec], dir, credentials, timeout)
+                    .await?;
+                run_git(&["checkout", "--force", "--detach", "FETCH_HEAD"], dir, None, timeout)
+                    .await
+            }
+            .await;
+            match updated {
+                Ok(_) => return Ok(()),
+                Err(e) => log_warning!("Git fetch of {} failed, re-cloning: {}", repo, e),
+            }
+        } else {
+            log_warning!(
+                "Git checkout {} is dirty or points at another remote, re-cloning",
+                dest_dir.display()
+            );
+        }
+    }
+
+    // Clone next to the dir and swap it in, so a failed clone keeps the last good checkout.
+    let tmp = dest_dir.with_extension("clone");
+    remove_path_if_exists(&tmp).await?;
+    if let Some(parent) = tmp.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create dir {}: {}", parent.display(), e))?;
+    }
+    let tmp_str = tmp.to_string_lossy().to_string();
+    let mut args = vec!["clone", "--depth", "1", "--single-branch"];
+    if let Some(b) = branch {
+        args.extend(["--branch", b]);
+    }
+    args.extend(["--", repo, tmp_str.as_str()]);
+    if let Err(e) = run_git(&args, None, credentials, timeout).await {
+        let _ = remove_path_if_exists(&tmp).await;
+        return Err(e);
+    }
+    remove_path_if_exists(dest_dir).await?;
+    tokio::fs::rename(&tmp, dest_dir)
+        .await
+        .map_err(|e| format!("Failed to move {} into place: {}", tmp.display(), e))
+}
+
+/// Fetch a subscription into its directory, retrying transient failures with
+/// exponential backoff. Returns the number of attempts made alongside the result.
 async fn prepare_subscription_dir(
     sub: &SubscriptionConfig,
     root: &StdPath,
-) -> Result<PathBuf, String> {
-    match &sub.source {
-        SubscriptionSource::Url { url } => {
-            let dir = root.join(&sub.id);
-            let _ = fetch_subscription_url(url, &dir).await?;
-            Ok(dir)
+    timeout: Duration,
+) -> (u32, Result<PathBuf, String>) {
+    let dir = root.join(&sub.id);
+    let mut attempt = 1;
+    loop {
+        let result = match &sub.source {
+            SubscriptionSource::Url { url, headers, user_agent } => {
+                fetch_subscription_url(url, headers, user_agent.as_deref(), &dir, timeout)
+                    .await
+                    .map(|_| ())
+            }
+            SubscriptionSource::Git { repo, branch, credentials } => {
+                fetch_subscription_git(repo, branch.as_deref(), credentials.as_ref(), &dir, timeout)
+                    .await
+            }
+        };
+        match result {
+            Ok(()) => return (attempt, Ok(dir)),
+            Err(e) if attempt < SUBSCRIPTION_FETCH_ATTEMPTS => {
+                log_warning!(
+                    "Subscription {} fetch failed (attempt {}/{}): {}",
+                    sub.id,
+                    attempt,
+                    SUBSCRIPTION_FETCH_ATTEMPTS,
+                    e
+                );
+                sleep(Duration::from_millis(SUBSCRIPTION_FETCH_BACKOFF_MS << (attempt - 1))).await;
+                attempt += 1;
+            }
+            Err(e) => return (attempt, Err(e)),
         }
     }
 }
@@ -10877,6 +18482,273 @@ async fn fix_singbox_routes() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// GET /healthz - 存活检查（无需登录，不采集系统信息）
+async fn healthz() -> Json<serde_json::Value> {
+    Json(json!({
+        "status": "ok",
+        "version": format!("v{}", VERSION),
+        "sing_box_running": sing_box_running().await,
+    }))
+}
+
+/// GET /readyz - 就绪检查：配置已加载（非首次设置模式）且指标库可写；未就绪时返回 503
+async fn readyz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let config_loaded = !state.setup_required.load(Ordering::Relaxed);
+    // 关闭 metrics 时不打开数据库，视为就绪
+    let metrics_db = if state.metrics_config.enabled {
+        with_metrics_db(&state, |conn| {
+            // 只获取写锁不写数据，确认文件系统与锁都可写
+            conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;")
+                .map_err(|e| format!("Metrics db not writable: {}", e))
+        })
+        .await
+        .err()
+    } else {
+        None
+    };
+    let ready = config_loaded && metrics_db.is_none();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ok" } else { "unavailable" },
+            "version": format!("v{}", VERSION),
+            "config_loaded": config_loaded,
+            "metrics_db_error": metrics_db,
+            "sing_box_running": sing_box_running().await,
+        })),
+    )
+}
+
+/// Prometheus 标签值转义（反斜杠、双引号、换行）
+fn prom_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// GET /metrics - Prometheus 文本格式的只读指标（metrics_port 监听器上提供，
+/// 同时以 GET /api/metrics/prometheus 挂在受保护路由下）
+async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> Response {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: &[(&str, f64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+
+    gauge("miao_up", "miao is running", &[("", 1.0)]);
+    let running = sing_box_running().await;
+    gauge("miao_sing_box_running", "sing-box process is running", &[("", running as u8 as f64)]);
+    gauge(
+        "miao_sing_box_pending_restart",
+        "config changed since sing-box was started",
+        &[("", state.sing_box_pending_restart.load(Ordering::Relaxed) as u8 as f64)],
+    );
+    let nodes = state.node_type_by_tag.lock().await.len();
+    gauge("miao_nodes", "known proxy nodes", &[("", nodes as f64)]);
+
+    if let Ok(t) = dashboard_tunnels(&state).await {
+        gauge(
+            "miao_tcp_tunnels",
+            "TCP tunnels by state",
+            &[
+                ("{state=\"forwarding\"}", t.forwarding as f64),
+                ("{state=\"connecting\"}", t.connecting as f64),
+                ("{state=\"error\"}", t.error as f64),
+                ("{state=\"stopped\"}", t.stopped as f64),
+            ],
+        );
+    }
+
+    let tunnels = { state.config.lock().await.tcp_tunnels.clone() };
+    let mut tunnel_labels = Vec::with_capacity(tunnels.len());
+    let mut tunnel_values = Vec::with_capacity(tunnels.len());
+    for t in tunnels.iter().filter(|t| t.managed_by.is_none()) {
+        let status = state.tcp_tunnel.get_status(&t.id).await.unwrap_or_default();
+        tunnel_labels.push(format!(
+            "{{id=\"{}\",name=\"{}\"}}",
+            prom_label(&t.id),
+            prom_label(t.name.as_deref().unwrap_or(""))
+        ));
+        tunnel_values.push(matches!(status.state, tcp_tunnel::TunnelState::Forwarding) as u8 as f64);
+    }
+    if !tunnel_labels.is_empty() {
+        let samples: Vec<(&str, f64)> = tunnel_labels
+            .iter()
+            .map(String::as_str)
+            .zip(tunnel_values)
+            .collect();
+        gauge("miao_tcp_tunnel_forwarding", "TCP tunnel is forwarding", &samples);
+    }
+
+    let (terminal_ids, app_ids) = {
+        let config = state.config.lock().await;
+        (
+            config.terminals.iter().map(|t| t.id.clone()).collect::<Vec<_>>(),
+            config.apps.iter().map(|a| a.id.clone()).collect::<Vec<_>>(),
+        )
+    };
+    let mut process_labels = Vec::new();
+    let mut process_values = Vec::new();
+    for id in &terminal_ids {
+        process_labels.push(format!("{{kind=\"terminal\",id=\"{}\"}}", prom_label(id)));
+        process_values.push(get_terminal_runtime_status(id).await.running as u8 as f64);
+    }
+    for id in &app_ids {
+        process_labels.push(format!("{{kind=\"app\",id=\"{}\"}}", prom_label(id)));
+        process_values.push(get_app_runtime_status(id).await.running as u8 as f64);
+    }
+    if !process_labels.is_empty() {
+        let samples: Vec<(&str, f64)> = process_labels
+            .iter()
+            .map(String::as_str)
+            .zip(process_values)
+            .collect();
+        gauge("miao_process_running", "managed terminal/app process is running", &samples);
+    }
+
+    let own = sample_self_metrics(&state).await;
+    if let Some(rss) = own.rss_kb {
+        gauge("miao_self_rss_bytes", "miao resident memory", &[("", rss as f64 * 1024.0)]);
+    }
+    if let Some(cpu) = own.cpu_time_secs {
+        gauge("miao_self_cpu_seconds", "miao CPU time consumed", &[("", cpu)]);
+    }
+    if let Some(fds) = own.open_fds {
+        gauge("miao_self_open_fds", "miao open file descriptors", &[("", fds as f64)]);
+    }
+    gauge("miao_self_tokio_tasks", "alive tokio tasks", &[("", own.tokio_alive_tasks as f64)]);
+
+    // 读取最新的 status_cache（与 metrics.enabled 无关，过期时按需刷新）
+    if let Ok(status) = load_system_status(&state).await {
+        if let Some(cpu) = status["cpuPercent"].as_f64() {
+            gauge("miao_cpu_percent", "host CPU usage", &[("", cpu)]);
+        }
+        if let Some(mem) = status["memoryUsedKb"].as_f64() {
+            gauge("miao_memory_used_kb", "host memory in use", &[("", mem)]);
+        }
+        let gpus: Vec<f64> = status["graphics"]
+            .as_array()
+            .map(|g| g.iter().filter_map(|g| g["gpu"].as_f64()).collect())
+            .unwrap_or_default();
+        if !gpus.is_empty() {
+            let avg = gpus.iter().sum::<f64>() / gpus.len() as f64;
+            gauge("miao_gpu_percent", "average GPU usage", &[("", avg)]);
+        }
+        let disks: Vec<(String, f64, f64)> = status["disks"]
+            .as_array()
+            .map(|disks| {
+                disks
+                    .iter()
+                    .map(|d| {
+                        (
+                            format!("{{disk=\"{}\"}}", prom_label(d["name"].as_str().unwrap_or(""))),
+                            d["used"].as_f64().unwrap_or(0.0),
+                            d["total"].as_f64().unwrap_or(0.0),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !disks.is_empty() {
+            let used: Vec<(&str, f64)> = disks.iter().map(|(l, u, _)| (l.as_str(), *u)).collect();
+            let total: Vec<(&str, f64)> = disks.iter().map(|(l, _, t)| (l.as_str(), *t)).collect();
+            gauge("miao_disk_used_bytes", "disk space in use", &used);
+            gauge("miao_disk_total_bytes", "disk capacity", &total);
+        }
+        if let Some(rx) = status["netRxBytesPerSec"].as_f64() {
+            gauge("miao_net_rx_bytes_per_second", "network receive rate", &[("", rx)]);
+        }
+        if let Some(tx) = status["netTxBytesPerSec"].as_f64() {
+            gauge("miao_net_tx_bytes_per_second", "network transmit rate", &[("", tx)]);
+        }
+        if let Some(swap) = status["swapUsedKb"].as_f64() {
+            gauge("miao_swap_used_kb", "swap in use", &[("", swap)]);
+        }
+        if let Some(swap) = status["swapTotalKb"].as_f64() {
+            gauge("miao_swap_total_kb", "swap capacity", &[("", swap)]);
+        }
+        if let Some(load) = status["loadAvg"].as_array() {
+            let samples: Vec<(&str, f64)> = ["{window=\"1m\"}", "{window=\"5m\"}", "{window=\"15m\"}"]
+                .into_iter()
+                .zip(load.iter().filter_map(|v| v.as_f64()))
+                .collect();
+            gauge("miao_load_average", "system load average", &samples);
+        }
+        if let Some(uptime) = status["uptimeSecs"].as_f64() {
+            gauge("miao_system_uptime_seconds", "host uptime", &[("", uptime)]);
+        }
+    }
+
+    (
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        out,
+    )
+        .into_response()
+}
+
+/// 在 metrics_port 上启动只含 /metrics 与健康检查的独立监听，与管理面板分开暴露
+async fn spawn_metrics_listener(state: Arc<AppState>, port: u16) {
+    let app = Router::new()
+        .route("/metrics", get(prometheus_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state)
+        .fallback(not_found_fallback);
+    let addr = std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED);
+    match bind_listener(addr, port).await {
+        Ok(listener) => {
+            log_info!("Metrics listener started: http://0.0.0.0:{}/metrics", port);
+            tokio::spawn(async move {
+                if let Err(e) = axum::serve(listener, app).await {
+                    log_error!("Metrics listener stopped: {}", e);
+                }
+            });
+        }
+        Err(e) => log_error!("无法监听 metrics 端口 {}: {}", port, e),
+    }
+}
+
+const BIND_RETRY_ATTEMPTS: u32 = 10;
+const BIND_RETRY_DELAY_MS: u64 = 500;
+
+/// Bind the HTTP listener. While the port is still held (e.g. the previous
+/// process is shutting down after a self-upgrade), retry for a few seconds.
+async fn bind_listener(
+    bind_addr: std::net::IpAddr,
+    port: u16,
+) -> std::io::Result<tokio::net::TcpListener> {
+    let addr = std::net::SocketAddr::new(bind_addr, port);
+    let mut attempt = 1;
+    loop {
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < BIND_RETRY_ATTEMPTS => {
+                log_warning!(
+                    "Port {} in use, retrying ({}/{})...",
+                    port,
+                    attempt,
+                    BIND_RETRY_ATTEMPTS
+                );
+                attempt += 1;
+                sleep(Duration::from_millis(BIND_RETRY_DELAY_MS)).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // CLI args (pre-parse for help; help should not require root)
@@ -10891,59 +18763,156 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         println!(
             "Miao - sing-box 管理器\n\n\
 用法:\n  {program} [OPTIONS]\n\n\
-选项:\n  -h, --help             显示帮助并退出\n\n\
-说明:\n  - 配置文件为当前目录下的 ./config.yaml\n  - 正常运行需要 root 权限（--help 例外）",
+选项:\n  -h, --help             显示帮助并退出\n  --data-dir <DIR>       数据目录（config.yaml、metrics.sqlite、sub/、二进制等均位于其下）\n\n\
+环境变量（优先于配置文件，不会写回 config.yaml）:\n  MIAO_PORT              HTTP 端口\n  MIAO_PASSWORD          登录密码（至少 4 位）\n  MIAO_BIND_ADDR         监听地址（默认 0.0.0.0）\n  MIAO_JWT_SECRET        JWT 签名密钥（至少 16 位）\n  MIAO_SECRET_KEY        config.yaml 敏感字段加密密钥（默认使用本机 machine-id）\n  MIAO_DATA_DIR          数据目录（--data-dir 优先）\n  MIAO_STUB_PROCESSES    设为 1 时以占位进程代替 sing-box/gotty/应用/iVnc（测试用，可免 root）\n\n\
+说明:\n  - 配置文件为数据目录（默认当前目录）下的 ./config.yaml\n  - 配置中的绝对路径（如 metrics.storage_path）不受数据目录影响\n  - 正常运行需要 root 权限（--help 例外）",
             program = program_name
         );
         return Ok(());
     }
 
-    // Check for root privileges
-    if !Uid::effective().is_root() {
+    // Check for root privileges (stub mode is meant for tests and may run unprivileged)
+    if process_stub_mode() {
+        log_warning!("MIAO_STUB_PROCESSES is set: managed processes are stubbed, not started");
+    } else if !Uid::effective().is_root() {
         log_error!("Error: This application must be run as root.");
         std::process::exit(1);
     }
 
+    let env_overrides = match load_env_overrides() {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            log_error!("Invalid environment override: {}", e);
+            std::process::exit(1);
+        }
+    };
+    *ENV_OVERRIDES.lock().unwrap() = env_overrides.clone();
+
+    // 数据目录：切换工作目录，使 config.yaml、metrics.sqlite、sub/、解压的二进制等相对路径都落在其下
+    let cli_data_dir = rest_args.iter().enumerate().find_map(|(i, arg)| {
+        if let Some(value) = arg.strip_prefix("--data-dir=") {
+            Some(value.to_string())
+        } else if arg == "--data-dir" {
+            rest_args.get(i + 1).cloned()
+        } else {
+            None
+        }
+    });
+    if let Some(data_dir) = cli_data_dir
+        .map(PathBuf::from)
+        .or_else(|| env_overrides.data_dir.clone())
+    {
+        if let Err(e) = fs::create_dir_all(&data_dir).and_then(|_| env::set_current_dir(&data_dir)) {
+            log_error!("无法使用数据目录 {}: {}", data_dir.display(), e);
+            std::process::exit(1);
+        }
+        log_info!("Data directory: {}", data_dir.display());
+    }
+
     let subscriptions_root = PathBuf::from("sub");
 
     log_info!("Reading configuration...");
-    let (mut config, setup_required) = match tokio::fs::read_to_string("config.yaml").await {
+    let (mut config, mut setup_required) = match tokio::fs::read_to_string(CONFIG_FILE).await {
         Ok(text) => (serde_yaml::from_str::<Config>(&text)?, false),
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => (
             Config {
+                schema_version: CONFIG_SCHEMA_VERSION,
                 port: Some(DEFAULT_PORT),
                 sing_box_home: None,
                 password: None,
+                jwt_secret: None,
+                token_ttl_secs: None,
+                ui_enabled: None,
+                encrypt_secrets: None,
                 terminal: None,
                 terminals: vec![],
                 apps: vec![],
                 syncs: vec![],
                 selections: HashMap::new(),
+                selection_keys: HashMap::new(),
                 nodes: vec![],
                 dns_active: None,
                 dns_candidates: None,
+                proxy_check_url: None,
                 tcp_tunnels: vec![],
                 tcp_tunnel_sets: vec![],
                 subscriptions: vec![],
+                subscription_fetch_timeout_ms: None,
+                regenerate_debounce_ms: None,
+                notify_webhook_url: None,
+                max_body_bytes: None,
+                metrics_port: None,
+                max_tunnels: None,
+                max_syncs: None,
+                max_terminals: None,
+                max_apps: None,
+                max_vnc_sessions: None,
+                tls_cert_path: None,
+                tls_key_path: None,
+                default_ssh_auth: None,
                 hosts: vec![],
                 host_groups: vec![],
                 metrics: MetricsConfig::default(),
+                connectivity_presets: vec![],
             },
             true,
         ),
         Err(e) => return Err(e.into()),
     };
 
-    migrate_terminals(&mut config);
-    if SUBSCRIPTIONS_ENABLED {
-        let subscriptions_changed = normalize_subscriptions(&mut config);
-        if subscriptions_changed && !setup_required {
-            let _ = save_config(&config).await;
+    // 解密配置中的敏感字段；开启 encrypt_secrets 但仍有明文时，首次保存即完成迁移
+    let (decrypted_secrets, plaintext_secrets) = match secrets::decrypt_config_secrets(&mut config) {
+        Ok(counts) => counts,
+        Err(e) => {
+            log_error!("Failed to decrypt config secrets: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if decrypted_secrets > 0 {
+        log_info!("Decrypted {} secret(s) from config", decrypted_secrets);
+    }
+    if config.encrypt_secrets.unwrap_or(false) && plaintext_secrets > 0 && !setup_required {
+        match save_config(&config).await {
+            Ok(()) => log_info!("Encrypted {} plaintext secret(s) in config.yaml", plaintext_secrets),
+            Err(e) => log_warning!("Failed to encrypt config secrets: {}", e),
+        }
+    }
+
+    let migrated = !run_config_migrations(&mut config).is_empty();
+    let terminals_changed = normalize_terminals(&mut config);
+    let subscriptions_changed = SUBSCRIPTIONS_ENABLED && normalize_subscriptions(&mut config);
+    let jwt_secret_generated = ensure_jwt_secret(&mut config);
+    if jwt_secret_generated {
+        log_info!("Generated a new JWT signing secret");
+    }
+    *JWT_SIGNING_SECRET.lock().unwrap() = config.jwt_secret.clone().unwrap_or_default();
+    if (migrated || terminals_changed || subscriptions_changed || jwt_secret_generated) && !setup_required {
+        if let Err(e) = save_config(&config).await {
+            log_warning!("Failed to save migrated config: {}", e);
         }
     }
 
-    let port = config.port.unwrap_or(DEFAULT_PORT);
+    // A password from MIAO_PASSWORD is enough to skip the first-run setup page.
+    if setup_required && env_overrides.password.is_some() {
+        log_info!("MIAO_PASSWORD set, skipping first-run setup");
+        setup_required = false;
+    }
+
+    let port = env_overrides.port.or(config.port).unwrap_or(DEFAULT_PORT);
     *MIAO_PORT.lock().unwrap() = port;
+    let bind_addr = env_overrides
+        .bind_addr
+        .unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED));
+    let ui_enabled = config.ui_enabled.unwrap_or(true);
+    let max_body_bytes = config.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES).max(1024);
+    let metrics_port = config.metrics_port.filter(|p| *p != 0);
+    let tls = match tls_paths(&config) {
+        Ok(tls) => tls,
+        Err(e) => {
+            log_error!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Check sing-box binary and determine working directory
     let sing_box_home = if let Some(custom_home) = &config.sing_box_home {
@@ -10960,7 +18929,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let (loaded_subs, subscription_status) = if SUBSCRIPTIONS_ENABLED {
         log_info!("Loading subscriptions from: {}", subscriptions_root.display());
-        load_subscriptions(&config, &subscriptions_root).await
+        load_subscriptions(&config, &subscriptions_root, true).await
     } else {
         log_info!("Subscriptions disabled");
         (
@@ -10973,7 +18942,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             HashMap::new(),
         )
     };
+    // 迁移/修正按稳定 key 记录的节点选择
+    if sync_selection_keys(&mut config, &loaded_subs) && !setup_required {
+        let _ = save_config(&config).await;
+    }
     let node_type_by_tag = build_node_type_map(&config, &loaded_subs);
+    let mut node_delays = load_node_delays().await;
+    node_delays.retain(|tag, _| node_type_by_tag.contains_key(tag));
 
     if !setup_required {
         // Generate initial config
@@ -11035,13 +19010,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         subscriptions_root: subscriptions_root.clone(),
         subscription_status: Mutex::new(subscription_status),
         node_type_by_tag: Mutex::new(node_type_by_tag),
+        node_delays: Mutex::new(node_delays),
         setup_required: AtomicBool::new(setup_required),
         sing_box_pending_restart: AtomicBool::new(false),
+        regenerate_generation: AtomicU64::new(0),
         tcp_tunnel: tcp_tunnel::TunnelManager::new(),
         full_tunnel: full_tunnel::FullTunnelManager::new(),
         sync_manager: sync::SyncManager::new(),
         system_monitor: SystemMonitor::new(),
         metrics_config: config.metrics.clone(),
+        metrics_db: Arc::new(StdMutex::new(None)),
         ivnc_process: Arc::new(Mutex::new(None)),
         ivnc_config: Arc::new(Mutex::new(load_ivnc_config().await)),
     });
@@ -11057,19 +19035,79 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         app_state.sync_manager.apply_config(&cfg.syncs).await;
     }
 
+    // 同步任务每次结束后写一条运行记录到指标库（在首次定时运行前订阅）
+    {
+        let state_clone = app_state.clone();
+        let mut rx = sync::subscribe_run_records();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(record) => {
+                        if let Err(e) =
+                            with_metrics_db(&state_clone, move |conn| insert_sync_run(conn, &record)).await
+                        {
+                            log_warning!("Failed to record sync run: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        log_warning!("Dropped {} sync run records", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    // 启动时预热 system info/status 缓存（不写历史），首个请求无需等待采样；
+    // 之后由采样循环保持缓存新鲜，关闭 metrics 时循环也只刷新缓存
+    match tokio::time::timeout(
+        Duration::from_secs(SYSTEM_CACHE_WARMUP_TIMEOUT_SECS),
+        refresh_system_metrics(&app_state, false),
+    )
+    .await
+    {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log_warning!("System metrics warm-up failed: {}", e),
+        Err(_) => log_warning!("System metrics warm-up timed out"),
+    }
+
     {
         let state_clone = app_state.clone();
-        let interval_secs = app_state.metrics_config.sample_interval_secs.max(1);
+        let interval = Duration::from_secs(app_state.metrics_config.sample_interval_secs.max(1));
+        let stagger_ms = app_state.metrics_config.sample_stagger_ms;
+        let jitter_percent = app_state.metrics_config.sample_jitter_percent;
         tokio::spawn(async move {
-            if let Err(e) = refresh_system_metrics(&state_clone).await {
-                log_error!("Failed to refresh system metrics: {}", e);
+            // Stagger the first sample so it does not line up with other periodic loops.
+            if stagger_ms > 0 {
+                sleep(Duration::from_millis(random_u64() % (stagger_ms + 1))).await;
             }
-            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
             loop {
-                interval.tick().await;
-                if let Err(e) = refresh_system_metrics(&state_clone).await {
+                if let Err(e) = refresh_system_metrics(&state_clone, true).await {
                     log_error!("Failed to refresh system metrics: {}", e);
                 }
+                sleep(jitter_duration(interval, jitter_percent)).await;
+            }
+        });
+    }
+
+    if SUBSCRIPTIONS_ENABLED {
+        spawn_subscription_refresher(app_state.clone());
+    }
+
+    // 定期截断 WAL 并 VACUUM，使裁剪后的指标库文件能真正缩小
+    if app_state.metrics_config.enabled {
+        let state_clone = app_state.clone();
+        tokio::spawn(async move {
+            let mut checkpoints: u64 = 0;
+            loop {
+                sleep(Duration::from_secs(METRICS_CHECKPOINT_INTERVAL_SECS)).await;
+                checkpoints += 1;
+                let vacuum = checkpoints % METRICS_VACUUM_EVERY_CHECKPOINTS == 0;
+                if let Err(e) =
+                    with_metrics_db(&state_clone, move |conn| maintain_metrics_db(conn, vacuum)).await
+                {
+                    log_warning!("Metrics db maintenance failed: {}", e);
+                }
             }
         });
     }
@@ -11082,6 +19120,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let protected_routes = Router::new()
         // Status and service control
         .route("/api/status", get(get_status))
+        .route("/api/processes", get(get_processes))
+        .route("/api/limits", get(get_resource_limits))
+        .route("/api/config/version", get(get_config_version))
+        .route("/api/config/export", get(export_config))
+        .route("/api/config/import", post(import_config))
+        .route("/api/config/validate", post(validate_config))
+        .route("/api/tls/self-signed", post(generate_tls_self_signed))
+        .route("/api/dashboard", get(get_dashboard))
         .route("/api/binaries/status", get(get_binaries_status))
         .route("/api/binaries/install/sing-box", post(install_sing_box))
         .route("/api/binaries/install/gotty", post(install_gotty))
@@ -11092,17 +19138,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/system/info", get(get_system_info))
         .route("/api/system/status", get(get_system_status))
         .route("/api/system/metrics", get(get_system_metrics))
+        .route("/api/system/metrics/reset", post(reset_system_metrics))
+        .route("/api/metrics/prometheus", get(prometheus_metrics))
+        .route("/api/metrics/export", get(export_system_metrics))
+        .route("/api/self/metrics", get(get_self_metrics))
         .route("/api/system/tools", get(get_tools_status))
         .route("/api/password", post(update_password))
+        .route("/api/token/rotate", post(rotate_token))
+        .route("/api/token/refresh", post(refresh_token))
         .route("/api/service/start", post(start_service))
         .route("/api/service/stop", post(stop_service))
         .route("/api/service/restart", post(restart_service))
+        .route("/api/service/apply", post(apply_service))
+        .route("/api/service/regenerate", post(regenerate_service_config))
+        .route("/api/service/regenerate/preview", post(preview_regenerate))
         .route("/api/terminals", get(get_terminals))
         .route("/api/terminals", post(create_terminal))
         .route("/api/terminals/{id}", put(update_terminal).delete(delete_terminal))
         .route("/api/terminals/{id}/start", post(start_terminal))
         .route("/api/terminals/{id}/stop", post(stop_terminal))
         .route("/api/terminals/{id}/restart", post(restart_terminal))
+        .route("/api/terminals/{id}/ticket", post(create_terminal_ticket))
         .route("/api/gotty/upgrade", post(upgrade_gotty))
         // iVnc routes
         .route("/api/ivnc/install", post(install_ivnc))
@@ -11121,6 +19177,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/apps/{id}/restart", post(restart_app))
         // Connectivity test
         .route("/api/connectivity", post(test_connectivity))
+        .route("/api/connectivity/presets", get(list_connectivity_presets).post(create_connectivity_preset))
+        .route("/api/connectivity/presets/{id}", put(update_connectivity_preset).delete(delete_connectivity_preset))
+        .route("/api/connectivity/run-presets", post(run_connectivity_presets))
         // Upgrade (protected)
         .route("/api/upgrade", post(upgrade))
         .merge(
@@ -11134,6 +19193,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/clash/proxies/{node}/delay", get(clash_test_delay))
         .route("/api/clash/proxies/delay", post(clash_test_batch_delay))
         .route("/api/selections", get(get_selections))
+        .route("/api/proxy/overview", get(get_proxy_overview))
         // Subscription file management
         .route("/api/sub-files", get(get_sub_files))
         .route("/api/sub-files/reload", post(reload_sub_files))
@@ -11146,21 +19206,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/nodes", get(get_nodes))
         .route("/api/nodes", post(add_node))
         .route("/api/nodes", delete(delete_node))
+        .route("/api/nodes/health-summary", get(get_node_health_summary))
+        .route("/api/nodes/import", post(import_nodes))
+        .route("/api/nodes/export", get(export_nodes))
         // Use a standalone endpoint to avoid colliding with node tags (e.g. tag == "test")
         .route("/api/node-test", post(test_node))
         .route("/api/nodes/{tag}", get(get_node).put(update_node))
         .route("/api/dns/status", get(get_dns_status))
+        .route("/api/dns/defaults", get(get_dns_defaults))
+        .route("/api/proxy/defaults", get(get_proxy_defaults))
         .route("/api/dns/switch", post(switch_dns_active))
         // TCP reverse tunnels (SSH -R)
         .route("/api/tcp-tunnels", get(get_tcp_tunnels))
         .route("/api/tcp-tunnels", post(create_tcp_tunnel))
         .route("/api/tcp-tunnels/{id}", put(update_tcp_tunnel))
         .route("/api/tcp-tunnels/{id}", delete(delete_tcp_tunnel))
+        .route("/api/tcp-tunnels/{id}/effective", get(get_tcp_tunnel_effective))
         .route("/api/tcp-tunnels/{id}/start", post(start_tcp_tunnel))
         .route("/api/tcp-tunnels/{id}/stop", post(stop_tcp_tunnel))
         .route("/api/tcp-tunnels/{id}/restart", post(restart_tcp_tunnel))
         .route("/api/tcp-tunnels/{id}/test", post(test_tcp_tunnel))
         .route("/api/tcp-tunnels/{id}/copy", post(copy_tcp_tunnel))
+        .route("/api/tcp-tunnels/{id}/save-host", post(save_tcp_tunnel_as_host))
         .route("/api/tcp-tunnels/bulk/start", post(bulk_start_tcp_tunnels))
         .route("/api/tcp-tunnels/bulk/stop", post(bulk_stop_tcp_tunnels))
         .route("/api/tcp-tunnel/overview", get(get_tcp_tunnel_overview))
@@ -11172,6 +19239,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/tcp-tunnel-sets/{id}/tunnels", get(get_tcp_tunnel_set_tunnels))
         .route("/api/tcp-tunnel-sets/{id}/copy", post(copy_tcp_tunnel_set))
         .route("/api/tcp-tunnel-sets/{id}/test", post(test_tcp_tunnel_set))
+        .route("/api/tcp-tunnel-sets/{id}/scan-now", post(scan_tcp_tunnel_set_now))
         .route("/api/tcp-tunnel-sets/bulk/start", post(bulk_start_tcp_tunnel_sets))
         .route("/api/tcp-tunnel-sets/bulk/stop", post(bulk_stop_tcp_tunnel_sets))
         .route("/api/syncs", get(get_syncs))
@@ -11180,9 +19248,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/syncs/{id}/start", post(start_sync))
         .route("/api/syncs/{id}/stop", post(stop_sync))
         .route("/api/syncs/{id}/run", post(run_sync))
+        .route("/api/syncs/{id}/dry-run", post(dry_run_sync))
+        .route("/api/syncs/{id}/save-host", post(save_sync_as_host))
         .route("/api/syncs/{id}/schedule", post(toggle_schedule_sync))
         .route("/api/syncs/{id}/logs", get(get_sync_logs))
+        .route("/api/syncs/{id}/history", get(get_sync_history))
         .route("/api/syncs/{id}/ws/logs", get(sync_ws_logs))
+        .route("/api/logs", get(get_logs))
         .route("/api/sing-box/logs", get(get_sing_box_logs))
         .route("/api/sing-box/ws/logs", get(sing_box_ws_logs))
         .route("/api/apps/{id}/logs", get(get_app_logs))
@@ -11195,13 +19267,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .merge(app::host_groups::routes())
         // Host Execute
         .merge(app::host_execute::routes())
-        .route_layer(middleware::from_fn(auth_middleware));  // 应用认证中间件
+        .route_layer(middleware::from_fn(auth_middleware))  // 应用认证中间件
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::map_response(move |res: Response| {
+            json_payload_too_large(max_body_bytes, res)
+        }));
 
     // 公开路由（不需要认证）
     let ws_routes = Router::new()
         .route("/api/clash/ws/traffic", get(clash_ws_traffic))
         .route("/api/clash/ws/logs", get(clash_ws_logs))
-        .route("/api/upgrade/ws", get(upgrade_ws));
+        .route("/api/upgrade/ws", get(upgrade_ws))
+        .route("/api/tcp-tunnels/status/ws", get(tcp_tunnel_status_ws))
+        .route("/api/syncs/{id}/progress", get(sync_ws_progress))
+        // 内嵌终端的 gotty 代理，凭一次性票据认证
+        .route("/api/terminals/{id}/ws", get(terminal_ws_proxy))
+        // SSE variants for clients behind WebSocket-hostile proxies
+        .route("/api/logs/sse", get(logs_sse))
+        .route("/api/system/status/sse", get(system_status_sse));
 
     let app = Router::new()
         // API routes (highest priority)
@@ -11209,25 +19292,369 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/api/setup/init", post(setup_init))
         .route("/api/login", post(login))
         .route("/api/version", get(get_version))
+        // Liveness / readiness probes (no auth)
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/api/openapi.json", get(get_openapi))
         // Gotty injection script
         .route("/miao-inject/restart-button.js", get(serve_gotty_restart_script))
         // No-auth restart endpoint for gotty inject script
         .route("/api/terminals/restart-by-port", post(restart_terminal_by_port))
         .merge(ws_routes)
         .merge(protected_routes)
-        // Static assets route (matches files in public/)
-        .route("/{*path}", get(serve_static))
-        .with_state(app_state)
-        // SPA fallback (must be last, catches all unmatched routes)
-        .fallback(spa_fallback);
+        // Unknown API paths get a JSON 404 instead of the SPA page
+        .route("/api/{*path}", any(api_not_found));
+
+    if let Some(metrics_port) = metrics_port {
+        if metrics_port == port {
+            log_warning!("metrics_port {} equals the main port, metrics listener disabled", metrics_port);
+        } else {
+            spawn_metrics_listener(app_state.clone(), metrics_port).await;
+        }
+    }
+
+    let app = if ui_enabled {
+        app
+            // Static assets route (matches files in public/)
+            .route("/{*path}", get(serve_static))
+            .with_state(app_state)
+            // SPA fallback (must be last, catches all unmatched routes)
+            .fallback(spa_fallback)
+    } else {
+        log_info!("UI disabled, serving API only");
+        app.with_state(app_state).fallback(not_found_fallback)
+    };
 
     // Fix sing-box routing rules
     if let Err(e) = fix_singbox_routes().await {
         log_error!("Failed to fix sing-box routes: {}", e);
     }
 
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
-    log_info!("✅ Miao 控制面板已启动: http://localhost:{}", port);
-    axum::serve(listener, app).await?;
+    let listener = match bind_listener(bind_addr, port).await {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            log_error!(
+                "❌ 端口 {} 已被占用 (address in use)。请停止占用该端口的进程（如旧的 miao 实例: `ss -ltnp | grep :{}`），或在 config.yaml（或 MIAO_PORT）中修改端口",
+                port,
+                port
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            log_error!("❌ 无法监听端口 {}: {}", port, e);
+            std::process::exit(1);
+        }
+    };
+
+    let Some((cert, key)) = tls else {
+        log_info!("✅ Miao 控制面板已启动: http://localhost:{}", port);
+        axum::serve(listener, app).await?;
+        return Ok(());
+    };
+
+    // reqwest 也会启用 rustls，显式选定 ring 避免多个 CryptoProvider 时的歧义
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let tls_config = match axum_server::tls_rustls::RustlsConfig::from_pem_file(&cert, &key).await {
+        Ok(c) => c,
+        Err(e) => {
+            // 不回退到 HTTP，避免密码在明文连接上传输
+            log_error!("❌ 无法加载 TLS 证书 {} / {}: {}", cert, key, e);
+            std::process::exit(1);
+        }
+    };
+    spawn_tls_reloader(tls_config.clone(), cert, key);
+    log_info!("✅ Miao 控制面板已启动: https://localhost:{}", port);
+    axum_server::from_tcp_rustls(listener.into_std()?, tls_config)
+        .serve(app.into_make_service())
+        .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_host_port_brackets_ipv6_only() {
+        assert_eq!(join_host_port("2001:db8::1", 8388), "[2001:db8::1]:8388");
+        assert_eq!(join_host_port("[2001:db8::1]", 8388), "[2001:db8::1]:8388");
+        assert_eq!(join_host_port("::1", 22), "[::1]:22");
+        assert_eq!(join_host_port("192.0.2.10", 443), "192.0.2.10:443");
+        assert_eq!(join_host_port("example.com", 443), "example.com:443");
+        assert_eq!(join_host_port(" example.com ", "80"), "example.com:80");
+    }
+
+    #[test]
+    fn parse_single_ss_url_accepts_bracketed_ipv6() {
+        let (tag, outbound) =
+            parse_single_ss_url("ss://YWVzLTI1Ni1nY206cGFzcw==@[2001:db8::1]:8388").unwrap();
+        assert_eq!(tag, "[2001:db8::1]:8388");
+        assert_eq!(outbound["server"], "2001:db8::1");
+        assert_eq!(outbound["server_port"], 8388);
+        assert_eq!(outbound["method"], "aes-256-gcm");
+        assert_eq!(outbound["password"], "pass");
+
+        let (tag, outbound) =
+            parse_single_ss_url("ss://YWVzLTI1Ni1nY206cGFzcw==@192.0.2.10:8388#my%20node").unwrap();
+        assert_eq!(tag, "my node");
+        assert_eq!(outbound["server"], "192.0.2.10");
+    }
+
+    #[test]
+    fn parse_share_link_cases() {
+        use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+        let vmess = json!({
+            "ps": "jp 1", "add": "vmess.example.com", "port": "443", "id": "uuid-1",
+            "aid": 0, "net": "ws", "path": "/ws", "host": "cdn.example.com", "tls": "tls",
+        })
+        .to_string();
+        let cases = [
+            (
+                "ss://YWVzLTI1Ni1nY206cGFzcw==@192.0.2.10:8388#hk".to_string(),
+                "hk",
+                vec![("/type", json!("shadowsocks")), ("/server_port", json!(8388))],
+            ),
+            (
+                format!("vmess://{}", STANDARD.encode(&vmess)),
+                "jp 1",
+                vec![
+                    ("/type", json!("vmess")),
+                    ("/server_port", json!(443)),
+                    ("/uuid", json!("uuid-1")),
+                    ("/security", json!("auto")),
+                    ("/tls/server_name", json!("cdn.example.com")),
+                    ("/transport/type", json!("ws")),
+                    ("/transport/headers/Host", json!("cdn.example.com")),
+                ],
+            ),
+            (
+                // 无填充的 base64
+                format!("vmess://{}", STANDARD_NO_PAD.encode(&vmess)),
+                "jp 1",
+                vec![("/server", json!("vmess.example.com"))],
+            ),
+            (
+                "hysteria2://pa%40ss@example.com/?sni=sni.example.com&insecure=1#us%201".to_string(),
+                "us 1",
+                vec![
+                    ("/type", json!("hysteria2")),
+                    ("/server_port", json!(443)),
+                    ("/password", json!("pa@ss")),
+                    ("/tls/server_name", json!("sni.example.com")),
+                    ("/tls/insecure", json!(true)),
+                ],
+            ),
+            (
+                "hy2://user:pass@[2001:db8::1]:8443?peer=p.example.com&obfs=salamander&obfs-password=x".to_string(),
+                "[2001:db8::1]:8443",
+                vec![
+                    ("/server", json!("2001:db8::1")),
+                    ("/password", json!("user:pass")),
+                    ("/tls/server_name", json!("p.example.com")),
+                    ("/tls/insecure", json!(false)),
+                    ("/obfs", json!({ "type": "salamander", "password": "x" })),
+                ],
+            ),
+        ];
+        for (link, want_tag, fields) in cases {
+            let (tag, outbound) = parse_share_link(&link).unwrap();
+            assert_eq!(tag, want_tag, "{}", link);
+            for (pointer, want) in fields {
+                assert_eq!(outbound.pointer(pointer), Some(&want), "{} {}", link, pointer);
+            }
+        }
+
+        let vmess_link = |v: serde_json::Value| format!("vmess://{}", STANDARD.encode(v.to_string()));
+        let errors = [
+            ("http://example.com/sub".to_string(), "Unsupported link scheme: http://"),
+            ("not a link".to_string(), "Not a share link"),
+            ("ss://no-userinfo".to_string(), "Invalid ss:// link"),
+            ("vmess://!!!".to_string(), "Invalid vmess:// link: payload is not base64"),
+            (vmess_link(json!({ "port": 443, "id": "u" })), "Invalid vmess:// link: missing add"),
+            (vmess_link(json!({ "add": "a", "port": "x", "id": "u" })), "Invalid vmess:// link: bad port"),
+            (vmess_link(json!({ "add": "a", "port": 443 })), "Invalid vmess:// link: missing id"),
+            (vmess_link(json!({ "add": "a", "port": 443, "id": "u", "net": "kcp" })), "Unsupported vmess network: kcp"),
+            ("hysteria2://@example.com:443".to_string(), "Invalid hysteria2:// link: missing password"),
+        ];
+        for (link, want) in errors {
+            assert_eq!(parse_share_link(&link).unwrap_err(), want, "{}", link);
+        }
+    }
+
+    #[test]
+    fn try_parse_share_links_cases() {
+        use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+        let ss = "ss://YWVzLTI1Ni1nY206cGFzcw==@192.0.2.10:8388#hk";
+        let hy2 = "hy2://secret@example.com:8443#us";
+        let list = format!("{}\r\nvless://uuid@example.com:443#skip\n\n{}\n", ss, hy2);
+        // 按 76 列折行的 base64 也要能识别
+        let wrapped = STANDARD
+            .encode(&list)
+            .as_bytes()
+            .chunks(76)
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let cases: [(String, Option<Result<Vec<&str>, &str>>); 9] = [
+            (String::new(), None),
+            ("  \n ".to_string(), None),
+            ("proxies:\n  - name: hk\n    type: ss\n".to_string(), None),
+            (r#"{"outbounds": []}"#.to_string(), None),
+            (list.clone(), Some(Ok(vec!["hk", "us"]))),
+            (STANDARD_NO_PAD.encode(&list), Some(Ok(vec!["hk", "us"]))),
+            (wrapped, Some(Ok(vec!["hk", "us"]))),
+            (
+                STANDARD.encode("hello world"),
+                Some(Err("Base64 subscription payload contains no share links")),
+            ),
+            (
+                "vless://uuid@example.com:443#a\nvmess://!!!".to_string(),
+                Some(Err("No usable share links (2 failed: Unsupported link scheme: vless://)")),
+            ),
+        ];
+        for (text, want) in cases {
+            let got = try_parse_share_links(&text)
+                .map(|r| r.map(|(names, _)| names).map_err(|e| e.to_string()));
+            let want = want.map(|r| {
+                r.map(|names| names.into_iter().map(String::from).collect::<Vec<_>>())
+                    .map_err(String::from)
+            });
+            assert_eq!(got, want, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn terminal_ticket_is_single_use_and_scoped() {
+        let ticket = issue_terminal_ticket("t1");
+        assert!(!consume_terminal_ticket(&ticket, "t2"));
+        // 用错终端同样会作废票据
+        assert!(!consume_terminal_ticket(&ticket, "t1"));
+
+        let ticket = issue_terminal_ticket("t1");
+        assert!(consume_terminal_ticket(&ticket, "t1"));
+        assert!(!consume_terminal_ticket(&ticket, "t1"));
+        assert!(!consume_terminal_ticket("unknown", "t1"));
+
+        let expired = issue_terminal_ticket("t1");
+        TERMINAL_TICKETS.lock().unwrap().get_mut(&expired).unwrap().1 = Instant::now();
+        assert!(!consume_terminal_ticket(&expired, "t1"));
+    }
+
+    #[test]
+    fn normalize_sync_command_cases() {
+        let cases = [
+            (None, None),
+            (Some(""), None),
+            (Some("   \n\t"), None),
+            (Some("systemctl reload nginx"), Some("systemctl reload nginx")),
+            (Some("  ./deploy.sh --fast \n"), Some("./deploy.sh --fast")),
+        ];
+        for (input, want) in cases {
+            assert_eq!(
+                normalize_sync_command(input.map(String::from)),
+                want.map(String::from),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn subscription_headers_reject_hop_by_hop() {
+        let cases = [
+            ("Authorization", Ok(())),
+            ("X-Token", Ok(())),
+            ("Connection", Err("不允许设置请求头: Connection")),
+            ("keep-alive", Err("不允许设置请求头: keep-alive")),
+            ("Proxy-Connection", Err("不允许设置请求头: Proxy-Connection")),
+            ("TE", Err("不允许设置请求头: TE")),
+            ("Trailer", Err("不允许设置请求头: Trailer")),
+            (" Transfer-Encoding ", Err("不允许设置请求头: Transfer-Encoding")),
+            ("Upgrade", Err("不允许设置请求头: Upgrade")),
+            ("Host", Err("不允许设置请求头: Host")),
+            ("content-length", Err("不允许设置请求头: content-length")),
+            ("bad header", Err("无效的请求头名称: bad header")),
+        ];
+        for (name, want) in cases {
+            let input = SubscriptionSourceInput::Url {
+                url: "https://example.com/sub".to_string(),
+                headers: Some(HashMap::from([(name.to_string(), "v".to_string())])),
+                user_agent: None,
+            };
+            let got = validate_subscription_source(&input, None).map(|_| ());
+            assert_eq!(got, want.map_err(String::from), "{:?}", name);
+        }
+    }
+
+    lazy_static! {
+        /// 改动进程环境变量或工作目录的测试在此串行，避免并发测试互相干扰
+        static ref PROCESS_ENV_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    /// 桩模式下的最小 AppState，config.yaml 写到独立的临时目录；
+    /// 返回的 guard 需持有到测试结束
+    async fn stub_app_state() -> (tokio::sync::MutexGuard<'static, ()>, Arc<AppState>) {
+        let guard = PROCESS_ENV_LOCK.lock().await;
+        std::env::set_var("MIAO_STUB_PROCESSES", "1");
+        assert!(process_stub_mode());
+        let dir = std::env::temp_dir().join(format!("miao-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        env::set_current_dir(&dir).unwrap();
+
+        let config: Config = serde_yaml::from_str("{}").unwrap();
+        let state = Arc::new(AppState {
+            metrics_config: config.metrics.clone(),
+            config: Mutex::new(config),
+            sing_box_home: dir.display().to_string(),
+            subscriptions_root: dir.join("sub"),
+            subscription_status: Mutex::new(HashMap::new()),
+            node_type_by_tag: Mutex::new(HashMap::new()),
+            node_delays: Mutex::new(HashMap::new()),
+            setup_required: AtomicBool::new(false),
+            sing_box_pending_restart: AtomicBool::new(false),
+            regenerate_generation: AtomicU64::new(0),
+            tcp_tunnel: tcp_tunnel::TunnelManager::new(),
+            full_tunnel: full_tunnel::FullTunnelManager::new(),
+            sync_manager: sync::SyncManager::new(),
+            system_monitor: SystemMonitor::new(),
+            metrics_db: Arc::new(StdMutex::new(None)),
+            ivnc_process: Arc::new(Mutex::new(None)),
+            ivnc_config: Arc::new(Mutex::new(IVncConfig::default())),
+        });
+        (guard, state)
+    }
+
+    #[tokio::test]
+    async fn terminal_lifecycle_in_stub_mode() {
+        let (_env, state) = stub_app_state().await;
+        let req: TerminalUpsertRequest = serde_json::from_value(json!({
+            "name": "stub",
+            "enabled": true,
+            "port": 17681,
+            "command": "bash",
+            "startup_check_ms": 100,
+        }))
+        .unwrap();
+
+        let created = create_terminal(State(state.clone()), Json(req))
+            .await
+            .unwrap_or_else(|_| panic!("create_terminal failed"));
+        let item = created.0.data.expect("terminal item");
+        assert!(item.status.running);
+        let id = item.id;
+        assert!(state.config.lock().await.terminals.iter().any(|t| t.id == id && t.enabled));
+
+        assert!(stop_terminal(State(state.clone()), Path(id.clone())).await.is_ok());
+        assert!(!get_terminal_runtime_status(&id).await.running);
+        assert!(!state.config.lock().await.terminals[0].enabled);
+
+        assert!(start_terminal(State(state.clone()), Path(id.clone())).await.is_ok());
+        assert!(get_terminal_runtime_status(&id).await.running);
+        assert!(state.config.lock().await.terminals[0].enabled);
+
+        assert!(delete_terminal(State(state.clone()), Path(id.clone())).await.is_ok());
+        assert!(!get_terminal_runtime_status(&id).await.running);
+        assert!(state.config.lock().await.terminals.is_empty());
+        assert!(StdPath::new(CONFIG_FILE).is_file());
+    }
+}
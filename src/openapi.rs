@@ -0,0 +1,114 @@
+// OpenAPI 文档
+// OpenAPI document served at GET /api/openapi.json
+
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::*;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Miao API", description = "Miao control panel HTTP API"),
+    paths(
+        // System / metrics
+        crate::get_system_info,
+        crate::get_system_status,
+        crate::get_system_metrics,
+        crate::reset_system_metrics,
+        // Nodes
+        crate::get_nodes,
+        crate::add_node,
+        crate::delete_node,
+        crate::get_node,
+        crate::update_node,
+        crate::import_nodes,
+        crate::test_node,
+        crate::test_connectivity,
+        // TCP tunnels
+        crate::get_tcp_tunnels,
+        crate::create_tcp_tunnel,
+        crate::update_tcp_tunnel,
+        crate::delete_tcp_tunnel,
+        crate::get_tcp_tunnel_effective,
+        crate::start_tcp_tunnel,
+        crate::stop_tcp_tunnel,
+        crate::restart_tcp_tunnel,
+        // Full tunnels (tunnel sets)
+        crate::get_tcp_tunnel_sets,
+        crate::create_tcp_tunnel_set,
+        crate::get_tcp_tunnel_set,
+        crate::update_tcp_tunnel_set,
+        crate::delete_tcp_tunnel_set,
+        crate::start_tcp_tunnel_set,
+        crate::stop_tcp_tunnel_set,
+        crate::restart_tcp_tunnel_set,
+        // Syncs
+        crate::get_syncs,
+        crate::create_sync,
+        crate::update_sync,
+        crate::delete_sync,
+        crate::start_sync,
+        crate::stop_sync,
+        crate::run_sync,
+        crate::dry_run_sync,
+    ),
+    components(schemas(
+        ApiMessage,
+        NodeListApiResponse,
+        NodeDetailApiResponse,
+        NodeTestApiResponse,
+        ConnectivityApiResponse,
+        NodeInfo,
+        NodeDetailResponse,
+        NodeRequest,
+        NodeUpdateRequest,
+        DeleteNodeRequest,
+        NodeImportRequest,
+        NodeImportResult,
+        NodeImportResponse,
+        NodeTestRequest,
+        NodeTestResponse,
+        ConnectivityRequest,
+        ConnectivityMethod,
+        ConnectivityResult,
+        TcpTunnelAuth,
+        TcpTunnelBackoff,
+        TcpTunnelDirection,
+        TcpTunnelUpsertRequest,
+        TcpTunnelSetCreateRequest,
+        TcpTunnelSetPortStrategy,
+        TcpTunnelSetScanMethod,
+        SyncUpsertRequest,
+        SyncOptions,
+        SyncSchedule,
+    )),
+    tags(
+        (name = "system", description = "System info and metrics"),
+        (name = "nodes", description = "Manual proxy nodes"),
+        (name = "connectivity", description = "Connectivity tests"),
+        (name = "tunnels", description = "SSH reverse TCP tunnels"),
+        (name = "tunnel-sets", description = "Full tunnels (port sets)"),
+        (name = "syncs", description = "File sync tasks"),
+    ),
+    modifiers(&BearerAuth),
+)]
+pub struct ApiDoc;
+
+/// 注册 JWT Bearer 认证方式（POST /api/login 获取 token）
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,429 @@
+// 配置文件敏感字段加密
+// Encrypt-at-rest for secret fields in config.yaml (login password, JWT secret, SSH passwords,
+// key passphrases, terminal basic-auth passwords, node passwords/uuids, Git tokens and
+// sensitive subscription request headers).
+//
+// Encrypted values are stored as `enc:v2:<base64(salt || nonce || ciphertext)>` using AES-256-GCM.
+// The per-value key is HKDF-SHA256 over MIAO_SECRET_KEY when set, otherwise over the machine id,
+// with the random salt, so a copied config.yaml cannot be decrypted on another host.
+// `enc:v1:` values (key = bare SHA-256 of the same input) are still read and are rewritten as v2
+// on the next save. Non-secret fields stay plain YAML.
+//
+// In memory every secret is plaintext, so saving seals each non-empty value unconditionally:
+// a password that merely starts with `enc:v2:` is encrypted like any other value instead of
+// being written verbatim and misread as ciphertext on the next load.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    is_sensitive_header, Config, GitCredentials, HostAuth, SubscriptionSource, TcpTunnelAuth,
+    REDACTED_SECRET,
+};
+
+pub const SECRET_KEY_ENV: &str = "MIAO_SECRET_KEY";
+const ENCRYPTED_PREFIX: &str = "enc:v2:";
+const LEGACY_ENCRYPTED_PREFIX: &str = "enc:v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HKDF_INFO: &[u8] = b"miao config secret v2";
+const MACHINE_ID_PATHS: [&str; 2] = ["/etc/machine-id", "/var/lib/dbus/machine-id"];
+/// 节点 JSON（及其 obfs 对象）中的凭据字段
+const NODE_SECRET_FIELDS: [&str; 2] = ["password", "uuid"];
+
+/// 密钥材料：MIAO_SECRET_KEY 优先，否则为本机 machine id（带来源前缀以区分两种输入）
+fn key_material() -> Result<Vec<u8>, String> {
+    match std::env::var(SECRET_KEY_ENV) {
+        Ok(value) if !value.trim().is_empty() => {
+            Ok([b"miao-secret-env:".as_slice(), value.trim().as_bytes()].concat())
+        }
+        _ => {
+            let machine_id = MACHINE_ID_PATHS
+                .iter()
+                .filter_map(|p| std::fs::read_to_string(p).ok())
+                .map(|s| s.trim().to_string())
+                .find(|s| !s.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "no machine id found ({}), set {} to encrypt secrets",
+                        MACHINE_ID_PATHS.join(", "),
+                        SECRET_KEY_ENV
+                    )
+                })?;
+            Ok([b"miao-secret-machine:".as_slice(), machine_id.as_bytes()].concat())
+        }
+    }
+}
+
+fn derive_cipher(ikm: &[u8], salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(HKDF_INFO, &mut key)
+        .map_err(|_| "failed to derive secret key".to_string())?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+/// enc:v1 的密钥：密钥材料的 SHA-256，仅用于读取旧配置
+fn legacy_cipher(ikm: &[u8]) -> Aes256Gcm {
+    let digest = Sha256::digest(ikm);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&digest))
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX) || value.starts_with(LEGACY_ENCRYPTED_PREFIX)
+}
+
+fn encrypt_value(ikm: &[u8], plain: &str) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = derive_cipher(ikm, &salt)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain.as_bytes())
+        .map_err(|_| "failed to encrypt secret".to_string())?;
+    let mut raw = salt.to_vec();
+    raw.extend_from_slice(&nonce);
+    raw.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{}{}",
+        ENCRYPTED_PREFIX,
+        base64::engine::general_purpose::STANDARD.encode(raw)
+    ))
+}
+
+fn decrypt_value(ikm: &[u8], value: &str) -> Result<String, String> {
+    let (legacy, encoded) = match value.strip_prefix(ENCRYPTED_PREFIX) {
+        Some(rest) => (false, rest),
+        None => (true, &value[LEGACY_ENCRYPTED_PREFIX.len()..]),
+    };
+    let raw = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("invalid encrypted secret: {}", e))?;
+    let (cipher, rest) = if legacy {
+        (legacy_cipher(ikm), raw.as_slice())
+    } else {
+        if raw.len() <= SALT_LEN {
+            return Err("invalid encrypted secret: too short".to_string());
+        }
+        let (salt, rest) = raw.split_at(SALT_LEN);
+        (derive_cipher(ikm, salt)?, rest)
+    };
+    if rest.len() <= NONCE_LEN {
+        return Err("invalid encrypted secret: too short".to_string());
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            format!(
+                "failed to decrypt secret (wrong {} or config copied from another machine?)",
+                SECRET_KEY_ENV
+            )
+        })?;
+    String::from_utf8(plain).map_err(|_| "decrypted secret is not valid UTF-8".to_string())
+}
+
+fn visit_tunnel_auth(
+    auth: &mut TcpTunnelAuth,
+    f: &mut dyn FnMut(&mut String) -> Result<(), String>,
+) -> Result<(), String> {
+    match auth {
+        TcpTunnelAuth::Password { password } => f(password),
+        TcpTunnelAuth::PrivateKeyPath { passphrase, .. } => passphrase.as_mut().map_or(Ok(()), f),
+    }
+}
+
+fn visit_host_auth(
+    auth: &mut HostAuth,
+    f: &mut dyn FnMut(&mut String) -> Result<(), String>,
+) -> Result<(), String> {
+    match auth {
+        HostAuth::Password { password } => password.as_mut().map_or(Ok(()), f),
+        HostAuth::PrivateKeyPath { passphrase, .. } => passphrase.as_mut().map_or(Ok(()), f),
+    }
+}
+
+fn visit_node_fields(
+    obj: &mut serde_json::Map<String, serde_json::Value>,
+    f: &mut dyn FnMut(&mut String) -> Result<(), String>,
+    changed: &mut bool,
+) -> Result<(), String> {
+    for field in NODE_SECRET_FIELDS {
+        if let Some(serde_json::Value::String(value)) = obj.get_mut(field) {
+            let before = value.clone();
+            f(value)?;
+            *changed |= *value != before;
+        }
+    }
+    Ok(())
+}
+
+/// 手动节点以 JSON 字符串保存，只有凭据字段被改写时才重新序列化
+fn visit_node_secrets(
+    node: &mut String,
+    f: &mut dyn FnMut(&mut String) -> Result<(), String>,
+) -> Result<(), String> {
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(node) else {
+        return Ok(());
+    };
+    let Some(obj) = value.as_object_mut() else {
+        return Ok(());
+    };
+    let mut changed = false;
+    visit_node_fields(obj, f, &mut changed)?;
+    if let Some(obfs) = obj.get_mut("obfs").and_then(|v| v.as_object_mut()) {
+        visit_node_fields(obfs, f, &mut changed)?;
+    }
+    if changed {
+        *node = serde_json::to_string(&value).map_err(|e| format!("invalid node JSON: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 遍历配置中所有敏感字段
+fn visit_secrets(
+    config: &mut Config,
+    f: &mut dyn FnMut(&mut String) -> Result<(), String>,
+) -> Result<(), String> {
+    if let Some(password) = config.password.as_mut() {
+        f(password)?;
+    }
+    if let Some(secret) = config.jwt_secret.as_mut() {
+        f(secret)?;
+    }
+    if let Some(auth) = config.default_ssh_auth.as_mut() {
+        visit_tunnel_auth(auth, f)?;
+    }
+    for host in config.hosts.iter_mut() {
+        visit_host_auth(&mut host.auth, f)?;
+    }
+    for tunnel in config.tcp_tunnels.iter_mut() {
+        visit_tunnel_auth(&mut tunnel.auth, f)?;
+    }
+    for set in config.tcp_tunnel_sets.iter_mut() {
+        visit_tunnel_auth(&mut set.auth, f)?;
+    }
+    for sync in config.syncs.iter_mut() {
+        visit_tunnel_auth(&mut sync.ssh.auth, f)?;
+    }
+    if let Some(password) = config.terminal.as_mut().and_then(|t| t.auth_password.as_mut()) {
+        f(password)?;
+    }
+    for terminal in config.terminals.iter_mut() {
+        if let Some(password) = terminal.auth_password.as_mut() {
+            f(password)?;
+        }
+    }
+    for node in config.nodes.iter_mut() {
+        visit_node_secrets(node, f)?;
+    }
+    for sub in config.subscriptions.iter_mut() {
+        match &mut sub.source {
+            SubscriptionSource::Url { headers, .. } => {
+                for (name, value) in headers.iter_mut() {
+                    if is_sensitive_header(name) {
+                        f(value)?;
+                    }
+                }
+            }
+            SubscriptionSource::Git {
+                credentials: Some(GitCredentials::Token { token, .. }),
+                ..
+            } => f(token)?,
+            SubscriptionSource::Git { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+/// 加载后解密：返回 (已解密数量, 明文数量)
+pub fn decrypt_config_secrets(config: &mut Config) -> Result<(usize, usize), String> {
+    let mut ikm: Option<Vec<u8>> = None;
+    let mut decrypted = 0;
+    let mut plaintext = 0;
+    visit_secrets(config, &mut |value| {
+        if value.is_empty() {
+            return Ok(());
+        }
+        if !is_encrypted(value) {
+            plaintext += 1;
+            return Ok(());
+        }
+        if ikm.is_none() {
+            ikm = Some(key_material()?);
+        }
+        *value = decrypt_value(ikm.as_deref().unwrap(), value)?;
+        decrypted += 1;
+        Ok(())
+    })?;
+    Ok((decrypted, plaintext))
+}
+
+/// 保存前加密（作用于配置副本，内存中始终是明文，因此不按前缀跳过任何值）
+pub fn encrypt_config_secrets(config: &mut Config) -> Result<(), String> {
+    let ikm = key_material()?;
+    visit_secrets(config, &mut |value| {
+        if value.is_empty() {
+            return Ok(());
+        }
+        *value = encrypt_value(&ikm, value)?;
+        Ok(())
+    })
+}
+
+/// 导出前把所有敏感字段替换为占位符
+pub fn redact_config_secrets(config: &mut Config) {
+    let _ = visit_secrets(config, &mut |value| {
+        if !value.is_empty() {
+            *value = REDACTED_SECRET.to_string();
+        }
+        Ok(())
+    });
+}
+
+fn restore_node_fields(
+    value: &mut serde_json::Value,
+    old: &serde_json::Value,
+    changed: &mut bool,
+) {
+    for field in NODE_SECRET_FIELDS {
+        let Some(old_value) = old.get(field).and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if let Some(v) = value.get_mut(field) {
+            if v.as_str() == Some(REDACTED_SECRET) {
+                *v = serde_json::Value::String(old_value.to_string());
+                *changed = true;
+            }
+        }
+    }
+}
+
+/// 导入时把节点 JSON 中的占位符还原为当前配置中同 tag 节点的原值
+pub fn restore_node_secrets(node: &mut String, current: &[String]) {
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(node) else {
+        return;
+    };
+    let Some(tag) = value.get("tag").and_then(|t| t.as_str()).map(str::to_string) else {
+        return;
+    };
+    let Some(old) = current
+        .iter()
+        .filter_map(|n| serde_json::from_str::<serde_json::Value>(n).ok())
+        .find(|n| n.get("tag").and_then(|t| t.as_str()) == Some(tag.as_str()))
+    else {
+        return;
+    };
+    let mut changed = false;
+    restore_node_fields(&mut value, &old, &mut changed);
+    if let (Some(obfs), Some(old_obfs)) = (value.get_mut("obfs"), old.get("obfs")) {
+        restore_node_fields(obfs, old_obfs, &mut changed);
+    }
+    if changed {
+        if let Ok(restored) = serde_json::to_string(&value) {
+            *node = restored;
+        }
+    }
+}
+
+/// 仍为占位符（无法还原）的敏感字段数量
+pub fn count_redacted_secrets(config: &mut Config) -> usize {
+    let mut count = 0;
+    let _ = visit_secrets(config, &mut |value| {
+        if *value == REDACTED_SECRET {
+            count += 1;
+        }
+        Ok(())
+    });
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IKM: &[u8] = b"miao-secret-env:test-key";
+
+    /// 按 enc:v1 格式（SHA-256 密钥，nonce || ciphertext）构造旧配置中的密文
+    fn legacy_encrypt(ikm: &[u8], plain: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = legacy_cipher(ikm).encrypt(&nonce, plain.as_bytes()).unwrap();
+        let mut raw = nonce.to_vec();
+        raw.extend_from_slice(&ciphertext);
+        format!(
+            "{}{}",
+            LEGACY_ENCRYPTED_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(raw)
+        )
+    }
+
+    #[test]
+    fn v2_round_trip() {
+        for plain in ["secret", "p@ss word", "密码", "enc:v2:looks-encrypted", "enc:v1:AAAA"] {
+            let sealed = encrypt_value(IKM, plain).unwrap();
+            assert!(sealed.starts_with(ENCRYPTED_PREFIX), "{}", plain);
+            assert_ne!(encrypt_value(IKM, plain).unwrap(), sealed, "salt must differ: {}", plain);
+            assert_eq!(decrypt_value(IKM, &sealed).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn legacy_v1_is_readable() {
+        for plain in ["secret", "密码"] {
+            let sealed = legacy_encrypt(IKM, plain);
+            assert!(is_encrypted(&sealed));
+            assert_eq!(decrypt_value(IKM, &sealed).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn decrypt_rejects_bad_input() {
+        let sealed = encrypt_value(IKM, "secret").unwrap();
+        let cases = [
+            ("wrong key", b"miao-secret-env:other".as_slice(), sealed.clone()),
+            ("not base64", IKM, format!("{}!!!", ENCRYPTED_PREFIX)),
+            ("too short", IKM, format!("{}AAAA", ENCRYPTED_PREFIX)),
+            ("legacy too short", IKM, format!("{}AAAA", LEGACY_ENCRYPTED_PREFIX)),
+        ];
+        for (name, ikm, value) in cases {
+            assert!(decrypt_value(ikm, &value).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn visit_secrets_covers_subscription_headers() {
+        let mut config: Config = serde_yaml::from_str(
+            r#"
+subscriptions:
+  - id: a
+    type: url
+    url: https://example.com/sub
+    headers:
+      Authorization: Bearer abc
+      X-Api-Key: k
+      Accept: text/plain
+"#,
+        )
+        .unwrap();
+        let mut seen = Vec::new();
+        visit_secrets(&mut config, &mut |value| {
+            seen.push(value.clone());
+            Ok(())
+        })
+        .unwrap();
+        seen.sort();
+        assert_eq!(seen, vec!["Bearer abc".to_string(), "k".to_string()]);
+
+        redact_config_secrets(&mut config);
+        let SubscriptionSource::Url { headers, .. } = &config.subscriptions[0].source else {
+            panic!("expected url subscription");
+        };
+        assert_eq!(headers["Authorization"], REDACTED_SECRET);
+        assert_eq!(headers["X-Api-Key"], REDACTED_SECRET);
+        assert_eq!(headers["Accept"], "text/plain");
+    }
+}
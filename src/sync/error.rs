@@ -15,6 +15,7 @@ pub enum SyncError {
     RemoteError(String),
     Cancelled,
     IoError(String),
+    HookError(String),
 }
 
 impl fmt::Display for SyncError {
@@ -32,6 +33,7 @@ impl fmt::Display for SyncError {
             SyncError::RemoteError(msg) => write!(f, "Remote error: {}", msg),
             SyncError::Cancelled => write!(f, "Operation cancelled"),
             SyncError::IoError(msg) => write!(f, "IO error: {}", msg),
+            SyncError::HookError(msg) => write!(f, "Hook error: {}", msg),
         }
     }
 }
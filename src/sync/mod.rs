@@ -6,12 +6,12 @@ pub mod pipeline;
 pub mod scanner;
 pub mod transport;
 
-use crate::{SyncConfig, SyncErrorInfo, SyncRuntimeStatus, SyncState};
+use crate::{SyncConfig, SyncDryRunPathResult, SyncErrorInfo, SyncRuntimeStatus, SyncState};
 use chrono::Utc;
 use chrono_tz::Tz;
 use cron::Schedule;
 use error::SyncError;
-use pipeline::BackupPipeline;
+use pipeline::{BackupPipeline, SyncRunStats};
 use serde::Serialize;
 use std::collections::{HashMap, VecDeque};
 use std::str::FromStr;
@@ -47,18 +47,78 @@ impl SyncLogEntry {
     }
 }
 
+// One finished run, persisted by main into the sync_runs table
+#[derive(Clone, Debug, Serialize)]
+pub struct SyncRunRecord {
+    pub sync_id: String,
+    pub started_at_ms: i64,
+    pub finished_at_ms: i64,
+    /// ok | error | cancelled
+    pub status: String,
+    pub error: Option<String>,
+    pub files_transferred: u64,
+    pub bytes_sent: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref RUN_RECORDS: broadcast::Sender<SyncRunRecord> = broadcast::channel(64).0;
+}
+
+pub fn subscribe_run_records() -> broadcast::Receiver<SyncRunRecord> {
+    RUN_RECORDS.subscribe()
+}
+
+// Transfer progress frame pushed over /api/syncs/{id}/progress
+#[derive(Clone, Debug, Serialize)]
+pub struct SyncProgress {
+    pub path: String,
+    pub percent: f64,
+    pub bytes_transferred: u64,
+    pub bytes_total: u64,
+    /// bytes per second, averaged since the transfer started
+    pub speed: u64,
+    /// seconds remaining at the current speed
+    pub eta: Option<u64>,
+}
+
+impl SyncProgress {
+    pub fn new(path: &str, transferred: u64, total: u64, elapsed: std::time::Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let speed = if secs > 0.0 { (transferred as f64 / secs) as u64 } else { 0 };
+        let percent = if total > 0 {
+            (transferred as f64 * 1000.0 / total as f64).round() / 10.0
+        } else {
+            100.0
+        };
+        Self {
+            path: path.to_string(),
+            percent,
+            bytes_transferred: transferred,
+            bytes_total: total,
+            speed,
+            eta: (speed > 0).then(|| total.saturating_sub(transferred) / speed),
+        }
+    }
+}
+
 // Global sync log storage per sync ID
 struct SyncLogStorage {
     logs: Mutex<VecDeque<SyncLogEntry>>,
     broadcast_tx: broadcast::Sender<SyncLogEntry>,
+    progress_tx: broadcast::Sender<SyncProgress>,
+    // 最近一帧进度，新连接的客户端先收到它；运行结束后清空
+    last_progress: Arc<std::sync::Mutex<Option<SyncProgress>>>,
 }
 
 impl SyncLogStorage {
     fn new() -> Self {
         let (broadcast_tx, _) = broadcast::channel(100);
+        let (progress_tx, _) = broadcast::channel(16);
         Self {
             logs: Mutex::new(VecDeque::with_capacity(1000)),
             broadcast_tx,
+            progress_tx,
+            last_progress: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
@@ -185,11 +245,13 @@ impl SyncManager {
             entry.status.clone()
         };
 
+        // 检查与置位在同一把写锁内完成，避免手动触发与定时触发同时启动两次
         {
-            let s = status.read().await;
+            let mut s = status.write().await;
             if s.state == SyncState::Running {
                 return Err("Sync is already running".to_string());
             }
+            s.state = SyncState::Running;
         }
 
         let (stop_tx, stop_rx) = watch::channel(false);
@@ -212,8 +274,11 @@ impl SyncManager {
                 manager.add_log(&cfg_id, entry).await;
             });
         }));
+        let progress_tx = self.progress_sink(&cfg_id).await;
+        let manager = self.clone();
         let join = tokio::spawn(async move {
-            run_sync_task(cfg, status_clone, stop_rx, cfg_id_for_task, log_tx).await;
+            run_sync_task(cfg, status_clone, stop_rx, cfg_id_for_task.clone(), log_tx, progress_tx).await;
+            manager.clear_progress(&cfg_id_for_task).await;
         });
 
         {
@@ -226,6 +291,26 @@ impl SyncManager {
         Ok(())
     }
 
+    /// 逐个本地路径预演一次同步，只读远端，不上传也不删除
+    pub async fn dry_run(&self, cfg: &SyncConfig) -> Vec<SyncDryRunPathResult> {
+        let remote_path = cfg.remote_path.clone().unwrap_or_else(|| "/".to_string());
+        let pipeline = BackupPipeline::new(cfg.clone());
+        let mut results = Vec::with_capacity(cfg.local_paths.len());
+        for local in &cfg.local_paths {
+            let (changes, error) = match pipeline.dry_run(&local.path).await {
+                Ok(changes) => (changes, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            };
+            results.push(SyncDryRunPathResult {
+                local_path: local.path.clone(),
+                remote_path: remote_path.clone(),
+                changes,
+                error,
+            });
+        }
+        results
+    }
+
     pub async fn stop(&self, id: &str) -> Result<(), String> {
         let stop_tx = {
             let runtimes = self.inner.runtimes.lock().await;
@@ -262,6 +347,42 @@ impl SyncManager {
         logs.get(id).map(|storage| storage.subscribe())
     }
 
+    /// 订阅传输进度，同时返回当前正在进行的最近一帧（如果有）；
+    /// 还没运行过的同步也可以先订阅，存储按需创建
+    pub async fn subscribe_progress(
+        &self,
+        id: &str,
+    ) -> (Option<SyncProgress>, broadcast::Receiver<SyncProgress>) {
+        let mut logs = self.inner.logs.lock().await;
+        let storage = logs.entry(id.to_string()).or_insert_with(SyncLogStorage::new);
+        let rx = storage.progress_tx.subscribe();
+        let last = storage
+            .last_progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        (last, rx)
+    }
+
+    async fn progress_sink(&self, id: &str) -> Option<Arc<dyn Fn(SyncProgress) + Send + Sync>> {
+        let mut logs = self.inner.logs.lock().await;
+        let storage = logs.entry(id.to_string()).or_insert_with(SyncLogStorage::new);
+        let tx = storage.progress_tx.clone();
+        let last = storage.last_progress.clone();
+        *last.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Some(Arc::new(move |progress: SyncProgress| {
+            *last.lock().unwrap_or_else(|e| e.into_inner()) = Some(progress.clone());
+            let _ = tx.send(progress);
+        }))
+    }
+
+    async fn clear_progress(&self, id: &str) {
+        let logs = self.inner.logs.lock().await;
+        if let Some(storage) = logs.get(id) {
+            *storage.last_progress.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
     #[allow(dead_code)]
     pub async fn clear_logs(&self, id: &str) {
         let logs = self.inner.logs.lock().await;
@@ -351,13 +472,23 @@ async fn run_sync_task(
     stop_rx: watch::Receiver<bool>,
     _sync_id: String,
     log_tx: Option<Arc<dyn Fn(SyncLogEntry) + Send + Sync>>,
+    progress_tx: Option<Arc<dyn Fn(SyncProgress) + Send + Sync>>,
 ) {
+    let started_at_ms = Utc::now().timestamp_millis();
     {
         let mut s = status.write().await;
         s.state = SyncState::Running;
-        s.last_run_at_ms = Some(Utc::now().timestamp_millis());
+        s.last_run_at_ms = Some(started_at_ms);
         s.last_error = None;
     }
+    crate::broadcast_event(
+        "info",
+        "sync",
+        &cfg.id,
+        "state_changed",
+        &format!("Sync {}: running", cfg.id),
+        serde_json::json!({ "to": "running" }),
+    );
 
     let log = |entry: SyncLogEntry| {
         if let Some(ref tx) = log_tx {
@@ -367,39 +498,159 @@ async fn run_sync_task(
 
     let local_paths = cfg.local_paths.clone();
     let mut had_error = false;
+    let mut totals = SyncRunStats::default();
 
-    for local in local_paths {
+    // pre_command 失败或被取消时跳过传输，也不执行 post_command
+    let mut skip_transfer = false;
+    if let Some(command) = cfg.pre_command.as_deref() {
+        log(SyncLogEntry::info(None, format!("执行 pre_command: {}", command)));
+        match run_hook(&cfg.id, "pre", command, stop_rx.clone()).await {
+            Ok(()) => {}
+            Err(SyncError::Cancelled) => {
+                log(SyncLogEntry::info(None, "备份已取消".to_string()));
+                skip_transfer = true;
+            }
+            Err(e) => {
+                log(SyncLogEntry::error(None, format!("pre_command 失败，中止同步: {}", e)));
+                record_sync_error(&status, &e).await;
+                had_error = true;
+                skip_transfer = true;
+            }
+        }
+    }
+
+    for local in local_paths.into_iter().filter(|_| !skip_transfer) {
         if *stop_rx.borrow() {
             log(SyncLogEntry::info(Some(&local.path), "备份已取消".to_string()));
             break;
         }
 
         let pipeline = BackupPipeline::new(cfg.clone());
-        match pipeline.run(&local.path, status.clone(), stop_rx.clone(), log_tx.clone()).await {
-            Ok(()) => {}
+        match pipeline
+            .run(&local.path, status.clone(), stop_rx.clone(), log_tx.clone(), progress_tx.clone())
+            .await
+        {
+            Ok(stats) => {
+                totals.files += stats.files;
+                totals.bytes += stats.bytes;
+            }
             Err(SyncError::Cancelled) => {
                 log(SyncLogEntry::info(Some(&local.path), "备份已取消".to_string()));
                 break;
             }
             Err(e) => {
                 log(SyncLogEntry::error(Some(&local.path), format!("备份失败: {}", e)));
-                let mut s = status.write().await;
-                s.last_error = Some(SyncErrorInfo {
-                    message: e.to_string(),
-                    at_ms: Utc::now().timestamp_millis(),
-                });
+                record_sync_error(&status, &e).await;
                 had_error = true;
                 break;
             }
         }
     }
 
+    if let Some(command) = cfg.post_command.as_deref().filter(|_| !skip_transfer) {
+        log(SyncLogEntry::info(None, format!("执行 post_command: {}", command)));
+        match run_hook(&cfg.id, "post", command, stop_rx.clone()).await {
+            Ok(()) | Err(SyncError::Cancelled) => {}
+            Err(e) => {
+                log(SyncLogEntry::error(None, format!("post_command 失败: {}", e)));
+                // 传输本身已失败时保留传输错误
+                if !had_error {
+                    record_sync_error(&status, &e).await;
+                }
+                had_error = true;
+            }
+        }
+    }
+
     let mut s = status.write().await;
     s.running_path = None;
     s.state = if had_error { SyncState::Error } else { SyncState::Stopped };
-    if !had_error && !*stop_rx.borrow() {
+    let cancelled = *stop_rx.borrow();
+    if !had_error && !cancelled {
         s.last_ok_at_ms = Some(Utc::now().timestamp_millis());
     }
+    let to = if had_error { "error" } else { "stopped" };
+    let error = s.last_error.as_ref().filter(|_| had_error).map(|e| e.message.clone());
+    crate::broadcast_event(
+        if had_error { "error" } else { "info" },
+        "sync",
+        &cfg.id,
+        "state_changed",
+        &match &error {
+            Some(e) => format!("Sync {}: {} ({})", cfg.id, to, e),
+            None => format!("Sync {}: {}", cfg.id, to),
+        },
+        serde_json::json!({ "to": to, "cancelled": cancelled, "error": error }),
+    );
+    drop(s);
+
+    // 没有订阅者时（历史库不可用）记录直接丢弃
+    let _ = RUN_RECORDS.send(SyncRunRecord {
+        sync_id: cfg.id.clone(),
+        started_at_ms,
+        finished_at_ms: Utc::now().timestamp_millis(),
+        status: if had_error {
+            "error"
+        } else if cancelled {
+            "cancelled"
+        } else {
+            "ok"
+        }
+        .to_string(),
+        error,
+        files_transferred: totals.files,
+        bytes_sent: totals.bytes,
+    });
+}
+
+async fn record_sync_error(status: &RwLock<SyncRuntimeStatus>, e: &SyncError) {
+    let mut s = status.write().await;
+    s.last_error = Some(SyncErrorInfo {
+        message: e.to_string(),
+        at_ms: Utc::now().timestamp_millis(),
+    });
+}
+
+/// 钩子命令的最长执行时间
+const SYNC_HOOK_TIMEOUT_SECS: u64 = 3600;
+
+/// 在本地用 sh -c 执行 pre/post 钩子，输出进进程日志；同步被停止或超时时终止钩子
+async fn run_hook(
+    sync_id: &str,
+    stage: &str,
+    command: &str,
+    mut stop_rx: watch::Receiver<bool>,
+) -> Result<(), SyncError> {
+    if *stop_rx.borrow() {
+        return Err(SyncError::Cancelled);
+    }
+    let mut cmd = tokio::process::Command::new("sh");
+    cmd.arg("-c").arg(command).kill_on_drop(true);
+    let mut child = crate::spawn_with_log_capture(&mut cmd, format!("sync-{}-{}", sync_id, stage))
+        .map_err(|e| SyncError::HookError(format!("{}_command failed to start: {}", stage, e)))?;
+
+    tokio::select! {
+        result = child.wait() => {
+            let exit = result
+                .map_err(|e| SyncError::HookError(format!("{}_command: {}", stage, e)))?;
+            if exit.success() {
+                Ok(())
+            } else {
+                Err(SyncError::HookError(format!("{}_command exited with {}", stage, exit)))
+            }
+        }
+        _ = tokio::time::sleep(std::time::Duration::from_secs(SYNC_HOOK_TIMEOUT_SECS)) => {
+            let _ = child.kill().await;
+            Err(SyncError::HookError(format!(
+                "{}_command timed out after {}s",
+                stage, SYNC_HOOK_TIMEOUT_SECS
+            )))
+        }
+        _ = stop_rx.wait_for(|stop| *stop) => {
+            let _ = child.kill().await;
+            Err(SyncError::Cancelled)
+        }
+    }
 }
 
 async fn run_schedule_loop(
@@ -459,3 +710,47 @@ async fn run_schedule_loop(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn sync_progress_percent_speed_eta() {
+        // (transferred, total, elapsed ms) -> (percent, speed, eta)
+        let cases = [
+            (0, 1000, 0, 0.0, 0, None),
+            (0, 1000, 1000, 0.0, 0, None),
+            (500, 1000, 1000, 50.0, 500, Some(1)),
+            (1, 3, 1000, 33.3, 1, Some(2)),
+            (2, 3, 2000, 66.7, 1, Some(1)),
+            (1000, 1000, 4000, 100.0, 250, Some(0)),
+            (0, 0, 1000, 100.0, 0, None),
+            (1200, 1000, 1000, 120.0, 1200, Some(0)),
+        ];
+        for (transferred, total, ms, percent, speed, eta) in cases {
+            let p = SyncProgress::new("/data", transferred, total, Duration::from_millis(ms));
+            let case = format!("{}/{} in {}ms", transferred, total, ms);
+            assert_eq!(p.percent, percent, "{}", case);
+            assert_eq!(p.speed, speed, "{}", case);
+            assert_eq!(p.eta, eta, "{}", case);
+            assert_eq!(p.bytes_transferred, transferred);
+            assert_eq!(p.bytes_total, total);
+        }
+    }
+
+    #[tokio::test]
+    async fn progress_can_be_subscribed_before_the_first_run() {
+        let manager = SyncManager::new();
+        let (last, mut rx) = manager.subscribe_progress("never-run").await;
+        assert!(last.is_none());
+
+        let sink = manager.progress_sink("never-run").await.expect("progress sink");
+        sink(SyncProgress::new("/data", 1, 2, Duration::from_secs(1)));
+        assert_eq!(rx.recv().await.unwrap().bytes_transferred, 1);
+
+        let (last, _rx) = manager.subscribe_progress("never-run").await;
+        assert_eq!(last.map(|p| p.bytes_transferred), Some(1));
+    }
+}
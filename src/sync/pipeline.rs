@@ -4,8 +4,9 @@ use crate::sync::error::SyncError;
 use crate::sync::manifest::BackupManifest;
 use crate::sync::scanner::{FileEntry, Scanner};
 use crate::sync::transport::SshTransport;
-use crate::sync::SyncLogEntry;
-use crate::{SyncConfig, SyncOptions, SyncRuntimeStatus};
+use crate::sync::{SyncLogEntry, SyncProgress};
+use crate::{SyncChangeType, SyncConfig, SyncDryRunChange, SyncOptions, SyncRuntimeStatus};
+use std::collections::HashSet;
 use std::io::Cursor;
 use std::path::Path;
 use std::sync::{
@@ -21,6 +22,14 @@ pub struct BackupPipeline {
     config: SyncConfig,
 }
 
+/// 单个本地路径一次备份实际传输的量
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncRunStats {
+    pub files: u64,
+    /// 压缩后经 SSH 发送的字节数
+    pub bytes: u64,
+}
+
 struct ProgressReader<R> {
     inner: R,
     sent: Arc<AtomicU64>,
@@ -61,7 +70,8 @@ impl BackupPipeline {
         status: Arc<RwLock<SyncRuntimeStatus>>,
         stop_rx: watch::Receiver<bool>,
         log_tx: Option<Arc<dyn Fn(SyncLogEntry) + Send + Sync>>,
-    ) -> Result<(), SyncError> {
+        progress_tx: Option<Arc<dyn Fn(SyncProgress) + Send + Sync>>,
+    ) -> Result<SyncRunStats, SyncError> {
         let log = |entry: SyncLogEntry| {
             if let Some(ref tx) = log_tx {
                 tx(entry);
@@ -71,7 +81,7 @@ impl BackupPipeline {
         let options = &self.config.options;
         let remote_path = self.config.remote_path.as_deref().unwrap_or("/");
 
-        log(SyncLogEntry::info(Some(local_path), format!("开始备份: {} -> {}@{}:{}", local_path, self.config.ssh.username, self.config.ssh.host, self.config.ssh.port)));
+        log(SyncLogEntry::info(Some(local_path), format!("开始备份: {} -> {}@{}", local_path, self.config.ssh.username, crate::join_host_port(&self.config.ssh.host, self.config.ssh.port))));
 
         let mut transport = SshTransport::connect(&self.config.ssh).await?;
         log(SyncLogEntry::info(Some(local_path), "SSH 连接成功".to_string()));
@@ -84,19 +94,16 @@ impl BackupPipeline {
             None
         };
 
-        let scanner = Scanner::new(
-            options.exclude.clone(),
-            options.include.clone(),
-            options.follow_symlinks,
-        );
-
+        // 始终完整扫描：清单与多余文件判定需要完整的本地文件集，增量模式只传输其中的变更
+        let scanner = self.scanner();
         let root = Path::new(local_path);
-        let entries = scanner.scan(root, manifest.as_ref())?;
+        let all_entries = scanner.scan(root, None)?;
+        let entries = changed_entries(&scanner, &all_entries, manifest.as_ref());
 
         if entries.is_empty() {
             log(SyncLogEntry::info(Some(local_path), "没有需要备份的文件".to_string()));
             transport.disconnect().await;
-            return Ok(());
+            return Ok(SyncRunStats::default());
         }
 
         log(SyncLogEntry::info(Some(local_path), format!("扫描到 {} 个文件需要备份", entries.len())));
@@ -115,11 +122,24 @@ impl BackupPipeline {
         }
 
         log(SyncLogEntry::info(Some(local_path), format!("压缩完成，数据大小: {} bytes", compressed_data.len())));
+        let stats = SyncRunStats {
+            files: entries.iter().filter(|e| !e.is_dir).count() as u64,
+            bytes: compressed_data.len() as u64,
+        };
 
-        self.transfer_and_extract(&mut transport, remote_path, compressed_data, options, log_tx.clone()).await?;
+        self.transfer_and_extract(
+            &mut transport,
+            local_path,
+            remote_path,
+            compressed_data,
+            options,
+            log_tx.clone(),
+            progress_tx,
+        )
+        .await?;
         log(SyncLogEntry::info(Some(local_path), "文件传输完成".to_string()));
 
-        let new_manifest = BackupManifest::from_entries(local_path, remote_path, &entries);
+        let new_manifest = BackupManifest::from_entries(local_path, remote_path, &all_entries);
         self.save_remote_manifest(&mut transport, remote_path, &new_manifest).await?;
         log(SyncLogEntry::info(Some(local_path), "清单已保存".to_string()));
 
@@ -130,7 +150,44 @@ impl BackupPipeline {
 
         transport.disconnect().await;
         log(SyncLogEntry::info(Some(local_path), "备份完成".to_string()));
-        Ok(())
+        Ok(stats)
+    }
+
+    /// 预演一次备份：对比本地文件与远端现有文件，返回将新建/覆盖/删除的相对路径
+    pub async fn dry_run(&self, local_path: &str) -> Result<Vec<SyncDryRunChange>, SyncError> {
+        let mut transport = SshTransport::connect(&self.config.ssh).await?;
+        let result = self.plan_changes(&mut transport, local_path).await;
+        transport.disconnect().await;
+        result
+    }
+
+    async fn plan_changes(
+        &self,
+        transport: &mut SshTransport,
+        local_path: &str,
+    ) -> Result<Vec<SyncDryRunChange>, SyncError> {
+        let options = &self.config.options;
+        let remote_path = self.config.remote_path.as_deref().unwrap_or("/");
+
+        let manifest = if options.incremental {
+            self.load_remote_manifest(transport, remote_path).await.ok()
+        } else {
+            None
+        };
+        let remote_files = self.list_remote_files(transport, remote_path).await?;
+
+        let scanner = self.scanner();
+        let entries = scanner.scan(Path::new(local_path), None)?;
+        Ok(plan_entry_changes(&scanner, &entries, &remote_files, manifest.as_ref(), options.delete))
+    }
+
+    fn scanner(&self) -> Scanner {
+        let options = &self.config.options;
+        Scanner::new(
+            options.exclude.clone(),
+            options.include.clone(),
+            options.follow_symlinks,
+        )
     }
 
     async fn ensure_remote_tools(&self, transport: &mut SshTransport) -> Result<(), SyncError> {
@@ -175,10 +232,12 @@ impl BackupPipeline {
     async fn transfer_and_extract(
         &self,
         transport: &mut SshTransport,
+        local_path: &str,
         remote_path: &str,
         data: Vec<u8>,
         options: &SyncOptions,
         log_tx: Option<Arc<dyn Fn(SyncLogEntry) + Send + Sync>>,
+        progress_tx: Option<Arc<dyn Fn(SyncProgress) + Send + Sync>>,
     ) -> Result<(), SyncError> {
         let log = |entry: SyncLogEntry| {
             if let Some(ref tx) = log_tx {
@@ -198,25 +257,35 @@ impl BackupPipeline {
 
         let sent = Arc::new(AtomicU64::new(0));
         let done = Arc::new(AtomicBool::new(false));
-        let progress_handle = if log_tx.is_some() {
+        let start = std::time::Instant::now();
+        let progress_handle = if log_tx.is_some() || progress_tx.is_some() {
             let sent_clone = sent.clone();
             let done_clone = done.clone();
             let log_tx_clone = log_tx.clone();
+            let progress_tx_clone = progress_tx.clone();
+            let path = local_path.to_string();
             Some(tokio::spawn(async move {
                 let log = |entry: SyncLogEntry| {
                     if let Some(ref tx) = log_tx_clone {
                         tx(entry);
                     }
                 };
+                // 每秒推一次进度帧，每 5 秒写一条日志
+                let mut ticks_since_log = 0u32;
                 loop {
                     if done_clone.load(Ordering::Relaxed) {
                         break;
                     }
-                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    ticks_since_log += 1;
                     let sent_now = sent_clone.load(Ordering::Relaxed);
-                    if sent_now == 0 {
+                    if let Some(ref tx) = progress_tx_clone {
+                        tx(SyncProgress::new(&path, sent_now, total, start.elapsed()));
+                    }
+                    if sent_now == 0 || ticks_since_log < 5 {
                         continue;
                     }
+                    ticks_since_log = 0;
                     log(SyncLogEntry::info(None, format!("传输中: {}/{} bytes", sent_now, total)));
                 }
             }))
@@ -226,12 +295,15 @@ impl BackupPipeline {
 
         let cursor = Cursor::new(data);
         let reader = ProgressReader::new(cursor, sent.clone());
-        let start = std::time::Instant::now();
-        let result = transport.exec_with_stdin(&cmd, reader).await?;
+        let result = transport.exec_with_stdin(&cmd, reader).await;
         done.store(true, Ordering::Relaxed);
         if let Some(handle) = progress_handle {
             let _ = handle.await;
         }
+        let result = result?;
+        if let Some(ref tx) = progress_tx {
+            tx(SyncProgress::new(local_path, sent.load(Ordering::Relaxed), total, start.elapsed()));
+        }
         let elapsed = start.elapsed().as_secs_f64();
         log(SyncLogEntry::info(None, format!("传输结束: {} bytes, {:.2}s", total, elapsed)));
         log(SyncLogEntry::info(None, "进度停止".to_string()));
@@ -294,14 +366,11 @@ impl BackupPipeline {
         remote_path: &str,
         manifest: &BackupManifest,
     ) -> Result<(), SyncError> {
-        let cmd = format!("cd {} && find . -type f 2>/dev/null || true", shell_escape(remote_path));
-        let result = transport.exec(&cmd).await?;
-
-        let stdout = String::from_utf8_lossy(&result.stdout);
-        let orphans: Vec<&str> = stdout
-            .lines()
-            .map(|l| l.trim_start_matches("./"))
-            .filter(|l| !l.is_empty() && *l != BackupManifest::FILENAME)
+        let remote_files = self.list_remote_files(transport, remote_path).await?;
+        let orphans: Vec<&str> = remote_files
+            .iter()
+            .map(|f| f.as_str())
+            .filter(|f| *f != BackupManifest::FILENAME)
             .filter(|f| !manifest.entries.contains_key(*f))
             .collect();
 
@@ -316,8 +385,193 @@ impl BackupPipeline {
         }
         Ok(())
     }
+
+    /// 远端目标目录下的全部文件（相对路径），目录不存在时为空
+    async fn list_remote_files(
+        &self,
+        transport: &mut SshTransport,
+        remote_path: &str,
+    ) -> Result<HashSet<String>, SyncError> {
+        let cmd = format!("cd {} && find . -type f 2>/dev/null || true", shell_escape(remote_path));
+        let result = transport.exec(&cmd).await?;
+
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        Ok(stdout
+            .lines()
+            .map(|l| l.trim_start_matches("./"))
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect())
+    }
 }
 
 fn shell_escape(s: &str) -> String {
     format!("'{}'", s.replace('\'', "'\\''"))
 }
+
+/// 增量模式下需要传输的条目：目录与相对清单有变化的文件；没有清单时为全部条目
+fn changed_entries(
+    scanner: &Scanner,
+    all: &[FileEntry],
+    manifest: Option<&BackupManifest>,
+) -> Vec<FileEntry> {
+    all.iter()
+        .filter(|e| match manifest {
+            Some(m) => e.is_dir || scanner.is_changed(e, m),
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// 预演变更：远端没有的为新建；有清单时未变更的文件跳过，其余（含非增量模式下的全部文件）为覆盖；
+/// delete 开启时远端多余的文件为删除
+fn plan_entry_changes(
+    scanner: &Scanner,
+    entries: &[FileEntry],
+    remote_files: &HashSet<String>,
+    manifest: Option<&BackupManifest>,
+    delete: bool,
+) -> Vec<SyncDryRunChange> {
+    let mut changes = Vec::new();
+    let mut local_files = HashSet::new();
+    for entry in entries.iter().filter(|e| !e.is_dir) {
+        let path = entry.rel_path.to_string_lossy().to_string();
+        let change_type = if !remote_files.contains(&path) {
+            Some(SyncChangeType::Created)
+        } else if manifest.is_some_and(|m| !scanner.is_changed(entry, m)) {
+            None
+        } else {
+            Some(SyncChangeType::Updated)
+        };
+        if let Some(change_type) = change_type {
+            changes.push(SyncDryRunChange { path: path.clone(), change_type });
+        }
+        local_files.insert(path);
+    }
+
+    if delete {
+        let mut orphans: Vec<&String> = remote_files
+            .iter()
+            .filter(|f| f.as_str() != BackupManifest::FILENAME && !local_files.contains(*f))
+            .collect();
+        orphans.sort();
+        changes.extend(orphans.into_iter().map(|f| SyncDryRunChange {
+            path: f.clone(),
+            change_type: SyncChangeType::Deleted,
+        }));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(path: &str, size: u64, mtime_ms: i64, is_dir: bool) -> FileEntry {
+        FileEntry {
+            rel_path: PathBuf::from(path),
+            abs_path: PathBuf::from("/src").join(path),
+            size,
+            mtime_ms,
+            is_dir,
+            is_symlink: false,
+        }
+    }
+
+    fn scanner() -> Scanner {
+        Scanner::new(Vec::new(), Vec::new(), false)
+    }
+
+    fn paths(entries: &[FileEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.rel_path.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn changed_entries_cases() {
+        let previous = [
+            entry("dir", 0, 0, true),
+            entry("dir/a.txt", 10, 100, false),
+            entry("b.txt", 20, 200, false),
+        ];
+        let manifest = BackupManifest::from_entries("/src", "/dst", &previous);
+
+        let cases = vec![
+            ("no manifest sends everything", previous.to_vec(), None, vec!["dir", "dir/a.txt", "b.txt"]),
+            ("unchanged files are skipped", previous.to_vec(), Some(&manifest), vec!["dir"]),
+            (
+                "size or mtime change is sent",
+                vec![entry("dir", 0, 0, true), entry("dir/a.txt", 11, 100, false), entry("b.txt", 20, 201, false)],
+                Some(&manifest),
+                vec!["dir", "dir/a.txt", "b.txt"],
+            ),
+            (
+                "new file is sent",
+                vec![entry("b.txt", 20, 200, false), entry("c.txt", 1, 1, false)],
+                Some(&manifest),
+                vec!["c.txt"],
+            ),
+        ];
+        for (name, all, manifest, want) in cases {
+            assert_eq!(paths(&changed_entries(&scanner(), &all, manifest)), want, "{}", name);
+        }
+    }
+
+    #[test]
+    fn incremental_manifest_keeps_unchanged_files() {
+        let previous = [entry("a.txt", 1, 1, false), entry("b.txt", 2, 2, false)];
+        let manifest = BackupManifest::from_entries("/src", "/dst", &previous);
+        let all = [entry("a.txt", 1, 1, false), entry("b.txt", 3, 3, false)];
+
+        let sent = changed_entries(&scanner(), &all, Some(&manifest));
+        assert_eq!(paths(&sent), vec!["b.txt"]);
+
+        // 新清单来自完整扫描，未变更的 a.txt 仍在其中，delete 不会把它当成多余文件
+        let next = BackupManifest::from_entries("/src", "/dst", &all);
+        assert!(next.entries.contains_key("a.txt"));
+        assert_eq!(next.entries["b.txt"].size, 3);
+    }
+
+    #[test]
+    fn plan_entry_changes_cases() {
+        let local = [
+            entry("dir", 0, 0, true),
+            entry("dir/a.txt", 1, 1, false),
+            entry("b.txt", 2, 2, false),
+            entry("new.txt", 3, 3, false),
+        ];
+        let manifest = BackupManifest::from_entries("/src", "/dst", &local[..3]);
+        let remote: HashSet<String> = ["dir/a.txt", "b.txt", "gone.txt", BackupManifest::FILENAME]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        use SyncChangeType::{Created, Deleted, Updated};
+        let cases = vec![
+            (
+                "non-incremental lists every local file",
+                None,
+                false,
+                vec![("dir/a.txt", Updated), ("b.txt", Updated), ("new.txt", Created)],
+            ),
+            ("incremental skips unchanged files", Some(&manifest), false, vec![("new.txt", Created)]),
+            (
+                "delete lists remote orphans but not the manifest",
+                Some(&manifest),
+                true,
+                vec![("new.txt", Created), ("gone.txt", Deleted)],
+            ),
+        ];
+        for (name, manifest, delete, want) in cases {
+            let got: Vec<(String, SyncChangeType)> =
+                plan_entry_changes(&scanner(), &local, &remote, manifest, delete)
+                    .into_iter()
+                    .map(|c| (c.path, c.change_type))
+                    .collect();
+            let want: Vec<(String, SyncChangeType)> =
+                want.into_iter().map(|(p, t)| (p.to_string(), t)).collect();
+            assert_eq!(got, want, "{}", name);
+        }
+    }
+}
@@ -165,7 +165,7 @@ impl Scanner {
         path == pattern || path.starts_with(&format!("{}/", pattern))
     }
 
-    fn is_changed(&self, entry: &FileEntry, manifest: &BackupManifest) -> bool {
+    pub fn is_changed(&self, entry: &FileEntry, manifest: &BackupManifest) -> bool {
         let key = entry.rel_path.to_string_lossy().to_string();
         match manifest.entries.get(&key) {
             None => true, // New file
@@ -96,8 +96,16 @@ impl SshTransport {
                 Self::try_default_keys(session, cfg, timeout).await
             }
             TcpTunnelAuth::PrivateKeyPath { path, passphrase } => {
-                let key = load_secret_key(path, passphrase.as_deref())
-                    .map_err(|e| SyncError::SshAuthError(format!("load key: {e:?}")))?;
+                // 空路径回退到 /root/.ssh 下的默认私钥
+                let path = crate::resolve_private_key_path(path).map_err(SyncError::SshAuthError)?;
+                let key = load_secret_key(&path, passphrase.as_deref()).map_err(|e| match e {
+                    russh::keys::Error::KeyIsEncrypted if passphrase.is_none() => {
+                        SyncError::SshAuthError(format!(
+                            "private key {path} is passphrase-protected, set auth.passphrase"
+                        ))
+                    }
+                    e => SyncError::SshAuthError(format!("load key {path}: {e:?}")),
+                })?;
 
                 let rsa_hash = tokio::time::timeout(timeout, session.best_supported_rsa_hash())
                     .await
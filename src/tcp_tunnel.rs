@@ -1,13 +1,14 @@
-use crate::{TcpTunnelConfig, TcpTunnelManagedBy};
+use crate::{TcpTunnelConfig, TcpTunnelDirection, TcpTunnelManagedBy};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::{RwLock, watch, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock, watch, Mutex};
 use tokio::time::{sleep, Duration};
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum TunnelState {
     Stopped,
@@ -29,8 +30,18 @@ pub struct TunnelRuntimeStatus {
     pub active_conns: u32,
     pub bytes_in: u64,
     pub bytes_out: u64,
+    /// 实时累计流量：up = 本地 -> SSH，down = SSH -> 本地（连接进行中也会增长）
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    /// 最近一个采样窗口内的双向总吞吐
+    pub bytes_per_sec: u64,
     pub last_ok_at_ms: Option<i64>,
     pub last_error: Option<TunnelErrorInfo>,
+    /// 转发已建立但远端实际监听地址与 remote_bind_addr 不一致（通常是 sshd 未开启 GatewayPorts）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_warning: Option<TunnelErrorInfo>,
+    #[serde(skip)]
+    traffic: Arc<TunnelTraffic>,
 }
 
 impl Default for TunnelRuntimeStatus {
@@ -40,10 +51,152 @@ impl Default for TunnelRuntimeStatus {
             active_conns: 0,
             bytes_in: 0,
             bytes_out: 0,
+            bytes_up: 0,
+            bytes_down: 0,
+            bytes_per_sec: 0,
             last_ok_at_ms: None,
             last_error: None,
+            bind_warning: None,
+            traffic: Arc::new(TunnelTraffic::new()),
+        }
+    }
+}
+
+impl TunnelRuntimeStatus {
+    /// 带上流量计数器当前值的快照
+    fn snapshot(&self) -> Self {
+        let mut out = self.clone();
+        out.bytes_up = self.traffic.up.load(Ordering::Relaxed);
+        out.bytes_down = self.traffic.down.load(Ordering::Relaxed);
+        out.bytes_per_sec = self.traffic.bytes_per_sec();
+        out
+    }
+}
+
+/// 吞吐至少按这么长的窗口计算，避免频繁查询时抖动
+const TRAFFIC_RATE_MIN_WINDOW: Duration = Duration::from_secs(1);
+
+/// 隧道流量计数器，由拷贝循环实时累加
+#[derive(Debug)]
+struct TunnelTraffic {
+    up: AtomicU64,
+    down: AtomicU64,
+    rate: std::sync::Mutex<TrafficRateSample>,
+}
+
+#[derive(Debug)]
+struct TrafficRateSample {
+    at: Instant,
+    total: u64,
+    bytes_per_sec: u64,
+}
+
+impl TunnelTraffic {
+    fn new() -> Self {
+        Self {
+            up: AtomicU64::new(0),
+            down: AtomicU64::new(0),
+            rate: std::sync::Mutex::new(TrafficRateSample {
+                at: Instant::now(),
+                total: 0,
+                bytes_per_sec: 0,
+            }),
+        }
+    }
+
+    fn bytes_per_sec(&self) -> u64 {
+        let total = self
+            .up
+            .load(Ordering::Relaxed)
+            .saturating_add(self.down.load(Ordering::Relaxed));
+        let mut sample = self.rate.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = sample.at.elapsed();
+        if elapsed >= TRAFFIC_RATE_MIN_WINDOW {
+            let delta = total.saturating_sub(sample.total);
+            sample.bytes_per_sec = (delta as f64 / elapsed.as_secs_f64()) as u64;
+            sample.at = Instant::now();
+            sample.total = total;
+        }
+        sample.bytes_per_sec
+    }
+}
+
+/// 包装本地 TCP 连接，读写时实时累加隧道流量
+#[cfg(feature = "tcp_tunnel")]
+struct CountingStream<S> {
+    inner: S,
+    traffic: Arc<TunnelTraffic>,
+}
+
+#[cfg(feature = "tcp_tunnel")]
+impl<S: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for CountingStream<S> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let res = std::pin::Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let std::task::Poll::Ready(Ok(())) = res {
+            let n = (buf.filled().len() - before) as u64;
+            self.traffic.up.fetch_add(n, Ordering::Relaxed);
+        }
+        res
+    }
+}
+
+#[cfg(feature = "tcp_tunnel")]
+impl<S: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for CountingStream<S> {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        let res = std::pin::Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let std::task::Poll::Ready(Ok(n)) = res {
+            self.traffic.down.fetch_add(n as u64, Ordering::Relaxed);
         }
+        res
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_flush(cx)
     }
+
+    fn poll_shutdown(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// 单条隧道运行状态变更，推送给 `/api/tcp-tunnels/status/ws`
+#[derive(Clone, Debug, Serialize)]
+pub struct TunnelStatusUpdate {
+    pub id: String,
+    pub at_ms: i64,
+    pub status: TunnelRuntimeStatus,
+}
+
+lazy_static::lazy_static! {
+    static ref STATUS_UPDATES: broadcast::Sender<TunnelStatusUpdate> = broadcast::channel(256).0;
+}
+
+pub fn subscribe_status_updates() -> broadcast::Receiver<TunnelStatusUpdate> {
+    STATUS_UPDATES.subscribe()
+}
+
+fn publish_status(id: &str, status: &TunnelRuntimeStatus) {
+    // No subscribers is fine; the update is simply dropped.
+    let _ = STATUS_UPDATES.send(TunnelStatusUpdate {
+        id: id.to_string(),
+        at_ms: now_ms(),
+        status: status.snapshot(),
+    });
 }
 
 #[derive(Clone)]
@@ -64,6 +217,7 @@ struct TunnelHandle {
 
 fn runtime_config_equal(a: &TcpTunnelConfig, b: &TcpTunnelConfig) -> bool {
     a.enabled == b.enabled
+        && a.direction == b.direction
         && a.local_addr == b.local_addr
         && a.local_port == b.local_port
         && a.remote_bind_addr == b.remote_bind_addr
@@ -74,6 +228,7 @@ fn runtime_config_equal(a: &TcpTunnelConfig, b: &TcpTunnelConfig) -> bool {
         && a.auth == b.auth
         && a.strict_host_key_checking == b.strict_host_key_checking
         && a.host_key_fingerprint == b.host_key_fingerprint
+        && a.known_hosts_path == b.known_hosts_path
         && a.allow_public_bind == b.allow_public_bind
         && a.connect_timeout_ms == b.connect_timeout_ms
         && a.keepalive_interval_ms == b.keepalive_interval_ms
@@ -172,7 +327,7 @@ impl TunnelManager {
     pub async fn get_status(&self, id: &str) -> Option<TunnelRuntimeStatus> {
         let guard = self.inner.tunnels.lock().await;
         let handle = guard.get(id)?;
-        let out = handle.status.read().await.clone();
+        let out = handle.status.read().await.snapshot();
         Some(out)
     }
 
@@ -229,25 +384,43 @@ fn now_ms() -> i64 {
     chrono::Utc::now().timestamp_millis()
 }
 
-fn validate(cfg: &TcpTunnelConfig) -> Result<(), (String, String)> {
+pub fn validate(cfg: &TcpTunnelConfig) -> Result<(), (String, String)> {
     if cfg.remote_port == 0 {
         return Err((
             "CONFIG_INVALID".to_string(),
             "remote_port must be > 0".to_string(),
         ));
     }
-    if cfg.remote_bind_addr == "0.0.0.0" && !cfg.allow_public_bind {
-        return Err((
-            "PUBLIC_BIND_NOT_ALLOWED".to_string(),
-            "allow_public_bind must be true when remote_bind_addr is 0.0.0.0".to_string(),
-        ));
-    }
-    if cfg.strict_host_key_checking && cfg.host_key_fingerprint.trim().is_empty() {
-        return Err((
-            "HOSTKEY_MISSING".to_string(),
-            "host_key_fingerprint is required when strict_host_key_checking is true".to_string(),
-        ));
+    match cfg.direction {
+        TcpTunnelDirection::Reverse => {
+            if cfg.remote_bind_addr == "0.0.0.0" && !cfg.allow_public_bind {
+                return Err((
+                    "PUBLIC_BIND_NOT_ALLOWED".to_string(),
+                    "allow_public_bind must be true when remote_bind_addr is 0.0.0.0".to_string(),
+                ));
+            }
+        }
+        TcpTunnelDirection::Forward => {
+            if cfg.local_port == 0 {
+                return Err((
+                    "CONFIG_INVALID".to_string(),
+                    "local_port must be > 0 for forward tunnels".to_string(),
+                ));
+            }
+            if cfg.local_addr == "0.0.0.0" && !cfg.allow_public_bind {
+                return Err((
+                    "PUBLIC_BIND_NOT_ALLOWED".to_string(),
+                    "allow_public_bind must be true when local_addr is 0.0.0.0".to_string(),
+                ));
+            }
+        }
     }
+    crate::validate_host_key_settings(
+        cfg.strict_host_key_checking,
+        &cfg.host_key_fingerprint,
+        cfg.known_hosts_path.as_deref(),
+    )
+    .map_err(|e| ("HOSTKEY_MISSING".to_string(), e))?;
     Ok(())
 }
 
@@ -369,27 +542,77 @@ async fn authenticate_session(
     }
 }
 
+fn state_name(st: &TunnelState) -> &'static str {
+    match st {
+        TunnelState::Stopped => "stopped",
+        TunnelState::Connecting => "connecting",
+        TunnelState::Forwarding => "forwarding",
+        TunnelState::Error => "error",
+    }
+}
+
+fn emit_state_changed(id: &str, from: &TunnelState, to: &TunnelState, error: Option<&TunnelErrorInfo>) {
+    let level = if matches!(to, TunnelState::Error) { "error" } else { "info" };
+    let message = match error {
+        Some(e) => format!(
+            "TCP tunnel {id}: {} -> {} ({}: {})",
+            state_name(from),
+            state_name(to),
+            e.code,
+            e.message
+        ),
+        None => format!("TCP tunnel {id}: {} -> {}", state_name(from), state_name(to)),
+    };
+    crate::broadcast_event(
+        level,
+        "tunnel",
+        id,
+        "state_changed",
+        &message,
+        serde_json::json!({
+            "from": state_name(from),
+            "to": state_name(to),
+            "error": error,
+        }),
+    );
+}
+
 async fn set_error(
     status: &Arc<RwLock<TunnelRuntimeStatus>>,
+    id: &str,
     code: &str,
     message: &str,
 ) {
     let mut s = status.write().await;
+    let prev = s.state.clone();
     s.state = TunnelState::Error;
-    s.last_error = Some(TunnelErrorInfo {
+    let info = TunnelErrorInfo {
         code: code.to_string(),
         message: message.to_string(),
         at_ms: now_ms(),
-    });
+    };
+    if prev != TunnelState::Error {
+        emit_state_changed(id, &prev, &TunnelState::Error, Some(&info));
+    }
+    s.last_error = Some(info);
+    publish_status(id, &s);
 }
 
-async fn set_state(status: &Arc<RwLock<TunnelRuntimeStatus>>, st: TunnelState) {
+async fn set_state(status: &Arc<RwLock<TunnelRuntimeStatus>>, id: &str, st: TunnelState) {
     let mut s = status.write().await;
+    let prev = s.state.clone();
     s.state = st.clone();
     if matches!(st, TunnelState::Forwarding) {
         s.last_ok_at_ms = Some(now_ms());
         s.last_error = None;
     }
+    if matches!(st, TunnelState::Connecting | TunnelState::Stopped) {
+        s.bind_warning = None;
+    }
+    if prev != st {
+        emit_state_changed(id, &prev, &st, None);
+        publish_status(id, &s);
+    }
 }
 
 #[allow(dead_code)]
@@ -436,14 +659,14 @@ async fn run_tunnel(
     mut stop_rx: watch::Receiver<bool>,
 ) {
     if let Err((c, m)) = validate(&cfg) {
-        set_error(&status, &c, &m).await;
+        set_error(&status, &cfg.id, &c, &m).await;
     }
 
     let mut attempt: u32 = 0;
 
     loop {
         if *stop_rx.borrow() {
-            set_state(&status, TunnelState::Stopped).await;
+            set_state(&status, &cfg.id, TunnelState::Stopped).await;
             if stop_rx.changed().await.is_err() {
                 break;
             }
@@ -451,15 +674,15 @@ async fn run_tunnel(
             continue;
         }
 
-        set_state(&status, TunnelState::Connecting).await;
+        set_state(&status, &cfg.id, TunnelState::Connecting).await;
 
         match connect_and_forward(&cfg, &status, &mut stop_rx).await {
             Ok(()) => {
-                set_state(&status, TunnelState::Stopped).await;
+                set_state(&status, &cfg.id, TunnelState::Stopped).await;
                 attempt = 0;
             }
             Err((code, message, retryable)) => {
-                set_error(&status, &code, &message).await;
+                set_error(&status, &cfg.id, &code, &message).await;
                 if !retryable {
                     let _ = stop_rx.changed().await;
                     attempt = 0;
@@ -523,6 +746,10 @@ async fn connect_and_forward(
         ));
     }
 
+    if cfg.direction == TcpTunnelDirection::Forward {
+        return run_local_forward(session, cfg, status, stop_rx).await;
+    }
+
     let retryable_forward_errors = matches!(cfg.managed_by, Some(TcpTunnelManagedBy::FullTunnel { .. }));
 
     tokio::time::timeout(
@@ -551,7 +778,14 @@ async fn connect_and_forward(
         ),
     })?;
 
-    set_state(status, TunnelState::Forwarding).await;
+    set_state(status, &cfg.id, TunnelState::Forwarding).await;
+
+    let bind_warning = if is_loopback_host(&cfg.remote_bind_addr) {
+        None
+    } else {
+        verify_remote_bind(&session, cfg, connect_timeout).await
+    };
+    set_bind_warning(status, &cfg.id, bind_warning).await;
 
     let keepalive_interval = Duration::from_millis(cfg.keepalive_interval_ms);
 
@@ -577,13 +811,208 @@ async fn connect_and_forward(
     Ok(())
 }
 
+/// forward 方向（ssh -L）：本地监听 local_addr:local_port，每个入站连接经
+/// direct-tcpip 通道连到 SSH 服务器可达的 remote_bind_addr:remote_port
+#[cfg(feature = "tcp_tunnel")]
+async fn run_local_forward(
+    session: russh::client::Handle<TunnelClientHandler>,
+    cfg: &TcpTunnelConfig,
+    status: &Arc<RwLock<TunnelRuntimeStatus>>,
+    stop_rx: &mut watch::Receiver<bool>,
+) -> Result<(), (String, String, bool)> {
+    use russh::Disconnect;
+
+    let bind_addr = crate::join_host_port(&cfg.local_addr, cfg.local_port);
+    let listener = tokio::net::TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| ("LOCAL_BIND_FAILED".to_string(), format!("bind {bind_addr}: {e}"), true))?;
+
+    set_state(status, &cfg.id, TunnelState::Forwarding).await;
+
+    let connect_timeout = Duration::from_millis(cfg.connect_timeout_ms);
+    let keepalive_interval = Duration::from_millis(cfg.keepalive_interval_ms);
+
+    loop {
+        tokio::select! {
+            r = stop_rx.changed() => {
+                let _ = r;
+                if *stop_rx.borrow() {
+                    let _ = session.disconnect(Disconnect::ByApplication, "stop", "en").await;
+                    break;
+                }
+            }
+            _ = sleep(keepalive_interval) => {
+                if session.is_closed() {
+                    return Err(("SSH_DISCONNECTED".to_string(), "session closed".to_string(), true));
+                }
+                let _ = session.send_keepalive(false).await;
+            }
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(v) => v,
+                    Err(e) => {
+                        record_last_error(status, "LOCAL_ACCEPT_FAILED", &format!("{e}")).await;
+                        continue;
+                    }
+                };
+                let opened = tokio::time::timeout(
+                    connect_timeout,
+                    session.channel_open_direct_tcpip(
+                        cfg.remote_bind_addr.clone(),
+                        cfg.remote_port as u32,
+                        peer.ip().to_string(),
+                        peer.port() as u32,
+                    ),
+                )
+                .await;
+                match opened {
+                    Ok(Ok(channel)) => {
+                        let status = status.clone();
+                        let id = cfg.id.clone();
+                        tokio::spawn(async move {
+                            pipe_channel(channel, stream, &status, &id).await;
+                        });
+                    }
+                    Ok(Err(e)) => {
+                        record_last_error(status, "DIRECT_TCPIP_FAILED", &format!("{e:?}")).await;
+                    }
+                    Err(_) => {
+                        record_last_error(status, "DIRECT_TCPIP_TIMEOUT", "direct-tcpip channel open timeout").await;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 在 SSH 通道与本地 TCP 连接之间双向拷贝，并维护活动连接数与流量统计
+#[cfg(feature = "tcp_tunnel")]
+async fn pipe_channel(
+    channel: russh::Channel<russh::client::Msg>,
+    stream: tokio::net::TcpStream,
+    status: &Arc<RwLock<TunnelRuntimeStatus>>,
+    id: &str,
+) {
+    let traffic = {
+        let mut s = status.write().await;
+        s.active_conns = s.active_conns.saturating_add(1);
+        publish_status(id, &s);
+        s.traffic.clone()
+    };
+
+    let mut channel_stream = channel.into_stream();
+    let mut stream = CountingStream { inner: stream, traffic };
+    let copy_res = tokio::io::copy_bidirectional(&mut channel_stream, &mut stream).await;
+    let _ = tokio::io::AsyncWriteExt::shutdown(&mut channel_stream).await;
+
+    let mut s = status.write().await;
+    if let Ok((a, b)) = copy_res {
+        s.bytes_in = s.bytes_in.saturating_add(a);
+        s.bytes_out = s.bytes_out.saturating_add(b);
+    }
+    s.active_conns = s.active_conns.saturating_sub(1);
+    publish_status(id, &s);
+}
+
+#[cfg(feature = "tcp_tunnel")]
+fn is_loopback_host(host: &str) -> bool {
+    let host = host.trim().trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+#[cfg(feature = "tcp_tunnel")]
+/// 从 `ss -ltn` / `netstat -ltn` 输出中提取监听在指定端口上的本地地址
+fn parse_listen_hosts(output: &str, port: u16) -> Vec<String> {
+    let suffix = format!(":{port}");
+    output
+        .lines()
+        .filter_map(|line| {
+            line.split_whitespace()
+                .find(|tok| tok.ends_with(&suffix))
+                .map(|tok| tok[..tok.len() - suffix.len()].to_string())
+        })
+        .collect()
+}
+
+#[cfg(feature = "tcp_tunnel")]
+async fn set_bind_warning(
+    status: &Arc<RwLock<TunnelRuntimeStatus>>,
+    id: &str,
+    warning: Option<TunnelErrorInfo>,
+) {
+    let mut s = status.write().await;
+    if s.bind_warning.is_none() && warning.is_none() {
+        return;
+    }
+    if let Some(w) = &warning {
+        crate::broadcast_event(
+            "warning",
+            "tunnel",
+            id,
+            "bind_fallback",
+            &format!("TCP tunnel {id}: {}", w.message),
+            serde_json::json!({ "warning": w }),
+        );
+    }
+    s.bind_warning = warning;
+    publish_status(id, &s);
+}
+
+/// 请求的 remote_bind_addr 不是回环地址时，登录远端查看实际监听地址。
+/// sshd 未开启 GatewayPorts 时会静默改为绑定 127.0.0.1，此时返回告警；
+/// 远端没有 ss/netstat 或输出无法识别时不做判断。
+#[cfg(feature = "tcp_tunnel")]
+async fn verify_remote_bind<H: russh::client::Handler>(
+    session: &russh::client::Handle<H>,
+    cfg: &TcpTunnelConfig,
+    timeout: Duration,
+) -> Option<TunnelErrorInfo> {
+    let probe = async {
+        let mut channel = session.channel_open_session().await.ok()?;
+        channel
+            .exec(true, "ss -ltnH 2>/dev/null || netstat -ltn 2>/dev/null")
+            .await
+            .ok()?;
+        let mut output = Vec::new();
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                russh::ChannelMsg::Data { ref data } => output.extend_from_slice(data),
+                russh::ChannelMsg::Eof => break,
+                _ => {}
+            }
+        }
+        Some(String::from_utf8_lossy(&output).to_string())
+    };
+    let output = tokio::time::timeout(timeout, probe).await.ok().flatten()?;
+
+    let hosts = parse_listen_hosts(&output, cfg.remote_port);
+    if hosts.is_empty() || !hosts.iter().all(|h| is_loopback_host(h)) {
+        return None;
+    }
+    Some(TunnelErrorInfo {
+        code: "REMOTE_BIND_FALLBACK".to_string(),
+        message: format!(
+            "requested remote bind {} but sshd is listening on {} only; enable `GatewayPorts clientspecified` (or `yes`) in the remote sshd_config",
+            crate::join_host_port(&cfg.remote_bind_addr, cfg.remote_port),
+            crate::join_host_port(&hosts[0], cfg.remote_port),
+        ),
+        at_ms: now_ms(),
+    })
+}
+
 #[cfg(not(feature = "tcp_tunnel"))]
 async fn connect_and_forward(
-    _cfg: &TcpTunnelConfig,
+    cfg: &TcpTunnelConfig,
     status: &Arc<RwLock<TunnelRuntimeStatus>>,
     stop_rx: &mut watch::Receiver<bool>,
 ) -> Result<(), (String, String, bool)> {
-    set_error(status, "NOT_SUPPORTED", "tcp_tunnel feature not enabled").await;
+    set_error(status, &cfg.id, "NOT_SUPPORTED", "tcp_tunnel feature not enabled").await;
     while stop_rx.changed().await.is_ok() {
         if *stop_rx.borrow() {
             break;
@@ -617,28 +1046,52 @@ impl russh::client::Handler for TunnelClientHandler {
             return Ok(true);
         }
         let expected = self.cfg.host_key_fingerprint.trim();
-        if expected.is_empty() {
+        let known_hosts_path = self.cfg.known_hosts_path.as_deref();
+        if expected.is_empty() && known_hosts_path.is_none() {
             set_error(
                 &self.status,
+                &self.cfg.id,
                 "HOSTKEY_MISSING",
-                "host_key_fingerprint is required",
+                "host_key_fingerprint or known_hosts_path is required",
             )
             .await;
             return Ok(false);
         }
         let actual = compute_openssh_sha256_fingerprint(server_public_key)
             .unwrap_or_else(|_| "<unknown>".to_string());
-        if expected == actual {
-            Ok(true)
-        } else {
-            set_error(
-                &self.status,
-                "HOSTKEY_MISMATCH",
-                &format!("expected {expected}, got {actual}"),
-            )
-            .await;
-            Ok(false)
+        if !expected.is_empty() && expected == actual {
+            return Ok(true);
+        }
+        // 指纹未命中时再查 known_hosts（OpenSSH 格式，支持 [host]:port 与哈希主机名）
+        if let Some(path) = known_hosts_path {
+            let (code, message) = match russh::keys::check_known_hosts_path(
+                &self.cfg.ssh_host,
+                self.cfg.ssh_port,
+                server_public_key,
+                path,
+            ) {
+                Ok(true) => return Ok(true),
+                Ok(false) => (
+                    "HOSTKEY_UNKNOWN",
+                    format!("{} not found in {path} (fingerprint {actual})", self.cfg.ssh_host),
+                ),
+                Err(russh::keys::Error::KeyChanged { line }) => (
+                    "HOSTKEY_MISMATCH",
+                    format!("host key changed, conflicts with {path}:{line} (got {actual})"),
+                ),
+                Err(e) => ("KNOWN_HOSTS_ERROR", format!("read {path} failed: {e}")),
+            };
+            set_error(&self.status, &self.cfg.id, code, &message).await;
+            return Ok(false);
         }
+        set_error(
+            &self.status,
+            &self.cfg.id,
+            "HOSTKEY_MISMATCH",
+            &format!("expected {expected}, got {actual}"),
+        )
+        .await;
+        Ok(false)
     }
 
     fn server_channel_open_forwarded_tcpip(
@@ -650,41 +1103,21 @@ impl russh::client::Handler for TunnelClientHandler {
         _originator_port: u32,
         _session: &mut russh::client::Session,
     ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send {
-        let local_addr = format!("{}:{}", self.cfg.local_addr, self.cfg.local_port);
+        let local_addr = crate::join_host_port(&self.cfg.local_addr, self.cfg.local_port);
         let status = self.status.clone();
+        let id = self.cfg.id.clone();
         async move {
             // Important: do not block the SSH session handler with a long-lived copy loop.
             // If we await I/O here, the underlying session task may stop processing packets,
             // resulting in deadlocks (no data forwarded; disconnect/cancel not applied).
             tokio::spawn(async move {
-                {
-                    let mut s = status.write().await;
-                    s.active_conns = s.active_conns.saturating_add(1);
-                }
-
-                let result = tokio::net::TcpStream::connect(&local_addr).await;
-                match result {
-                    Ok(mut stream) => {
-                        let mut channel_stream = channel.into_stream();
-                        let copy_res =
-                            tokio::io::copy_bidirectional(&mut channel_stream, &mut stream).await;
-                        let _ = tokio::io::AsyncWriteExt::shutdown(&mut channel_stream).await;
-                        if let Ok((a, b)) = copy_res {
-                            let mut s = status.write().await;
-                            s.bytes_in = s.bytes_in.saturating_add(a);
-                            s.bytes_out = s.bytes_out.saturating_add(b);
-                        }
-                    }
+                match tokio::net::TcpStream::connect(&local_addr).await {
+                    Ok(stream) => pipe_channel(channel, stream, &status, &id).await,
                     Err(e) => {
                         record_last_error(&status, "LOCAL_CONNECT_FAILED", &format!("{e}")).await;
                         let _ = channel.close().await;
                     }
                 }
-
-                {
-                    let mut s = status.write().await;
-                    s.active_conns = s.active_conns.saturating_sub(1);
-                }
             });
             Ok(())
         }
@@ -748,6 +1181,25 @@ async fn test_once(cfg: &TcpTunnelConfig) -> Result<(), (String, String)> {
         return Err(("AUTH_FAILED".to_string(), "authentication failed".to_string()));
     }
 
+    if cfg.direction == TcpTunnelDirection::Forward {
+        // 只验证 SSH 服务器能连到目标地址；本地端口可能已被运行中的隧道占用，不做 bind 检查
+        let channel = tokio::time::timeout(
+            connect_timeout,
+            session.channel_open_direct_tcpip(
+                cfg.remote_bind_addr.clone(),
+                cfg.remote_port as u32,
+                "127.0.0.1",
+                0,
+            ),
+        )
+        .await
+        .map_err(|_| ("DIRECT_TCPIP_TIMEOUT".to_string(), "direct-tcpip channel open timeout".to_string()))?
+        .map_err(|e| ("DIRECT_TCPIP_FAILED".to_string(), format!("{e:?}")))?;
+        let _ = channel.close().await;
+        let _ = session.disconnect(russh::Disconnect::ByApplication, "test done", "en").await;
+        return Ok(());
+    }
+
     tokio::time::timeout(
         connect_timeout,
         session.tcpip_forward(cfg.remote_bind_addr.clone(), cfg.remote_port as u32),
@@ -781,12 +1233,12 @@ async fn test_ssh_only_once(cfg: &TcpTunnelConfig) -> Result<(), (String, String
     if cfg.username.trim().is_empty() {
         return Err(("USERNAME_MISSING".to_string(), "username is required".to_string()));
     }
-    if cfg.strict_host_key_checking && cfg.host_key_fingerprint.trim().is_empty() {
-        return Err((
-            "HOSTKEY_MISSING".to_string(),
-            "host_key_fingerprint is required".to_string(),
-        ));
-    }
+    crate::validate_host_key_settings(
+        cfg.strict_host_key_checking,
+        &cfg.host_key_fingerprint,
+        cfg.known_hosts_path.as_deref(),
+    )
+    .map_err(|e| ("HOSTKEY_MISSING".to_string(), e))?;
     match &cfg.auth {
         TcpTunnelAuth::PrivateKeyPath { path, .. } if path.is_empty() => {
             return Err((